@@ -0,0 +1,148 @@
+//! Contains ISO 9660 specific listing and unpacking functions, read only.
+//!
+//! Backed by the `iso9660` crate, which resolves long filenames where the
+//! image provides them.
+
+use std::{
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+use iso9660::{DirectoryEntry, ISO9660Reader, ISODirectory, ISO9660};
+
+use crate::{
+    error::Error,
+    list::FileInArchive,
+    utils::{self, bomb::BombGuard, logger::info, Bytes, EscapedPathDisplay},
+};
+
+/// Unpacks the image read from `reader` into the folder given by `output_folder`.
+/// Assumes that output_folder is empty
+pub fn unpack_archive<R: Read + Seek>(
+    reader: R,
+    output_folder: &Path,
+    quiet: bool,
+    bomb_guard: &BombGuard,
+) -> crate::Result<usize> {
+    assert!(output_folder.read_dir().expect("dir exists").count() == 0);
+
+    let iso = ISO9660::new(reader)?;
+
+    let mut unpacked_files = 0;
+    unpack_directory(&iso.root, Path::new(""), output_folder, quiet, bomb_guard, &mut unpacked_files)?;
+
+    Ok(unpacked_files)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn unpack_directory<T: ISO9660Reader>(
+    directory: &ISODirectory<T>,
+    relative_path: &Path,
+    output_folder: &Path,
+    quiet: bool,
+    bomb_guard: &BombGuard,
+    unpacked_files: &mut usize,
+) -> crate::Result<()> {
+    for entry in directory.contents() {
+        let entry = entry?;
+        if matches!(entry.identifier(), "." | "..") {
+            continue;
+        }
+
+        let entry_path = relative_path.join(entry.identifier());
+        let output_path = utils::extended_length_path(&output_folder.join(&entry_path));
+
+        match entry {
+            DirectoryEntry::Directory(subdirectory) => {
+                fs::create_dir_all(&output_path)?;
+                unpack_directory(&subdirectory, &entry_path, output_folder, quiet, bomb_guard, unpacked_files)?;
+            }
+            DirectoryEntry::File(file) => {
+                bomb_guard.add(file.size().into())?;
+
+                if let Some(parent) = output_path.parent() {
+                    if !parent.exists() {
+                        fs::create_dir_all(parent)?;
+                    }
+                }
+
+                let mut output_file = fs::File::create(&output_path)?;
+                io::copy(&mut file.read(), &mut output_file)?;
+
+                // This is printed for every file in the archive and has little
+                // importance for most users, but would generate lots of
+                // spoken text for users using screen readers, braille displays
+                // and so on
+                if !quiet {
+                    info(format!(
+                        "\"{}\" extracted. ({})",
+                        EscapedPathDisplay::new(&entry_path),
+                        Bytes::new(file.size().into()),
+                    ));
+                }
+
+                *unpacked_files += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// List contents of the image read from `reader`, returning a vector of archive entries
+pub fn list_archive<R: Read + Seek>(reader: R) -> crate::Result<Vec<crate::Result<FileInArchive>>> {
+    let iso = ISO9660::new(reader)?;
+
+    let mut files = vec![];
+    list_directory(&iso.root, Path::new(""), &mut files)?;
+
+    Ok(files)
+}
+
+fn list_directory<T: ISO9660Reader>(
+    directory: &ISODirectory<T>,
+    relative_path: &Path,
+    files: &mut Vec<crate::Result<FileInArchive>>,
+) -> crate::Result<()> {
+    for entry in directory.contents() {
+        let entry = entry?;
+        if matches!(entry.identifier(), "." | "..") {
+            continue;
+        }
+
+        let entry_path: PathBuf = relative_path.join(entry.identifier());
+
+        match entry {
+            DirectoryEntry::Directory(subdirectory) => {
+                files.push(Ok(FileInArchive {
+                    path: entry_path.clone(),
+                    is_dir: true,
+                    details: None,
+                    link_target: None,
+                    mtime: None,
+                }));
+                list_directory(&subdirectory, &entry_path, files)?;
+            }
+            DirectoryEntry::File(file) => files.push(Ok(FileInArchive {
+                path: entry_path,
+                is_dir: false,
+                details: Some(crate::list::EntryDetails {
+                    method: "store".into(),
+                    compressed_size: file.size().into(),
+                    uncompressed_size: file.size().into(),
+                }),
+                link_target: None,
+                mtime: None,
+            })),
+        }
+    }
+
+    Ok(())
+}
+
+pub fn no_compression() -> Error {
+    Error::UnsupportedFormat {
+        reason: "Creating ISO 9660 images is not supported, only listing and extraction.".into(),
+    }
+}
@@ -1,9 +1,11 @@
 //! Archive compression algorithms
 
+pub mod iso;
 #[cfg(feature = "unrar")]
 pub mod rar;
 #[cfg(not(feature = "unrar"))]
 pub mod rar_stub;
 pub mod sevenz;
+pub mod spanned_zip;
 pub mod tar;
 pub mod zip;
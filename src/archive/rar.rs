@@ -44,7 +44,13 @@ pub fn list_archive(archive_path: &Path) -> impl Iterator<Item = crate::Result<F
             let is_dir = item.is_directory();
             let path = item.filename;
 
-            Ok(FileInArchive { path, is_dir })
+            Ok(FileInArchive {
+                path,
+                is_dir,
+                details: None,
+                link_target: None,
+                mtime: None,
+            })
         })
 }
 
@@ -10,37 +10,67 @@ use fs_err as fs;
 use same_file::Handle;
 
 use crate::{
-    error::FinalError,
     utils::{
-        self, cd_into_same_dir_as,
-        logger::{info, warning},
-        Bytes, EscapedPathDisplay, FileVisibilityPolicy,
+        self,
+        logger::{info, info_accessible, warning},
+        Bytes, EscapedPathDisplay, FileVisibilityPolicy, SizeFilter,
     },
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn compress_sevenz<W>(
     files: &[PathBuf],
     output_path: &Path,
     writer: W,
     file_visibility_policy: FileVisibilityPolicy,
     quiet: bool,
+    base_dir: Option<&Path>,
+    size_filter: SizeFilter,
+    time_filter: utils::TimeFilter,
+    total_files: Option<u64>,
+    error_on_empty: bool,
+    solid: bool,
+    solid_block_size: Option<u64>,
+    non_utf8: Option<crate::cli::NonUtf8Policy>,
 ) -> crate::Result<W>
 where
     W: Write + Seek,
 {
     let mut writer = sevenz_rust::SevenZWriter::new(writer)?;
     let output_handle = Handle::from_path(output_path);
+    let mut discovery_counter = utils::DiscoveryCounter::new(quiet).with_total(total_files);
+    let mut size_filtered_count: u64 = 0;
+    let mut appended_entries: u64 = 0;
+    let mut solid_files: Vec<SolidFile> = vec![];
 
     for filename in files {
-        let previous_location = cd_into_same_dir_as(filename)?;
+        let (previous_location, filename) = utils::cd_for_archiving(filename, base_dir)?;
 
-        // Unwrap safety:
-        //   paths should be canonicalized by now, and the root directory rejected.
-        let filename = filename.file_name().unwrap();
-
-        for entry in file_visibility_policy.build_walker(filename) {
+        for entry in file_visibility_policy.build_walker(filename)? {
             let entry = entry?;
             let path = entry.path();
+            discovery_counter.tick();
+
+            // Stat before anything opens the path: both the same-file check
+            // below and the compression itself would block on a FIFO
+            let metadata = match path.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
+                        // This path is for a broken symlink
+                        // We just ignore it
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            };
+            if !metadata.is_dir() && !metadata.is_file() {
+                warning(format!(
+                    "Skipping '{}', not a regular file",
+                    EscapedPathDisplay::new(path)
+                ));
+                continue;
+            }
 
             // If the output_path is the same as the input file, warn the user and skip the input (in order to avoid compression recursion)
             if let Ok(handle) = &output_handle {
@@ -62,47 +92,178 @@ where
                 info(format!("Compressing '{}'.", EscapedPathDisplay::new(path)));
             }
 
-            let metadata = match path.metadata() {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
-                        // This path is for a broken symlink
-                        // We just ignore it
-                        continue;
-                    }
-                    return Err(e.into());
-                }
-            };
+            if !metadata.is_dir() && size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                warning(format!(
+                    "Skipping '{}' ({}), outside of the size filter",
+                    EscapedPathDisplay::new(path),
+                    Bytes::new(metadata.len()),
+                ));
+                size_filtered_count += 1;
+                continue;
+            }
 
-            let entry_name = path.to_str().ok_or_else(|| {
-                FinalError::with_title("7z requires that all entry names are valid UTF-8")
-                    .detail(format!("File at '{path:?}' has a non-UTF-8 name"))
-            })?;
+            if !metadata.is_dir()
+                && time_filter.is_active()
+                && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime))
+            {
+                size_filtered_count += 1;
+                continue;
+            }
 
-            let entry = sevenz_rust::SevenZArchiveEntry::from_path(path, entry_name.to_owned());
-            let entry_data = if metadata.is_dir() {
-                None
-            } else {
-                Some(fs::File::open(path)?)
+            let Some(entry_name) = utils::resolve_non_utf8_name(path, non_utf8, "7z")? else {
+                continue;
             };
 
-            writer.push_archive_entry::<fs::File>(entry, entry_data)?;
+            let entry = sevenz_rust::SevenZArchiveEntry::from_path(path, entry_name);
+            if metadata.is_dir() {
+                writer.push_archive_entry::<fs::File>(entry, None)?;
+            } else if solid {
+                appended_entries += 1;
+                discovery_counter.add_bytes(metadata.len());
+                // The blocks are emitted after the walk; the stored path has
+                // to survive leaving the cd_for_archiving directory
+                solid_files.push(SolidFile {
+                    entry,
+                    absolute_path: env::current_dir()?.join(path),
+                    size: metadata.len(),
+                });
+            } else {
+                appended_entries += 1;
+                discovery_counter.add_bytes(metadata.len());
+                let _fd_permit = utils::io::fd_quota::acquire();
+                writer.push_archive_entry(entry, Some(fs::File::open(path)?))?;
+            }
         }
 
         env::set_current_dir(previous_location)?;
     }
 
-    let bytes = writer.finish()?;
+    crate::archive::tar::check_empty_archive(appended_entries, error_on_empty)?;
+
+    if size_filtered_count > 0 {
+        info_accessible(format!("{size_filtered_count} files skipped by the size/time filters."));
+    }
+
+    let total_input: u64 = solid_files.iter().map(|file| file.size).sum();
+    if solid {
+        push_solid_blocks(&mut writer, solid_files, solid_block_size)?;
+    }
+
+    let mut bytes = writer.finish()?;
+
+    if solid && total_input > 0 {
+        // finish() seeks back to patch the signature header, the archive
+        // size is at the end of the stream
+        if let Ok(archive_size) = bytes.seek(io::SeekFrom::End(0)) {
+            info_accessible(format!(
+                "Solid 7z ratio: {:.1}% ({} in, {} out).",
+                archive_size as f64 / total_input as f64 * 100.0,
+                Bytes::new(total_input),
+                Bytes::new(archive_size),
+            ));
+        }
+    }
+
     Ok(bytes)
 }
 
-pub fn decompress_sevenz<R>(reader: R, output_path: &Path, quiet: bool) -> crate::Result<usize>
+/// A file whose contents wait for solid-block emission after the walk.
+struct SolidFile {
+    entry: sevenz_rust::SevenZArchiveEntry,
+    absolute_path: PathBuf,
+    size: u64,
+}
+
+/// Emits the collected files as solid blocks: entries are sorted so similar
+/// files (same extension, then similar size) sit in the same shared
+/// compression stream, and each block stays under `block_size` of input
+/// (one block holds everything when unset).
+fn push_solid_blocks<W: Write + Seek>(
+    writer: &mut sevenz_rust::SevenZWriter<W>,
+    mut files: Vec<SolidFile>,
+    block_size: Option<u64>,
+) -> crate::Result<()> {
+    files.sort_by(|a, b| {
+        let extension = |file: &SolidFile| {
+            file.absolute_path
+                .extension()
+                .map(|extension| extension.to_ascii_lowercase())
+        };
+        extension(a).cmp(&extension(b)).then(a.size.cmp(&b.size))
+    });
+
+    let mut block: Vec<SolidFile> = vec![];
+    let mut block_input = 0;
+    for file in files {
+        if !block.is_empty() && block_size.is_some_and(|limit| block_input + file.size > limit) {
+            push_one_block(writer, std::mem::take(&mut block))?;
+            block_input = 0;
+        }
+        block_input += file.size;
+        block.push(file);
+    }
+    if !block.is_empty() {
+        push_one_block(writer, block)?;
+    }
+
+    Ok(())
+}
+
+fn push_one_block<W: Write + Seek>(
+    writer: &mut sevenz_rust::SevenZWriter<W>,
+    block: Vec<SolidFile>,
+) -> crate::Result<()> {
+    let mut entries = Vec::with_capacity(block.len());
+    let mut readers = Vec::with_capacity(block.len());
+    for file in block {
+        entries.push(file.entry);
+        readers.push(sevenz_rust::SourceReader::new(LazyFileReader {
+            path: file.absolute_path,
+            file: None,
+        }));
+    }
+
+    writer.push_archive_entries(entries, sevenz_rust::SeqReader::new(readers))?;
+    Ok(())
+}
+
+/// Opens each file only once the solid block actually reads it, so a block
+/// spanning many files does not hold them all open at the same time.
+struct LazyFileReader {
+    path: PathBuf,
+    file: Option<(utils::io::fd_quota::Permit, fs::File)>,
+}
+
+impl Read for LazyFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.file.is_none() {
+            let permit = utils::io::fd_quota::acquire();
+            self.file = Some((permit, fs::File::open(&self.path)?));
+        }
+        self.file.as_mut().expect("opened above").1.read(buf)
+    }
+}
+
+pub fn decompress_sevenz<R>(
+    reader: R,
+    output_path: &Path,
+    quiet: bool,
+    bomb_guard: &utils::bomb::BombGuard,
+) -> crate::Result<usize>
 where
     R: Read + Seek,
 {
     let mut count: usize = 0;
+    let mut guard_tripped = false;
     sevenz_rust::decompress_with_extract_fn(reader, output_path, |entry, reader, path| {
         count += 1;
+        // The extended-length prefix lets paths beyond MAX_PATH extract on
+        // Windows (identity elsewhere)
+        let path = &utils::extended_length_path(path);
+        if bomb_guard.add(entry.size()).is_err() {
+            guard_tripped = true;
+            return Ok(false);
+        }
         // Manually handle writing all files from 7z archive, due to library exluding empty files
         use std::io::BufWriter;
 
@@ -152,5 +313,10 @@ where
         Ok(true)
     })?;
 
+    if guard_tripped {
+        // Re-raise the limit error that stopped the extraction callback
+        bomb_guard.add(0)?;
+    }
+
     Ok(count)
 }
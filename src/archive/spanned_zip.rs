@@ -0,0 +1,350 @@
+//! Reading spanned (multi-disk) zips split into `.z01`, `.z02`, …, `.zip`.
+//!
+//! The final `.zip` holds the central directory; its entries address their
+//! local headers as (disk number, offset within that disk). Reassembly maps
+//! those pairs onto the concatenation of all parts — plain `cat` is not
+//! enough, since the stored offsets are per-disk.
+
+use std::{
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+
+use crate::{
+    error::FinalError,
+    list::{EntryDetails, FileInArchive},
+    utils::{
+        self,
+        logger::{info, warning},
+        Bytes,
+    },
+};
+
+const EOCD_SIGNATURE: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0201_4b50;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+
+/// One central directory entry of the spanned archive.
+struct SpannedEntry {
+    name: String,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    disk_start: u16,
+    local_offset: u64,
+    unix_mode: Option<u32>,
+}
+
+/// Returns the ordered part list (`.z01` … then the final `.zip`) when
+/// `final_zip` is the last disk of a spanned archive, `None` for ordinary
+/// zips without a `.z01` sibling.
+pub fn find_parts(final_zip: &Path) -> Option<Vec<PathBuf>> {
+    let first_part = final_zip.with_extension("z01");
+    if !first_part.exists() {
+        return None;
+    }
+
+    let mut parts = vec![];
+    for number in 1.. {
+        let part = final_zip.with_extension(format!("z{number:02}"));
+        if !part.exists() {
+            break;
+        }
+        parts.push(part);
+    }
+    parts.push(final_zip.to_path_buf());
+    Some(parts)
+}
+
+/// Extracts the spanned archive into `output_folder`.
+pub fn unpack_archive(
+    parts: &[PathBuf],
+    output_folder: &Path,
+    quiet: bool,
+    bomb_guard: &utils::bomb::BombGuard,
+) -> crate::Result<usize> {
+    let mut reader = ConcatReader::open(parts)?;
+    let entries = read_central_directory(&mut reader)?;
+
+    let mut files_unpacked = 0;
+    for entry in entries {
+        let Some(file_path) = crate::archive::zip::sanitize_entry_path(&entry.name) else {
+            warning(format!("Skipping entry '{}', it has an unsafe path", entry.name));
+            continue;
+        };
+
+        if entry.name.ends_with('/') {
+            fs::create_dir_all(output_folder.join(&file_path))?;
+            continue;
+        }
+
+        bomb_guard.add(entry.uncompressed_size)?;
+
+        let output_path = output_folder.join(&file_path);
+        if let Some(parent) = output_path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let mut output = io::BufWriter::new(fs::File::create(&output_path)?);
+        let mut hasher = crc32fast::Hasher::new();
+        entry_reader(&mut reader, &entry, |data| {
+            let mut buffer = [0u8; crate::BUFFER_CAPACITY];
+            loop {
+                let read = data.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                io::Write::write_all(&mut output, &buffer[..read])?;
+            }
+            Ok(())
+        })?;
+        io::Write::flush(&mut output)?;
+        if hasher.finalize() != entry.crc32 {
+            warning(format!("The extracted '{}' failed its CRC32 check", entry.name));
+        }
+
+        #[cfg(unix)]
+        if let Some(mode) = entry.unix_mode {
+            use std::os::unix::fs::PermissionsExt;
+            let _ = std::fs::set_permissions(&output_path, std::fs::Permissions::from_mode(mode & 0o7777));
+        }
+
+        if !quiet {
+            info(format!(
+                "{:?} extracted. ({})",
+                utils::strip_cur_dir(&output_path),
+                Bytes::new(entry.uncompressed_size),
+            ));
+        }
+        files_unpacked += 1;
+    }
+
+    Ok(files_unpacked)
+}
+
+/// Lists the spanned archive's entries for `ouch list`.
+pub fn list_archive(parts: &[PathBuf]) -> crate::Result<Vec<crate::Result<FileInArchive>>> {
+    let mut reader = ConcatReader::open(parts)?;
+    let entries = read_central_directory(&mut reader)?;
+
+    Ok(entries
+        .into_iter()
+        .map(|entry| {
+            Ok(FileInArchive {
+                is_dir: entry.name.ends_with('/'),
+                path: PathBuf::from(&entry.name),
+                details: Some(EntryDetails {
+                    method: match entry.method {
+                        0 => "store".into(),
+                        8 => "deflate".into(),
+                        other => format!("method {other}"),
+                    },
+                    compressed_size: entry.compressed_size,
+                    uncompressed_size: entry.uncompressed_size,
+                }),
+                link_target: None,
+                mtime: None,
+            })
+        })
+        .collect())
+}
+
+/// Reads the central directory from the final disk and resolves every
+/// entry's (disk, offset) pair.
+fn read_central_directory(reader: &mut ConcatReader) -> crate::Result<Vec<SpannedEntry>> {
+    // The EOCD record sits in the last 64KiB + 22B of the archive (a
+    // trailing comment can push it away from the very end)
+    let total_len = reader.total_len();
+    let tail_start = total_len.saturating_sub(64 * 1024 + 22);
+    reader.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = vec![];
+    reader.read_to_end(&mut tail)?;
+
+    let eocd_position = tail
+        .windows(4)
+        .rposition(|window| window == EOCD_SIGNATURE.to_le_bytes())
+        .ok_or_else(|| -> crate::Error {
+            FinalError::with_title("Could not read the spanned zip")
+                .detail("The end-of-central-directory record was not found in the final part")
+                .into()
+        })?;
+    let eocd = &tail[eocd_position..];
+    if eocd.len() < 22 {
+        return Err(FinalError::with_title("Could not read the spanned zip")
+            .detail("The end-of-central-directory record is truncated")
+            .into());
+    }
+
+    let cd_disk = read_u16(&eocd[6..]);
+    let total_entries = read_u16(&eocd[10..]) as u64;
+    let cd_offset = read_u32(&eocd[16..]) as u64;
+
+    let cd_start = reader.disk_base(cd_disk).ok_or_else(|| missing_part(cd_disk))? + cd_offset;
+    reader.seek(SeekFrom::Start(cd_start))?;
+
+    let mut entries = Vec::with_capacity(total_entries as usize);
+    for _ in 0..total_entries {
+        let mut fixed = [0u8; 46];
+        reader.read_exact(&mut fixed)?;
+        if read_u32(&fixed) != CENTRAL_DIRECTORY_SIGNATURE {
+            return Err(FinalError::with_title("Could not read the spanned zip")
+                .detail("A central directory entry has an invalid signature")
+                .into());
+        }
+
+        let name_len = read_u16(&fixed[28..]) as usize;
+        let extra_len = read_u16(&fixed[30..]) as usize;
+        let comment_len = read_u16(&fixed[32..]) as usize;
+
+        let mut name = vec![0u8; name_len];
+        reader.read_exact(&mut name)?;
+        let mut skipped = vec![0u8; extra_len + comment_len];
+        reader.read_exact(&mut skipped)?;
+
+        let external_attributes = read_u32(&fixed[38..]);
+        let unix_mode = (external_attributes >> 16 != 0).then_some(external_attributes >> 16);
+
+        entries.push(SpannedEntry {
+            name: String::from_utf8_lossy(&name).into_owned(),
+            method: read_u16(&fixed[10..]),
+            crc32: read_u32(&fixed[16..]),
+            compressed_size: read_u32(&fixed[20..]) as u64,
+            uncompressed_size: read_u32(&fixed[24..]) as u64,
+            disk_start: read_u16(&fixed[34..]),
+            local_offset: read_u32(&fixed[42..]) as u64,
+            unix_mode,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Positions the reader on an entry's data and hands a size-limited,
+/// decompressing reader to `consume`.
+fn entry_reader(
+    reader: &mut ConcatReader,
+    entry: &SpannedEntry,
+    consume: impl FnOnce(&mut dyn Read) -> crate::Result<()>,
+) -> crate::Result<()> {
+    let base = reader
+        .disk_base(entry.disk_start)
+        .ok_or_else(|| missing_part(entry.disk_start))?;
+    reader.seek(SeekFrom::Start(base + entry.local_offset))?;
+
+    let mut local = [0u8; 30];
+    reader.read_exact(&mut local)?;
+    if read_u32(&local) != LOCAL_HEADER_SIGNATURE {
+        return Err(FinalError::with_title("Could not read the spanned zip")
+            .detail(format!("The local header of '{}' has an invalid signature", entry.name))
+            .into());
+    }
+    let name_len = read_u16(&local[26..]) as usize;
+    let extra_len = read_u16(&local[28..]) as usize;
+    let mut skipped = vec![0u8; name_len + extra_len];
+    reader.read_exact(&mut skipped)?;
+
+    let data = reader.take(entry.compressed_size);
+    match entry.method {
+        0 => consume(&mut { data }),
+        8 => consume(&mut flate2::read::DeflateDecoder::new(data)),
+        other => Err(FinalError::with_title("Cannot extract the spanned zip")
+            .detail(format!(
+                "Entry '{}' uses unsupported compression method {other}",
+                entry.name
+            ))
+            .into()),
+    }
+}
+
+fn missing_part(disk: u16) -> crate::Error {
+    FinalError::with_title("A part of the spanned zip is missing")
+        .detail(format!("The archive references disk {} which was not found", disk + 1))
+        .hint("Make sure every .zNN part sits next to the final .zip.")
+        .into()
+}
+
+fn read_u16(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// `Read + Seek` over the concatenation of all parts, tracking which global
+/// offset each disk starts at.
+struct ConcatReader {
+    parts: Vec<(fs::File, u64)>,
+    bases: Vec<u64>,
+    position: u64,
+    total: u64,
+}
+
+impl ConcatReader {
+    fn open(parts: &[PathBuf]) -> crate::Result<Self> {
+        let mut opened = vec![];
+        let mut bases = vec![];
+        let mut total = 0;
+        for part in parts {
+            let file = fs::File::open(part)?;
+            let len = file.metadata()?.len();
+            bases.push(total);
+            total += len;
+            opened.push((file, len));
+        }
+
+        Ok(Self {
+            parts: opened,
+            bases,
+            position: 0,
+            total,
+        })
+    }
+
+    fn total_len(&self) -> u64 {
+        self.total
+    }
+
+    /// The global offset where `disk` starts, `None` when that part is
+    /// missing.
+    fn disk_base(&self, disk: u16) -> Option<u64> {
+        self.bases.get(disk as usize).copied()
+    }
+}
+
+impl Read for ConcatReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // Locate the part holding the current position and read from it
+        let mut remaining = self.position;
+        for (file, len) in self.parts.iter_mut() {
+            if remaining < *len {
+                file.seek(SeekFrom::Start(remaining))?;
+                let capped = buf.len().min((*len - remaining) as usize);
+                let read = file.read(&mut buf[..capped])?;
+                self.position += read as u64;
+                return Ok(read);
+            }
+            remaining -= *len;
+        }
+        Ok(0)
+    }
+}
+
+impl Seek for ConcatReader {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let new_position = match position {
+            SeekFrom::Start(offset) => offset as i64,
+            SeekFrom::End(offset) => self.total as i64 + offset,
+            SeekFrom::Current(offset) => self.position as i64 + offset,
+        };
+        if new_position < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start"));
+        }
+        self.position = new_position as u64;
+        Ok(self.position)
+    }
+}
@@ -1,37 +1,242 @@
 //! Contains Tar-specific building and unpacking functions
 
 use std::{
+    collections::{hash_map::Entry, HashMap},
     env,
-    io::prelude::*,
+    io::{self, prelude::*},
+    ops::ControlFlow,
     path::{Path, PathBuf},
-    sync::mpsc::{self, Receiver},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
     thread,
 };
 
 use fs_err as fs;
 use same_file::Handle;
+use time::OffsetDateTime;
 
 use crate::{
     error::FinalError,
     list::FileInArchive,
     utils::{
         self,
-        logger::{info, warning},
-        Bytes, EscapedPathDisplay, FileVisibilityPolicy,
+        logger::{info, info_accessible, warning},
+        bomb::BombGuard,
+        Bytes, DuplicatePolicy, EscapedPathDisplay, FileVisibilityPolicy, SizeFilter,
     },
+    BUFFER_CAPACITY,
 };
 
+/// Pax record carrying the real size of an all-zero file stored as an
+/// empty placeholder by `--skip-zeros`.
+const PAX_ZEROS_SIZE_KEY: &str = "OUCH.zeros_size";
+
+/// Pax global record carrying the total uncompressed size of the archive,
+/// written by `--embed-total-size`.
+const PAX_TOTAL_SIZE_KEY: &str = "OUCH.total_size";
+
 /// Unpacks the archive given by `archive` into the folder given by `into`.
 /// Assumes that output_folder is empty
-pub fn unpack_archive(reader: Box<dyn Read>, output_folder: &Path, quiet: bool) -> crate::Result<usize> {
+#[allow(clippy::too_many_arguments)]
+pub fn unpack_archive(
+    reader: Box<dyn Read>,
+    output_folder: &Path,
+    quiet: bool,
+    absolute_paths: bool,
+    preserve_special: bool,
+    on_duplicate: Option<DuplicatePolicy>,
+    bomb_guard: &BombGuard,
+    owner_map: Option<&utils::OwnerMap>,
+    strict_tar: bool,
+    allow_setuid: bool,
+    non_utf8: Option<crate::cli::NonUtf8Policy>,
+) -> crate::Result<usize> {
     assert!(output_folder.read_dir().expect("dir exists").count() == 0);
     let mut archive = tar::Archive::new(reader);
 
     let mut files_unpacked = 0;
+    let mut written_paths = utils::WrittenPaths::for_output_dir(output_folder);
     for file in archive.entries()? {
         let mut file = file?;
 
-        file.unpack_in(output_folder)?;
+        let entry_path = file.path()?.into_owned();
+
+        // Tar stores raw bytes, so entry names may not be valid UTF-8; by
+        // default they are restored as-is, --non-utf8 opts into a policy
+        if utils::is_invalid_utf8(&entry_path) {
+            match non_utf8 {
+                None => {}
+                Some(crate::cli::NonUtf8Policy::Error) => {
+                    return Err(FinalError::with_title("The archive contains a non-UTF-8 entry name")
+                        .detail(format!("Entry: {entry_path:?}"))
+                        .hint("Pass --non-utf8 skip or --non-utf8 sanitize to continue without it.")
+                        .into());
+                }
+                Some(crate::cli::NonUtf8Policy::Skip) => {
+                    warning(format!("Skipping '{entry_path:?}', its name is not valid UTF-8"));
+                    continue;
+                }
+                Some(crate::cli::NonUtf8Policy::Sanitize) => {
+                    // The replacement name needs a manual unpack, with the
+                    // same escape refusal unpack_in would apply
+                    let sanitized = PathBuf::from(entry_path.to_string_lossy().into_owned());
+                    if sanitized.is_absolute()
+                        || sanitized
+                            .components()
+                            .any(|component| matches!(component, std::path::Component::ParentDir))
+                    {
+                        warning(format!(
+                            "Skipping entry '{}', it would escape the output directory",
+                            sanitized.display()
+                        ));
+                        continue;
+                    }
+
+                    // The manual path gets the same accounting the regular
+                    // one does below: lossy replacement can make distinct
+                    // raw names collide, and the bytes still count against
+                    // the bomb limits
+                    if !file.header().entry_type().is_dir() {
+                        if let ControlFlow::Break(_) =
+                            utils::handle_duplicate_entry(&sanitized, &mut written_paths, on_duplicate)?
+                        {
+                            continue;
+                        }
+                        bomb_guard.add(file.size())?;
+                    }
+
+                    warning(format!(
+                        "Sanitizing the non-UTF-8 entry name {entry_path:?} to '{}'",
+                        sanitized.display()
+                    ));
+                    let target = output_folder.join(&sanitized);
+                    if file.header().entry_type().is_dir() {
+                        fs::create_dir_all(&target)?;
+                    } else {
+                        if let Some(parent) = target.parent() {
+                            fs::create_dir_all(parent)?;
+                        }
+                        file.unpack(&target)?;
+                        files_unpacked += 1;
+                    }
+                    continue;
+                }
+            }
+        }
+
+        if !file.header().entry_type().is_dir() {
+            if let ControlFlow::Break(_) =
+                utils::handle_duplicate_entry(&entry_path, &mut written_paths, on_duplicate)?
+            {
+                continue;
+            }
+        }
+
+        let entry_type = file.header().entry_type();
+        // Metadata-only entries (e.g. the --embed-total-size record) are
+        // not files and never unpack
+        if entry_type == tar::EntryType::XGlobalHeader {
+            continue;
+        }
+        if matches!(entry_type, tar::EntryType::Fifo | tar::EntryType::Char | tar::EntryType::Block) {
+            if !preserve_special {
+                warning(format!(
+                    "Skipping special file '{}', pass --preserve-special to restore it",
+                    EscapedPathDisplay::new(&entry_path)
+                ));
+                continue;
+            }
+
+            unpack_special_file(file.header(), &entry_path, output_folder)?;
+            continue;
+        }
+
+        bomb_guard.add(file.size())?;
+
+        if absolute_paths && entry_path.is_absolute() {
+            // Entries stored with absolute paths are restored to their
+            // absolute location, the user opted in with --absolute-paths
+            if let Some(parent) = entry_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            file.unpack(&entry_path)?;
+        } else {
+            let unpacked = file.unpack_in(output_folder).map_err(|err| {
+                // Most useful when the disk fills up mid-extraction
+                warning(format!("{files_unpacked} entries were extracted before the failure"));
+                crate::Error::from(err)
+            })?;
+            if !unpacked {
+                // Paths with `..` components are refused to avoid writing
+                // outside of the output directory
+                warning(format!(
+                    "Skipping entry '{}', it would escape the output directory",
+                    EscapedPathDisplay::new(&entry_path)
+                ));
+                continue;
+            }
+        }
+
+        // Placeholders written by --skip-zeros grow back to their real
+        // size; set_len keeps them sparse, so the zeros cost no disk blocks
+        if entry_type.is_file() {
+            if let Some(zeros_size) = pax_zeros_size(&mut file)? {
+                let target = if absolute_paths && entry_path.is_absolute() {
+                    entry_path.clone()
+                } else {
+                    output_folder.join(&entry_path)
+                };
+                fs::OpenOptions::new().write(true).open(&target)?.set_len(zeros_size)?;
+            }
+        }
+
+        // Special mode bits (setuid/setgid/sticky) are dropped by the
+        // baseline unpack; sticky is restored freely, setuid/setgid only
+        // with --allow-setuid since restoring them from untrusted archives
+        // is dangerous
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            // Tolerate blank/invalid mode fields like the unpack itself does
+            let mode = file.header().mode().unwrap_or(0);
+            let special_bits = mode & 0o7000;
+            if special_bits != 0 {
+                let target = output_folder.join(file.path()?);
+                let restored_mode = if special_bits & 0o6000 != 0 && !allow_setuid {
+                    warning(format!(
+                        "Not restoring the setuid/setgid bits of '{}', pass --allow-setuid to keep them",
+                        EscapedPathDisplay::new(&target)
+                    ));
+                    mode & 0o1777
+                } else {
+                    mode & 0o7777
+                };
+                let _ = std::fs::set_permissions(&target, std::fs::Permissions::from_mode(restored_mode));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = allow_setuid;
+
+        // Restore (remapped) ownership when requested, which needs the
+        // privileges to chown
+        #[cfg(unix)]
+        if let Some(owner_map) = owner_map {
+            let target = output_folder.join(file.path()?);
+            let uid = owner_map.resolve(file.header().uid()?) as u32;
+            let gid = owner_map.resolve(file.header().gid()?) as u32;
+            if std::os::unix::fs::chown(&target, Some(uid), Some(gid)).is_err() && !quiet {
+                warning(format!(
+                    "Could not change the owner of '{}' to {uid}:{gid}",
+                    EscapedPathDisplay::new(&target)
+                ));
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = owner_map;
 
         // This is printed for every file in the archive and has little
         // importance for most users, but would generate lots of
@@ -43,14 +248,308 @@ pub fn unpack_archive(reader: Box<dyn Read>, output_folder: &Path, quiet: bool)
                 utils::strip_cur_dir(&output_folder.join(file.path()?)),
                 Bytes::new(file.size()),
             ));
+        }
+
+        files_unpacked += 1;
+    }
 
-            files_unpacked += 1;
+    // The reader tolerates padding and trailing garbage after the two zero
+    // end-of-archive blocks; --strict-tar enforces a clean ending
+    if strict_tar {
+        let mut reader = archive.into_inner();
+        let mut trailing = [0u8; 512];
+        loop {
+            let read = reader.read(&mut trailing)?;
+            if read == 0 {
+                break;
+            }
+            if trailing[..read].iter().any(|byte| *byte != 0) {
+                return Err(FinalError::with_title("Trailing data after the tar end-of-archive marker")
+                    .detail("The archive continues past its end blocks, which --strict-tar refuses")
+                    .hint("Drop --strict-tar to tolerate the trailing bytes.")
+                    .into());
+            }
         }
     }
 
     Ok(files_unpacked)
 }
 
+/// Whether the opened file consists only of zero bytes.
+fn is_all_zeros(file: &mut std::fs::File) -> io::Result<bool> {
+    let mut buffer = [0u8; BUFFER_CAPACITY];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(true);
+        }
+        if buffer[..read].iter().any(|byte| *byte != 0) {
+            return Ok(false);
+        }
+    }
+}
+
+/// Appends a pax extended header record describing the next entry, in the
+/// POSIX length-prefixed `<len> <key>=<value>\n` encoding (the tar crate
+/// can read pax records but not write them).
+fn append_pax_record<W: Write>(builder: &mut tar::Builder<W>, key: &str, value: &str) -> io::Result<()> {
+    append_pax_record_with_type(builder, key, value, tar::EntryType::XHeader)
+}
+
+fn append_pax_record_with_type<W: Write>(
+    builder: &mut tar::Builder<W>,
+    key: &str,
+    value: &str,
+    entry_type: tar::EntryType,
+) -> io::Result<()> {
+    let record = format!(" {key}={value}\n");
+    // The prefixed length counts its own digits, so grow to a fixed point
+    let mut length = record.len();
+    loop {
+        let total = length.to_string().len() + record.len();
+        if total == length {
+            break;
+        }
+        length = total;
+    }
+    let content = format!("{length}{record}");
+
+    let name = if entry_type == tar::EntryType::XGlobalHeader {
+        "pax_global_header"
+    } else {
+        "pax-extended-header"
+    };
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(entry_type);
+    header.set_mode(0o644);
+    header.set_size(content.len() as u64);
+    builder.append_data(&mut header, name, content.as_bytes())
+}
+
+/// Reads the `OUCH.zeros_size` pax record written by `--skip-zeros`, if the
+/// entry carries one.
+fn pax_zeros_size(file: &mut tar::Entry<impl Read>) -> crate::Result<Option<u64>> {
+    let Ok(Some(pax_extensions)) = file.pax_extensions() else {
+        return Ok(None);
+    };
+
+    for extension in pax_extensions.flatten() {
+        if extension.key() == Ok(PAX_ZEROS_SIZE_KEY) {
+            return Ok(extension.value().ok().and_then(|value| value.parse().ok()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Delivers exactly `size` bytes from the inner reader: shorter sources are
+/// zero-padded, longer ones truncated, and either case raises the mismatch
+/// flag. Keeps a file that changes mid-read from corrupting the tar layout,
+/// whose entry size was already written.
+struct FixedSizeReader<R> {
+    inner: R,
+    remaining: u64,
+    mismatch: Arc<AtomicBool>,
+}
+
+impl<R: Read> FixedSizeReader<R> {
+    fn new(inner: R, size: u64, mismatch: Arc<AtomicBool>) -> Self {
+        Self {
+            inner,
+            remaining: size,
+            mismatch,
+        }
+    }
+}
+
+impl<R: Read> Read for FixedSizeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining == 0 {
+            // Detect a source that kept growing past the declared size
+            let mut probe = [0u8; 1];
+            if self.inner.read(&mut probe)? > 0 {
+                self.mismatch.store(true, Ordering::Relaxed);
+            }
+            return Ok(0);
+        }
+
+        let capped = buf.len().min(self.remaining as usize);
+        let read = self.inner.read(&mut buf[..capped])?;
+        if read == 0 {
+            // The source shrank, pad the rest of the entry with zeros
+            self.mismatch.store(true, Ordering::Relaxed);
+            buf[..capped].fill(0);
+            self.remaining -= capped as u64;
+            return Ok(capped);
+        }
+
+        self.remaining -= read as u64;
+        Ok(read)
+    }
+}
+
+/// Directory state for `--listed-incremental`: what the previous run saw
+/// (loaded from the snapshot file) and what this run records.
+///
+/// The snapshot is ouch's own line-based format, not GNU tar's binary
+/// `.snar` (which needs dumpdir entries the tar crate cannot write);
+/// deletions therefore aren't recorded, only changed and new files are
+/// archived on incremental runs.
+struct IncrementalSnapshot {
+    previous: HashMap<PathBuf, (u64, u64)>,
+    current: Vec<(PathBuf, (u64, u64))>,
+}
+
+const INCREMENTAL_SNAPSHOT_HEADER: &str = "ouch-incremental-1";
+
+impl IncrementalSnapshot {
+    /// Loads the snapshot, an absent file meaning a level-0 (full) run.
+    fn load(path: &Path) -> crate::Result<Self> {
+        let previous = match fs::read_to_string(path) {
+            Ok(contents) => {
+                let mut lines = contents.lines();
+                if lines.next() != Some(INCREMENTAL_SNAPSHOT_HEADER) {
+                    return Err(FinalError::with_title(format!(
+                        "Invalid incremental snapshot file '{}'",
+                        EscapedPathDisplay::new(path)
+                    ))
+                    .detail("The file was not written by ouch --listed-incremental")
+                    .into());
+                }
+
+                let mut previous = HashMap::new();
+                for line in lines {
+                    let mut fields = line.splitn(3, '\t');
+                    let (Some(mtime), Some(size), Some(entry_path)) =
+                        (fields.next(), fields.next(), fields.next())
+                    else {
+                        continue;
+                    };
+                    if let (Ok(mtime), Ok(size)) = (mtime.parse(), size.parse()) {
+                        previous.insert(PathBuf::from(entry_path), (mtime, size));
+                    }
+                }
+                previous
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        Ok(Self {
+            previous,
+            current: vec![],
+        })
+    }
+
+    fn is_unchanged(&self, path: &Path, mtime: u64, size: u64) -> bool {
+        self.previous.get(path) == Some(&(mtime, size))
+    }
+
+    fn record(&mut self, path: &Path, mtime: u64, size: u64) {
+        self.current.push((path.to_path_buf(), (mtime, size)));
+    }
+
+    fn save(&self, path: &Path) -> crate::Result<()> {
+        let mut contents = String::from(INCREMENTAL_SNAPSHOT_HEADER);
+        contents.push('\n');
+        for (entry_path, (mtime, size)) in &self.current {
+            contents.push_str(&format!("{mtime}\t{size}\t{}\n", entry_path.display()));
+        }
+        fs::write(path, contents)?;
+
+        Ok(())
+    }
+}
+
+
+/// Reports a walk that matched nothing: the archive would be empty, which
+/// is usually a filtering mistake (see `--error-on-empty`).
+pub(crate) fn check_empty_archive(appended_entries: u64, error_on_empty: bool) -> crate::Result<()> {
+    if appended_entries > 0 {
+        return Ok(());
+    }
+
+    if error_on_empty {
+        return Err(crate::error::FinalError::with_title("The archive would be empty")
+            .detail("The walk yielded 0 entries, no file matched the filters")
+            .into());
+    }
+    warning("Wrote an empty archive, the walk yielded 0 entries (filters may be too aggressive)".into());
+
+    Ok(())
+}
+
+/// Recreates a FIFO or device node under `output_folder`, requested with
+/// `--preserve-special`. Creation failures (e.g. mknod without privileges)
+/// are reported as warnings instead of aborting the extraction.
+#[cfg(unix)]
+fn unpack_special_file(header: &tar::Header, entry_path: &Path, output_folder: &Path) -> crate::Result<()> {
+    use std::{ffi::CString, os::unix::ffi::OsStrExt};
+
+    // Special entries don't go through tar's unpack_in, so apply the same
+    // path hygiene here: trim a leading '/' and refuse `..` components
+    let relative_path = entry_path.strip_prefix("/").unwrap_or(entry_path);
+    if relative_path
+        .components()
+        .any(|component| matches!(component, std::path::Component::ParentDir))
+    {
+        warning(format!(
+            "Skipping special file '{}', it would escape the output directory",
+            EscapedPathDisplay::new(entry_path)
+        ));
+        return Ok(());
+    }
+
+    let target_path = output_folder.join(relative_path);
+    if let Some(parent) = target_path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let Ok(target_cstr) = CString::new(target_path.as_os_str().as_bytes()) else {
+        warning(format!(
+            "Skipping special file '{}', the path contains a NUL byte",
+            EscapedPathDisplay::new(entry_path)
+        ));
+        return Ok(());
+    };
+
+    let mode = header.mode()? as libc::mode_t;
+    let result = match header.entry_type() {
+        tar::EntryType::Fifo => unsafe { libc::mkfifo(target_cstr.as_ptr(), mode) },
+        entry_type => {
+            let kind = if entry_type == tar::EntryType::Char {
+                libc::S_IFCHR
+            } else {
+                libc::S_IFBLK
+            };
+            let major = header.device_major()?.unwrap_or(0);
+            let minor = header.device_minor()?.unwrap_or(0);
+            unsafe { libc::mknod(target_cstr.as_ptr(), kind | mode, libc::makedev(major, minor)) }
+        }
+    };
+
+    if result != 0 {
+        warning(format!(
+            "Could not create special file '{}': {}",
+            EscapedPathDisplay::new(entry_path),
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Special files cannot be restored on this platform, skip them with a warning.
+#[cfg(not(unix))]
+fn unpack_special_file(_header: &tar::Header, entry_path: &Path, _output_folder: &Path) -> crate::Result<()> {
+    warning(format!(
+        "Skipping special file '{}', it cannot be restored on this platform",
+        EscapedPathDisplay::new(entry_path)
+    ));
+
+    Ok(())
+}
+
 /// List contents of `archive`, returning a vector of archive entries
 pub fn list_archive(
     mut archive: tar::Archive<impl Read + Send + 'static>,
@@ -68,12 +567,50 @@ pub fn list_archive(
     thread::spawn(move || {
         for file in archive.entries().expect("entries is only used once") {
             let file_in_archive = (|| {
-                let file = file?;
+                let mut file = file?;
+
+                // Surface metadata from pax global extended headers (e.g. a
+                // comment or build ids embedded by CI tarball producers)
+                // instead of listing them as a file
+                if file.header().entry_type() == tar::EntryType::XGlobalHeader {
+                    if let Ok(Some(pax_extensions)) = file.pax_extensions() {
+                        for extension in pax_extensions.flatten() {
+                            if let (Ok(key), Ok(value)) = (extension.key(), extension.value()) {
+                                info_accessible(format!("Global pax header: {key}={value}"));
+                            }
+                        }
+                    }
+                    return Ok(None);
+                }
+
                 let path = file.path()?.into_owned();
                 let is_dir = file.header().entry_type().is_dir();
-                Ok(FileInArchive { path, is_dir })
+                // Tar itself does not compress, entries are plainly stored
+                let details = Some(crate::list::EntryDetails {
+                    method: "store".into(),
+                    compressed_size: file.size(),
+                    uncompressed_size: file.size(),
+                });
+                let link_target = file.link_name()?.map(|target| target.into_owned());
+                let mtime = file
+                    .header()
+                    .mtime()
+                    .ok()
+                    .and_then(|secs| OffsetDateTime::from_unix_timestamp(secs as i64).ok());
+                Ok(Some(FileInArchive {
+                    path,
+                    is_dir,
+                    details,
+                    link_target,
+                    mtime,
+                }))
             })();
-            tx.send(file_in_archive).unwrap();
+
+            match file_in_archive {
+                Ok(None) => continue,
+                Ok(Some(file_in_archive)) => tx.send(Ok(file_in_archive)).unwrap(),
+                Err(error) => tx.send(Err(error)).unwrap(),
+            }
         }
     });
 
@@ -81,12 +618,29 @@ pub fn list_archive(
 }
 
 /// Compresses the archives given by `input_filenames` into the file given previously to `writer`.
+#[allow(clippy::too_many_arguments)]
 pub fn build_archive_from_paths<W>(
     input_filenames: &[PathBuf],
     output_path: &Path,
     writer: W,
     file_visibility_policy: FileVisibilityPolicy,
     quiet: bool,
+    mtime: Option<OffsetDateTime>,
+    base_dir: Option<&Path>,
+    size_filter: SizeFilter,
+    time_filter: utils::TimeFilter,
+    prune_empty: bool,
+    no_dir_entries: bool,
+    dedup: bool,
+    total_files: Option<u64>,
+    io_threads: usize,
+    listed_incremental: Option<&Path>,
+    error_on_empty: bool,
+    relativize_symlinks: bool,
+    manifest: bool,
+    normalize_permissions: bool,
+    skip_zeros: bool,
+    embed_total_size: bool,
 ) -> crate::Result<W>
 where
     W: Write,
@@ -94,16 +648,123 @@ where
     let mut builder = tar::Builder::new(writer);
     let output_handle = Handle::from_path(output_path);
 
+    // --embed-total-size pre-walks the inputs to know the grand total
+    // before the first entry, so consumers can read it without scanning
+    // the whole archive
+    if embed_total_size {
+        let mut total: u64 = 0;
+        for filename in input_filenames {
+            let (previous_location, filename) = utils::cd_for_archiving(filename, base_dir)?;
+            for entry in file_visibility_policy.build_walker(&filename)? {
+                let entry = entry?;
+                let path = entry.path();
+                let Ok(metadata) = path.metadata() else { continue };
+                if !metadata.is_file() {
+                    continue;
+                }
+                if size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                    continue;
+                }
+                if time_filter.is_active() && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime)) {
+                    continue;
+                }
+                total += metadata.len();
+            }
+            env::set_current_dir(previous_location)?;
+        }
+
+        append_pax_record_with_type(
+            &mut builder,
+            PAX_TOTAL_SIZE_KEY,
+            &total.to_string(),
+            tar::EntryType::XGlobalHeader,
+        )?;
+    }
+    let mut discovery_counter = utils::DiscoveryCounter::new(quiet).with_total(total_files);
+    let mut size_filtered_count: u64 = 0;
+    let mut appended_entries: u64 = 0;
+    // Maps (size, content hash) of already-stored files to their entry path,
+    // used by --dedup to emit hard-link entries for byte-identical files
+    let mut stored_contents: HashMap<(u64, [u8; 32]), PathBuf> = HashMap::new();
+    let mut dedup_saved_bytes: u64 = 0;
+    let mut dedup_count: u64 = 0;
+    // An explicit --mtime always wins over the on-disk modification times
+    let fixed_mtime = mtime.map(|mtime| mtime.unix_timestamp().max(0) as u64);
+    let mut incremental = listed_incremental.map(IncrementalSnapshot::load).transpose()?;
+    let mut manifest_lines = String::new();
+
     for filename in input_filenames {
-        let previous_location = utils::cd_into_same_dir_as(filename)?;
+        let (previous_location, filename) = utils::cd_for_archiving(filename, base_dir)?;
 
-        // Unwrap safety:
-        //   paths should be canonicalized by now, and the root directory rejected.
-        let filename = filename.file_name().unwrap();
+        // With --prune-empty, a pre-walk finds the directories that still
+        // hold at least one file after the filters
+        let kept_dirs = if prune_empty {
+            Some(utils::dirs_with_passing_files(
+                &filename,
+                &file_visibility_policy,
+                size_filter,
+                time_filter,
+            )?)
+        } else {
+            None
+        };
 
-        for entry in file_visibility_policy.build_walker(filename) {
+        for entry in file_visibility_policy.build_walker(&filename)? {
             let entry = entry?;
             let path = entry.path();
+            discovery_counter.tick();
+
+            // Symlinks are followed (and their content stored) unless
+            // --relativize-symlinks asked for link entries
+            if relativize_symlinks && utils::is_symlink(path) {
+                if let Ok(target) = fs::read_link(path) {
+                    let target = if target.is_absolute() {
+                        let root_abs = env::current_dir()?.join(&filename);
+                        match utils::relativize_symlink_target(&target, path, &filename, &root_abs) {
+                            Some(relative) => relative,
+                            None => {
+                                warning(format!(
+                                    "The symlink '{}' points outside of the archived tree, storing the absolute target",
+                                    EscapedPathDisplay::new(path)
+                                ));
+                                target
+                            }
+                        }
+                    } else {
+                        target
+                    };
+
+                    let mut header = tar::Header::new_gnu();
+                    header.set_metadata(&fs::symlink_metadata(path)?);
+                    if let Some(fixed_mtime) = fixed_mtime {
+                        header.set_mtime(fixed_mtime);
+                    }
+                    builder.append_link(&mut header, path, &target)?;
+                    appended_entries += 1;
+                    continue;
+                }
+            }
+
+            // Stat before anything opens the path: both the same-file check
+            // below and the compression itself would block on a FIFO
+            let metadata = match fs::metadata(path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
+                        // This path is for a broken symlink
+                        // We just ignore it
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            };
+            if !metadata.is_dir() && !metadata.is_file() {
+                warning(format!(
+                    "Skipping '{}', not a regular file",
+                    EscapedPathDisplay::new(path)
+                ));
+                continue;
+            }
 
             // If the output_path is the same as the input file, warn the user and skip the input (in order to avoid compression recursion)
             if let Ok(handle) = &output_handle {
@@ -126,20 +787,158 @@ where
             }
 
             if path.is_dir() {
-                builder.append_dir(path, path)?;
+                if no_dir_entries {
+                    continue;
+                }
+                if let Some(kept_dirs) = &kept_dirs {
+                    if !kept_dirs.contains(path) {
+                        continue;
+                    }
+                }
+
+                if fixed_mtime.is_some() || normalize_permissions {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_metadata(&metadata);
+                    if let Some(fixed_mtime) = fixed_mtime {
+                        header.set_mtime(fixed_mtime);
+                    }
+                    if normalize_permissions {
+                        header.set_mode(utils::normalized_mode(path, &metadata));
+                    }
+                    builder.append_data(&mut header, path, io::empty())?;
+                } else {
+                    builder.append_dir(path, path)?;
+                }
             } else {
-                let mut file = match fs::File::open(path) {
-                    Ok(f) => f,
-                    Err(e) => {
-                        if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
-                            // This path is for a broken symlink
-                            // We just ignore it
+                // Incremental runs only archive files that are new or whose
+                // mtime/size changed since the snapshot was written
+                if let Some(incremental) = incremental.as_mut() {
+                    let entry_mtime = metadata
+                        .modified()
+                        .ok()
+                        .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map_or(0, |since_epoch| since_epoch.as_secs());
+                    let unchanged = incremental.is_unchanged(path, entry_mtime, metadata.len());
+                    incremental.record(path, entry_mtime, metadata.len());
+                    if unchanged {
+                        continue;
+                    }
+                }
+
+                if size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                    warning(format!(
+                        "Skipping '{}' ({}), outside of the size filter",
+                        EscapedPathDisplay::new(path),
+                        Bytes::new(metadata.len()),
+                    ));
+                    size_filtered_count += 1;
+                    continue;
+                }
+
+                if time_filter.is_active() && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime)) {
+                    size_filtered_count += 1;
+                    continue;
+                }
+
+                appended_entries += 1;
+                discovery_counter.add_bytes(metadata.len());
+
+                // The manifest hashes each member before it gets appended
+                if manifest {
+                    let digest = utils::checksum::hash_file(path, crate::cli::ChecksumAlgorithm::Sha256)?;
+                    manifest_lines.push_str(&format!("{digest}  {}\n", path.display()));
+                }
+
+                let _fd_permit = utils::io::fd_quota::acquire();
+                let mut file = fs::File::open(path)?;
+
+                // --skip-zeros stores files that are entirely zero bytes
+                // (preallocated disk images, database files) as empty
+                // placeholders; the real size travels in a pax record and
+                // extraction grows them back via set_len
+                if skip_zeros && metadata.len() > 0 {
+                    let all_zeros = is_all_zeros(file.file_mut())?;
+                    file.file_mut().rewind()?;
+
+                    if all_zeros {
+                        append_pax_record(&mut builder, PAX_ZEROS_SIZE_KEY, &metadata.len().to_string())?;
+
+                        let mut header = tar::Header::new_gnu();
+                        header.set_metadata(&metadata);
+                        header.set_size(0);
+                        if let Some(fixed_mtime) = fixed_mtime {
+                            header.set_mtime(fixed_mtime);
+                        }
+                        if normalize_permissions {
+                            header.set_mode(utils::normalized_mode(path, &metadata));
+                        }
+                        builder.append_data(&mut header, path, io::empty())?;
+                        continue;
+                    }
+                }
+
+                if dedup {
+                    let file_size = metadata.len();
+                    let content_hash = utils::io::hash_reader_blake3(file.file_mut())?;
+                    file.file_mut().rewind()?;
+
+                    match stored_contents.entry((file_size, content_hash)) {
+                        Entry::Occupied(original) => {
+                            // Byte-identical to an already-stored file, emit
+                            // a hard-link entry referencing it instead
+                            let mut header = tar::Header::new_gnu();
+                            header.set_metadata(&file.metadata()?);
+                            header.set_entry_type(tar::EntryType::Link);
+                            header.set_size(0);
+                            if let Some(fixed_mtime) = fixed_mtime {
+                                header.set_mtime(fixed_mtime);
+                            }
+                            builder.append_link(&mut header, path, original.get())?;
+
+                            dedup_saved_bytes += file_size;
+                            dedup_count += 1;
                             continue;
                         }
-                        return Err(e.into());
+                        Entry::Vacant(slot) => {
+                            slot.insert(path.to_path_buf());
+                        }
+                    }
+                }
+                // Every append goes through a size-pinned reader: a file
+                // shrinking or growing between stat and read (live logs)
+                // would otherwise corrupt the tar layout
+                let append_result = {
+                    let mut header = tar::Header::new_gnu();
+                    header.set_metadata(&metadata);
+                    if let Some(fixed_mtime) = fixed_mtime {
+                        header.set_mtime(fixed_mtime);
+                    }
+                    if normalize_permissions {
+                        header.set_mode(utils::normalized_mode(path, &metadata));
                     }
+
+                    let mismatch = Arc::new(AtomicBool::new(false));
+                    let result = if io_threads > 0 {
+                        // Reads run on a background thread so input latency
+                        // overlaps with the compression work
+                        let reader = utils::io::PrefetchReader::new(file.into_parts().0, io_threads);
+                        let reader = FixedSizeReader::new(reader, metadata.len(), Arc::clone(&mismatch));
+                        builder.append_data(&mut header, path, reader)
+                    } else {
+                        let reader = FixedSizeReader::new(file.file_mut(), metadata.len(), Arc::clone(&mismatch));
+                        builder.append_data(&mut header, path, reader)
+                    };
+
+                    if mismatch.load(Ordering::Relaxed) {
+                        warning(format!(
+                            "'{}' changed while being archived, its entry was padded/truncated to the declared size",
+                            EscapedPathDisplay::new(path)
+                        ));
+                    }
+
+                    result
                 };
-                builder.append_file(path, file.file_mut()).map_err(|err| {
+                append_result.map_err(|err| {
                     FinalError::with_title("Could not create archive")
                         .detail("Unexpected error while trying to read file")
                         .detail(format!("Error: {err}."))
@@ -149,5 +948,67 @@ where
         env::set_current_dir(previous_location)?;
     }
 
+    check_empty_archive(appended_entries, error_on_empty)?;
+
+    if size_filtered_count > 0 {
+        info_accessible(format!("{size_filtered_count} files skipped by the size/time filters."));
+    }
+    if dedup_count > 0 {
+        info_accessible(format!(
+            "Deduplicated {dedup_count} files, saving {}.",
+            Bytes::new(dedup_saved_bytes)
+        ));
+    }
+
+    if manifest {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(manifest_lines.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(fixed_mtime.unwrap_or(0));
+        builder.append_data(&mut header, "MANIFEST.sha256", manifest_lines.as_bytes())?;
+    }
+
+    if let (Some(incremental), Some(snapshot_path)) = (incremental, listed_incremental) {
+        incremental.save(snapshot_path)?;
+        info_accessible(format!(
+            "Recorded {} files in the snapshot '{}'.",
+            incremental.current.len(),
+            EscapedPathDisplay::new(snapshot_path)
+        ));
+    }
+
     Ok(builder.into_inner()?)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixed_size_reader_pads_and_truncates() {
+        // Shrunk source: padded with zeros up to the declared size
+        let mismatch = Arc::new(AtomicBool::new(false));
+        let mut reader = FixedSizeReader::new(&b"abc"[..], 6, Arc::clone(&mismatch));
+        let mut out = vec![];
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"abc\0\0\0");
+        assert!(mismatch.load(Ordering::Relaxed));
+
+        // Grown source: truncated to the declared size
+        let mismatch = Arc::new(AtomicBool::new(false));
+        let mut reader = FixedSizeReader::new(&b"abcdef"[..], 3, Arc::clone(&mismatch));
+        let mut out = vec![];
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"abc");
+        assert!(mismatch.load(Ordering::Relaxed));
+
+        // Stable source: untouched, no mismatch
+        let mismatch = Arc::new(AtomicBool::new(false));
+        let mut reader = FixedSizeReader::new(&b"abc"[..], 3, Arc::clone(&mismatch));
+        let mut out = vec![];
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"abc");
+        assert!(!mismatch.load(Ordering::Relaxed));
+    }
+}
@@ -5,6 +5,7 @@ use std::os::unix::fs::PermissionsExt;
 use std::{
     env,
     io::{self, prelude::*},
+    ops::ControlFlow,
     path::{Path, PathBuf},
     sync::mpsc,
     thread,
@@ -20,30 +21,71 @@ use crate::{
     error::FinalError,
     list::FileInArchive,
     utils::{
-        self, cd_into_same_dir_as, get_invalid_utf8_paths,
+        self, get_invalid_utf8_paths,
         logger::{info, info_accessible, warning},
-        pretty_format_list_of_paths, strip_cur_dir, Bytes, EscapedPathDisplay, FileVisibilityPolicy,
+        bomb::BombGuard,
+        pretty_format_list_of_paths, strip_cur_dir, Bytes, DuplicatePolicy, EscapedPathDisplay, FileVisibilityPolicy,
+        SizeFilter,
     },
 };
 
 /// Unpacks the archive given by `archive` into the folder given by `output_folder`.
 /// Assumes that output_folder is empty
-pub fn unpack_archive<R>(mut archive: ZipArchive<R>, output_folder: &Path, quiet: bool) -> crate::Result<usize>
+pub fn unpack_archive<R>(
+    mut archive: ZipArchive<R>,
+    output_folder: &Path,
+    quiet: bool,
+    absolute_paths: bool,
+    preserve_attributes: bool,
+    on_duplicate: Option<DuplicatePolicy>,
+    bomb_guard: &BombGuard,
+) -> crate::Result<usize>
 where
     R: Read + Seek,
 {
     assert!(output_folder.read_dir().expect("dir exists").count() == 0);
 
+    // Check what the central directory declares before extracting anything,
+    // catching bombs made of huge counts of tiny entries up front
+    let declared_size: u64 = (0..archive.len())
+        .filter_map(|idx| archive.by_index_raw(idx).ok().map(|entry| entry.size()))
+        .sum();
+    bomb_guard.check_declared(archive.len() as u64, declared_size)?;
+
     let mut unpacked_files = 0;
+    let mut written_paths = utils::WrittenPaths::for_output_dir(output_folder);
 
     for idx in 0..archive.len() {
         let mut file = archive.by_index(idx)?;
-        let file_path = match file.enclosed_name() {
-            Some(path) => path.to_owned(),
-            None => continue,
+        let file_path = if absolute_paths && file.name().starts_with('/') {
+            // Entries stored with absolute paths are restored to their
+            // absolute location, the user opted in with --absolute-paths
+            PathBuf::from(file.name())
+        } else {
+            match sanitize_entry_path(file.name()) {
+                Some(path) => path,
+                None => {
+                    // Paths with `..` components are refused to avoid
+                    // writing outside of the output directory
+                    warning(format!(
+                        "Skipping entry '{}', it would escape the output directory",
+                        file.name()
+                    ));
+                    continue;
+                }
+            }
         };
 
-        let file_path = output_folder.join(file_path);
+        // The extended-length prefix lets paths beyond MAX_PATH extract on
+        // Windows (identity elsewhere)
+        let file_path = utils::extended_length_path(&output_folder.join(file_path));
+
+        if !file.is_dir() {
+            if let ControlFlow::Break(_) = utils::handle_duplicate_entry(&file_path, &mut written_paths, on_duplicate)? {
+                continue;
+            }
+            bomb_guard.add(file.size())?;
+        }
 
         display_zip_comment_if_exists(&file);
 
@@ -76,12 +118,101 @@ where
                 }
 
                 let mut output_file = fs::File::create(file_path)?;
-                io::copy(&mut file, &mut output_file)?;
+                io::copy(&mut file, &mut output_file).map_err(|err| {
+                    // Most useful when the disk fills up mid-extraction
+                    warning(format!("{unpacked_files} entries were extracted before the failure"));
+                    crate::Error::from(err)
+                })?;
+
+                // Restore a stored creation time where the platform allows
+                // setting it (no-op elsewhere)
+                if let Some(birth_time) = parse_extended_timestamp_btime(file.extra_data()) {
+                    let birth_time = filetime_creation::FileTime::from_unix_time(birth_time, 0);
+                    let _ = filetime_creation::set_file_handle_times(
+                        output_file.file(),
+                        None,
+                        None,
+                        Some(birth_time),
+                    );
+                }
 
                 set_last_modified_time(&file, file_path)?;
             }
         }
 
+        #[cfg(unix)]
+        unix_set_permissions(&file_path, &file)?;
+        #[cfg(windows)]
+        if preserve_attributes {
+            windows_set_attributes(&file_path, &file)?;
+        }
+        #[cfg(not(windows))]
+        let _ = preserve_attributes;
+
+        unpacked_files += 1;
+    }
+
+    Ok(unpacked_files)
+}
+
+/// Unpacks a zip read from a non-seekable source (e.g. a pipe) by walking
+/// the local file headers with `read_zipfile_from_stream`, instead of the
+/// central directory that `unpack_archive` can seek to. Data descriptors and
+/// the authoritative central directory metadata are not available here, so
+/// on-disk archives should prefer the seekable path.
+pub fn unpack_archive_streaming(
+    mut reader: impl Read,
+    output_folder: &Path,
+    quiet: bool,
+    on_duplicate: Option<DuplicatePolicy>,
+    bomb_guard: &BombGuard,
+) -> crate::Result<usize> {
+    assert!(output_folder.read_dir().expect("dir exists").count() == 0);
+
+    let mut unpacked_files = 0;
+    let mut written_paths = utils::WrittenPaths::for_output_dir(output_folder);
+
+    while let Some(mut file) = zip::read::read_zipfile_from_stream(&mut reader)? {
+        bomb_guard.add(file.size())?;
+        let file_path = match sanitize_entry_path(file.name()) {
+            Some(path) => path,
+            None => {
+                warning(format!(
+                    "Skipping entry '{}', it would escape the output directory",
+                    file.name()
+                ));
+                continue;
+            }
+        };
+        let file_path = utils::extended_length_path(&output_folder.join(file_path));
+
+        if file.is_dir() {
+            fs::create_dir_all(&file_path)?;
+            continue;
+        }
+
+        if let ControlFlow::Break(_) = utils::handle_duplicate_entry(&file_path, &mut written_paths, on_duplicate)? {
+            continue;
+        }
+
+        if let Some(parent) = file_path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent)?;
+            }
+        }
+
+        if !quiet {
+            info(format!(
+                "{:?} extracted. ({})",
+                file_path.display(),
+                Bytes::new(file.size())
+            ));
+        }
+
+        let mut output_file = fs::File::create(&file_path)?;
+        io::copy(&mut file, &mut output_file)?;
+
+        set_last_modified_time(&file, &file_path)?;
         #[cfg(unix)]
         unix_set_permissions(&file_path, &file)?;
 
@@ -109,15 +240,39 @@ where
     thread::spawn(move || {
         for idx in 0..archive.len() {
             let maybe_file_in_archive = (|| {
-                let file = match archive.by_index(idx) {
+                let mut file = match archive.by_index(idx) {
                     Ok(f) => f,
                     Err(e) => return Some(Err(e.into())),
                 };
 
                 let path = file.enclosed_name()?.to_owned();
                 let is_dir = file.is_dir();
+                let details = Some(crate::list::EntryDetails {
+                    method: file.compression().to_string(),
+                    compressed_size: file.compressed_size(),
+                    uncompressed_size: file.size(),
+                });
 
-                Some(Ok(FileInArchive { path, is_dir }))
+                // Symlink entries store their target as the content; the
+                // declared entry size is attacker-controlled and must not
+                // drive an allocation, and targets are path-sized anyway
+                let is_symlink = file.unix_mode().is_some_and(|mode| mode & 0o170000 == 0o120000);
+                let link_target = if is_symlink {
+                    let mut target = vec![];
+                    (&mut file).take(SYMLINK_TARGET_LIMIT).read_to_end(&mut target).ok()?;
+                    Some(PathBuf::from(String::from_utf8_lossy(&target).into_owned()))
+                } else {
+                    None
+                };
+
+                let mtime = file.last_modified().to_time().ok();
+                Some(Ok(FileInArchive {
+                    path,
+                    is_dir,
+                    details,
+                    link_target,
+                    mtime,
+                }))
             })();
             if let Some(file_in_archive) = maybe_file_in_archive {
                 tx.send(file_in_archive).unwrap();
@@ -129,29 +284,58 @@ where
 }
 
 /// Compresses the archives given by `input_filenames` into the file given previously to `writer`.
+///
+/// Encrypted zip output (with an `--aes-strength <128|192|256>` selector on
+/// the key size) is blocked on write-side AES support in the `zip` crate:
+/// 0.6 only decrypts AES entries, and its legacy ZipCrypto writer is both
+/// crate-private and too weak to be worth exposing. Once the dependency can
+/// encrypt, the strength belongs in the `FileOptions` built below.
+#[allow(clippy::too_many_arguments)]
 pub fn build_archive_from_paths<W>(
     input_filenames: &[PathBuf],
     output_path: &Path,
     writer: W,
     file_visibility_policy: FileVisibilityPolicy,
     quiet: bool,
+    force_zip64: bool,
+    mtime: Option<OffsetDateTime>,
+    base_dir: Option<&Path>,
+    size_filter: SizeFilter,
+    time_filter: utils::TimeFilter,
+    prune_empty: bool,
+    no_dir_entries: bool,
+    total_files: Option<u64>,
+    auto_level: bool,
+    preserve_btime: bool,
+    error_on_empty: bool,
+    manifest: bool,
+    normalize_permissions: bool,
+    non_utf8: Option<crate::cli::NonUtf8Policy>,
 ) -> crate::Result<W>
 where
     W: Write + Seek,
 {
+    // Entries of 4GB or more need the zip64 extension to store their true
+    // size, smaller ones get the 20B of extra headers only with --force-zip64
+    // (the zip64 end-of-central-directory records needed when the archive
+    // exceeds 65535 entries are written by the zip crate automatically)
+    const ZIP64_SIZE_THRESHOLD: u64 = u32::MAX as u64;
+
     let mut writer = zip::ZipWriter::new(writer);
-    // always use ZIP64 to allow compression of files larger than 4GB
-    // the format is widely supported and the extra 20B is negligible in most cases
-    let options = zip::write::FileOptions::default().large_file(true);
+    let options = zip::write::FileOptions::default().large_file(force_zip64);
+    let mut discovery_counter = utils::DiscoveryCounter::new(quiet).with_total(total_files);
+    let mut size_filtered_count: u64 = 0;
+    let mut appended_entries: u64 = 0;
+    let mut manifest_lines = String::new();
+    // An explicit --mtime always wins over the on-disk modification times
+    let fixed_mtime = mtime.map(|mtime| DateTime::try_from(mtime).unwrap_or_default());
     let output_handle = Handle::from_path(output_path);
 
-    #[cfg(not(unix))]
-    let executable = options.unix_permissions(0o755);
-
-    // Vec of any filename that failed the UTF-8 check
+    // Vec of any filename that failed the UTF-8 check; with a --non-utf8
+    // policy the walk below handles every name individually instead
     let invalid_unicode_filenames = get_invalid_utf8_paths(input_filenames);
 
-    if !invalid_unicode_filenames.is_empty() {
+    if non_utf8.is_none() && !invalid_unicode_filenames.is_empty() {
         let error = FinalError::with_title("Cannot build zip archive")
             .detail("Zip archives require files to have valid UTF-8 paths")
             .detail(format!(
@@ -163,15 +347,46 @@ where
     }
 
     for filename in input_filenames {
-        let previous_location = cd_into_same_dir_as(filename)?;
+        let (previous_location, filename) = utils::cd_for_archiving(filename, base_dir)?;
 
-        // Unwrap safety:
-        //   paths should be canonicalized by now, and the root directory rejected.
-        let filename = filename.file_name().unwrap();
+        // With --prune-empty, a pre-walk finds the directories that still
+        // hold at least one file after the filters
+        let kept_dirs = if prune_empty {
+            Some(utils::dirs_with_passing_files(
+                &filename,
+                &file_visibility_policy,
+                size_filter,
+                time_filter,
+            )?)
+        } else {
+            None
+        };
 
-        for entry in file_visibility_policy.build_walker(filename) {
+        for entry in file_visibility_policy.build_walker(&filename)? {
             let entry = entry?;
             let path = entry.path();
+            discovery_counter.tick();
+
+            // Stat before anything opens the path: both the same-file check
+            // below and the compression itself would block on a FIFO
+            let metadata = match path.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
+                        // This path is for a broken symlink
+                        // We just ignore it
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            };
+            if !metadata.is_dir() && !metadata.is_file() {
+                warning(format!(
+                    "Skipping '{}', not a regular file",
+                    EscapedPathDisplay::new(path)
+                ));
+                continue;
+            }
 
             // If the output_path is the same as the input file, warn the user and skip the input (in order to avoid compression recursion)
             if let Ok(handle) = &output_handle {
@@ -180,6 +395,8 @@ where
                         "The output file and the input file are the same: `{}`, skipping...",
                         output_path.display()
                     ));
+
+                    continue;
                 }
             }
 
@@ -191,42 +408,156 @@ where
                 info(format!("Compressing '{}'.", EscapedPathDisplay::new(path)));
             }
 
-            let metadata = match path.metadata() {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
-                        // This path is for a broken symlink
-                        // We just ignore it
-                        continue;
-                    }
-                    return Err(e.into());
-                }
-            };
+            if !metadata.is_dir() && size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                warning(format!(
+                    "Skipping '{}' ({}), outside of the size filter",
+                    EscapedPathDisplay::new(path),
+                    Bytes::new(metadata.len()),
+                ));
+                size_filtered_count += 1;
+                continue;
+            }
+
+            if !metadata.is_dir()
+                && time_filter.is_active()
+                && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime))
+            {
+                size_filtered_count += 1;
+                continue;
+            }
 
             #[cfg(unix)]
-            let options = options.unix_permissions(metadata.permissions().mode());
+            let options = options.unix_permissions(if normalize_permissions {
+                utils::normalized_mode(path, &metadata)
+            } else {
+                metadata.permissions().mode()
+            });
+            #[cfg(not(unix))]
+            let _ = normalize_permissions;
+            let options = options.large_file(force_zip64 || metadata.len() >= ZIP64_SIZE_THRESHOLD);
+            // Store the real modification time of files and directories (in
+            // the DOS-format zip timestamp), unless --mtime pinned one
+            let options = options.last_modified_time(match fixed_mtime {
+                Some(fixed_mtime) => fixed_mtime,
+                None => get_last_modified_time(&metadata),
+            });
 
-            let entry_name = path.to_str().ok_or_else(|| {
-                FinalError::with_title("Zip requires that all directories names are valid UTF-8")
-                    .detail(format!("File at '{path:?}' has a non-UTF-8 name"))
-            })?;
+            let Some(entry_name) = utils::resolve_non_utf8_name(path, non_utf8, "Zip")? else {
+                continue;
+            };
+            let entry_name = entry_name.as_str();
 
             if metadata.is_dir() {
+                if no_dir_entries {
+                    continue;
+                }
+                if let Some(kept_dirs) = &kept_dirs {
+                    if !kept_dirs.contains(path) {
+                        continue;
+                    }
+                }
+
                 writer.add_directory(entry_name, options)?;
             } else {
+                // Store read-only inputs without write bits so extraction
+                // elsewhere can restore the attribute
                 #[cfg(not(unix))]
-                let options = if is_executable::is_executable(path) {
-                    executable
+                let options = {
+                    let mode = match (is_executable::is_executable(path), metadata.permissions().readonly()) {
+                        (true, false) => 0o755,
+                        (true, true) => 0o555,
+                        (false, false) => 0o644,
+                        (false, true) => 0o444,
+                    };
+                    options.unix_permissions(mode)
+                };
+
+                appended_entries += 1;
+                discovery_counter.add_bytes(metadata.len());
+
+                // The manifest hashes each member before it gets appended
+                if manifest {
+                    let digest = utils::checksum::hash_file(path, crate::cli::ChecksumAlgorithm::Sha256)?;
+                    manifest_lines.push_str(&format!("{digest}  {}\n", path.display()));
+                }
+
+                let _fd_permit = utils::io::fd_quota::acquire();
+                let mut file = fs::File::open(path)?;
+
+                // --auto-level stores entries whose first block looks
+                // incompressible instead of wasting time deflating them
+                let options = if auto_level {
+                    let mut probe = [0; crate::BUFFER_CAPACITY];
+                    let mut filled = 0;
+                    while filled < probe.len() {
+                        match file.read(&mut probe[filled..])? {
+                            0 => break,
+                            read => filled += read,
+                        }
+                    }
+                    file.seek(io::SeekFrom::Start(0))?;
+
+                    let entropy = utils::io::shannon_entropy(&probe[..filled]);
+                    if entropy > utils::io::INCOMPRESSIBLE_ENTROPY {
+                        if !quiet {
+                            info(format!(
+                                "Storing '{}' uncompressed (probe entropy {entropy:.2} bits/byte)",
+                                EscapedPathDisplay::new(path)
+                            ));
+                        }
+                        options.compression_method(zip::CompressionMethod::Stored)
+                    } else {
+                        options
+                    }
                 } else {
                     options
                 };
 
-                let mut file = fs::File::open(path)?;
+                // --preserve-btime stores the creation time in an extended
+                // timestamp (0x5455) extra field
+                let birth_time = if preserve_btime {
+                    metadata
+                        .created()
+                        .ok()
+                        .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+                        .map(|since_epoch| since_epoch.as_secs() as i32)
+                } else {
+                    None
+                };
 
-                // Updated last modified time
-                let last_modified_time = options.last_modified_time(get_last_modified_time(&file));
+                // Hidden/system/readonly travel in ouch's DOS-attributes
+                // extra field (Windows only, see DOS_ATTRIBUTES_TAG)
+                #[cfg(windows)]
+                let dos_attributes: u8 = {
+                    use std::os::windows::fs::MetadataExt;
+                    (metadata.file_attributes()
+                        & u32::from(DOS_ATTRIBUTE_READONLY | DOS_ATTRIBUTE_HIDDEN | DOS_ATTRIBUTE_SYSTEM))
+                        as u8
+                };
+                #[cfg(not(windows))]
+                let dos_attributes: u8 = 0;
 
-                writer.start_file(entry_name, last_modified_time)?;
+                if birth_time.is_some() || dos_attributes != 0 {
+                    writer.start_file_with_extra_data(entry_name, options)?;
+                    if let Some(birth_time) = birth_time {
+                        let mut field = Vec::with_capacity(9);
+                        field.extend(EXTENDED_TIMESTAMP_TAG.to_le_bytes());
+                        field.extend(5u16.to_le_bytes());
+                        field.push(EXTENDED_TIMESTAMP_CREATION_FLAG);
+                        field.extend(birth_time.to_le_bytes());
+                        writer.write_all(&field)?;
+                    }
+                    if dos_attributes != 0 {
+                        let mut field = Vec::with_capacity(5);
+                        field.extend(DOS_ATTRIBUTES_TAG.to_le_bytes());
+                        field.extend(1u16.to_le_bytes());
+                        field.push(dos_attributes);
+                        writer.write_all(&field)?;
+                    }
+                    writer.end_extra_data()?;
+                } else {
+                    writer.start_file(entry_name, options)?;
+                }
                 io::copy(&mut file, &mut writer)?;
             }
         }
@@ -234,10 +565,91 @@ where
         env::set_current_dir(previous_location)?;
     }
 
+    if manifest {
+        writer.start_file("MANIFEST.sha256", zip::write::FileOptions::default())?;
+        writer.write_all(manifest_lines.as_bytes())?;
+    }
+
+    crate::archive::tar::check_empty_archive(appended_entries, error_on_empty)?;
+
+    if size_filtered_count > 0 {
+        info_accessible(format!("{size_filtered_count} files skipped by the size/time filters."));
+    }
+
     let bytes = writer.finish()?;
     Ok(bytes)
 }
 
+/// Longest symlink target read out of an entry's content, comfortably
+/// above every platform's path limit.
+const SYMLINK_TARGET_LIMIT: u64 = 4096;
+
+/// The "UT" extended timestamp extra field and its creation-time flag bit.
+const EXTENDED_TIMESTAMP_TAG: u16 = 0x5455;
+const EXTENDED_TIMESTAMP_CREATION_FLAG: u8 = 1 << 2;
+
+/// Ouch's DOS-attributes extra field: the readonly/hidden/system bits
+/// canonically live in the MS-DOS byte of the external attributes, which
+/// the zip crate gives no way to write, so they travel in an (unreserved)
+/// extra field instead and are applied back with SetFileAttributes.
+const DOS_ATTRIBUTES_TAG: u16 = 0x6F75;
+#[cfg(windows)]
+const DOS_ATTRIBUTE_READONLY: u8 = 0x01;
+#[cfg(windows)]
+const DOS_ATTRIBUTE_HIDDEN: u8 = 0x02;
+#[cfg(windows)]
+const DOS_ATTRIBUTE_SYSTEM: u8 = 0x04;
+
+/// Looks for a creation time stored in an extended timestamp extra field.
+fn parse_extended_timestamp_btime(extra: &[u8]) -> Option<i64> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = usize::from(u16::from_le_bytes([rest[2], rest[3]]));
+        let body = rest.get(4..4 + size)?;
+
+        if id == EXTENDED_TIMESTAMP_TAG && !body.is_empty() {
+            let flags = body[0];
+            let mut offset = 1;
+            // Times appear in flag-bit order: mtime, atime, creation
+            for bit in 0..3 {
+                if flags & (1 << bit) == 0 {
+                    continue;
+                }
+                let bytes: [u8; 4] = body.get(offset..offset + 4)?.try_into().ok()?;
+                if bit == 2 {
+                    return Some(i32::from_le_bytes(bytes).into());
+                }
+                offset += 4;
+            }
+            return None;
+        }
+
+        rest = &rest[4 + size..];
+    }
+
+    None
+}
+
+/// Strips the leading '/' from entry names stored with absolute paths and
+/// refuses paths that would escape the output directory through `..`.
+///
+/// Windows-made zips may store backslash separators; those are normalized
+/// to nested directories instead of extracting as flat files with literal
+/// backslashes in their names.
+pub(crate) fn sanitize_entry_path(name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    let name = name.trim_start_matches('/');
+    let path = Path::new(name);
+
+    let is_path_unsafe = path.components().any(|component| {
+        use std::path::Component::*;
+        matches!(component, Prefix(_) | RootDir | ParentDir)
+    });
+
+    (!is_path_unsafe).then(|| path.to_path_buf())
+}
+
 fn display_zip_comment_if_exists(file: &ZipFile) {
     let comment = file.comment();
     if !comment.is_empty() {
@@ -255,9 +667,9 @@ fn display_zip_comment_if_exists(file: &ZipFile) {
     }
 }
 
-fn get_last_modified_time(file: &fs::File) -> DateTime {
-    file.metadata()
-        .and_then(|metadata| metadata.modified())
+fn get_last_modified_time(metadata: &std::fs::Metadata) -> DateTime {
+    metadata
+        .modified()
         .ok()
         .and_then(|time| DateTime::try_from(OffsetDateTime::from(time)).ok())
         .unwrap_or_default()
@@ -288,3 +700,377 @@ fn unix_set_permissions(file_path: &Path, file: &ZipFile) -> crate::Result<()> {
 
     Ok(())
 }
+
+/// Restores the readonly/hidden/system attributes on Windows, requested
+/// with `--preserve-attributes`: the stored DOS-attributes extra field
+/// wins, entries without one fall back to the readonly signal a write-less
+/// unix mode carries.
+#[cfg(windows)]
+fn windows_set_attributes(file_path: &Path, file: &ZipFile) -> crate::Result<()> {
+    use std::os::windows::ffi::OsStrExt;
+
+    use windows_sys::Win32::Storage::FileSystem::{
+        SetFileAttributesW, FILE_ATTRIBUTE_HIDDEN, FILE_ATTRIBUTE_READONLY, FILE_ATTRIBUTE_SYSTEM,
+    };
+
+    let attributes = match parse_dos_attributes(file.extra_data()) {
+        Some(stored) => {
+            let mut attributes = 0;
+            if stored & DOS_ATTRIBUTE_READONLY != 0 {
+                attributes |= FILE_ATTRIBUTE_READONLY;
+            }
+            if stored & DOS_ATTRIBUTE_HIDDEN != 0 {
+                attributes |= FILE_ATTRIBUTE_HIDDEN;
+            }
+            if stored & DOS_ATTRIBUTE_SYSTEM != 0 {
+                attributes |= FILE_ATTRIBUTE_SYSTEM;
+            }
+            attributes
+        }
+        None => match file.unix_mode() {
+            Some(mode) if mode & 0o222 == 0 => FILE_ATTRIBUTE_READONLY,
+            _ => 0,
+        },
+    };
+
+    if attributes != 0 {
+        let wide: Vec<u16> = file_path.as_os_str().encode_wide().chain([0]).collect();
+        // SAFETY: the path is NUL-terminated and outlives the call
+        if unsafe { SetFileAttributesW(wide.as_ptr(), attributes) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks for ouch's DOS-attributes extra field, see `DOS_ATTRIBUTES_TAG`.
+#[cfg(windows)]
+fn parse_dos_attributes(extra: &[u8]) -> Option<u8> {
+    let mut rest = extra;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let size = usize::from(u16::from_le_bytes([rest[2], rest[3]]));
+        let body = rest.get(4..4 + size)?;
+
+        if id == DOS_ATTRIBUTES_TAG {
+            return body.first().copied();
+        }
+
+        rest = &rest[4 + size..];
+    }
+
+    None
+}
+
+
+/// Minimal streaming zip writer used when the output cannot seek
+/// (stdout/pipes): local headers set the data-descriptor flag (bit 3) and
+/// the real CRC and sizes follow each entry in a trailing descriptor, so
+/// nothing ever needs back-patching. The seekable `ZipWriter` layout stays
+/// the default for files, where it produces slightly smaller archives.
+struct StreamingZipWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    central_records: Vec<StreamedEntry>,
+}
+
+struct StreamedEntry {
+    name: String,
+    crc: u32,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    header_offset: u64,
+    is_dir: bool,
+    unix_mode: u32,
+}
+
+/// General purpose flag bit 3: sizes and CRC live in the data descriptor.
+const FLAG_DATA_DESCRIPTOR: u16 = 1 << 3;
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+
+impl<W: Write> StreamingZipWriter<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            central_records: vec![],
+        }
+    }
+
+    fn write_all_counted(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn write_local_header(&mut self, name: &str, method: u16) -> io::Result<u64> {
+        let header_offset = self.offset;
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend(0x04034b50u32.to_le_bytes());
+        header.extend(20u16.to_le_bytes()); // version needed
+        header.extend(FLAG_DATA_DESCRIPTOR.to_le_bytes());
+        header.extend(method.to_le_bytes());
+        header.extend([0; 4]); // dos time/date
+        header.extend([0; 4]); // crc, in the descriptor
+        header.extend([0; 4]); // compressed size, in the descriptor
+        header.extend([0; 4]); // uncompressed size, in the descriptor
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // extra length
+        header.extend(name.as_bytes());
+        self.write_all_counted(&header)?;
+        Ok(header_offset)
+    }
+
+    fn add_directory(&mut self, name: &str, unix_mode: u32) -> io::Result<()> {
+        let name = if name.ends_with('/') {
+            name.to_owned()
+        } else {
+            format!("{name}/")
+        };
+        let header_offset = self.write_local_header(&name, METHOD_STORED)?;
+        // An empty entry still gets its (all zero) descriptor
+        self.write_descriptor(0, 0, 0)?;
+        self.central_records.push(StreamedEntry {
+            name,
+            crc: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            header_offset,
+            is_dir: true,
+            unix_mode,
+        });
+        Ok(())
+    }
+
+    fn add_file(&mut self, name: &str, reader: &mut dyn Read, unix_mode: u32) -> crate::Result<()> {
+        let header_offset = self.write_local_header(name, METHOD_DEFLATED)?;
+
+        let mut hasher = crc32fast::Hasher::new();
+        let mut encoder = flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut uncompressed_size: u64 = 0;
+        let mut compressed_size: u64 = 0;
+        let mut buffer = [0; crate::BUFFER_CAPACITY];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            uncompressed_size += read as u64;
+            encoder.write_all(&buffer[..read])?;
+            // Drain what the encoder produced so far, keeping memory flat
+            let produced = std::mem::take(encoder.get_mut());
+            compressed_size += produced.len() as u64;
+            self.write_all_counted(&produced)?;
+        }
+        let remainder = encoder.finish()?;
+        compressed_size += remainder.len() as u64;
+        self.write_all_counted(&remainder)?;
+
+        let crc = hasher.finalize();
+        if uncompressed_size >= u64::from(u32::MAX) || compressed_size >= u64::from(u32::MAX) {
+            return Err(crate::error::FinalError::with_title("Cannot stream this zip")
+                .detail("Entries of 4GB or more need zip64, which the streaming writer does not produce")
+                .hint("Write to a file instead of a pipe for large entries.")
+                .into());
+        }
+        self.write_descriptor(crc, compressed_size as u32, uncompressed_size as u32)?;
+
+        self.central_records.push(StreamedEntry {
+            name: name.to_owned(),
+            crc,
+            compressed_size,
+            uncompressed_size,
+            header_offset,
+            is_dir: false,
+            unix_mode,
+        });
+        Ok(())
+    }
+
+    fn write_descriptor(&mut self, crc: u32, compressed: u32, uncompressed: u32) -> io::Result<()> {
+        let mut descriptor = Vec::with_capacity(16);
+        descriptor.extend(0x08074b50u32.to_le_bytes());
+        descriptor.extend(crc.to_le_bytes());
+        descriptor.extend(compressed.to_le_bytes());
+        descriptor.extend(uncompressed.to_le_bytes());
+        self.write_all_counted(&descriptor)
+    }
+
+    fn finish(mut self) -> crate::Result<()> {
+        let central_start = self.offset;
+        let records = std::mem::take(&mut self.central_records);
+
+        // The same archive-level limits the classic central directory and
+        // EOCD fields impose: entry counts beyond u16 and offsets beyond
+        // u32 need zip64 records the streaming writer does not produce
+        if records.len() > u16::MAX as usize
+            || central_start >= u64::from(u32::MAX)
+            || records
+                .iter()
+                .any(|entry| entry.header_offset >= u64::from(u32::MAX))
+        {
+            return Err(crate::error::FinalError::with_title("Cannot stream this zip")
+                .detail("Archives with more than 65535 entries or over 4GB of data need zip64,")
+                .detail("which the streaming writer does not produce")
+                .hint("Write to a file instead of a pipe for archives this large.")
+                .into());
+        }
+
+        for entry in &records {
+            let method = if entry.is_dir { METHOD_STORED } else { METHOD_DEFLATED };
+            let mut record = Vec::with_capacity(46 + entry.name.len());
+            record.extend(0x02014b50u32.to_le_bytes());
+            record.extend((20u16 | (3 << 8)).to_le_bytes()); // made by unix
+            record.extend(20u16.to_le_bytes());
+            record.extend(FLAG_DATA_DESCRIPTOR.to_le_bytes());
+            record.extend(method.to_le_bytes());
+            record.extend([0; 4]); // dos time/date
+            record.extend(entry.crc.to_le_bytes());
+            record.extend((entry.compressed_size as u32).to_le_bytes());
+            record.extend((entry.uncompressed_size as u32).to_le_bytes());
+            record.extend((entry.name.len() as u16).to_le_bytes());
+            record.extend([0; 2]); // extra
+            record.extend([0; 2]); // comment
+            record.extend([0; 2]); // disk number
+            record.extend([0; 2]); // internal attributes
+            let external = (entry.unix_mode << 16) | if entry.is_dir { 0x10 } else { 0 };
+            record.extend(external.to_le_bytes());
+            record.extend((entry.header_offset as u32).to_le_bytes());
+            record.extend(entry.name.as_bytes());
+            self.write_all_counted(&record)?;
+        }
+
+        let central_size = self.offset - central_start;
+        if central_size >= u64::from(u32::MAX) {
+            return Err(crate::error::FinalError::with_title("Cannot stream this zip")
+                .detail("The central directory grew past 4GB, which needs zip64")
+                .into());
+        }
+        let mut eocd = Vec::with_capacity(22);
+        eocd.extend(0x06054b50u32.to_le_bytes());
+        eocd.extend([0; 2]); // disk number
+        eocd.extend([0; 2]); // central directory disk
+        eocd.extend((records.len() as u16).to_le_bytes());
+        eocd.extend((records.len() as u16).to_le_bytes());
+        eocd.extend((central_size as u32).to_le_bytes());
+        eocd.extend((central_start as u32).to_le_bytes());
+        eocd.extend([0; 2]); // comment length
+        self.write_all_counted(&eocd)?;
+        self.writer.flush()?;
+
+        Ok(())
+    }
+}
+
+/// Streams a zip straight to a non-seekable writer (see `StreamingZipWriter`),
+/// walking the inputs like `build_archive_from_paths`.
+#[allow(clippy::too_many_arguments)]
+pub fn build_archive_streaming<W>(
+    input_filenames: &[PathBuf],
+    output_path: &Path,
+    writer: W,
+    file_visibility_policy: FileVisibilityPolicy,
+    quiet: bool,
+    base_dir: Option<&Path>,
+    size_filter: SizeFilter,
+    time_filter: utils::TimeFilter,
+    prune_empty: bool,
+    no_dir_entries: bool,
+    non_utf8: Option<crate::cli::NonUtf8Policy>,
+) -> crate::Result<()>
+where
+    W: Write,
+{
+    let mut writer = StreamingZipWriter::new(writer);
+    let output_handle = Handle::from_path(output_path);
+
+    for filename in input_filenames {
+        let (previous_location, filename) = utils::cd_for_archiving(filename, base_dir)?;
+
+        // With --prune-empty, a pre-walk finds the directories that still
+        // hold at least one file after the filters
+        let kept_dirs = if prune_empty {
+            Some(utils::dirs_with_passing_files(
+                &filename,
+                &file_visibility_policy,
+                size_filter,
+                time_filter,
+            )?)
+        } else {
+            None
+        };
+
+        for entry in file_visibility_policy.build_walker(&filename)? {
+            let entry = entry?;
+            let path = entry.path();
+
+            let metadata = match path.metadata() {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    if e.kind() == std::io::ErrorKind::NotFound && utils::is_symlink(path) {
+                        continue;
+                    }
+                    return Err(e.into());
+                }
+            };
+            if !metadata.is_dir() && !metadata.is_file() {
+                warning(format!(
+                    "Skipping '{}', not a regular file",
+                    EscapedPathDisplay::new(path)
+                ));
+                continue;
+            }
+
+            if let Ok(handle) = &output_handle {
+                if matches!(Handle::from_path(path), Ok(x) if &x == handle) {
+                    continue;
+                }
+            }
+
+            if !metadata.is_dir() && size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                continue;
+            }
+
+            if !metadata.is_dir()
+                && time_filter.is_active()
+                && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime))
+            {
+                continue;
+            }
+
+            if !quiet {
+                info(format!("Compressing '{}'.", EscapedPathDisplay::new(path)));
+            }
+
+            let Some(entry_name) = utils::resolve_non_utf8_name(path, non_utf8, "Zip")? else {
+                continue;
+            };
+            let entry_name = entry_name.as_str();
+
+            #[cfg(unix)]
+            let unix_mode = metadata.permissions().mode();
+            #[cfg(not(unix))]
+            let unix_mode = 0o644;
+
+            if metadata.is_dir() {
+                let pruned = kept_dirs
+                    .as_ref()
+                    .is_some_and(|kept_dirs| !kept_dirs.contains(path));
+                if !no_dir_entries && !pruned {
+                    writer.add_directory(entry_name, unix_mode)?;
+                }
+            } else {
+                let _fd_permit = utils::io::fd_quota::acquire();
+                let mut file = fs::File::open(path)?;
+                writer.add_file(entry_name, &mut file, unix_mode)?;
+            }
+        }
+
+        env::set_current_dir(previous_location)?;
+    }
+
+    writer.finish()
+}
@@ -28,6 +28,7 @@ pub fn check_mime_type(
     path: &Path,
     formats: &mut Vec<Extension>,
     question_policy: QuestionPolicy,
+    trust_magic: bool,
 ) -> Result<ControlFlow<()>> {
     if formats.is_empty() {
         // File with no extension
@@ -56,6 +57,17 @@ pub fn check_mime_type(
             .compression_formats
             .ends_with(detected_format.compression_formats)
         {
+            if trust_magic {
+                // --trust-magic switches to the detected format, handling
+                // misnamed files automatically
+                info_accessible(format!(
+                    "Trusting the detected format `{detected_format}` over the `{outer_ext}` extension"
+                ));
+                formats.pop();
+                formats.push(detected_format);
+                return Ok(ControlFlow::Continue(()));
+            }
+
             warning(format!(
                 "The file extension: `{}` differ from the detected extension: `{}`",
                 outer_ext, detected_format
@@ -126,6 +138,26 @@ pub fn check_archive_formats_position(formats: &[Extension], output_path: &Path)
     Ok(())
 }
 
+/// Rejects chains that put an archive format anywhere but the first
+/// position, which the encoder/decoder chaining cannot express (its inner
+/// layers are single-stream only); catching it here keeps malformed
+/// `--format` values and file names from hitting unreachable code.
+pub fn check_format_chain_is_decodable(formats: &[Extension]) -> Result<()> {
+    let mut flattened = crate::extension::flatten_compression_formats(formats);
+    if flattened.is_empty() {
+        return Ok(());
+    }
+
+    flattened.remove(0);
+    if let Some(nested_archive) = flattened.iter().find(|format| format.is_archive()) {
+        return Err(crate::Error::InvalidFormatChain {
+            reason: format!("The archive format '{nested_archive}' cannot be nested inside a compression layer"),
+        });
+    }
+
+    Ok(())
+}
+
 /// Check if all provided files have formats to decompress.
 pub fn check_missing_formats_when_decompressing(files: &[PathBuf], formats: &[Vec<Extension>]) -> Result<()> {
     let files_with_broken_extension: Vec<&PathBuf> = files
@@ -139,6 +171,16 @@ pub fn check_missing_formats_when_decompressing(files: &[PathBuf], formats: &[Ve
         return Ok(());
     }
 
+    // A single file with an unrecognized extension gets the dedicated error,
+    // naming the offending extension
+    if let &[path] = files_with_broken_extension.as_slice() {
+        if let Some(extension) = path.extension() {
+            return Err(crate::Error::UnknownFormat {
+                extension: extension.to_string_lossy().into_owned(),
+            });
+        }
+    }
+
     let (files_with_unsupported_extensions, files_missing_extension): (Vec<&PathBuf>, Vec<&PathBuf>) =
         files_with_broken_extension
             .iter()
@@ -179,9 +221,183 @@ pub fn check_missing_formats_when_decompressing(files: &[PathBuf], formats: &[Ve
     Err(error.into())
 }
 
+/// Verifies a detached OpenPGP signature over the archive bytes before any
+/// extraction happens, refusing to continue when it doesn't check out.
+pub fn check_detached_signature(archive_path: &Path, signature_path: &Path, keyring_path: &Path) -> Result<()> {
+    use pgp::composed::{Deserializable, DetachedSignature, SignedPublicKey};
+
+    let signature_verification_error = |detail: String| {
+        crate::Error::from(
+            FinalError::with_title("Signature verification failed, refusing to extract")
+                .detail(detail)
+                .hint("Check that the signature and keyring belong to this archive."),
+        )
+    };
+
+    let is_armored = |contents: &[u8]| contents.starts_with(b"-----BEGIN");
+
+    let signature_contents = fs_err::read(signature_path)?;
+    let signature = if is_armored(&signature_contents) {
+        DetachedSignature::from_armor_single(&signature_contents[..]).map(|(signature, _)| signature)
+    } else {
+        DetachedSignature::from_bytes(&signature_contents[..])
+    }
+    .map_err(|err| signature_verification_error(format!("Could not parse the signature: {err}")))?;
+
+    let keyring_contents = fs_err::read(keyring_path)?;
+    let keys: Vec<SignedPublicKey> = if is_armored(&keyring_contents) {
+        SignedPublicKey::from_armor_many(&keyring_contents[..])
+            .and_then(|(keys, _)| keys.collect::<pgp::errors::Result<_>>())
+    } else {
+        SignedPublicKey::from_bytes_many(&keyring_contents[..]).and_then(|keys| keys.collect())
+    }
+    .map_err(|err| signature_verification_error(format!("Could not parse the keyring: {err}")))?;
+
+    if keys.is_empty() {
+        return Err(signature_verification_error("The keyring contains no public keys".into()));
+    }
+
+    let archive_contents = fs_err::read(archive_path)?;
+
+    // Accept a signature by any key in the keyring, including signing subkeys
+    let verified = keys.iter().any(|key| {
+        signature.verify(key, &archive_contents).is_ok()
+            || key
+                .public_subkeys
+                .iter()
+                .any(|subkey| signature.verify(subkey, &archive_contents).is_ok())
+    });
+
+    if !verified {
+        return Err(signature_verification_error(
+            "No key in the keyring signed this archive".into(),
+        ));
+    }
+
+    info_accessible(format!(
+        "Good signature for '{}'.",
+        EscapedPathDisplay::new(archive_path)
+    ));
+
+    Ok(())
+}
+
+/// The age encryption layer only makes sense as the outermost layer of the
+/// chain, i.e. the last extension of the file name.
+pub fn check_age_position(formats: &[Extension]) -> Result<()> {
+    let age_out_of_place = formats
+        .iter()
+        .flat_map(|ext| ext.compression_formats)
+        .rev()
+        .skip(1)
+        .any(|format| *format == crate::extension::CompressionFormat::Age);
+
+    if age_out_of_place {
+        let error = FinalError::with_title("Cannot handle this age-encrypted chain")
+            .detail("The '.age' encryption layer must be the outermost (last) extension")
+            .hint("Example: archive.tar.zst.age");
+
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+/// Pre-flight consistency between the output name and the requested
+/// formats: catches `--format` contradicting the file's recognized
+/// extensions and redundant suffixes like `.gz.gz`, erroring unless
+/// `--force` downgrades the finding to a warning.
+pub fn check_output_format_consistency(
+    output_path: &Path,
+    formats: &[Extension],
+    formats_from_flag: Option<&OsString>,
+    force: bool,
+) -> Result<()> {
+    let report = |title: String, details: Vec<String>| {
+        if force {
+            warning(format!("{title} ({})", details.join("; ")));
+            Ok(())
+        } else {
+            let mut error = FinalError::with_title(title);
+            for detail in details {
+                error = error.detail(detail);
+            }
+            Err(error.hint("Pass --force to proceed anyway.").into())
+        }
+    };
+
+    if formats_from_flag.is_some() {
+        let from_path = crate::extension::extensions_from_path(output_path);
+        if !from_path.is_empty()
+            && crate::extension::flatten_compression_formats(&from_path)
+                != crate::extension::flatten_compression_formats(formats)
+        {
+            let path_text: Vec<String> = from_path.iter().map(ToString::to_string).collect();
+            let flag_text: Vec<String> = formats.iter().map(ToString::to_string).collect();
+            return report(
+                format!(
+                    "The output extension contradicts --format, would write '{}'",
+                    EscapedPathDisplay::new(output_path)
+                ),
+                vec![
+                    format!("The file name suggests: {}", path_text.join(".")),
+                    format!("But --format requests: {}", flag_text.join(".")),
+                ],
+            );
+        }
+    }
+
+    // A doubled suffix is legal (and sometimes deliberate), so it only
+    // warns: applying the same compression twice gains nothing
+    let flat = crate::extension::flatten_compression_formats(formats);
+    if flat
+        .windows(2)
+        .any(|window| window[0] == window[1] && !window[0].is_archive())
+    {
+        warning(format!(
+            "Redundant compression suffix in '{}', applying the same compression twice gains nothing",
+            EscapedPathDisplay::new(output_path)
+        ));
+    }
+
+    Ok(())
+}
+
+/// With `--strict-level`, error when `--level` is outside of the target
+/// format's valid range instead of silently clamping it.
+pub fn check_level_in_format_ranges(level: i16, formats: &[Extension]) -> Result<()> {
+    for format in formats.iter().flat_map(|ext| ext.compression_formats) {
+        let Some(range) = format.level_range() else {
+            continue;
+        };
+
+        if !range.contains(&i32::from(level)) {
+            let error = FinalError::with_title(format!("Invalid compression level {level} for the '{format}' format"))
+                .detail(format!(
+                    "The valid levels for this format range from {} to {}",
+                    range.start(),
+                    range.end()
+                ))
+                .hint("Drop --strict-level to clamp the level to the valid range instead.");
+
+            return Err(error.into());
+        }
+    }
+
+    Ok(())
+}
+
 /// Check if there is a first format when compressing, and returns it.
 pub fn check_first_format_when_compressing<'a>(formats: &'a [Extension], output_path: &Path) -> Result<&'a Extension> {
     formats.first().ok_or_else(|| {
+        // An unrecognized output extension gets the dedicated error, naming
+        // the offending extension
+        if let Some(extension) = output_path.extension() {
+            return crate::Error::UnknownFormat {
+                extension: extension.to_string_lossy().into_owned(),
+            };
+        }
+
         let output_path = EscapedPathDisplay::new(output_path);
         FinalError::with_title(format!("Cannot compress to '{output_path}'."))
             .detail("You shall supply the compression format")
@@ -9,7 +9,7 @@ use clap::{Parser, ValueHint};
 ///
 /// Repository: https://github.com/ouch-org/ouch
 #[derive(Parser, Debug, PartialEq)]
-#[command(about, version)]
+#[command(about, version, arg_required_else_help = true)]
 // Disable rustdoc::bare_urls because rustdoc parses URLs differently than Clap
 #[allow(rustdoc::bare_urls)]
 pub struct CliArgs {
@@ -29,8 +29,9 @@ pub struct CliArgs {
     #[arg(short = 'H', long, global = true)]
     pub hidden: bool,
 
-    /// Silences output
-    #[arg(short = 'q', long, global = true)]
+    /// Silences output and never prompts: overwrite questions are
+    /// answered negatively unless --yes is given
+    #[arg(short = 'q', long, env = "OUCH_QUIET", global = true)]
     pub quiet: bool,
 
     /// Ignores files matched by git's ignore files
@@ -38,32 +39,156 @@ pub struct CliArgs {
     pub gitignore: bool,
 
     /// Specify the format of the archive
-    #[arg(short, long, global = true)]
+    #[arg(short, long, env = "OUCH_FORMAT", global = true)]
     pub format: Option<OsString>,
 
+    /// Print the detailed error chain when something fails,
+    /// also enabled by OUCH_LOG=debug
+    #[arg(long, global = true)]
+    pub debug: bool,
+
+    /// Exit nonzero when any warning was emitted, for strict CI pipelines
+    #[arg(long, global = true)]
+    pub strict: bool,
+
+    /// Move overwritten files to the system trash instead of deleting
+    /// them permanently
+    #[arg(long, global = true)]
+    pub trash: bool,
+
+    /// Write a machine-readable JSON summary of the run to this path
+    /// ('-' for stderr), even on failure and under --quiet
+    #[arg(long, value_name = "PATH", global = true)]
+    pub result_json: Option<PathBuf>,
+
+    /// Retry transient I/O errors (timeouts and friends, common on flaky
+    /// network mounts) up to this many times with backoff
+    #[arg(long, value_name = "N", default_value_t = 0, global = true)]
+    pub retry: u32,
+
+    /// Directory for intermediate temporary files, defaults to the system
+    /// temporary directory (which honors TMPDIR)
+    #[arg(long, value_name = "DIR", global = true, value_hint = ValueHint::DirPath)]
+    pub temp_dir: Option<PathBuf>,
+
+    /// Cap on simultaneously-open data files during parallel operations,
+    /// defaults to half of the soft file-descriptor limit
+    #[arg(long, value_name = "N", global = true)]
+    pub max_open_files: Option<usize>,
+
+    /// List the supported formats with their default compression levels
+    /// and valid ranges, then exit
+    #[arg(long, exclusive = true)]
+    pub list_formats: bool,
+
     // Ouch and claps subcommands
     #[command(subcommand)]
-    pub cmd: Subcommand,
+    pub cmd: Option<Subcommand>,
+}
+
+/// Checksum algorithms accepted by `--checksum`
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ChecksumAlgorithm {
+    /// Multithreaded on large files
+    Blake3,
+    Sha256,
+    Sha512,
+    Crc32,
+}
+
+impl std::fmt::Display for ChecksumAlgorithm {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let text = match self {
+            ChecksumAlgorithm::Blake3 => "blake3",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Crc32 => "crc32",
+        };
+
+        write!(f, "{text}")
+    }
+}
+
+/// Determines what happens when an archive contains several entries with
+/// the same path, set by `--on-duplicate` (the default overwrites with a
+/// warning)
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DuplicatePolicy {
+    /// Let later entries replace earlier ones
+    Overwrite,
+    /// Keep the first occurrence and skip later ones
+    Skip,
+    /// Abort the extraction
+    Error,
+}
+
+/// What to do with paths that are not valid UTF-8, set by `--non-utf8`
+/// (without the flag the formats keep their historical behavior: tar
+/// stores and restores the raw bytes, zip and 7z abort)
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NonUtf8Policy {
+    /// Abort the operation
+    Error,
+    /// Omit the offending entry with a warning
+    Skip,
+    /// Replace the invalid sequences with a placeholder and log the mapping
+    Sanitize,
+}
+
+/// Output format of the `--metadata-only` index sidecar
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MetadataFormat {
+    /// One JSON array of entry objects
+    Json,
+    /// A `path,size,mtime,sha256` header line plus one row per file
+    Csv,
+}
+
+/// Entry kinds shown by `ouch list --only`
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EntryKind {
+    /// Show only file entries
+    Files,
+    /// Show only directory entries
+    Dirs,
+}
+
+/// Determines how to resolve a conflict with an existing output file,
+/// set by `--on-conflict` (the interactive prompt remains the default)
+#[derive(clap::ValueEnum, Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep the existing file and skip this output
+    Skip,
+    /// Write next to the existing file, under a name like `file (1).txt`
+    Rename,
+    /// Overwrite only if the incoming file is more recent than the existing one
+    Newer,
 }
 
 #[derive(Parser, PartialEq, Eq, Debug)]
 #[allow(rustdoc::bare_urls)]
+// The enum is only instantiated once when parsing the command line
+#[allow(clippy::large_enum_variant)]
 pub enum Subcommand {
     /// Compress one or more files into one output file
     #[command(visible_alias = "c")]
     Compress {
-        /// Files to be compressed
-        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        /// Files to be compressed, followed by the resulting file whose
+        /// extensions can be used to specify the compression formats
+        #[arg(required = true, num_args = 1.., value_name = "FILES_AND_OUTPUT", value_hint = ValueHint::FilePath)]
         files: Vec<PathBuf>,
 
-        /// The resulting file. Its extensions can be used to specify the compression formats
-        #[arg(required = true, value_hint = ValueHint::FilePath)]
-        output: PathBuf,
-
         /// Compression level, applied to all formats
-        #[arg(short, long, group = "compression-level")]
+        #[arg(short, long, env = "OUCH_LEVEL", group = "compression-level")]
         level: Option<i16>,
 
+        /// Error when --level is outside of the format's valid range,
+        /// instead of silently clamping it
+        #[arg(long, requires = "level")]
+        strict_level: bool,
+
         /// Fastest compression level possible,
         /// conflicts with --level and --slow
         #[arg(long, group = "compression-level")]
@@ -73,6 +198,335 @@ pub enum Subcommand {
         /// conflicts with --level and --fast
         #[arg(long, group = "compression-level")]
         slow: bool,
+
+        /// Probe each input's compressibility and pick levels automatically:
+        /// store-or-low for incompressible data, high for compressible
+        #[arg(long, group = "compression-level")]
+        auto_level: bool,
+
+        /// Always store zip64 headers, even for entries that do not
+        /// require them (only relevant for the zip format)
+        #[arg(long)]
+        force_zip64: bool,
+
+        /// Set the last modified time of all archive entries to this value,
+        /// given as unix epoch seconds or an RFC3339 timestamp
+        #[arg(long, value_name = "TIMESTAMP")]
+        mtime: Option<String>,
+
+        /// Number of threads used by the parallel compressors,
+        /// defaults to the number of physical cores
+        #[arg(short = 'j', long, env = "OUCH_THREADS", value_name = "N")]
+        threads: Option<usize>,
+
+        /// Store entry paths relative to this directory, like tar's -C
+        #[arg(short = 'C', long = "base", value_name = "DIR", value_hint = ValueHint::DirPath)]
+        base_dir: Option<PathBuf>,
+
+        /// Only include files of at least this size, e.g. 500kB or 1MiB
+        #[arg(long, value_name = "SIZE")]
+        min_size: Option<String>,
+
+        /// Only include files of at most this size, e.g. 1GB
+        #[arg(long, value_name = "SIZE")]
+        max_size: Option<String>,
+
+        /// Compress every input into its own archive, deriving each output
+        /// name from the input, requires --format
+        #[arg(long, visible_alias = "individual")]
+        each: bool,
+
+        /// Do not emit standalone directory entries, letting file paths
+        /// imply the structure (empty directories are lost)
+        #[arg(long)]
+        no_dir_entries: bool,
+
+        /// Record the uncompressed content size in the lz4 frame header,
+        /// buffering the stream in memory instead of streaming it
+        #[arg(long)]
+        lz4_content_size: bool,
+
+        /// Apply a named settings profile from profiles.toml,
+        /// 'fast' and 'max' are built in (explicit flags win)
+        #[arg(short = 'p', long, value_name = "NAME")]
+        profile: Option<String>,
+
+        /// Store byte-identical files once, as hard-link entries
+        /// (tar output only)
+        #[arg(long)]
+        dedup: bool,
+
+        /// Print the resolved compression chain before doing the work
+        #[arg(long)]
+        explain: bool,
+
+        /// Encrypt the '.age' layer for this recipient public key instead
+        /// of a passphrase, can be given multiple times
+        #[arg(long, value_name = "RECIPIENT")]
+        age_recipient: Vec<String>,
+
+        /// Only include the direct children of directory inputs,
+        /// instead of recursing into their whole subtree
+        #[arg(long)]
+        no_recursive: bool,
+
+        /// Pipe the compressed bytes into this shell command's stdin
+        /// instead of writing the output file (the file name still
+        /// determines the format)
+        #[arg(long, value_name = "COMMAND")]
+        pipe_through: Option<String>,
+
+        /// Only include files matching this glob, can be given multiple times
+        #[arg(long, value_name = "GLOB")]
+        include: Vec<String>,
+
+        /// Skip files matching this glob, can be given multiple times
+        #[arg(long, value_name = "GLOB")]
+        exclude: Vec<String>,
+
+        /// Match the --include and --exclude globs case-insensitively
+        #[arg(long)]
+        ignore_case: bool,
+
+        /// Exclude VCS metadata: the .git/.svn/.hg/.bzr/CVS directories
+        /// and .gitignore/.gitattributes/.gitmodules/.cvsignore files
+        #[arg(long)]
+        exclude_vcs: bool,
+
+        /// Write a checksum sidecar file next to the output,
+        /// e.g. archive.tar.gz.sha256
+        #[arg(long, value_name = "ALGORITHM")]
+        checksum: Option<ChecksumAlgorithm>,
+
+        /// Pre-walk the inputs to report the total size and file count
+        /// up front, making the walk progress accurate
+        #[arg(long)]
+        scan_total: bool,
+
+        /// Refuse to run at all if the output file already exists,
+        /// instead of prompting or overwriting
+        #[arg(long)]
+        no_clobber: bool,
+
+        /// Format applied (and suffix appended) when the output name has
+        /// no recognized extension, e.g. --default-format zst
+        #[arg(long, value_name = "FORMAT")]
+        default_format: Option<OsString>,
+
+        /// Use xz's extreme preset modifier for a better ratio at a
+        /// noticeably higher CPU cost (xz/lzma only)
+        #[arg(long)]
+        xz_extreme: bool,
+
+        /// Use this file as a raw zstd dictionary, good for compressing
+        /// data similar to the reference (zstd only)
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        zstd_ref: Option<PathBuf>,
+
+        /// Read the exact list of inputs from this file, newline separated,
+        /// bypassing directory recursion for the listed paths
+        #[arg(short = 'T', long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        entries_from: Option<PathBuf>,
+
+        /// The --entries-from list is NUL separated, as produced by
+        /// `find -print0`
+        #[arg(long, requires = "entries_from")]
+        null: bool,
+
+        /// Skip paths from --entries-from that do not exist,
+        /// instead of erroring
+        #[arg(long, requires = "entries_from")]
+        ignore_missing: bool,
+
+        /// Produce one archive per immediate subdirectory of the input
+        /// directory, written into the output directory (requires --format);
+        /// loose files go into a '_root' archive
+        #[arg(long)]
+        split_by_dir: bool,
+
+        /// Store each file's creation (birth) time in a zip extended
+        /// timestamp field, restored on platforms that support setting it
+        #[arg(long)]
+        preserve_btime: bool,
+
+        /// Downgrade output-name consistency errors (extension vs --format
+        /// mismatches, redundant suffixes) to warnings
+        #[arg(long)]
+        force: bool,
+
+        /// Additionally write the same contents in this compression format
+        /// in the same walk, can be given multiple times (e.g. zst)
+        #[arg(long, value_name = "FORMAT")]
+        also_format: Vec<OsString>,
+
+        /// Embed a content checksum in the zstd frame, verified when the
+        /// archive is decompressed
+        #[arg(long)]
+        zstd_checksum: bool,
+
+        /// Read inputs on a background thread, keeping this many chunks in
+        /// flight so I/O latency overlaps with compression (0 disables)
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        io_threads: usize,
+
+        /// Apply a built-in option bundle: source-release (exclude-vcs,
+        /// zstd-19 tar, single-threaded), quick-backup (fastest level,
+        /// honor gitignore) or windows-safe (zip, case-insensitive globs)
+        #[arg(long, value_name = "NAME")]
+        preset: Option<String>,
+
+        /// Incremental tar backups: record directory state in this snapshot
+        /// file and only archive files changed since it was written
+        #[arg(long, value_name = "SNAPSHOT", value_hint = ValueHint::FilePath)]
+        listed_incremental: Option<PathBuf>,
+
+        /// Error out when the walk yields no entries, instead of warning
+        /// and writing an empty archive
+        #[arg(long)]
+        error_on_empty: bool,
+
+        /// Store symlinks as symlinks (instead of following them),
+        /// rewriting absolute targets inside the archived tree into
+        /// relative ones for portability
+        #[arg(long)]
+        relativize_symlinks: bool,
+
+        /// Write a MANIFEST.sha256 entry into the archive listing every
+        /// member and its digest, in the standard 'hash  path' format
+        #[arg(long)]
+        manifest: bool,
+
+        /// Delete the archived source files after a successful compression
+        /// (honors --trash)
+        #[arg(short = 'r', long, conflicts_with = "listed_incremental")]
+        remove: bool,
+
+        /// Also prune directories left empty by --remove, bottom-up,
+        /// never touching directories that still contain files
+        #[arg(long, requires = "remove")]
+        remove_empty_dirs: bool,
+
+        /// Name the output after the sha256 of its content
+        /// (e.g. <digest>.tar.zst), for content-addressed stores
+        #[arg(long)]
+        name_by_hash: bool,
+
+        /// Store entries in exactly the order inputs were listed, recursing
+        /// each directory in deterministic (sorted) listing order
+        #[arg(long)]
+        preserve_input_order: bool,
+
+        /// Emit a headerless raw stream instead of the framed variant
+        /// (raw deflate for gz); not self-describing, decode with a
+        /// matching --format and --raw
+        #[arg(long)]
+        raw: bool,
+
+        /// Compression level for one specific layer of the chain, e.g.
+        /// --level-for zst=19 --level-for gz=6 (layers without one use
+        /// --level or the format default)
+        #[arg(long, value_name = "FORMAT=LEVEL")]
+        level_for: Vec<String>,
+
+        /// Store canonical modes instead of the OS-reported ones: 0644 for
+        /// files, 0755 for directories and executables (any exec bit on
+        /// unix, extension heuristics elsewhere)
+        #[arg(long)]
+        normalize_permissions: bool,
+
+        /// Do not store the original file name and mtime in single-file
+        /// gzip headers (stored by default, like gzip does)
+        #[arg(long)]
+        no_gzip_name: bool,
+
+        /// Flush the finished archive (and its directory entry) to disk
+        /// before reporting success; slower, but without it a crash right
+        /// after ouch exits can lose the archive on some filesystems
+        #[arg(long)]
+        fsync: bool,
+
+        /// Store files consisting entirely of zero bytes as empty
+        /// placeholder entries that grow back to their full (sparse) size
+        /// on extraction (tar archives)
+        #[arg(long)]
+        skip_zeros: bool,
+
+        /// Split the output into numbered part files of at most this size,
+        /// e.g. 100MB: `archive.tar.gz.001`, `.002`, ... restored by plain
+        /// concatenation (not available for seekable zip output)
+        #[arg(long, value_name = "SIZE")]
+        split_size: Option<String>,
+
+        /// Compress 7z entries into shared solid blocks, grouping similar
+        /// files (by extension, then size) for a better ratio
+        #[arg(long)]
+        solid: bool,
+
+        /// Maximum uncompressed input per solid block, e.g. 16MiB (one
+        /// block holds everything when omitted)
+        #[arg(long, value_name = "SIZE", requires = "solid")]
+        solid_block_size: Option<String>,
+
+        /// What to do with input paths that are not valid UTF-8 when the
+        /// output format requires it (zip, 7z): error, skip or sanitize
+        #[arg(long, value_name = "POLICY")]
+        non_utf8: Option<NonUtf8Policy>,
+
+        /// Embed the total uncompressed size in a pax global header at the
+        /// start of the tar, readable without scanning the whole archive
+        #[arg(long)]
+        embed_total_size: bool,
+
+        /// Block size of the parallel gzip/snappy compressors, e.g. 512kB
+        /// (larger blocks improve ratio and throughput at the cost of
+        /// memory; only effective with --threads above 1, minimum 32kB)
+        #[arg(long, value_name = "SIZE")]
+        par_block_size: Option<String>,
+
+        /// Write an index of the inputs (path, size, mtime, sha256) to the
+        /// output instead of archiving their contents; the output may add a
+        /// compression extension like `.json.gz`
+        #[arg(long, value_name = "FORMAT")]
+        metadata_only: Option<MetadataFormat>,
+
+        /// Read the finished archive back and verify every extracted file
+        /// against its source by hash; roughly doubles the work, catches
+        /// encoder bugs and silent disk corruption at creation time
+        #[arg(long)]
+        verify: bool,
+
+        /// Run up to this many per-file compressions concurrently in
+        /// --each mode (best with --yes, prompts would interleave)
+        #[arg(long, value_name = "N", default_value_t = 1)]
+        jobs: usize,
+
+        /// Skip the contents of directories tagged with a CACHEDIR.TAG
+        /// file, keeping just the tag as a marker (like GNU tar)
+        #[arg(long)]
+        exclude_caches: bool,
+
+        /// Skip tagged cache directories entirely, without the marker
+        #[arg(long, conflicts_with = "exclude_caches")]
+        exclude_caches_all: bool,
+
+        /// Only include files modified at or after this time: RFC3339,
+        /// epoch seconds, or relative like 7d/24h/30m
+        #[arg(long, value_name = "TIME")]
+        newer_than: Option<String>,
+
+        /// Only include files modified at or before this time (same forms
+        /// as --newer-than)
+        #[arg(long, value_name = "TIME")]
+        older_than: Option<String>,
+
+        /// Skip directory entries left empty by the size/time filters
+        #[arg(long)]
+        prune_empty: bool,
+
+        /// Write a bare uncompressed tar regardless of the output name,
+        /// for fast archiving without compression
+        #[arg(long, conflicts_with = "format")]
+        no_compression: bool,
     },
     /// Decompresses one or more files, optionally into another folder
     #[command(visible_alias = "d")]
@@ -84,6 +538,257 @@ pub enum Subcommand {
         /// Place results in a directory other than the current one
         #[arg(short = 'd', long = "dir", value_hint = ValueHint::FilePath)]
         output_dir: Option<PathBuf>,
+
+        /// Disable Smart Unpack
+        #[arg(long)]
+        no_smart_unpack: bool,
+
+        /// Extract archive entries with absolute paths to their absolute
+        /// location instead of stripping the leading '/'
+        #[arg(long)]
+        absolute_paths: bool,
+
+        /// How to resolve conflicts with files that already exist,
+        /// instead of asking for each one
+        #[arg(long, value_name = "POLICY")]
+        on_conflict: Option<ConflictPolicy>,
+
+        /// Recreate FIFOs and device nodes stored in tar archives instead
+        /// of skipping them (unix only, devices require privileges)
+        #[arg(long)]
+        preserve_special: bool,
+
+        /// Print the resolved decompression chain before doing the work
+        #[arg(long)]
+        explain: bool,
+
+        /// When the magic bytes contradict the file extension, use the
+        /// detected format instead of asking
+        #[arg(long)]
+        trust_magic: bool,
+
+        /// Verify this detached OpenPGP signature over the archive before
+        /// extracting, requires --keyring
+        #[arg(long, value_name = "SIG", requires = "keyring")]
+        verify_signature: Option<PathBuf>,
+
+        /// Keyring with the public keys trusted for --verify-signature
+        #[arg(long, value_name = "KEYS", requires = "verify_signature")]
+        keyring: Option<PathBuf>,
+
+        /// Decrypt the '.age' layer with the identities from this file
+        /// instead of a passphrase
+        #[arg(long, value_name = "PATH", value_hint = ValueHint::FilePath)]
+        age_identity: Option<PathBuf>,
+
+        /// Restore stored file attributes like read-only on extraction
+        /// (only meaningful on Windows)
+        #[arg(long)]
+        preserve_attributes: bool,
+
+        /// How to handle several archive entries sharing the same path,
+        /// by default later entries overwrite with a warning
+        #[arg(long, value_name = "POLICY")]
+        on_duplicate: Option<DuplicatePolicy>,
+
+        /// Also extract recognized archives found inside the extracted
+        /// output, each into a directory named after it
+        #[arg(long, visible_alias = "recursive")]
+        unnest: bool,
+
+        /// How many rounds of nested extraction --unnest may do,
+        /// bounding runaway nesting
+        #[arg(long, value_name = "N", default_value_t = 3)]
+        max_depth: u32,
+
+        /// Remap stored uids/gids to local ones while extracting,
+        /// comma-separated old:new pairs (requires privileges to chown)
+        #[arg(long, value_name = "MAP")]
+        owner_map: Option<String>,
+
+        /// Local id applied to stored ids without an --owner-map entry,
+        /// which otherwise pass through unchanged
+        #[arg(long, value_name = "ID", requires = "owner_map")]
+        owner_map_default: Option<u64>,
+
+        /// Use this file as the raw zstd dictionary the archive was
+        /// compressed with
+        #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+        zstd_ref: Option<PathBuf>,
+
+        /// Abort when the output exceeds this many times the archive size
+        /// (decompression-bomb protection, defaults to 10000)
+        #[arg(long, value_name = "N", conflicts_with = "no_bomb_check")]
+        max_ratio: Option<u64>,
+
+        /// Abort when the output exceeds this absolute size, e.g. 10GB
+        #[arg(long, value_name = "SIZE", conflicts_with = "no_bomb_check")]
+        max_extracted_size: Option<String>,
+
+        /// Refuse archives declaring more than this many entries
+        /// (defaults to 1000000)
+        #[arg(long, value_name = "N", conflicts_with = "no_bomb_check")]
+        max_entries: Option<u64>,
+
+        /// Disable the decompression-bomb protection entirely
+        #[arg(long)]
+        no_bomb_check: bool,
+
+        /// Run this shell command in the output directory after a successful
+        /// extraction, with '{}' expanding to the output path (the command
+        /// runs with your full privileges, only use trusted commands)
+        #[arg(long, value_name = "COMMAND")]
+        exec: Option<String>,
+
+        /// Only list the output paths that already exist (and what the
+        /// conflict policy would do to them), without extracting anything
+        #[arg(long)]
+        preview_conflicts: bool,
+
+        /// Error on trailing data after the tar end-of-archive marker,
+        /// instead of tolerating it
+        #[arg(long)]
+        strict_tar: bool,
+
+        /// Restore setuid/setgid bits from the archive, which is dangerous
+        /// for untrusted archives and therefore skipped (with a warning)
+        /// by default
+        #[arg(long)]
+        allow_setuid: bool,
+
+        /// Verify every extracted file against the MANIFEST.sha256 entry
+        /// written by --manifest, failing on any mismatch
+        #[arg(long)]
+        verify_manifest: bool,
+
+        /// The input is a headerless raw stream produced with --raw
+        /// (raw deflate for gz)
+        #[arg(long)]
+        raw: bool,
+
+        /// Restore the original file name stored in the gzip header
+        /// (FNAME) instead of deriving one from the archive name
+        #[arg(long)]
+        use_stored_name: bool,
+
+        /// What to do with entry names that are not valid UTF-8:
+        /// error, skip or sanitize (default: restore the raw bytes)
+        #[arg(long, value_name = "POLICY")]
+        non_utf8: Option<NonUtf8Policy>,
+
+        /// Input buffer size used while decoding, e.g. 4MiB; larger buffers
+        /// (with background read-ahead) help on fast storage, the default
+        /// keeps the regular 32kB buffering
+        #[arg(long, value_name = "SIZE")]
+        buffer_size: Option<String>,
+
+        /// Extract nothing; report what would happen and whether it would
+        /// succeed: existing outputs, free disk space against the declared
+        /// extraction size, and target writability
+        #[arg(long, conflicts_with = "preview_conflicts")]
+        dry_run: bool,
+    },
+    /// Mount an archive as a read-only filesystem
+    #[cfg(feature = "mount")]
+    #[command(visible_alias = "m")]
+    Mount {
+        /// Archive to mount
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        archive: PathBuf,
+
+        /// Directory where the archive contents will be presented
+        #[arg(required = true, value_hint = ValueHint::DirPath)]
+        mount_point: PathBuf,
+    },
+    /// Estimate the compressed size of files by compressing a sample
+    Estimate {
+        /// Files to estimate the compressed size of
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        files: Vec<PathBuf>,
+
+        /// Compression level used for the sample, applied to all formats
+        #[arg(short, long)]
+        level: Option<i16>,
+
+        /// Print the estimate as a json object instead of text
+        #[arg(long)]
+        json: bool,
+
+        /// Percentage of the input bytes to sample, higher is more accurate
+        /// but slower (defaults to 10)
+        #[arg(long, value_name = "PERCENT")]
+        sample: Option<u8>,
+    },
+    /// Rewrite an archive into a new one, optionally copying the raw
+    /// compressed entries without recompressing (zip to zip)
+    Recompress {
+        /// The archive to read
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// The archive to write
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+
+        /// Copy the raw already-compressed entry bytes unchanged instead
+        /// of decompressing and recompressing them
+        #[arg(long)]
+        passthrough: bool,
+    },
+    /// Salvage the complete entries of a damaged tar into a fresh archive
+    Repair {
+        /// The damaged archive to read
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// The repaired archive to write
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+
+        /// Also keep the readable prefix of the entry the damage cut short
+        #[arg(long)]
+        include_partial: bool,
+    },
+    /// Convert an archive into another container format, preserving
+    /// paths, permissions and modification times
+    Convert {
+        /// The archive to read
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        input: PathBuf,
+
+        /// The archive to write, its extension picks the new format
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        output: PathBuf,
+    },
+    /// Browse an archive interactively and extract chosen entries
+    #[cfg(feature = "tui")]
+    Browse {
+        /// Archive to browse
+        #[arg(required = true, value_hint = ValueHint::FilePath)]
+        archive: PathBuf,
+    },
+    /// Compare the contents of two archives
+    Diff {
+        /// The two archives to compare
+        #[arg(required = true, num_args = 2, value_hint = ValueHint::FilePath)]
+        archives: Vec<PathBuf>,
+
+        /// Also compare the contents of entries, not just paths and sizes
+        #[arg(long)]
+        content: bool,
+    },
+    /// Generate roff man pages, to stdout or into a directory
+    Man {
+        /// Directory to write ouch.1 and the per-subcommand pages into,
+        /// prints the main page to stdout when omitted
+        #[arg(short, long, value_name = "DIR", value_hint = ValueHint::DirPath)]
+        output: Option<PathBuf>,
+    },
+    /// Generate shell completions for ouch, printed to stdout
+    Completions {
+        /// Shell to generate the completions for
+        #[arg(value_name = "SHELL")]
+        shell: clap_complete::Shell,
     },
     /// List contents of an archive
     #[command(visible_aliases = ["l", "ls"])]
@@ -95,6 +800,70 @@ pub enum Subcommand {
         /// Show archive contents as a tree
         #[arg(short, long)]
         tree: bool,
+
+        /// Show only entries of this kind
+        #[arg(long, value_name = "KIND")]
+        only: Option<EntryKind>,
+
+        /// Print entries NUL separated without decorations,
+        /// for piping into xargs -0 and friends
+        #[arg(long, conflicts_with = "tree")]
+        null: bool,
+
+        /// Show each entry's compression method, sizes and ratio
+        #[arg(long, conflicts_with_all = ["tree", "null"])]
+        long: bool,
+
+        /// Never pipe the listing through a pager, even on a terminal
+        #[arg(long)]
+        no_pager: bool,
+
+        /// Print the listing as one JSON array (buffers all entries
+        /// in memory first)
+        #[arg(long, conflicts_with_all = ["tree", "null", "long"])]
+        json: bool,
+
+        /// Stream the listing as newline-delimited JSON, one entry object
+        /// per line, without buffering (for huge archives)
+        #[arg(long, conflicts_with_all = ["tree", "null", "long", "json"])]
+        ndjson: bool,
+
+        /// Show raw byte counts in --long instead of human-readable sizes
+        #[arg(long, requires = "long")]
+        bytes: bool,
+
+        /// Never elide long paths in --long, even on narrow terminals
+        #[arg(long, requires = "long")]
+        full_paths: bool,
+
+        /// Print only the total uncompressed size, summed from the entry
+        /// metadata without extracting (composes with --json)
+        #[arg(long, conflicts_with_all = ["tree", "null", "long", "ndjson"])]
+        total_only: bool,
+
+        /// Show only the N largest entries by uncompressed size, sorted
+        /// descending (stored size for formats without per-entry metadata)
+        #[arg(long, value_name = "N", conflicts_with_all = ["tree", "total_only"])]
+        top: Option<usize>,
+
+        /// Show the timestamps in --long as UTC instead of local time
+        /// (JSON output is always RFC3339 UTC)
+        #[arg(long, requires = "long")]
+        utc: bool,
+
+        /// Show the timestamps in --long as full RFC3339 instead of the
+        /// short `2024-01-02 03:04` form
+        #[arg(long, requires = "long")]
+        iso: bool,
+
+        /// Also list the contents of nested archives (recognized by their
+        /// extension) indented beneath their entry
+        #[arg(long, conflicts_with_all = ["tree", "null", "long", "json", "ndjson", "total_only", "top"])]
+        peek_nested: bool,
+
+        /// How many nesting levels --peek-nested descends (defaults to 1)
+        #[arg(long, value_name = "N", requires = "peek_nested")]
+        peek_depth: Option<usize>,
     },
 }
 
@@ -132,44 +901,177 @@ mod tests {
             quiet: false,
             gitignore: false,
             format: None,
-            // This is usually replaced in assertion tests
-            cmd: Subcommand::Decompress {
-                // Put a crazy value here so no test can assert it unintentionally
-                files: vec!["\x00\x11\x22".into()],
-                output_dir: None,
-            },
+            debug: false,
+            strict: false,
+            trash: false,
+            result_json: None,
+            retry: 0,
+            temp_dir: None,
+            max_open_files: None,
+            list_formats: false,
+            // Always replaced in assertions; a forgotten override fails the
+            // comparison instead of passing by accident
+            cmd: None,
+        }
+    }
+
+    /// Every `decompress` flag at its parser default; tests override the
+    /// fields they assert through `decompress_cmd!`.
+    fn mock_decompress_cmd() -> Subcommand {
+        Subcommand::Decompress {
+            files: vec![],
+            output_dir: None,
+            no_smart_unpack: false,
+            absolute_paths: false,
+            on_conflict: None,
+            preserve_special: false,
+            explain: false,
+            trust_magic: false,
+            verify_signature: None,
+            keyring: None,
+            age_identity: None,
+            preserve_attributes: false,
+            on_duplicate: None,
+            unnest: false,
+            max_depth: 3,
+            owner_map: None,
+            owner_map_default: None,
+            zstd_ref: None,
+            max_ratio: None,
+            max_extracted_size: None,
+            max_entries: None,
+            no_bomb_check: false,
+            exec: None,
+            preview_conflicts: false,
+            strict_tar: false,
+            allow_setuid: false,
+            verify_manifest: false,
+            raw: false,
+            use_stored_name: false,
+            non_utf8: None,
+            buffer_size: None,
+            dry_run: false,
+        }
+    }
+
+    /// Every `compress` flag at its parser default, see `compress_cmd!`.
+    fn mock_compress_cmd() -> Subcommand {
+        Subcommand::Compress {
+            files: vec![],
+            level: None,
+            strict_level: false,
+            fast: false,
+            slow: false,
+            auto_level: false,
+            force_zip64: false,
+            mtime: None,
+            threads: None,
+            base_dir: None,
+            min_size: None,
+            max_size: None,
+            each: false,
+            no_dir_entries: false,
+            lz4_content_size: false,
+            profile: None,
+            dedup: false,
+            explain: false,
+            age_recipient: vec![],
+            no_recursive: false,
+            pipe_through: None,
+            include: vec![],
+            exclude: vec![],
+            ignore_case: false,
+            exclude_vcs: false,
+            checksum: None,
+            scan_total: false,
+            no_clobber: false,
+            default_format: None,
+            xz_extreme: false,
+            zstd_ref: None,
+            entries_from: None,
+            null: false,
+            ignore_missing: false,
+            split_by_dir: false,
+            preserve_btime: false,
+            force: false,
+            also_format: vec![],
+            zstd_checksum: false,
+            io_threads: 0,
+            preset: None,
+            listed_incremental: None,
+            error_on_empty: false,
+            relativize_symlinks: false,
+            manifest: false,
+            remove: false,
+            remove_empty_dirs: false,
+            name_by_hash: false,
+            preserve_input_order: false,
+            raw: false,
+            level_for: vec![],
+            normalize_permissions: false,
+            no_gzip_name: false,
+            fsync: false,
+            skip_zeros: false,
+            split_size: None,
+            solid: false,
+            solid_block_size: None,
+            non_utf8: None,
+            embed_total_size: false,
+            par_block_size: None,
+            metadata_only: None,
+            verify: false,
+            jobs: 1,
+            exclude_caches: false,
+            exclude_caches_all: false,
+            newer_than: None,
+            older_than: None,
+            prune_empty: false,
+            no_compression: false,
         }
     }
 
+    /// Struct-update-style overrides for the subcommand mocks, which enum
+    /// variants cannot express with plain `..` syntax.
+    macro_rules! decompress_cmd {
+        ($($field:ident: $value:expr),* $(,)?) => {{
+            let mut cmd = mock_decompress_cmd();
+            if let Subcommand::Decompress { $(ref mut $field,)* .. } = cmd {
+                $(*$field = $value;)*
+            }
+            cmd
+        }};
+    }
+
+    macro_rules! compress_cmd {
+        ($($field:ident: $value:expr),* $(,)?) => {{
+            let mut cmd = mock_compress_cmd();
+            if let Subcommand::Compress { $(ref mut $field,)* .. } = cmd {
+                $(*$field = $value;)*
+            }
+            cmd
+        }};
+    }
+
     #[test]
     fn test_clap_cli_ok() {
         test!(
             "ouch decompress file.tar.gz",
             CliArgs {
-                cmd: Subcommand::Decompress {
-                    files: to_paths(["file.tar.gz"]),
-                    output_dir: None,
-                },
+                cmd: Some(decompress_cmd!(files: to_paths(["file.tar.gz"]))),
                 ..mock_cli_args()
             }
         );
         test!(
             "ouch d file.tar.gz",
             CliArgs {
-                cmd: Subcommand::Decompress {
-                    files: to_paths(["file.tar.gz"]),
-                    output_dir: None,
-                },
+                cmd: Some(decompress_cmd!(files: to_paths(["file.tar.gz"]))),
                 ..mock_cli_args()
             }
         );
         test!(
             "ouch d a b c",
             CliArgs {
-                cmd: Subcommand::Decompress {
-                    files: to_paths(["a", "b", "c"]),
-                    output_dir: None,
-                },
+                cmd: Some(decompress_cmd!(files: to_paths(["a", "b", "c"]))),
                 ..mock_cli_args()
             }
         );
@@ -177,39 +1079,14 @@ mod tests {
         test!(
             "ouch compress file file.tar.gz",
             CliArgs {
-                cmd: Subcommand::Compress {
-                    files: to_paths(["file"]),
-                    output: PathBuf::from("file.tar.gz"),
-                    level: None,
-                    fast: false,
-                    slow: false,
-                },
-                ..mock_cli_args()
-            }
-        );
-        test!(
-            "ouch compress a b c archive.tar.gz",
-            CliArgs {
-                cmd: Subcommand::Compress {
-                    files: to_paths(["a", "b", "c"]),
-                    output: PathBuf::from("archive.tar.gz"),
-                    level: None,
-                    fast: false,
-                    slow: false,
-                },
+                cmd: Some(compress_cmd!(files: to_paths(["file", "file.tar.gz"]))),
                 ..mock_cli_args()
             }
         );
         test!(
             "ouch compress a b c archive.tar.gz",
             CliArgs {
-                cmd: Subcommand::Compress {
-                    files: to_paths(["a", "b", "c"]),
-                    output: PathBuf::from("archive.tar.gz"),
-                    level: None,
-                    fast: false,
-                    slow: false,
-                },
+                cmd: Some(compress_cmd!(files: to_paths(["a", "b", "c", "archive.tar.gz"]))),
                 ..mock_cli_args()
             }
         );
@@ -227,13 +1104,7 @@ mod tests {
             test!(
                 input,
                 CliArgs {
-                    cmd: Subcommand::Compress {
-                        files: to_paths(["a", "b", "c"]),
-                        output: PathBuf::from("output"),
-                        level: None,
-                        fast: false,
-                        slow: false,
-                    },
+                    cmd: Some(compress_cmd!(files: to_paths(["a", "b", "c", "output"]))),
                     format: Some("tar.gz".into()),
                     ..mock_cli_args()
                 }
@@ -244,7 +1115,10 @@ mod tests {
     #[test]
     fn test_clap_cli_err() {
         assert!(CliArgs::try_parse_from(args_splitter("ouch c")).is_err());
-        assert!(CliArgs::try_parse_from(args_splitter("ouch c input")).is_err());
+        // A single positional is the output alone, which parses since the
+        // inputs may come from --entries-from; missing inputs are a runtime
+        // error instead
+        assert!(CliArgs::try_parse_from(args_splitter("ouch c input")).is_ok());
         assert!(CliArgs::try_parse_from(args_splitter("ouch d")).is_err());
         assert!(CliArgs::try_parse_from(args_splitter("ouch l")).is_err());
     }
@@ -10,8 +10,28 @@ use std::{
 use clap::Parser;
 use fs_err as fs;
 
-pub use self::args::{CliArgs, Subcommand};
-use crate::{accessible::set_accessible, utils::FileVisibilityPolicy, QuestionPolicy};
+/// What `--exclude-vcs` filters out of the compression walk.
+const VCS_EXCLUDE_PATTERNS: &[&str] = &[
+    ".git",
+    ".svn",
+    ".hg",
+    ".bzr",
+    "CVS",
+    ".gitignore",
+    ".gitattributes",
+    ".gitmodules",
+    ".cvsignore",
+];
+
+pub use self::args::{
+    ChecksumAlgorithm, CliArgs, ConflictPolicy, DuplicatePolicy, EntryKind, MetadataFormat, NonUtf8Policy, Subcommand,
+};
+use crate::{
+    accessible::set_accessible,
+    error::{set_debug, set_strict},
+    utils::FileVisibilityPolicy,
+    QuestionPolicy,
+};
 
 impl CliArgs {
     /// A helper method that calls `clap::Parser::parse`.
@@ -24,12 +44,55 @@ impl CliArgs {
 
         set_accessible(args.accessible);
 
-        let (Subcommand::Compress { files, .. }
-        | Subcommand::Decompress { files, .. }
-        | Subcommand::List { archives: files, .. }) = &mut args.cmd;
-        *files = canonicalize_files(files)?;
+        apply_compress_preset(&mut args)?;
+
+        let debug_from_env = std::env::var("OUCH_LOG").is_ok_and(|value| value.eq_ignore_ascii_case("debug"));
+        set_debug(args.debug || debug_from_env);
+        set_strict(args.strict);
+
+        if let Some(result_json) = &args.result_json {
+            crate::utils::report::enable(result_json.clone());
+        }
+
+        crate::utils::io::fd_quota::configure(args.max_open_files);
+
+        match &mut args.cmd {
+            Some(Subcommand::Compress { files, .. }) => {
+                // The last element is the output file, which may not exist yet
+                let output = files.pop().expect("clap requires at least one value");
+                *files = canonicalize_files(files)?;
+                files.push(output);
+            }
+            Some(
+                Subcommand::Decompress { files, .. }
+                | Subcommand::List { archives: files, .. }
+                | Subcommand::Diff { archives: files, .. }
+                | Subcommand::Estimate { files, .. },
+            ) => {
+                *files = canonicalize_files(files)?;
+            }
+            Some(
+                Subcommand::Recompress { input, .. }
+                | Subcommand::Convert { input, .. }
+                | Subcommand::Repair { input, .. },
+            ) => {
+                *input = fs::canonicalize(&input)?;
+            }
+            #[cfg(feature = "tui")]
+            Some(Subcommand::Browse { archive }) => {
+                *archive = fs::canonicalize(&archive)?;
+            }
+            #[cfg(feature = "mount")]
+            Some(Subcommand::Mount { archive, .. }) => {
+                *archive = fs::canonicalize(&archive)?;
+            }
+            Some(Subcommand::Completions { .. } | Subcommand::Man { .. }) | None => {}
+        }
 
         let skip_questions_positively = match (args.yes, args.no) {
+            // Quiet mode is non-interactive: blocking on a hidden prompt
+            // would deadlock scripts, so questions default to "no"
+            (false, false) if args.quiet => QuestionPolicy::AlwaysNo,
             (false, false) => QuestionPolicy::Ask,
             (true, false) => QuestionPolicy::AlwaysYes,
             (false, true) => QuestionPolicy::AlwaysNo,
@@ -42,10 +105,111 @@ impl CliArgs {
             .read_git_ignore(args.gitignore)
             .read_hidden(args.hidden);
 
+        // Compression-only walk settings: --no-recursive caps the depth, and
+        // the --include/--exclude globs filter the walked files
+        let file_visibility_policy = match &args.cmd {
+            Some(Subcommand::Compress {
+                no_recursive,
+                include,
+                exclude,
+                ignore_case,
+                exclude_vcs,
+                preserve_input_order,
+                exclude_caches,
+                exclude_caches_all,
+                ..
+            }) => {
+                let mut exclude = exclude.clone();
+                if *exclude_vcs {
+                    // The curated shortcut composes with user-supplied excludes
+                    exclude.extend(VCS_EXCLUDE_PATTERNS.iter().map(ToString::to_string));
+                }
+                file_visibility_policy
+                    .max_depth(no_recursive.then_some(1))
+                    .sort_entries(*preserve_input_order)
+                    .exclude_caches(*exclude_caches, *exclude_caches_all)
+                    .glob_filters(include.clone(), exclude, *ignore_case)
+            }
+            _ => file_visibility_policy,
+        };
+
         Ok((args, skip_questions_positively, file_visibility_policy))
     }
 }
 
 fn canonicalize_files(files: &[impl AsRef<Path>]) -> io::Result<Vec<PathBuf>> {
-    files.iter().map(fs::canonicalize).collect()
+    files
+        .iter()
+        .map(|path| {
+            let path = path.as_ref();
+            // "-" stands for stdin/stdout, and fd-passing paths resolve to
+            // pipes: both have no canonical path and are read as streams
+            if path == Path::new("-") || is_fd_path(path) || is_url(path) {
+                Ok(path.to_path_buf())
+            } else {
+                fs::canonicalize(path)
+            }
+        })
+        .collect()
+}
+
+/// Resolves a `--preset` bundle into the individual options, leaving
+/// anything the user set explicitly untouched.
+fn apply_compress_preset(args: &mut CliArgs) -> crate::Result<()> {
+    let Some(Subcommand::Compress {
+        preset: Some(preset),
+        level,
+        threads,
+        exclude_vcs,
+        ignore_case,
+        default_format,
+        ..
+    }) = &mut args.cmd
+    else {
+        return Ok(());
+    };
+
+    match preset.as_str() {
+        "source-release" => {
+            // Reproducible-leaning release archives: deterministic single
+            // threaded encoding, no VCS noise, high-ratio tar.zst
+            *exclude_vcs = true;
+            *level = level.or(Some(19));
+            *threads = threads.or(Some(1));
+            *default_format = default_format.take().or_else(|| Some("tar.zst".into()));
+        }
+        "quick-backup" => {
+            args.gitignore = true;
+            if let Some(Subcommand::Compress { level, default_format, .. }) = &mut args.cmd {
+                *level = level.or(Some(1));
+                *default_format = default_format.take().or_else(|| Some("tar.zst".into()));
+            }
+        }
+        "windows-safe" => {
+            // Portable zips for case-insensitive consumers, special files
+            // are skipped by the walk anyway
+            *ignore_case = true;
+            *default_format = default_format.take().or_else(|| Some("zip".into()));
+        }
+        other => {
+            return Err(crate::error::FinalError::with_title(format!("Unknown preset '{other}'"))
+                .detail("Available presets: source-release, quick-backup, windows-safe")
+                .into())
+        }
+    }
+
+    Ok(())
+}
+
+/// Paths handing over a file descriptor, e.g. from process substitution or
+/// socket activation, which must not be canonicalized away.
+fn is_fd_path(path: &Path) -> bool {
+    path.starts_with("/dev/fd") || path.starts_with("/proc/self/fd") || path.starts_with("/dev/stdin")
+}
+
+/// URLs are downloaded later (with the `remote` feature) and have no
+/// canonical local path.
+fn is_url(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    text.starts_with("http://") || text.starts_with("https://")
 }
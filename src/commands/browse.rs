@@ -0,0 +1,187 @@
+//! Interactive TUI for browsing an archive and extracting chosen entries,
+//! see the `tui` cargo feature.
+
+use std::{
+    collections::BTreeSet,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use fs_err as fs;
+use ratatui::{
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, ListState},
+};
+
+use crate::{
+    error::FinalError,
+    extension::CompressionFormat::{self, *},
+    utils::{self, logger::info_accessible},
+    BUFFER_CAPACITY,
+};
+
+/// Lists the archive in a scrollable TUI; space toggles entries, `a` toggles
+/// everything, Enter extracts the selection into the current directory and
+/// q/Esc leaves without extracting.
+pub fn browse(archive_path: &Path, formats: Vec<CompressionFormat>) -> crate::Result<()> {
+    if !atty::is(atty::Stream::Stdout) {
+        return Err(FinalError::with_title("ouch browse needs a terminal")
+            .hint("Use `ouch list` for scriptable output.")
+            .into());
+    }
+
+    match formats.as_slice() {
+        [Zip] | [Tar, ..] => {}
+        _ => {
+            return Err(FinalError::with_title("Cannot browse this archive")
+                .detail("Browsing supports zip and (optionally compressed) tar archives")
+                .into())
+        }
+    }
+
+    let entries = crate::commands::diff::collect_entries(archive_path, &formats, false)?;
+    let entry_paths: Vec<PathBuf> = entries.into_keys().collect();
+    if entry_paths.is_empty() {
+        return Err(FinalError::with_title("The archive is empty").into());
+    }
+
+    let Some(selected) = run_picker(archive_path, &entry_paths)? else {
+        info_accessible("Nothing extracted.".into());
+        return Ok(());
+    };
+
+    extract_selected(archive_path, &formats, &selected)?;
+    info_accessible(format!("Extracted {} selected entries.", selected.len()));
+
+    Ok(())
+}
+
+/// Runs the picker loop, returning the chosen paths or `None` on cancel.
+fn run_picker(archive_path: &Path, entry_paths: &[PathBuf]) -> crate::Result<Option<BTreeSet<PathBuf>>> {
+    let mut terminal = ratatui::init();
+    let result = picker_loop(&mut terminal, archive_path, entry_paths);
+    ratatui::restore();
+    result
+}
+
+fn picker_loop(
+    terminal: &mut ratatui::DefaultTerminal,
+    archive_path: &Path,
+    entry_paths: &[PathBuf],
+) -> crate::Result<Option<BTreeSet<PathBuf>>> {
+    let mut selected = BTreeSet::new();
+    let mut state = ListState::default();
+    state.select(Some(0));
+
+    loop {
+        terminal.draw(|frame| {
+            let items: Vec<ListItem> = entry_paths
+                .iter()
+                .map(|path| {
+                    let mark = if selected.contains(path) { "[x] " } else { "[ ] " };
+                    ListItem::new(Line::from(format!("{mark}{}", path.display())))
+                })
+                .collect();
+
+            let title = format!(
+                " {} — space: select, a: all, enter: extract, q: quit ",
+                archive_path.display()
+            );
+            let list = List::new(items)
+                .block(Block::bordered().title(title))
+                .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+            frame.render_stateful_widget(list, frame.area(), &mut state);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let cursor = state.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Up | KeyCode::Char('k') => state.select(Some(cursor.saturating_sub(1))),
+                KeyCode::Down | KeyCode::Char('j') => {
+                    state.select(Some((cursor + 1).min(entry_paths.len() - 1)))
+                }
+                KeyCode::Char(' ') => {
+                    let path = &entry_paths[cursor];
+                    if !selected.remove(path) {
+                        selected.insert(path.clone());
+                    }
+                }
+                KeyCode::Char('a') => {
+                    if selected.len() == entry_paths.len() {
+                        selected.clear();
+                    } else {
+                        selected = entry_paths.iter().cloned().collect();
+                    }
+                }
+                KeyCode::Enter => return Ok(Some(selected)),
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Extracts only the chosen entries into the current directory.
+fn extract_selected(
+    archive_path: &Path,
+    formats: &[CompressionFormat],
+    selected: &BTreeSet<PathBuf>,
+) -> crate::Result<()> {
+    match formats {
+        [Zip] => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(archive_path)?)?;
+            for idx in 0..archive.len() {
+                let mut entry = archive.by_index(idx)?;
+                let Some(path) = entry.enclosed_name().map(Path::to_owned) else {
+                    continue;
+                };
+                if !selected.contains(&path) {
+                    continue;
+                }
+
+                if entry.is_dir() {
+                    fs::create_dir_all(&path)?;
+                } else {
+                    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+                        fs::create_dir_all(parent)?;
+                    }
+                    let mut output = fs::File::create(&path)?;
+                    io::copy(&mut entry, &mut output)?;
+                }
+            }
+        }
+        [Tar, single_file_formats @ ..] => {
+            let reader = fs::File::open(archive_path)?;
+            let mut reader: Box<dyn Read> = Box::new(io::BufReader::with_capacity(BUFFER_CAPACITY, reader));
+            for format in single_file_formats.iter().rev() {
+                reader = match format {
+                    Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+                    Bzip => Box::new(bzip2::read::BzDecoder::new(reader)),
+                    Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+                    Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+                    Snappy => Box::new(snap::read::FrameDecoder::new(reader)),
+                    Zstd => Box::new(zstd::stream::Decoder::new(reader)?),
+                    Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(reader)?)),
+                    Tar | Zip | Rar | SevenZip | Iso | Age => unreachable!(),
+                };
+            }
+
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let path = entry.path()?.into_owned();
+                if selected.contains(&path) {
+                    entry.unpack_in(utils::strip_cur_dir(Path::new(".")))?;
+                }
+            }
+        }
+        _ => unreachable!("validated in browse"),
+    }
+
+    Ok(())
+}
@@ -1,5 +1,5 @@
 use std::{
-    io::{self, BufWriter, Cursor, Seek, Write},
+    io::{self, BufWriter, Cursor, Read, Seek, Write},
     path::{Path, PathBuf},
 };
 
@@ -8,97 +8,535 @@ use fs_err as fs;
 use super::warn_user_about_loading_sevenz_in_memory;
 use crate::{
     archive,
+    error::FinalError,
     commands::warn_user_about_loading_zip_in_memory,
-    extension::{split_first_compression_format, CompressionFormat::*, Extension},
-    utils::{io::lock_and_flush_output_stdio, user_wants_to_continue, FileVisibilityPolicy},
+    extension::{
+        split_first_compression_format,
+        CompressionFormat::{self, *},
+        Extension,
+    },
+    utils::{
+        io::lock_and_flush_output_stdio, logger::warning, user_wants_to_continue, EscapedPathDisplay,
+        FileVisibilityPolicy, SizeFilter,
+    },
     QuestionAction, QuestionPolicy, BUFFER_CAPACITY,
 };
 
+/// One additional output of `--also-format`, fed from the same walk.
+pub struct AlsoOutput {
+    /// The additional output file, opened for writing
+    pub file: Box<dyn Send + Write>,
+    /// The encoder chain applied to this output (compression formats only)
+    pub formats: Vec<CompressionFormat>,
+}
+
+/// Options for compressing a set of files, see `compress_files`.
+pub struct CompressOptions<'a> {
+    /// The list of paths to be compressed: ["dir/file1.txt", "dir/file2.txt"]
+    pub files: Vec<PathBuf>,
+    /// The compression formats for compressing, example: [Tar, Gz] (in compression order)
+    pub extensions: Vec<Extension>,
+    /// Where the compressed bytes go: the output file, or the stdin of a
+    /// `--pipe-through` child process
+    pub output_file: Box<dyn Send + Write>,
+    /// The resulting compressed file name, example: "archive.tar.gz"
+    pub output_path: &'a Path,
+    pub quiet: bool,
+    pub question_policy: QuestionPolicy,
+    pub file_visibility_policy: FileVisibilityPolicy,
+    pub level: Option<i16>,
+    pub force_zip64: bool,
+    pub mtime: Option<time::OffsetDateTime>,
+    pub threads: Option<usize>,
+    pub base_dir: Option<PathBuf>,
+    pub size_filter: SizeFilter,
+    pub time_filter: crate::utils::TimeFilter,
+    /// Skip directory entries the filters emptied, see `--prune-empty`
+    pub prune_empty: bool,
+    pub no_dir_entries: bool,
+    pub lz4_content_size: bool,
+    pub dedup: bool,
+    /// Recipients for the '.age' encryption layer, passphrase mode when empty
+    pub age_recipients: &'a [String],
+    /// Entry count from the `--scan-total` pre-walk, for accurate progress
+    pub total_files: Option<u64>,
+    /// Use the lzma extreme preset modifier, see `--xz-extreme`
+    pub xz_extreme: bool,
+    /// Raw zstd dictionary contents, see `--zstd-ref`
+    pub zstd_dictionary: Option<&'a [u8]>,
+    /// Probe inputs and pick levels automatically, see `--auto-level`
+    pub auto_level: bool,
+    /// Store creation times in zip extended timestamp fields, see `--preserve-btime`
+    pub preserve_btime: bool,
+    /// Additional outputs fed from the same walk, see `--also-format`
+    pub also_outputs: Vec<AlsoOutput>,
+    /// Embed a content checksum in zstd frames, see `--zstd-checksum`
+    pub zstd_checksum: bool,
+    /// Chunks kept in flight by the prefetching reader, see `--io-threads`
+    pub io_threads: usize,
+    /// Snapshot file for incremental tar runs, see `--listed-incremental`
+    pub listed_incremental: Option<PathBuf>,
+    /// Error instead of warning when nothing was archived, see `--error-on-empty`
+    pub error_on_empty: bool,
+    /// Store symlinks as (relativized) links, see `--relativize-symlinks`
+    pub relativize_symlinks: bool,
+    /// Write a MANIFEST.sha256 entry listing every member, see `--manifest`
+    pub manifest: bool,
+    /// Whether the output supports seeking (false for stdout/pipes), which
+    /// picks the streaming data-descriptor zip layout
+    pub output_is_seekable: bool,
+    /// Emit headerless raw streams, see `--raw`
+    pub raw: bool,
+    /// Per-layer level overrides from `--level-for`
+    pub level_overrides: Vec<(CompressionFormat, i16)>,
+    /// Store canonical 0644/0755 modes, see `--normalize-permissions`
+    pub normalize_permissions: bool,
+    /// Skip the gzip FNAME/MTIME header fields, see `--no-gzip-name`
+    pub no_gzip_name: bool,
+    /// Store all-zero files as placeholders, see `--skip-zeros`
+    pub skip_zeros: bool,
+    /// Policy for input names that are not valid UTF-8, see `--non-utf8`
+    pub non_utf8: Option<crate::cli::NonUtf8Policy>,
+    /// Embed the total size in a pax global header, see `--embed-total-size`
+    pub embed_total_size: bool,
+    /// Block size of the parallel compressors, see `--par-block-size`
+    pub par_block_size: Option<usize>,
+    /// Roll the output over into numbered parts, see `--split-size`
+    pub split_size: Option<u64>,
+    /// Group 7z entries into shared solid blocks, see `--solid`
+    pub solid: bool,
+    /// Uncompressed input cap per solid block, see `--solid-block-size`
+    pub solid_block_size: Option<u64>,
+    /// Transient-error retries for reads and writes, see `--retry`
+    pub retry: u32,
+}
+
 /// Compress files into `output_file`.
 ///
-/// # Arguments:
-/// - `files`: is the list of paths to be compressed: ["dir/file1.txt", "dir/file2.txt"]
-/// - `extensions`: is a list of compression formats for compressing, example: [Tar, Gz] (in compression order)
-/// - `output_file` is the resulting compressed file name, example: "archive.tar.gz"
-///
 /// # Return value
 /// - Returns `Ok(true)` if compressed all files normally.
 /// - Returns `Ok(false)` if user opted to abort compression mid-way.
-#[allow(clippy::too_many_arguments)]
-pub fn compress_files(
-    files: Vec<PathBuf>,
-    extensions: Vec<Extension>,
-    output_file: fs::File,
-    output_path: &Path,
-    quiet: bool,
-    question_policy: QuestionPolicy,
-    file_visibility_policy: FileVisibilityPolicy,
-    level: Option<i16>,
-) -> crate::Result<bool> {
+pub fn compress_files(options: CompressOptions) -> crate::Result<bool> {
+    let CompressOptions {
+        files,
+        extensions,
+        output_file,
+        output_path,
+        quiet,
+        question_policy,
+        file_visibility_policy,
+        level,
+        force_zip64,
+        mtime,
+        threads,
+        base_dir,
+        size_filter,
+        time_filter,
+        prune_empty,
+        no_dir_entries,
+        lz4_content_size,
+        dedup,
+        age_recipients,
+        total_files,
+        xz_extreme,
+        zstd_dictionary,
+        auto_level,
+        preserve_btime,
+        also_outputs,
+        zstd_checksum,
+        io_threads,
+        listed_incremental,
+        error_on_empty,
+        relativize_symlinks,
+        manifest,
+        output_is_seekable,
+        raw,
+        level_overrides,
+        normalize_permissions,
+        no_gzip_name,
+        skip_zeros,
+        non_utf8,
+        embed_total_size,
+        par_block_size,
+        split_size,
+        solid,
+        solid_block_size,
+        retry,
+    } = options;
     // If the input files contain a directory, then the total size will be underestimated
     let file_writer = BufWriter::with_capacity(BUFFER_CAPACITY, output_file);
 
-    let mut writer: Box<dyn Send + Write> = Box::new(file_writer);
+    // Threading is capped to the physical cores by default, hyperthreads only
+    // add contention for the compressors' number crunching
+    let threads = threads.unwrap_or_else(num_cpus::get_physical).max(1);
+
+    // --auto-level probes the first input block: high-entropy data gets the
+    // fastest level (it won't shrink anyway), compressible data a high one
+    let probe_target = files
+        .first()
+        .filter(|file| *file != Path::new("-") && file.is_file());
+    let level = match probe_target {
+        Some(file) if auto_level => probe_auto_level(file, quiet)?.or(level),
+        _ => level,
+    };
+
+    // --split-size swaps the single output file for numbered parts; the
+    // file pre-created by the overwrite handling is replaced by them
+    let mut writer: Box<dyn Send + Write> = match split_size {
+        Some(part_size) => {
+            drop(file_writer);
+            fs_err::remove_file(output_path)?;
+            Box::new(crate::utils::io::SplittingWriter::new(output_path.to_path_buf(), part_size))
+        }
+        None => Box::new(file_writer),
+    };
+
+    if xz_extreme
+        && !extensions
+            .iter()
+            .flat_map(|extension| extension.compression_formats)
+            .any(|format| *format == Lzma)
+    {
+        return Err(FinalError::with_title("Cannot use --xz-extreme")
+            .detail("The flag only applies to the xz/lzma format, which is not part of the output chain")
+            .into());
+    }
+
+    // The age encryptor has to be prepared up front: recipients are parsed
+    // (or a passphrase is asked for) before any data is written
+    let has_age_layer = extensions
+        .iter()
+        .flat_map(|extension| extension.compression_formats)
+        .any(|format| *format == Age);
+    let mut age_encryptor = if has_age_layer {
+        Some(build_age_encryptor(age_recipients)?)
+    } else {
+        None
+    };
+
+    // Resolves the effective level for a format: the user's --level clamped
+    // to the valid range, or the format's documented default
+    let effective_level = |format: CompressionFormat| -> i16 {
+        let default = format
+            .default_level()
+            .expect("only used with formats that take a level");
+        // A per-layer override wins over the global --level
+        let level = level_overrides
+            .iter()
+            .find(|(override_format, _)| *override_format == format)
+            .map(|(_, level)| *level)
+            .or(level);
+        let Some(level) = level else { return default };
+
+        let range = format.level_range().expect("leveled formats expose their range");
+        i32::from(level).clamp(*range.start(), *range.end()) as i16
+    };
 
     // Grab previous encoder and wrap it inside of a new one
-    let chain_writer_encoder = |format: &_, encoder| -> crate::Result<_> {
+    let mut chain_writer_encoder = |format: &_, encoder| -> crate::Result<_> {
         let encoder: Box<dyn Send + Write> = match format {
-            Gzip => Box::new(
-                // by default, ParCompress uses a default compression level of 3
-                // instead of the regular default that flate2 uses
-                gzp::par::compress::ParCompress::<gzp::deflate::Gzip>::builder()
-                    .compression_level(
-                        level.map_or_else(Default::default, |l| gzp::Compression::new((l as u32).clamp(0, 9))),
-                    )
-                    .from_writer(encoder),
-            ),
-            Bzip => Box::new(bzip2::write::BzEncoder::new(
+            // --raw swaps the gzip container for a bare deflate stream
+            Gzip if raw => Box::new(flate2::write::DeflateEncoder::new(
                 encoder,
-                level.map_or_else(Default::default, |l| bzip2::Compression::new((l as u32).clamp(1, 9))),
+                flate2::Compression::new(effective_level(Gzip) as u32),
             )),
-            Lz4 => Box::new(lz4_flex::frame::FrameEncoder::new(encoder).auto_finish()),
-            Lzma => Box::new(xz2::write::XzEncoder::new(
+            Gzip => {
+                if threads > 1 {
+                    let builder = gzp::par::compress::ParCompress::<gzp::deflate::Gzip>::builder()
+                        .num_threads(threads)
+                        .expect("threads is always at least 1")
+                        .buffer_size(par_block_size.unwrap_or(gzp::BUFSIZE).max(gzp::DICT_SIZE))
+                        .expect("the block size is clamped to the minimum")
+                        .compression_level(gzp::Compression::new(effective_level(Gzip) as u32));
+                    Box::new(builder.from_writer(encoder))
+                } else {
+                    // With a single thread the plain flate2 encoder avoids
+                    // gzp's worker machinery and is more deterministic
+                    Box::new(flate2::write::GzEncoder::new(
+                        encoder,
+                        flate2::Compression::new(effective_level(Gzip) as u32),
+                    ))
+                }
+            }
+            Bzip => Box::new(bzip2::write::BzEncoder::new(
                 encoder,
-                level.map_or(6, |l| (l as u32).clamp(0, 9)),
+                bzip2::Compression::new(effective_level(Bzip) as u32),
             )),
-            Snappy => Box::new(
-                gzp::par::compress::ParCompress::<gzp::snap::Snap>::builder()
-                    .compression_level(gzp::par::compress::Compression::new(
-                        level.map_or_else(Default::default, |l| (l as u32).clamp(0, 9)),
-                    ))
-                    .from_writer(encoder),
-            ),
+            Lz4 => {
+                if lz4_content_size {
+                    Box::new(Lz4ContentSizeEncoder::new(encoder))
+                } else {
+                    Box::new(lz4_flex::frame::FrameEncoder::new(encoder).auto_finish())
+                }
+            }
+            Lzma => {
+                // LZMA_PRESET_EXTREME trades noticeably more CPU time for a
+                // slightly better ratio
+                const LZMA_PRESET_EXTREME: u32 = 1 << 31;
+
+                let mut preset = effective_level(Lzma) as u32;
+                if xz_extreme {
+                    preset |= LZMA_PRESET_EXTREME;
+                }
+                Box::new(xz2::write::XzEncoder::new(encoder, preset))
+            }
+            Snappy => {
+                if threads > 1 {
+                    let builder = gzp::par::compress::ParCompress::<gzp::snap::Snap>::builder()
+                        .num_threads(threads)
+                        .expect("threads is always at least 1")
+                        .buffer_size(par_block_size.unwrap_or(gzp::BUFSIZE).max(gzp::DICT_SIZE))
+                        .expect("the block size is clamped to the minimum")
+                        .compression_level(gzp::par::compress::Compression::new(effective_level(Snappy) as u32));
+                    Box::new(builder.from_writer(encoder))
+                } else {
+                    Box::new(snap::write::FrameEncoder::new(encoder))
+                }
+            }
             Zstd => {
-                let mut zstd_encoder = zstd::stream::write::Encoder::new(
-                    encoder,
-                    level.map_or(zstd::DEFAULT_COMPRESSION_LEVEL, |l| {
-                        (l as i32).clamp(zstd::zstd_safe::min_c_level(), zstd::zstd_safe::max_c_level())
-                    }),
-                )?;
-                // Use all available PHYSICAL cores for compression
-                zstd_encoder.multithread(num_cpus::get_physical() as u32)?;
+                let mut zstd_encoder = match zstd_dictionary {
+                    // A raw reference dictionary primes the encoder for
+                    // data similar to the reference file
+                    Some(dictionary) => {
+                        zstd::stream::write::Encoder::with_dictionary(encoder, i32::from(effective_level(Zstd)), dictionary)?
+                    }
+                    None => zstd::stream::write::Encoder::new(encoder, i32::from(effective_level(Zstd)))?,
+                };
+                // Worker threads only help for sizable inputs, --threads 1
+                // keeps the encoder single-threaded
+                if threads > 1 {
+                    zstd_encoder.multithread(threads as u32)?;
+                }
+                if zstd_checksum {
+                    // The frame carries a content checksum, verified by
+                    // decompressors
+                    zstd_encoder.include_checksum(true)?;
+                }
                 Box::new(zstd_encoder.auto_finish())
             }
-            Tar | Zip | Rar | SevenZip => unreachable!(),
+            Age => {
+                let encryptor = age_encryptor
+                    .take()
+                    .expect("chain position was checked, age appears exactly once");
+                let writer = encryptor.wrap_output(encoder).map_err(|err| {
+                    FinalError::with_title("Could not start age encryption").detail(err.to_string())
+                })?;
+                Box::new(AgeStreamWriter(Some(writer)))
+            }
+            Lzw => {
+                return Err(FinalError::with_title("Cannot compress to the .Z format")
+                    .detail("compress(1) is obsolete, only decompression is supported")
+                    .hint("Use gzip or zstd instead, e.g. --format gz")
+                    .into())
+            }
+            Tar | Zip | Rar | SevenZip | Iso => unreachable!(),
         };
         Ok(encoder)
     };
 
     let (first_format, formats) = split_first_compression_format(&extensions);
 
+    if !also_outputs.is_empty() && matches!(first_format, Zip | SevenZip | Rar | Iso) {
+        return Err(FinalError::with_title("Cannot use --also-format with this container")
+            .detail("Teeing one walk into several outputs works for tar and single-stream formats")
+            .into());
+    }
+
+    if listed_incremental.is_some() && first_format != Tar {
+        return Err(FinalError::with_title("--listed-incremental only works with tar archives")
+            .detail("Incremental snapshots record state per tar entry")
+            .into());
+    }
+
+    if files.iter().any(|file| file == Path::new("-")) && first_format.is_archive() {
+        return Err(FinalError::with_title("Cannot compress stdin into an archive format")
+            .detail("A single stream cannot carry the file names an archive needs")
+            .hint("Use a single-stream format instead, e.g. --format gz or --format zst.")
+            .into());
+    }
+
+    if zstd_dictionary.is_some()
+        && !extensions
+            .iter()
+            .flat_map(|extension| extension.compression_formats)
+            .any(|format| *format == Zstd)
+    {
+        return Err(FinalError::with_title("Cannot use --zstd-ref")
+            .detail("The flag only applies to the zstd format, which is not part of the output chain")
+            .into());
+    }
+
+    if raw
+        && !extensions
+            .iter()
+            .flat_map(|extension| extension.compression_formats)
+            .any(|format| *format == Gzip)
+    {
+        return Err(FinalError::with_title("Cannot use --raw with this chain")
+            .detail("Headerless raw output is supported for the deflate family (gz) only")
+            .into());
+    }
+
+    if dedup && first_format != Tar {
+        // Only tar has a native mechanism (hard-link entries) to store a
+        // file once and reference it again
+        warning("--dedup only applies to tar output, duplicates will be stored in full".into());
+    }
+
+
     for format in formats.iter().rev() {
         writer = chain_writer_encoder(format, writer)?;
     }
 
+    // Each --also-format output runs its own encoder chain; where the tee
+    // attaches depends on the container (below): the raw tar stream feeds
+    // the branches for tar output, the raw input for single-stream formats
+    let mut also_writers: Vec<Box<dyn Send + Write>> = vec![];
+    for additional in also_outputs {
+        if additional.formats.contains(&Age) {
+            return Err(FinalError::with_title("Cannot use --also-format with the age layer")
+                .detail("Encrypt each output with its own ouch invocation instead")
+                .into());
+        }
+
+        let mut chain: Box<dyn Send + Write> = Box::new(BufWriter::with_capacity(BUFFER_CAPACITY, additional.file));
+        for format in additional.formats.iter().rev() {
+            chain = chain_writer_encoder(format, chain)?;
+        }
+        also_writers.push(chain);
+    }
+
     match first_format {
-        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd => {
-            writer = chain_writer_encoder(&first_format, writer)?;
-            let mut reader = fs::File::open(&files[0]).unwrap();
+        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd | Age | Lzw => {
+            // "-" streams from stdin, composing with --format for pipelines
+            let mut reader: Box<dyn Read> = if files[0] == Path::new("-") {
+                Box::new(io::stdin().lock())
+            } else {
+                // Single-file formats compress exactly one regular file,
+                // reject pipes, sockets and device files that could block
+                // forever
+                let metadata = fs::metadata(&files[0])?;
+                if !metadata.is_file() {
+                    return Err(FinalError::with_title("Cannot compress input")
+                        .detail(format!(
+                            "The input {} is not a regular file",
+                            EscapedPathDisplay::new(&files[0])
+                        ))
+                        .hint("Non-regular files can only be skipped inside of archives (tar/zip).")
+                        .into());
+                }
+                let _fd_permit = crate::utils::io::fd_quota::acquire();
+                let file = fs::File::open(&files[0])?;
+                let file: Box<dyn Read + Send> = if retry > 0 {
+                    Box::new(crate::utils::io::RetryingReader::new(file, retry))
+                } else {
+                    Box::new(file)
+                };
+                if io_threads > 0 {
+                    // Reads run on a background thread so input latency
+                    // overlaps with the compression work
+                    Box::new(crate::utils::io::PrefetchReader::new(file, io_threads))
+                } else {
+                    Box::new(file)
+                }
+            };
 
-            io::copy(&mut reader, &mut writer)?;
+            // The also-branches must see the raw input, not the primary's
+            // already-compressed bytes, so the tee sits on the read side
+            if !also_writers.is_empty() {
+                reader = Box::new(TeeReader {
+                    inner: reader,
+                    branches: std::mem::take(&mut also_writers),
+                });
+            }
+
+            // Plain single-file gzip stores the original name and mtime in
+            // the header (like gzip itself), so `gzip -N` can restore them
+            let store_gzip_header = first_format == Gzip
+                && formats.is_empty()
+                && !no_gzip_name
+                && !raw
+                && files[0] != Path::new("-");
+            if store_gzip_header {
+                let metadata = fs::metadata(&files[0])?;
+                let mtime = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map_or(0, |since_epoch| since_epoch.as_secs() as u32);
+                let file_name = files[0]
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+
+                let mut gz_writer = flate2::GzBuilder::new()
+                    .filename(file_name.as_bytes())
+                    .mtime(mtime)
+                    .write(writer, flate2::Compression::new(effective_level(Gzip) as u32));
+                io::copy(&mut reader, &mut gz_writer)?;
+                gz_writer.finish()?.flush()?;
+            } else {
+                writer = chain_writer_encoder(&first_format, writer)?;
+                io::copy(&mut reader, &mut writer)?;
+            }
         }
         Tar => {
-            archive::tar::build_archive_from_paths(&files, output_path, &mut writer, file_visibility_policy, quiet)?;
+            // Tee the raw tar stream into each --also-format branch
+            if !also_writers.is_empty() {
+                let mut writers = vec![writer];
+                writers.append(&mut also_writers);
+                writer = Box::new(BroadcastWriter { writers });
+            }
+
+            archive::tar::build_archive_from_paths(
+                &files,
+                output_path,
+                &mut writer,
+                file_visibility_policy,
+                quiet,
+                mtime,
+                base_dir.as_deref(),
+                size_filter,
+                time_filter,
+                prune_empty,
+                no_dir_entries,
+                dedup,
+                total_files,
+                io_threads,
+                listed_incremental.as_deref(),
+                error_on_empty,
+                relativize_symlinks,
+                manifest,
+                normalize_permissions,
+                skip_zeros,
+                embed_total_size,
+            )?;
+            writer.flush()?;
+        }
+        Zip if !output_is_seekable => {
+            // Streaming straight to a pipe/stdout with data descriptors,
+            // instead of buffering the whole zip in memory
+            if !formats.is_empty() {
+                let _locks = lock_and_flush_output_stdio();
+                warn_user_about_loading_zip_in_memory();
+                if !user_wants_to_continue(output_path, question_policy, QuestionAction::Compression)? {
+                    return Ok(false);
+                }
+            }
+
+            archive::zip::build_archive_streaming(
+                &files,
+                output_path,
+                &mut writer,
+                file_visibility_policy,
+                quiet,
+                base_dir.as_deref(),
+                size_filter,
+                time_filter,
+                prune_empty,
+                no_dir_entries,
+                non_utf8,
+            )?;
             writer.flush()?;
         }
         Zip => {
@@ -121,6 +559,20 @@ pub fn compress_files(
                 &mut vec_buffer,
                 file_visibility_policy,
                 quiet,
+                force_zip64,
+                mtime,
+                base_dir.as_deref(),
+                size_filter,
+                time_filter,
+                prune_empty,
+                no_dir_entries,
+                total_files,
+                auto_level,
+                preserve_btime,
+                error_on_empty,
+                manifest,
+                normalize_permissions,
+                non_utf8,
             )?;
             vec_buffer.rewind()?;
             io::copy(&mut vec_buffer, &mut writer)?;
@@ -132,6 +584,9 @@ pub fn compress_files(
             #[cfg(not(feature = "unrar"))]
             return Err(archive::rar_stub::no_support());
         }
+        Iso => {
+            return Err(archive::iso::no_compression());
+        }
         SevenZip => {
             if !formats.is_empty() {
                 // Locking necessary to guarantee that warning and question
@@ -144,12 +599,332 @@ pub fn compress_files(
                 }
             }
 
+            // The buffered phases can take a while for large inputs, let
+            // Ctrl-C cancel them cleanly (the incomplete output gets the
+            // usual cleanup)
+            crate::utils::io::interrupt::arm();
+
             let mut vec_buffer = Cursor::new(vec![]);
-            archive::sevenz::compress_sevenz(&files, output_path, &mut vec_buffer, file_visibility_policy, quiet)?;
+            archive::sevenz::compress_sevenz(
+                &files,
+                output_path,
+                &mut vec_buffer,
+                file_visibility_policy,
+                quiet,
+                base_dir.as_deref(),
+                size_filter,
+                time_filter,
+                total_files,
+                error_on_empty,
+                solid,
+                solid_block_size,
+                non_utf8,
+            )?;
+
+            // Distinct from the compression phase above: the archive was
+            // built in memory and is now copied to its destination
+            let total_bytes = vec_buffer.get_ref().len() as u64;
+            if !quiet {
+                crate::utils::logger::info(format!(
+                    "Writing the buffered 7z archive ({}).",
+                    crate::utils::Bytes::new(total_bytes)
+                ));
+            }
             vec_buffer.rewind()?;
-            io::copy(&mut vec_buffer, &mut writer)?;
+
+            let mut written: u64 = 0;
+            let mut chunk = [0; BUFFER_CAPACITY];
+            loop {
+                if crate::utils::io::interrupt::interrupted() {
+                    return Err(FinalError::with_title("Cancelled by Ctrl-C")
+                        .detail("The incomplete output file is being cleaned up")
+                        .into());
+                }
+                let read = io::Read::read(&mut vec_buffer, &mut chunk)?;
+                if read == 0 {
+                    break;
+                }
+                writer.write_all(&chunk[..read])?;
+                written += read as u64;
+                if !quiet && written.is_multiple_of(BUFFER_CAPACITY as u64 * 4096) {
+                    crate::utils::logger::info(format!(
+                        "Wrote {} of {}...",
+                        crate::utils::Bytes::new(written),
+                        crate::utils::Bytes::new(total_bytes)
+                    ));
+                }
+            }
         }
     }
 
     Ok(true)
 }
+
+
+/// Buffers the whole stream in memory and only encodes it when dropped, so
+/// the lz4 frame header can record the uncompressed content size (requested
+/// with --lz4-content-size, the default streams without the field).
+struct Lz4ContentSizeEncoder<W: Write> {
+    buffer: Vec<u8>,
+    writer: Option<W>,
+}
+
+impl<W: Write> Lz4ContentSizeEncoder<W> {
+    fn new(writer: W) -> Self {
+        Self {
+            buffer: vec![],
+            writer: Some(writer),
+        }
+    }
+}
+
+impl<W: Write> Write for Lz4ContentSizeEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Lz4ContentSizeEncoder<W> {
+    fn drop(&mut self) {
+        // Like the other encoders in the chain, finalization happens on drop
+        // and encoding errors surface when the underlying writer fails later
+        let frame_info = lz4_flex::frame::FrameInfo::new().content_size(Some(self.buffer.len() as u64));
+        let writer = self.writer.take().expect("writer is only taken on drop");
+        let mut encoder = lz4_flex::frame::FrameEncoder::with_frame_info(frame_info, writer);
+        let result = encoder
+            .write_all(&self.buffer)
+            .and_then(|_| encoder.try_finish().map_err(io::Error::from));
+        if result.is_err() {
+            warning("Failed to finish writing the lz4 stream".into());
+        }
+    }
+}
+
+
+/// Prepares the encryptor for the '.age' layer: for the given recipient
+/// public keys, or with a passphrase asked interactively when none were
+/// passed.
+fn build_age_encryptor(age_recipients: &[String]) -> crate::Result<age::Encryptor> {
+    if age_recipients.is_empty() {
+        let passphrase = crate::utils::ask_passphrase("Type the passphrase for the '.age' layer:")?;
+        return Ok(age::Encryptor::with_user_passphrase(passphrase.into()));
+    }
+
+    let recipients: Vec<age::x25519::Recipient> = age_recipients
+        .iter()
+        .map(|recipient| {
+            recipient.parse().map_err(|err: &str| {
+                FinalError::with_title(format!("Invalid age recipient '{recipient}'"))
+                    .detail(err.to_string())
+                    .into()
+            })
+        })
+        .collect::<crate::Result<_>>()?;
+
+    age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn age::Recipient)).map_err(
+        |err| {
+            FinalError::with_title("Could not set up age encryption")
+                .detail(err.to_string())
+                .into()
+        },
+    )
+}
+
+/// Adapter finishing the age encryption stream on drop, like the other
+/// encoders in the writer chain.
+struct AgeStreamWriter<W: Write>(Option<age::stream::StreamWriter<W>>);
+
+impl<W: Write> Write for AgeStreamWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.as_mut().expect("writer is only taken on drop").write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.as_mut().expect("writer is only taken on drop").flush()
+    }
+}
+
+impl<W: Write> Drop for AgeStreamWriter<W> {
+    fn drop(&mut self) {
+        let writer = self.0.take().expect("writer is only taken on drop");
+        if writer.finish().is_err() {
+            warning("Failed to finish writing the age encryption stream".into());
+        }
+    }
+}
+
+
+/// Probes the first block of the (first) input for `--auto-level`: returns a
+/// level override, or `None` to keep the defaults for middling data.
+fn probe_auto_level(path: &Path, quiet: bool) -> crate::Result<Option<i16>> {
+    use std::io::Read;
+
+    let mut probe = [0; BUFFER_CAPACITY];
+    let _fd_permit = crate::utils::io::fd_quota::acquire();
+    let mut file = fs::File::open(path)?;
+    let mut filled = 0;
+    while filled < probe.len() {
+        match file.read(&mut probe[filled..])? {
+            0 => break,
+            read => filled += read,
+        }
+    }
+
+    let entropy = crate::utils::io::shannon_entropy(&probe[..filled]);
+    let level = if entropy > crate::utils::io::INCOMPRESSIBLE_ENTROPY {
+        Some(1)
+    } else if entropy < crate::utils::io::COMPRESSIBLE_ENTROPY {
+        Some(9)
+    } else {
+        None
+    };
+
+    if !quiet {
+        if let Some(level) = level {
+            warning(format!(
+                "--auto-level picked level {level} (probe entropy {entropy:.2} bits/byte)"
+            ));
+        }
+    }
+
+    Ok(level)
+}
+
+
+/// Distributes every written byte to several writers, used by
+/// `--also-format` to feed multiple encoder chains from one walk.
+struct BroadcastWriter {
+    writers: Vec<Box<dyn Send + Write>>,
+}
+
+/// Copies everything read through to the `--also-format` branches, so each
+/// branch encodes the raw input independently of the primary encoder chain
+/// (which may include special headers like the gzip file name).
+struct TeeReader {
+    inner: Box<dyn Read>,
+    branches: Vec<Box<dyn Send + Write>>,
+}
+
+impl Read for TeeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        for branch in &mut self.branches {
+            branch.write_all(&buf[..read])?;
+        }
+        Ok(read)
+    }
+}
+
+impl Write for BroadcastWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for writer in &mut self.writers {
+            writer.write_all(buf)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        for writer in &mut self.writers {
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// One row of the `--metadata-only` index.
+#[derive(serde::Serialize)]
+struct MetadataEntry {
+    path: String,
+    size: u64,
+    mtime: u64,
+    sha256: String,
+}
+
+/// Writes an index of the inputs (path, size, mtime, content hash) to the
+/// output instead of archiving them, see `--metadata-only`. The same walk
+/// as compression runs, so visibility filters apply; a compression
+/// extension on the output (e.g. `index.json.gz`) compresses the sidecar.
+pub fn write_metadata_index(
+    files: &[PathBuf],
+    output_path: &Path,
+    metadata_format: crate::cli::MetadataFormat,
+    file_visibility_policy: &crate::utils::FileVisibilityPolicy,
+    question_policy: QuestionPolicy,
+) -> crate::Result<()> {
+    let Some(output_file) = crate::utils::ask_to_create_file(output_path, question_policy, None, None, false)? else {
+        return Ok(());
+    };
+
+    let mut entries = vec![];
+    for filename in files {
+        let (previous_location, filename) = crate::utils::cd_for_archiving(filename, None)?;
+        for entry in file_visibility_policy.build_walker(&filename)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Ok(metadata) = path.metadata() else { continue };
+            if !metadata.is_file() {
+                continue;
+            }
+
+            let mtime = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |since_epoch| since_epoch.as_secs());
+            entries.push(MetadataEntry {
+                path: path.to_string_lossy().into_owned(),
+                size: metadata.len(),
+                mtime,
+                sha256: crate::utils::checksum::hash_file(path, crate::cli::ChecksumAlgorithm::Sha256)?,
+            });
+        }
+        std::env::set_current_dir(previous_location)?;
+    }
+
+    // A recognized compression extension on the output name wraps the
+    // sidecar in that encoder chain
+    let mut writer: Box<dyn Write> = Box::new(BufWriter::with_capacity(BUFFER_CAPACITY, output_file));
+    let formats = crate::extension::flatten_compression_formats(&crate::extension::extensions_from_path(output_path));
+    for format in formats.iter().rev() {
+        if format.is_archive() || *format == crate::extension::CompressionFormat::Age {
+            return Err(FinalError::with_title("Cannot write the metadata index into an archive format")
+                .hint("Pick a plain or compressed sidecar name, like index.json or index.csv.gz.")
+                .into());
+        }
+        writer = crate::commands::convert::chain_default_encoder(format, writer)?;
+    }
+
+    match metadata_format {
+        crate::cli::MetadataFormat::Json => {
+            serde_json::to_writer_pretty(&mut writer, &entries).expect("entries serialize cleanly");
+            writeln!(writer)?;
+        }
+        crate::cli::MetadataFormat::Csv => {
+            writeln!(writer, "path,size,mtime,sha256")?;
+            for entry in &entries {
+                // Paths may contain commas, so that field is always quoted
+                writeln!(
+                    writer,
+                    "\"{}\",{},{},{}",
+                    entry.path.replace('"', "\"\""),
+                    entry.size,
+                    entry.mtime,
+                    entry.sha256
+                )?;
+            }
+        }
+    }
+    writer.flush()?;
+
+    crate::utils::logger::info_accessible(format!(
+        "Wrote the metadata index of {} files to '{}'.",
+        entries.len(),
+        crate::utils::to_utf(output_path)
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,230 @@
+//! Convert an archive to another container format, see `ouch convert`.
+//!
+//! Entries stream one by one from the input container into the output
+//! container, preserving paths, permissions and modification times;
+//! format-specific metadata without a counterpart (like zip comments in
+//! tar) is dropped with a warning.
+
+use std::{
+    io::{self, BufReader, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+
+use crate::{
+    error::FinalError,
+    extension::{
+        CompressionFormat::{self, *},
+        Extension,
+    },
+    utils::{self, logger::info_accessible, to_utf},
+    QuestionPolicy, BUFFER_CAPACITY,
+};
+
+/// Metadata carried over for every converted entry.
+struct EntryMeta {
+    path: PathBuf,
+    is_dir: bool,
+    mode: Option<u32>,
+    mtime: Option<u64>,
+    size: u64,
+}
+
+pub fn convert(
+    input_path: &Path,
+    output_path: &Path,
+    input_formats: Vec<Extension>,
+    output_formats: Vec<Extension>,
+    question_policy: QuestionPolicy,
+) -> crate::Result<()> {
+    let input_chain = crate::extension::flatten_compression_formats(&input_formats);
+    let output_chain = crate::extension::flatten_compression_formats(&output_formats);
+
+    let unsupported = |side: &str| {
+        crate::Error::from(
+            FinalError::with_title("Cannot convert this combination")
+                .detail(format!(
+                    "The {side} must be a tar chain or a plain zip, converting between containers"
+                ))
+                .hint("Use recompress for same-container rewrites, or decompress+compress manually."),
+        )
+    };
+
+    match input_chain.as_slice() {
+        [Zip] | [Tar, ..] => {}
+        _ => return Err(unsupported("input")),
+    }
+    match output_chain.as_slice() {
+        [Zip] | [Tar, ..] => {}
+        _ => return Err(unsupported("output")),
+    }
+
+    let Some(output_file) = utils::ask_to_create_file(output_path, question_policy, None, None, false)? else {
+        return Ok(());
+    };
+
+    // Build the output container (tar behind its encoder chain, or a
+    // directly-seekable zip)
+    let mut converted = 0;
+    match output_chain.as_slice() {
+        [Tar, encoder_formats @ ..] => {
+            let mut writer: Box<dyn Write> = Box::new(io::BufWriter::with_capacity(BUFFER_CAPACITY, output_file));
+            for format in encoder_formats.iter().rev() {
+                writer = chain_default_encoder(format, writer)?;
+            }
+            let mut builder = tar::Builder::new(writer);
+
+            for_each_entry(input_path, &input_chain, |meta, reader| {
+                let mut header = tar::Header::new_gnu();
+                header.set_size(if meta.is_dir { 0 } else { meta.size });
+                header.set_mode(meta.mode.unwrap_or(if meta.is_dir { 0o755 } else { 0o644 }) & 0o7777);
+                header.set_mtime(meta.mtime.unwrap_or(0));
+                if meta.is_dir {
+                    header.set_entry_type(tar::EntryType::Directory);
+                    builder.append_data(&mut header, &meta.path, io::empty())?;
+                } else {
+                    builder.append_data(&mut header, &meta.path, reader)?;
+                }
+                converted += 1;
+                Ok(())
+            })?;
+
+            builder.into_inner()?.flush()?;
+        }
+        [Zip] => {
+            let mut writer = zip::ZipWriter::new(output_file);
+
+            for_each_entry(input_path, &input_chain, |meta, reader| {
+                let mut options = zip::write::FileOptions::default();
+                if let Some(mode) = meta.mode {
+                    options = options.unix_permissions(mode);
+                }
+                if let Some(mtime) = meta.mtime {
+                    let datetime = time::OffsetDateTime::from_unix_timestamp(mtime as i64)
+                        .ok()
+                        .and_then(|datetime| zip::DateTime::try_from(datetime).ok())
+                        .unwrap_or_default();
+                    options = options.last_modified_time(datetime);
+                }
+
+                let name = meta.path.to_string_lossy().into_owned();
+                if meta.is_dir {
+                    writer.add_directory(name, options)?;
+                } else {
+                    writer.start_file(name, options)?;
+                    io::copy(reader, &mut writer)?;
+                }
+                converted += 1;
+                Ok(())
+            })?;
+
+            writer.finish()?.flush()?;
+        }
+        _ => unreachable!("validated above"),
+    }
+
+    info_accessible(format!(
+        "Converted {converted} entries from '{}' to '{}'.",
+        to_utf(input_path),
+        to_utf(output_path)
+    ));
+
+    Ok(())
+}
+
+/// Streams every entry of the input container to `handle`.
+fn for_each_entry(
+    input_path: &Path,
+    input_chain: &[CompressionFormat],
+    mut handle: impl FnMut(EntryMeta, &mut dyn Read) -> crate::Result<()>,
+) -> crate::Result<()> {
+    match input_chain {
+        [Zip] => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(input_path)?)?;
+            for idx in 0..archive.len() {
+                let mut entry = archive.by_index(idx)?;
+                let Some(path) = entry.enclosed_name().map(Path::to_owned) else {
+                    continue;
+                };
+                if !entry.comment().is_empty() {
+                    utils::logger::warning(format!(
+                        "The zip comment on '{}' has no counterpart in the target format, dropping it",
+                        entry.name()
+                    ));
+                }
+
+                let meta = EntryMeta {
+                    is_dir: entry.is_dir(),
+                    mode: entry.unix_mode(),
+                    mtime: entry
+                        .last_modified()
+                        .to_time()
+                        .ok()
+                        .map(|datetime| datetime.unix_timestamp().max(0) as u64),
+                    size: entry.size(),
+                    path,
+                };
+                handle(meta, &mut entry)?;
+            }
+        }
+        [Tar, decoder_formats @ ..] => {
+            let reader = fs::File::open(input_path)?;
+            let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, reader));
+            for format in decoder_formats.iter().rev() {
+                reader = chain_default_decoder(format, reader)?;
+            }
+
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let meta = EntryMeta {
+                    path: entry.path()?.into_owned(),
+                    is_dir: entry.header().entry_type().is_dir(),
+                    mode: entry.header().mode().ok(),
+                    mtime: entry.header().mtime().ok(),
+                    size: entry.size(),
+                };
+                handle(meta, &mut entry)?;
+            }
+        }
+        _ => unreachable!("validated by the caller"),
+    }
+
+    Ok(())
+}
+
+/// Encoder chaining with the per-format default levels, for the converted
+/// output.
+pub(crate) fn chain_default_encoder(format: &CompressionFormat, writer: Box<dyn Write>) -> crate::Result<Box<dyn Write>> {
+    let default_level = |format: &CompressionFormat| format.default_level().unwrap_or(0);
+
+    Ok(match format {
+        Gzip => Box::new(flate2::write::GzEncoder::new(
+            writer,
+            flate2::Compression::new(default_level(format) as u32),
+        )),
+        Bzip => Box::new(bzip2::write::BzEncoder::new(
+            writer,
+            bzip2::Compression::new(default_level(format) as u32),
+        )),
+        Lz4 => Box::new(lz4_flex::frame::FrameEncoder::new(writer).auto_finish()),
+        Lzma => Box::new(xz2::write::XzEncoder::new(writer, default_level(format) as u32)),
+        Snappy => Box::new(snap::write::FrameEncoder::new(writer)),
+        Zstd => Box::new(zstd::stream::write::Encoder::new(writer, i32::from(default_level(format)))?.auto_finish()),
+        Tar | Zip | Rar | SevenZip | Iso | Age | Lzw => unreachable!("not encoder formats here"),
+    })
+}
+
+pub(crate) fn chain_default_decoder(format: &CompressionFormat, reader: Box<dyn Read>) -> crate::Result<Box<dyn Read>> {
+    Ok(match format {
+        Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Bzip => Box::new(bzip2::read::BzDecoder::new(reader)),
+        Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Snappy => Box::new(snap::read::FrameDecoder::new(reader)),
+        Zstd => Box::new(zstd::stream::Decoder::new(reader)?),
+        Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(reader)?)),
+        Tar | Zip | Rar | SevenZip | Iso | Age => unreachable!("not decoder formats here"),
+    })
+}
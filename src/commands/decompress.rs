@@ -8,34 +8,105 @@ use fs_err as fs;
 
 use crate::{
     commands::{warn_user_about_loading_sevenz_in_memory, warn_user_about_loading_zip_in_memory},
+    error::FinalError,
     extension::{
         split_first_compression_format,
         CompressionFormat::{self, *},
         Extension,
     },
     utils::{
-        self, io::lock_and_flush_output_stdio, logger::info_accessible, nice_directory_display, user_wants_to_continue,
+        self,
+        bomb::{BombGuard, BombGuardSettings},
+        io::lock_and_flush_output_stdio,
+        logger::info_accessible,
+        nice_directory_display, user_wants_to_continue, ConflictPolicy, DuplicatePolicy, EscapedPathDisplay,
     },
     QuestionAction, QuestionPolicy, BUFFER_CAPACITY,
 };
 
+/// Options for decompressing a single file, see `decompress_file`.
+pub struct DecompressOptions<'a> {
+    /// File opened for reading, example: "archive.tar.gz"
+    pub input_file_path: &'a Path,
+    /// Each format necessary for decompression, example: [Gz, Tar] (in decompression order)
+    pub formats: Vec<Extension>,
+    /// Where the file will be decompressed to, this directory is assumed to exist
+    pub output_dir: &'a Path,
+    /// Only used when extracting single file formats, not archive formats like .tar or .zip
+    pub output_file_path: PathBuf,
+    pub question_policy: QuestionPolicy,
+    pub on_conflict: Option<ConflictPolicy>,
+    pub quiet: bool,
+    pub no_smart_unpack: bool,
+    pub absolute_paths: bool,
+    pub preserve_special: bool,
+    /// Where intermediate temporary files are spilled, see `--temp-dir`
+    pub temp_dir: &'a Path,
+    /// Identity file for decrypting an '.age' layer, passphrase mode when absent
+    pub age_identity: Option<&'a Path>,
+    /// Restore stored file attributes like read-only (Windows only)
+    pub preserve_attributes: bool,
+    /// What to do with several entries sharing the same path, see `--on-duplicate`
+    pub on_duplicate: Option<DuplicatePolicy>,
+    /// Decompression-bomb protection settings
+    pub bomb_guard: BombGuardSettings,
+    /// Raw zstd dictionary contents, see `--zstd-ref`
+    pub zstd_dictionary: Option<&'a [u8]>,
+    /// Remapping of stored uids/gids, see `--owner-map`
+    pub owner_map: Option<&'a utils::OwnerMap>,
+    /// Move overwritten files to the trash, see `--trash`
+    pub use_trash: bool,
+    /// Error on trailing data after the tar EOF marker, see `--strict-tar`
+    pub strict_tar: bool,
+    /// Restore setuid/setgid bits, see `--allow-setuid`
+    pub allow_setuid: bool,
+    /// The input is a headerless raw stream, see `--raw`
+    pub raw: bool,
+    /// Restore the gzip header's stored original name, see `--use-stored-name`
+    pub use_stored_name: bool,
+    /// Policy for entry names that are not valid UTF-8, see `--non-utf8`
+    pub non_utf8: Option<crate::cli::NonUtf8Policy>,
+    /// Input buffer size (with read-ahead) while decoding, see `--buffer-size`
+    pub buffer_size: Option<usize>,
+    /// Transient-error retries for input reads, see `--retry`
+    pub retry: u32,
+}
+
 /// Decompress a file
-///
-/// File at input_file_path is opened for reading, example: "archive.tar.gz"
-/// formats contains each format necessary for decompression, example: [Gz, Tar] (in decompression order)
-/// output_dir it's where the file will be decompressed to, this function assumes that the directory exists
-/// output_file_path is only used when extracting single file formats, not archive formats like .tar or .zip
-pub fn decompress_file(
-    input_file_path: &Path,
-    formats: Vec<Extension>,
-    output_dir: &Path,
-    output_file_path: PathBuf,
-    question_policy: QuestionPolicy,
-    quiet: bool,
-) -> crate::Result<()> {
+pub fn decompress_file(options: DecompressOptions) -> crate::Result<()> {
+    let DecompressOptions {
+        input_file_path,
+        formats,
+        output_dir,
+        output_file_path,
+        question_policy,
+        on_conflict,
+        quiet,
+        no_smart_unpack,
+        absolute_paths,
+        preserve_special,
+        temp_dir,
+        age_identity,
+        preserve_attributes,
+        on_duplicate,
+        bomb_guard,
+        zstd_dictionary,
+        owner_map,
+        use_trash,
+        strict_tar,
+        allow_setuid,
+        raw,
+        use_stored_name,
+        non_utf8,
+        buffer_size,
+        retry,
+    } = options;
     assert!(output_dir.exists());
     let reader = fs::File::open(input_file_path)?;
 
+    let archive_size = fs::metadata(input_file_path).map(|metadata| metadata.len()).unwrap_or(0);
+    let bomb_guard = BombGuard::new(archive_size, bomb_guard);
+
     // Zip archives are special, because they require io::Seek, so it requires it's logic separated
     // from decoder chaining.
     //
@@ -48,12 +119,51 @@ pub fn decompress_file(
         ..
     }] = formats.as_slice()
     {
-        let zip_archive = zip::ZipArchive::new(reader)?;
-        let files_unpacked = if let ControlFlow::Continue(files) = smart_unpack(
-            |output_dir| crate::archive::zip::unpack_archive(zip_archive, output_dir, quiet),
+        // On-disk archives use the seekable ZipArchive API (efficient random
+        // access through the central directory); non-seekable inputs like
+        // pipes fall back to streaming the local file headers
+        let input_is_seekable = fs::metadata(input_file_path)
+            .map(|metadata| metadata.is_file())
+            .unwrap_or(false);
+
+        type UnpackResult = crate::Result<usize>;
+        // Spanned archives (`.z01` … `.zip`) are reassembled from their
+        // sibling parts instead of reading the final disk alone
+        let spanned_parts = crate::archive::spanned_zip::find_parts(input_file_path);
+        let unpack_fn: Box<dyn FnOnce(&Path) -> UnpackResult> = if let Some(parts) = spanned_parts {
+            let bomb_guard = &bomb_guard;
+            Box::new(move |output_dir| {
+                crate::archive::spanned_zip::unpack_archive(&parts, output_dir, quiet, bomb_guard)
+            })
+        } else if input_is_seekable {
+            let zip_archive = zip::ZipArchive::new(reader)?;
+            let bomb_guard = &bomb_guard;
+            Box::new(move |output_dir| {
+                crate::archive::zip::unpack_archive(
+                    zip_archive,
+                    output_dir,
+                    quiet,
+                    absolute_paths,
+                    preserve_attributes,
+                    on_duplicate,
+                    bomb_guard,
+                )
+            })
+        } else {
+            let bomb_guard = &bomb_guard;
+            Box::new(move |output_dir| {
+                crate::archive::zip::unpack_archive_streaming(reader, output_dir, quiet, on_duplicate, bomb_guard)
+            })
+        };
+
+        let files_unpacked = if let ControlFlow::Continue(files) = unpack(
+            unpack_fn,
             output_dir,
             &output_file_path,
+            no_smart_unpack,
+            on_conflict,
             question_policy,
+            use_trash,
         )? {
             files
         } else {
@@ -69,24 +179,77 @@ pub fn decompress_file(
             nice_directory_display(output_dir),
             files_unpacked
         ));
+        utils::report::add_files_processed(files_unpacked as u64);
 
         return Ok(());
     }
 
-    // Will be used in decoder chaining
-    let reader = BufReader::with_capacity(BUFFER_CAPACITY, reader);
-    let mut reader: Box<dyn Read> = Box::new(reader);
+    // Will be used in decoder chaining; --buffer-size grows the input
+    // buffer and adds a background read-ahead thread, the default keeps
+    // the plain 32kB buffering
+    let reader = BufReader::with_capacity(buffer_size.unwrap_or(BUFFER_CAPACITY), reader);
+    let mut reader: Box<dyn Read> = if let Some(buffer_size) = buffer_size {
+        let depth = (buffer_size / BUFFER_CAPACITY).max(1);
+        Box::new(utils::io::PrefetchReader::new(reader, depth))
+    } else {
+        Box::new(reader)
+    };
+    if retry > 0 {
+        // --retry wraps the input in a transient-error retry loop
+        reader = Box::new(utils::io::RetryingReader::new(reader, retry));
+    }
+
+    // The path-based magic pre-check cannot sniff non-regular inputs (fd
+    // paths, pipes) without consuming them; probe the opened stream here
+    // instead and cross-check the outermost extension layer
+    let input_is_regular = fs::metadata(input_file_path)
+        .map(|metadata| metadata.is_file())
+        .unwrap_or(false);
+    if !input_is_regular {
+        let mut probe = vec![0u8; 270];
+        let mut filled = 0;
+        while filled < probe.len() {
+            match reader.read(&mut probe[filled..])? {
+                0 => break,
+                read => filled += read,
+            }
+        }
+        probe.truncate(filled);
+
+        if let Some(detected) = utils::detect_format_from_magic(&mut io::Cursor::new(&probe)) {
+            let outermost = formats
+                .iter()
+                .flat_map(|extension| extension.compression_formats)
+                .next_back();
+            if outermost.is_some_and(|outermost| *outermost != detected) {
+                utils::logger::warning(format!(
+                    "The input stream looks like {detected}, not the expected {}",
+                    outermost.expect("checked by is_some_and")
+                ));
+            }
+        }
+        reader = Box::new(io::Cursor::new(probe).chain(reader));
+    }
 
     // Grab previous decoder and wrap it inside of a new one
     let chain_reader_decoder = |format: &CompressionFormat, decoder: Box<dyn Read>| -> crate::Result<Box<dyn Read>> {
         let decoder: Box<dyn Read> = match format {
-            Gzip => Box::new(flate2::read::GzDecoder::new(decoder)),
+            Gzip if raw => Box::new(flate2::read::DeflateDecoder::new(decoder)),
+            Gzip => Box::new(flate2::read::MultiGzDecoder::new(decoder)),
             Bzip => Box::new(bzip2::read::BzDecoder::new(decoder)),
             Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(decoder)),
-            Lzma => Box::new(xz2::read::XzDecoder::new(decoder)),
+            Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(decoder)),
             Snappy => Box::new(snap::read::FrameDecoder::new(decoder)),
-            Zstd => Box::new(zstd::stream::Decoder::new(decoder)?),
-            Tar | Zip | Rar | SevenZip => unreachable!(),
+            Zstd => match zstd_dictionary {
+                Some(dictionary) => Box::new(zstd::stream::Decoder::with_dictionary(
+                    io::BufReader::new(decoder),
+                    dictionary,
+                )?),
+                None => Box::new(zstd::stream::Decoder::new(decoder)?),
+            },
+            Age => Box::new(age_decryption_stream(decoder, age_identity)?),
+            Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(decoder)?)),
+            Tar | Zip | Rar | SevenZip | Iso => unreachable!(),
         };
         Ok(decoder)
     };
@@ -98,24 +261,115 @@ pub fn decompress_file(
     }
 
     let files_unpacked = match first_extension {
-        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd => {
+        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd | Age | Lzw => {
             reader = chain_reader_decoder(&first_extension, reader)?;
 
-            let mut writer = match utils::ask_to_create_file(&output_file_path, question_policy)? {
-                Some(file) => file,
-                None => return Ok(()),
+            // --use-stored-name prefers the original name from the gzip
+            // FNAME header field over the one derived from the archive name
+            let output_file_path = if use_stored_name && first_extension == Gzip && extensions.is_empty() {
+                match gzip_stored_name(input_file_path) {
+                    Some(stored_name) => {
+                        info_accessible(format!("Using the stored original file name '{stored_name}'."));
+                        output_dir.join(stored_name)
+                    }
+                    None => output_file_path,
+                }
+            } else {
+                output_file_path
             };
 
-            io::copy(&mut reader, &mut writer)?;
+            let source_mtime = fs::metadata(input_file_path)
+                .and_then(|metadata| metadata.modified())
+                .ok();
+            let mut writer =
+                match utils::ask_to_create_file(&output_file_path, question_policy, on_conflict, source_mtime, use_trash)? {
+                    Some(file) => file,
+                    None => return Ok(()),
+                };
+
+            // Clean up the partial file when the copy fails (e.g. the bomb
+            // guard tripping or the disk filling up)
+            if let Err(err) = bomb_guard.copy_guarded(&mut reader, &mut writer) {
+                drop(writer);
+                let _ = utils::remove_file_or_dir(&output_file_path);
+                return Err(err);
+            }
 
             1
         }
         Tar => {
-            if let ControlFlow::Continue(files) = smart_unpack(
-                |output_dir| crate::archive::tar::unpack_archive(reader, output_dir, quiet),
+            // Validate the decoded stream is really tar before unpacking: a
+            // mislabeled .tar.gz that is just a .gz falls back to writing
+            // the decoded single file instead of failing confusingly
+            let mut probe = vec![0u8; 512];
+            let mut filled = 0;
+            while filled < probe.len() {
+                match reader.read(&mut probe[filled..])? {
+                    0 => break,
+                    read => filled += read,
+                }
+            }
+            probe.truncate(filled);
+
+            // Checksum-validating the first header block recognizes
+            // old-style (V7) tars too, which carry no ustar magic
+            let looks_like_tar = probe.len() == 512 && tar_header_checksum_is_valid(&probe);
+            // A tar holding no entries is nothing but zero blocks
+            let is_empty_tar = probe.iter().all(|byte| *byte == 0);
+            let reader: Box<dyn Read> = Box::new(io::Cursor::new(probe).chain(reader));
+
+            if !looks_like_tar && !is_empty_tar {
+                utils::logger::warning(format!(
+                    "'{}' does not contain a tar archive, writing the decoded stream as a single file",
+                    EscapedPathDisplay::new(input_file_path)
+                ));
+
+                let source_mtime = fs::metadata(input_file_path)
+                    .and_then(|metadata| metadata.modified())
+                    .ok();
+                let mut writer =
+                    match utils::ask_to_create_file(&output_file_path, question_policy, on_conflict, source_mtime, use_trash)? {
+                        Some(file) => file,
+                        None => return Ok(()),
+                    };
+                let mut reader = reader;
+                if let Err(err) = bomb_guard.copy_guarded(&mut reader, &mut writer) {
+                    drop(writer);
+                    let _ = utils::remove_file_or_dir(&output_file_path);
+                    return Err(err);
+                }
+
+                info_accessible(format!(
+                    "Successfully decompressed into {}.",
+                    nice_directory_display(&output_file_path)
+                ));
+                utils::report::add_files_processed(1);
+
+                return Ok(());
+            }
+
+            if let ControlFlow::Continue(files) = unpack(
+                |output_dir| {
+                    crate::archive::tar::unpack_archive(
+                        reader,
+                        output_dir,
+                        quiet,
+                        absolute_paths,
+                        preserve_special,
+                        on_duplicate,
+                        &bomb_guard,
+                        owner_map,
+                        strict_tar,
+                        allow_setuid,
+                        non_utf8,
+                    )
+                },
                 output_dir,
                 &output_file_path,
+                no_smart_unpack,
+                on_conflict,
                 question_policy,
+                use_trash,
             )? {
                 files
             } else {
@@ -138,11 +392,24 @@ pub fn decompress_file(
             io::copy(&mut reader, &mut vec)?;
             let zip_archive = zip::ZipArchive::new(io::Cursor::new(vec))?;
 
-            if let ControlFlow::Continue(files) = smart_unpack(
-                |output_dir| crate::archive::zip::unpack_archive(zip_archive, output_dir, quiet),
+            if let ControlFlow::Continue(files) = unpack(
+                |output_dir| {
+                    crate::archive::zip::unpack_archive(
+                        zip_archive,
+                        output_dir,
+                        quiet,
+                        absolute_paths,
+                        preserve_attributes,
+                        on_duplicate,
+                        &bomb_guard,
+                    )
+                },
                 output_dir,
                 &output_file_path,
+                no_smart_unpack,
+                on_conflict,
                 question_policy,
+                use_trash,
             )? {
                 files
             } else {
@@ -153,16 +420,22 @@ pub fn decompress_file(
         Rar => {
             type UnpackResult = crate::Result<usize>;
             let unpack_fn: Box<dyn FnOnce(&Path) -> UnpackResult> = if formats.len() > 1 {
-                let mut temp_file = tempfile::NamedTempFile::new()?;
+                let mut temp_file = tempfile::NamedTempFile::new_in(temp_dir)?;
                 io::copy(&mut reader, &mut temp_file)?;
                 Box::new(move |output_dir| crate::archive::rar::unpack_archive(temp_file.path(), output_dir, quiet))
             } else {
                 Box::new(|output_dir| crate::archive::rar::unpack_archive(input_file_path, output_dir, quiet))
             };
 
-            if let ControlFlow::Continue(files) =
-                smart_unpack(unpack_fn, output_dir, &output_file_path, question_policy)?
-            {
+            if let ControlFlow::Continue(files) = unpack(
+                unpack_fn,
+                output_dir,
+                &output_file_path,
+                no_smart_unpack,
+                on_conflict,
+                question_policy,
+                use_trash,
+            )? {
                 files
             } else {
                 return Ok(());
@@ -172,6 +445,26 @@ pub fn decompress_file(
         Rar => {
             return Err(crate::archive::rar_stub::no_support());
         }
+        Iso => {
+            // Like zip, iso images need io::Seek, a chained iso is loaded
+            // into memory first
+            let mut vec = vec![];
+            io::copy(&mut reader, &mut vec)?;
+
+            if let ControlFlow::Continue(files) = unpack(
+                |output_dir| crate::archive::iso::unpack_archive(io::Cursor::new(vec), output_dir, quiet, &bomb_guard),
+                output_dir,
+                &output_file_path,
+                no_smart_unpack,
+                on_conflict,
+                question_policy,
+                use_trash,
+            )? {
+                files
+            } else {
+                return Ok(());
+            }
+        }
         SevenZip => {
             if formats.len() > 1 {
                 // Locking necessary to guarantee that warning and question
@@ -187,11 +480,16 @@ pub fn decompress_file(
             let mut vec = vec![];
             io::copy(&mut reader, &mut vec)?;
 
-            if let ControlFlow::Continue(files) = smart_unpack(
-                |output_dir| crate::archive::sevenz::decompress_sevenz(io::Cursor::new(vec), output_dir, quiet),
+            if let ControlFlow::Continue(files) = unpack(
+                |output_dir| {
+                    crate::archive::sevenz::decompress_sevenz(io::Cursor::new(vec), output_dir, quiet, &bomb_guard)
+                },
                 output_dir,
                 &output_file_path,
+                no_smart_unpack,
+                on_conflict,
                 question_policy,
+                use_trash,
             )? {
                 files
             } else {
@@ -209,20 +507,116 @@ pub fn decompress_file(
         nice_directory_display(output_dir)
     ));
     info_accessible(format!("Files unpacked: {}", files_unpacked));
+    utils::report::add_files_processed(files_unpacked as u64);
 
     Ok(())
 }
 
+/// Unpacks an archive, dispatching between the Smart Unpack heuristics and
+/// plain unpacking directly into `output_dir` (requested with `--no-smart-unpack`)
+#[allow(clippy::too_many_arguments)]
+fn unpack(
+    unpack_fn: impl FnOnce(&Path) -> crate::Result<usize>,
+    output_dir: &Path,
+    output_file_path: &Path,
+    no_smart_unpack: bool,
+    on_conflict: Option<ConflictPolicy>,
+    question_policy: QuestionPolicy,
+    use_trash: bool,
+) -> crate::Result<ControlFlow<(), usize>> {
+    if no_smart_unpack {
+        unpack_directly(unpack_fn, output_dir, on_conflict, question_policy, use_trash)
+    } else {
+        smart_unpack(unpack_fn, output_dir, output_file_path, on_conflict, question_policy, use_trash)
+    }
+}
+
+/// Moves an unpacked root element to its resolved output path, honoring the
+/// conflict policy. Returns `Break` if the user aborted at the prompt.
+fn move_to_output(
+    file_path: &Path,
+    target_path: &Path,
+    on_conflict: Option<ConflictPolicy>,
+    question_policy: QuestionPolicy,
+    use_trash: bool,
+) -> crate::Result<ControlFlow<()>> {
+    let source_mtime = fs::metadata(file_path)
+        .and_then(|metadata| metadata.modified())
+        .ok();
+
+    match utils::resolve_path_conflict(target_path, source_mtime, on_conflict, question_policy, use_trash)? {
+        utils::ConflictResolution::Write(target_path) => {
+            fs::rename(file_path, &target_path)?;
+            info_accessible(format!(
+                "Successfully moved {} to {}.",
+                nice_directory_display(file_path),
+                nice_directory_display(&target_path)
+            ));
+        }
+        utils::ConflictResolution::Skip => {
+            info_accessible(format!(
+                "Skipped {}, a file with the same name already exists.",
+                nice_directory_display(target_path)
+            ));
+        }
+        utils::ConflictResolution::Abort => return Ok(ControlFlow::Break(())),
+    }
+
+    Ok(ControlFlow::Continue(()))
+}
+
+/// Unpacks an archive directly into the output directory, without the
+/// single-root heuristics from `smart_unpack`
+///
+/// Note: This function assumes that `output_dir` exists
+fn unpack_directly(
+    unpack_fn: impl FnOnce(&Path) -> crate::Result<usize>,
+    output_dir: &Path,
+    on_conflict: Option<ConflictPolicy>,
+    question_policy: QuestionPolicy,
+    use_trash: bool,
+) -> crate::Result<ControlFlow<(), usize>> {
+    assert!(output_dir.exists());
+    let temp_dir = tempfile::tempdir_in(output_dir)?;
+    let temp_dir_path = temp_dir.path();
+
+    info_accessible(format!(
+        "Created temporary directory {} to hold decompressed elements.",
+        nice_directory_display(temp_dir_path)
+    ));
+
+    let files = unpack_fn(temp_dir_path)?;
+
+    // Move every archive root element to the output directory
+    for file in fs::read_dir(temp_dir_path)? {
+        let file_path = file?.path();
+        let file_name = file_path
+            .file_name()
+            .expect("Should be safe because paths in archives should not end with '..'");
+        let correct_path = output_dir.join(file_name);
+        if let ControlFlow::Break(_) =
+            move_to_output(&file_path, &correct_path, on_conflict, question_policy, use_trash)?
+        {
+            return Ok(ControlFlow::Break(()));
+        }
+    }
+
+    Ok(ControlFlow::Continue(files))
+}
+
 /// Unpacks an archive with some heuristics
 /// - If the archive contains only one file, it will be extracted to the `output_dir`
 /// - If the archive contains multiple files, it will be extracted to a subdirectory of the
 ///   output_dir named after the archive (given by `output_file_path`)
+///
 /// Note: This functions assumes that `output_dir` exists
 fn smart_unpack(
     unpack_fn: impl FnOnce(&Path) -> crate::Result<usize>,
     output_dir: &Path,
     output_file_path: &Path,
+    on_conflict: Option<ConflictPolicy>,
     question_policy: QuestionPolicy,
+    use_trash: bool,
 ) -> crate::Result<ControlFlow<(), usize>> {
     assert!(output_dir.exists());
     let temp_dir = tempfile::tempdir_in(output_dir)?;
@@ -244,31 +638,230 @@ fn smart_unpack(
             .file_name()
             .expect("Should be safe because paths in archives should not end with '..'");
         let correct_path = output_dir.join(file_name);
-        // Before moving, need to check if a file with the same name already exists
-        if !utils::clear_path(&correct_path, question_policy)? {
+        if let ControlFlow::Break(_) =
+            move_to_output(&file_path, &correct_path, on_conflict, question_policy, use_trash)?
+        {
             return Ok(ControlFlow::Break(()));
         }
-        fs::rename(&file_path, &correct_path)?;
-
-        info_accessible(format!(
-            "Successfully moved {} to {}.",
-            nice_directory_display(&file_path),
-            nice_directory_display(&correct_path)
-        ));
     } else {
         // Multiple files in the root directory, so:
         // Rename the temporary directory to the archive name, which is output_file_path
-        // One case to handle tough is we need to check if a file with the same name already exists
-        if !utils::clear_path(output_file_path, question_policy)? {
+        if let ControlFlow::Break(_) =
+            move_to_output(temp_dir_path, output_file_path, on_conflict, question_policy, use_trash)?
+        {
             return Ok(ControlFlow::Break(()));
         }
-        fs::rename(temp_dir_path, output_file_path)?;
-        info_accessible(format!(
-            "Successfully moved {} to {}.",
-            nice_directory_display(temp_dir_path),
-            nice_directory_display(output_file_path)
-        ));
     }
 
     Ok(ControlFlow::Continue(files))
 }
+
+
+/// Opens the decryption stream of an '.age' layer, unlocked by the
+/// identities from `--age-identity` or an interactively asked passphrase.
+fn age_decryption_stream(
+    reader: Box<dyn Read>,
+    age_identity: Option<&Path>,
+) -> crate::Result<age::stream::StreamReader<Box<dyn Read>>> {
+    let age_error = |err: age::DecryptError| {
+        crate::Error::from(
+            FinalError::with_title("Could not decrypt the '.age' layer").detail(err.to_string()),
+        )
+    };
+
+    let decryptor = age::Decryptor::new(reader).map_err(age_error)?;
+
+    let identities: Vec<Box<dyn age::Identity + Send + Sync>> = match age_identity {
+        Some(path) => age::IdentityFile::from_file(path.display().to_string())?
+            .into_identities()
+            .map_err(age_error)?,
+        None => {
+            let passphrase = utils::ask_passphrase("Type the passphrase of the '.age' layer:")?;
+            vec![Box::new(age::scrypt::Identity::new(passphrase.into()))]
+        }
+    };
+
+    decryptor
+        .decrypt(identities.iter().map(|identity| identity.as_ref() as &dyn age::Identity))
+        .map_err(age_error)
+}
+
+
+/// Scans `output_dir` for recognized archives and extracts each into a
+/// directory named after it, repeating for what those extractions produce,
+/// up to `max_depth` rounds (requested with `--unnest`).
+#[allow(clippy::too_many_arguments)]
+pub fn unnest_extracted_archives(
+    output_dir: &Path,
+    max_depth: u32,
+    question_policy: QuestionPolicy,
+    on_conflict: Option<ConflictPolicy>,
+    quiet: bool,
+    temp_dir: &Path,
+    bomb_guard: BombGuardSettings,
+) -> crate::Result<()> {
+    let mut frontier = vec![output_dir.to_path_buf()];
+
+    for _ in 0..max_depth {
+        let mut discovered = vec![];
+        for dir in frontier.drain(..) {
+            collect_nested_archives(&dir, &mut discovered)?;
+        }
+        if discovered.is_empty() {
+            break;
+        }
+
+        for archive_path in discovered {
+            let (base_name, formats) = crate::extension::separate_known_extensions_from_name(&archive_path);
+            let target_dir = archive_path
+                .parent()
+                .expect("scanned archives live inside the output directory")
+                .join(base_name);
+            utils::create_dir_if_non_existent(&target_dir)?;
+
+            info_accessible(format!(
+                "Extracting nested archive {}.",
+                nice_directory_display(&archive_path)
+            ));
+
+            let output_file_path = target_dir.join(base_name);
+            decompress_file(DecompressOptions {
+                input_file_path: &archive_path,
+                formats,
+                output_dir: &target_dir,
+                output_file_path,
+                question_policy,
+                on_conflict,
+                quiet,
+                no_smart_unpack: false,
+                absolute_paths: false,
+                preserve_special: false,
+                temp_dir,
+                age_identity: None,
+                preserve_attributes: false,
+                on_duplicate: None,
+                bomb_guard,
+                zstd_dictionary: None,
+                owner_map: None,
+                use_trash: false,
+                strict_tar: false,
+                allow_setuid: false,
+                raw: false,
+                use_stored_name: false,
+                non_utf8: None,
+                buffer_size: None,
+                retry: 0,
+            })?;
+
+            frontier.push(target_dir);
+        }
+    }
+
+    Ok(())
+}
+
+/// Recursively collects files with recognized archive/compression extensions.
+fn collect_nested_archives(dir: &Path, found: &mut Vec<PathBuf>) -> crate::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            collect_nested_archives(&path, found)?;
+        } else if !crate::extension::extensions_from_path(&path).is_empty() {
+            found.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+
+/// Reads the original file name stored in a gzip FNAME header field, if
+/// any, reduced to its bare file name so it cannot escape the output
+/// directory.
+fn gzip_stored_name(path: &Path) -> Option<String> {
+    let file = std::fs::File::open(path).ok()?;
+    let mut decoder = flate2::read::GzDecoder::new(file);
+    // The header is parsed lazily, force it with a tiny read
+    let mut scratch = [0u8; 1];
+    let _ = decoder.read(&mut scratch);
+
+    let stored = decoder.header()?.filename()?;
+    let stored = String::from_utf8_lossy(stored).into_owned();
+    Path::new(&stored)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// Verifies extracted files against a MANIFEST.sha256 written by
+/// `--manifest`, looked up in the output directory or one level below it
+/// (where smart unpack places the archive root).
+pub fn verify_extracted_manifest(output_dir: &Path) -> crate::Result<()> {
+    let mut candidates = vec![output_dir.join("MANIFEST.sha256")];
+    if let Ok(entries) = fs::read_dir(output_dir) {
+        for entry in entries.flatten() {
+            candidates.push(entry.path().join("MANIFEST.sha256"));
+        }
+    }
+
+    let Some(manifest_path) = candidates.into_iter().find(|path| path.is_file()) else {
+        return Err(FinalError::with_title("Cannot verify the manifest")
+            .detail("No MANIFEST.sha256 was found in the extracted output")
+            .hint("Archives carry one when compressed with --manifest.")
+            .into());
+    };
+    let base_dir = manifest_path.parent().expect("the manifest lives in a directory");
+
+    let mut verified = 0;
+    let mut failures = vec![];
+    for line in fs::read_to_string(&manifest_path)?.lines() {
+        let Some((expected, entry_path)) = line.split_once("  ") else {
+            continue;
+        };
+
+        let target = base_dir.join(entry_path);
+        match crate::utils::checksum::hash_file(&target, crate::cli::ChecksumAlgorithm::Sha256) {
+            Ok(digest) if digest == expected => verified += 1,
+            Ok(_) => failures.push(format!("{entry_path}: digest mismatch")),
+            Err(_) => failures.push(format!("{entry_path}: missing or unreadable")),
+        }
+    }
+
+    if !failures.is_empty() {
+        let mut error = FinalError::with_title("Manifest verification failed");
+        for failure in failures {
+            error = error.detail(failure);
+        }
+        return Err(error.into());
+    }
+
+    info_accessible(format!("Manifest verified, {verified} files match."));
+
+    Ok(())
+}
+
+/// Whether a 512-byte block carries a valid tar header checksum: the sum of
+/// all bytes with the checksum field counted as spaces must equal the octal
+/// number stored in that field. Holds for ustar, GNU and old-style (V7)
+/// headers alike, unlike a magic-bytes check.
+fn tar_header_checksum_is_valid(block: &[u8]) -> bool {
+    let Some(stored) = block.get(148..156) else {
+        return false;
+    };
+    let stored = stored
+        .iter()
+        .map(|byte| *byte as char)
+        .filter(|character| character.is_digit(8))
+        .collect::<String>();
+    let Ok(stored) = u32::from_str_radix(&stored, 8) else {
+        return false;
+    };
+
+    let computed: u32 = block[..148]
+        .iter()
+        .chain(&block[156..])
+        .fold(0u32, |sum, byte| sum + u32::from(*byte))
+        + 8 * u32::from(b' ');
+
+    computed == stored
+}
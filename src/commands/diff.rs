@@ -0,0 +1,259 @@
+//! Compare the contents of two archives, entry by entry.
+
+use std::{
+    collections::BTreeMap,
+    hash::Hasher,
+    io::{self, BufReader, Read},
+    path::{Path, PathBuf},
+};
+
+
+use fs_err as fs;
+
+use crate::{
+    error::FinalError,
+    extension::CompressionFormat::{self, *},
+    utils::{io::hash_reader, logger::info_accessible, EscapedPathDisplay},
+    BUFFER_CAPACITY,
+};
+
+/// What we know about one entry when diffing, cheap by default with the
+/// content hash only computed for `--content`.
+pub(crate) struct EntryMetadata {
+    is_dir: bool,
+    pub(crate) size: u64,
+    content_hash: Option<u64>,
+}
+
+/// Compare two archives by entry path, reporting entries present in only one
+/// of them and entries differing in kind, size or (with `deep`) content.
+///
+/// Errors with a nonzero exit when the archives differ.
+pub fn diff_archives(
+    first_path: &Path,
+    second_path: &Path,
+    first_formats: Vec<CompressionFormat>,
+    second_formats: Vec<CompressionFormat>,
+    deep: bool,
+) -> crate::Result<()> {
+    let first = collect_entries(first_path, &first_formats, deep)?;
+    let second = collect_entries(second_path, &second_formats, deep)?;
+
+    let only_in_first: Vec<&PathBuf> = first.keys().filter(|path| !second.contains_key(*path)).collect();
+    let only_in_second: Vec<&PathBuf> = second.keys().filter(|path| !first.contains_key(*path)).collect();
+
+    let mut differing = vec![];
+    for (path, first_entry) in &first {
+        let Some(second_entry) = second.get(path) else {
+            continue;
+        };
+
+        if first_entry.is_dir != second_entry.is_dir {
+            differing.push((path, "kind differs"));
+        } else if first_entry.size != second_entry.size {
+            differing.push((path, "size differs"));
+        } else if first_entry.content_hash != second_entry.content_hash {
+            differing.push((path, "content differs"));
+        }
+    }
+
+    for path in &only_in_first {
+        println!("Only in {}: {}", EscapedPathDisplay::new(first_path), EscapedPathDisplay::new(path));
+    }
+    for path in &only_in_second {
+        println!(
+            "Only in {}: {}",
+            EscapedPathDisplay::new(second_path),
+            EscapedPathDisplay::new(path)
+        );
+    }
+    for (path, reason) in &differing {
+        println!("Differs ({reason}): {}", EscapedPathDisplay::new(path));
+    }
+
+    if only_in_first.is_empty() && only_in_second.is_empty() && differing.is_empty() {
+        info_accessible("Archives have identical contents.".into());
+        return Ok(());
+    }
+
+    Err(FinalError::with_title("Archives differ")
+        .detail(format!(
+            "{} entries only in {}",
+            only_in_first.len(),
+            EscapedPathDisplay::new(first_path)
+        ))
+        .detail(format!(
+            "{} entries only in {}",
+            only_in_second.len(),
+            EscapedPathDisplay::new(second_path)
+        ))
+        .detail(format!("{} entries differing", differing.len()))
+        .into())
+}
+
+/// Indexes the entries of an archive by path, going through the same decoder
+/// chaining as decompression. Also used by `--preview-conflicts`.
+pub(crate) fn collect_entries(
+    archive_path: &Path,
+    formats: &[CompressionFormat],
+    hash_contents: bool,
+) -> crate::Result<BTreeMap<PathBuf, EntryMetadata>> {
+    let mut entries = BTreeMap::new();
+
+    match formats {
+        [Zip, single_file_formats @ ..] => {
+            // Zip requires io::Seek, chained zips are loaded into memory like
+            // in the decompression path
+            let mut archive = if single_file_formats.is_empty() {
+                zip::ZipArchive::new(ZipSource::File(fs::File::open(archive_path)?))?
+            } else {
+                let mut reader = chain_reader_decoder(archive_path, single_file_formats)?;
+                let mut contents = vec![];
+                reader.read_to_end(&mut contents)?;
+                zip::ZipArchive::new(ZipSource::Memory(io::Cursor::new(contents)))?
+            };
+
+            for idx in 0..archive.len() {
+                let mut entry = archive.by_index(idx)?;
+                let Some(path) = entry.enclosed_name().map(Path::to_owned) else {
+                    continue;
+                };
+
+                let metadata = EntryMetadata {
+                    is_dir: entry.is_dir(),
+                    size: entry.size(),
+                    content_hash: if hash_contents && !entry.is_dir() {
+                        Some(hash_reader(&mut entry)?)
+                    } else {
+                        None
+                    },
+                };
+                entries.insert(path, metadata);
+            }
+        }
+        [Tar, single_file_formats @ ..] => {
+            let reader = chain_reader_decoder(archive_path, single_file_formats)?;
+            let mut archive = tar::Archive::new(reader);
+
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let path = entry.path()?.into_owned();
+                let is_dir = entry.header().entry_type().is_dir();
+
+                let metadata = EntryMetadata {
+                    is_dir,
+                    size: entry.size(),
+                    content_hash: if hash_contents && !is_dir {
+                        Some(hash_reader(&mut entry)?)
+                    } else {
+                        None
+                    },
+                };
+                entries.insert(path, metadata);
+            }
+        }
+        [Rar | SevenZip | Iso, ..] => {
+            return Err(FinalError::with_title("Cannot diff archive")
+                .detail(format!(
+                    "Diffing is supported for tar and zip archives, not for '{formats:?}'"
+                ))
+                .into());
+        }
+        single_file_formats => {
+            // A plain compressed stream holds a single unnamed file, compare
+            // it under a fixed entry name
+            let mut reader = chain_reader_decoder(archive_path, single_file_formats)?;
+            let mut contents = vec![];
+            reader.read_to_end(&mut contents)?;
+
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            hasher.write(&contents);
+            entries.insert(
+                PathBuf::from("<contents>"),
+                EntryMetadata {
+                    is_dir: false,
+                    size: contents.len() as u64,
+                    content_hash: Some(hasher.finish()),
+                },
+            );
+        }
+    }
+
+    // When the entries came from a listing of only one side of the archive,
+    // directory entries may be implicit, synthesize them so explicit
+    // directory entries on one side don't show up as spurious differences
+    let implied_dirs: Vec<PathBuf> = entries
+        .keys()
+        .filter_map(|path| path.parent())
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .map(Path::to_path_buf)
+        .collect();
+    for dir in implied_dirs {
+        let mut dir = dir.as_path();
+        loop {
+            entries.entry(dir.to_path_buf()).or_insert(EntryMetadata {
+                is_dir: true,
+                size: 0,
+                content_hash: None,
+            });
+            match dir.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => dir = parent,
+                _ => break,
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Seekable source for zip archives, either the file itself or the
+/// decompressed chain loaded into memory.
+enum ZipSource {
+    File(fs::File),
+    Memory(io::Cursor<Vec<u8>>),
+}
+
+impl Read for ZipSource {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            ZipSource::File(file) => file.read(buf),
+            ZipSource::Memory(cursor) => cursor.read(buf),
+        }
+    }
+}
+
+impl io::Seek for ZipSource {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        match self {
+            ZipSource::File(file) => file.seek(pos),
+            ZipSource::Memory(cursor) => cursor.seek(pos),
+        }
+    }
+}
+
+/// Opens `archive_path` and chains a decoder for every compression format,
+/// mirroring the decoder chaining done when decompressing.
+fn chain_reader_decoder(archive_path: &Path, formats: &[CompressionFormat]) -> crate::Result<Box<dyn Read>> {
+    let reader = fs::File::open(archive_path)?;
+    let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, reader));
+
+    for format in formats.iter().rev() {
+        reader = match format {
+            Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Bzip => Box::new(bzip2::read::BzDecoder::new(reader)),
+            Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+            Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Snappy => Box::new(snap::read::FrameDecoder::new(reader)),
+            Zstd => Box::new(zstd::stream::Decoder::new(reader)?),
+            Age => {
+                return Err(FinalError::with_title("Cannot diff age-encrypted archives")
+                    .hint("Decompress the archives instead: ouch decompress <ARCHIVE>")
+                    .into())
+            }
+            Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(reader)?)),
+            Tar | Zip | Rar | SevenZip | Iso => unreachable!(),
+        };
+    }
+
+    Ok(reader)
+}
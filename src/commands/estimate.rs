@@ -0,0 +1,175 @@
+//! Estimate the compressed size of inputs by compressing a sample of them.
+
+use std::{
+    io::{self, Write},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+
+use fs_err as fs;
+
+use crate::{
+    error::FinalError,
+    extension::CompressionFormat::{self, *},
+    utils::{logger::info_accessible, Bytes, FileVisibilityPolicy},
+};
+
+/// How much of the input the sample aims to cover.
+const SAMPLE_TARGET_FRACTION: f64 = 0.1;
+/// Lower and upper bounds for the sample, so tiny inputs are measured fully
+/// and huge ones stay fast to estimate.
+const SAMPLE_MIN_BYTES: u64 = 8 * 1024 * 1024;
+const SAMPLE_MAX_BYTES: u64 = 128 * 1024 * 1024;
+
+/// The estimated size of a tar header block per entry, used when the chain
+/// contains an archive format.
+const TAR_ENTRY_OVERHEAD: u64 = 3 * 512 / 2;
+
+/// Samples the inputs, compresses the sample with the requested formats and
+/// extrapolates the final archive size.
+pub fn estimate_compressed_size(
+    files: Vec<PathBuf>,
+    formats: Vec<CompressionFormat>,
+    level: Option<i16>,
+    json: bool,
+    sample_percent: Option<u8>,
+    file_visibility_policy: FileVisibilityPolicy,
+) -> crate::Result<()> {
+    // --sample overrides the default fraction, trading accuracy for speed
+    let sample_fraction = match sample_percent {
+        Some(percent) if (1..=100).contains(&percent) => f64::from(percent) / 100.0,
+        Some(percent) => {
+            return Err(FinalError::with_title(format!("Invalid --sample value: {percent}"))
+                .detail("The sampled percentage must be between 1 and 100")
+                .into())
+        }
+        None => SAMPLE_TARGET_FRACTION,
+    };
+    // Gather all regular files with their sizes through the usual walk
+    let mut input_files = vec![];
+    let mut total_size: u64 = 0;
+    let mut entry_count: u64 = 0;
+    for path in &files {
+        for entry in file_visibility_policy.build_walker(path)? {
+            let entry = entry?;
+            entry_count += 1;
+            let Ok(metadata) = fs::metadata(entry.path()) else {
+                continue;
+            };
+            if metadata.is_file() {
+                total_size += metadata.len();
+                input_files.push((entry.into_path(), metadata.len()));
+            }
+        }
+    }
+
+    // Sample roughly every k-th file so the sample covers the target
+    // fraction of the input bytes
+    let mut sample_budget = ((total_size as f64 * sample_fraction) as u64).max(1);
+    if sample_percent.is_none() {
+        // The default stays bounded so huge trees remain fast to estimate
+        sample_budget = sample_budget.clamp(SAMPLE_MIN_BYTES, SAMPLE_MAX_BYTES);
+    }
+    let step = (total_size / sample_budget.max(1)).max(1) as usize;
+
+    let counter = Arc::new(AtomicU64::new(0));
+    let mut sampled_bytes: u64 = 0;
+    {
+        let mut encoder = chain_sample_encoder(&formats, level, CountingWriter(Arc::clone(&counter)))?;
+        for (path, size) in input_files.iter().step_by(step) {
+            let mut reader = fs::File::open(path)?;
+            io::copy(&mut reader, &mut encoder)?;
+            sampled_bytes += size;
+        }
+        encoder.flush()?;
+    }
+    let compressed_sample = counter.load(Ordering::Relaxed);
+
+    let ratio = if sampled_bytes > 0 {
+        compressed_sample as f64 / sampled_bytes as f64
+    } else {
+        1.0
+    };
+    let mut estimated_size = (total_size as f64 * ratio) as u64;
+    if formats.contains(&Tar) {
+        estimated_size += entry_count * TAR_ENTRY_OVERHEAD;
+    }
+
+    if json {
+        println!(
+            "{{\"total_size\":{total_size},\"sampled_bytes\":{sampled_bytes},\
+             \"compressed_sample\":{compressed_sample},\"estimated_size\":{estimated_size}}}"
+        );
+    } else {
+        info_accessible(format!(
+            "Estimated compressed size: {} (input {}, sampled {}, ratio {:.1}%).",
+            Bytes::new(estimated_size),
+            Bytes::new(total_size),
+            Bytes::new(sampled_bytes),
+            ratio * 100.0,
+        ));
+        info_accessible("This is an extrapolation from a sample, the actual size will differ.".into());
+    }
+
+    Ok(())
+}
+
+/// A writer that discards everything but counts how many bytes went through.
+struct CountingWriter(Arc<AtomicU64>);
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.fetch_add(buf.len() as u64, Ordering::Relaxed);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds a simplified single-threaded encoder chain for the sample, using
+/// the same algorithms and levels as real compression. Archive containers
+/// are skipped, their per-entry overhead is accounted for separately.
+fn chain_sample_encoder(
+    formats: &[CompressionFormat],
+    level: Option<i16>,
+    writer: CountingWriter,
+) -> crate::Result<Box<dyn Write>> {
+    let mut encoder: Box<dyn Write> = Box::new(writer);
+
+    let effective_level = |format: CompressionFormat| -> i32 {
+        let default = format
+            .default_level()
+            .expect("only used with formats that take a level");
+        let Some(level) = level else { return default.into() };
+
+        let range = format.level_range().expect("leveled formats expose their range");
+        i32::from(level).clamp(*range.start(), *range.end())
+    };
+
+    for format in formats.iter().rev() {
+        encoder = match format {
+            Gzip => Box::new(flate2::write::GzEncoder::new(
+                encoder,
+                flate2::Compression::new(effective_level(Gzip) as u32),
+            )),
+            Bzip => Box::new(bzip2::write::BzEncoder::new(
+                encoder,
+                bzip2::Compression::new(effective_level(Bzip) as u32),
+            )),
+            Lz4 => Box::new(lz4_flex::frame::FrameEncoder::new(encoder).auto_finish()),
+            Lzma => Box::new(xz2::write::XzEncoder::new(encoder, effective_level(Lzma) as u32)),
+            Snappy => Box::new(snap::write::FrameEncoder::new(encoder)),
+            Zstd => Box::new(zstd::stream::write::Encoder::new(encoder, effective_level(Zstd))?.auto_finish()),
+            // Containers and the encryption layer add roughly constant
+            // overhead, the compression ratio comes from the encoders
+            Tar | Zip | Rar | SevenZip | Iso | Age | Lzw => encoder,
+        };
+    }
+
+    Ok(encoder)
+}
@@ -1,5 +1,5 @@
 use std::{
-    io::{self, BufReader, Read},
+    io::{self, BufReader, Read, Write},
     path::Path,
 };
 
@@ -20,6 +20,8 @@ pub fn list_archive_contents(
     formats: Vec<CompressionFormat>,
     list_options: ListOptions,
     question_policy: QuestionPolicy,
+    temp_dir: &Path,
+    out: &mut dyn Write,
 ) -> crate::Result<()> {
     let reader = fs::File::open(archive_path)?;
 
@@ -31,9 +33,17 @@ pub fn list_archive_contents(
     //
     // Any other Zip decompression done can take up the whole RAM and freeze ouch.
     if let &[Zip] = formats.as_slice() {
+        // Spanned archives (`.z01` … `.zip`) list through their reassembled
+        // central directory
+        if let Some(parts) = crate::archive::spanned_zip::find_parts(archive_path) {
+            let files = crate::archive::spanned_zip::list_archive(&parts)?;
+            list::list_files(archive_path, files, list_options, &mut *out)?;
+            return Ok(());
+        }
+
         let zip_archive = zip::ZipArchive::new(reader)?;
         let files = crate::archive::zip::list_archive(zip_archive);
-        list::list_files(archive_path, files, list_options)?;
+        list::list_files(archive_path, files, list_options, &mut *out)?;
 
         return Ok(());
     }
@@ -46,13 +56,19 @@ pub fn list_archive_contents(
     let chain_reader_decoder =
         |format: &CompressionFormat, decoder: Box<dyn Read + Send>| -> crate::Result<Box<dyn Read + Send>> {
             let decoder: Box<dyn Read + Send> = match format {
-                Gzip => Box::new(flate2::read::GzDecoder::new(decoder)),
+                Gzip => Box::new(flate2::read::MultiGzDecoder::new(decoder)),
                 Bzip => Box::new(bzip2::read::BzDecoder::new(decoder)),
                 Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(decoder)),
-                Lzma => Box::new(xz2::read::XzDecoder::new(decoder)),
+                Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(decoder)),
                 Snappy => Box::new(snap::read::FrameDecoder::new(decoder)),
                 Zstd => Box::new(zstd::stream::Decoder::new(decoder)?),
-                Tar | Zip | Rar | SevenZip => unreachable!(),
+                Age => {
+                    return Err(crate::error::FinalError::with_title("Cannot list age-encrypted archives")
+                        .hint("Decompress the archive instead: ouch decompress <ARCHIVE>")
+                        .into())
+                }
+                Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(decoder)?)),
+                Tar | Zip | Rar | SevenZip | Iso => unreachable!(),
             };
             Ok(decoder)
         };
@@ -84,7 +100,7 @@ pub fn list_archive_contents(
         #[cfg(feature = "unrar")]
         Rar => {
             if formats.len() > 1 {
-                let mut temp_file = tempfile::NamedTempFile::new()?;
+                let mut temp_file = tempfile::NamedTempFile::new_in(temp_dir)?;
                 io::copy(&mut reader, &mut temp_file)?;
                 Box::new(crate::archive::rar::list_archive(temp_file.path()))
             } else {
@@ -95,6 +111,14 @@ pub fn list_archive_contents(
         Rar => {
             return Err(crate::archive::rar_stub::no_support());
         }
+        Iso => {
+            // Like zip, iso images need io::Seek, a chained iso is loaded
+            // into memory first
+            let mut vec = vec![];
+            io::copy(&mut reader, &mut vec)?;
+            let files = crate::archive::iso::list_archive(io::Cursor::new(vec))?;
+            Box::new(files.into_iter())
+        }
         SevenZip => {
             if formats.len() > 1 {
                 // Locking necessary to guarantee that warning and question
@@ -113,15 +137,176 @@ pub fn list_archive_contents(
                 files.push(Ok(FileInArchive {
                     path: entry.name().into(),
                     is_dir: entry.is_directory(),
+                    details: Some(crate::list::EntryDetails {
+                        method: "lzma2".into(),
+                        compressed_size: entry.compressed_size,
+                        uncompressed_size: entry.size(),
+                    }),
+                    link_target: None,
+                    mtime: entry.has_last_modified_date.then(|| {
+                        // FileTime counts 100ns ticks since 1601-01-01
+                        let unix_seconds = entry.last_modified_date().to_raw() as i64 / 10_000_000 - 11_644_473_600;
+                        time::OffsetDateTime::from_unix_timestamp(unix_seconds).ok()
+                    }).flatten(),
                 }));
                 Ok(true)
             })?;
             Box::new(files.into_iter())
         }
-        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd => {
+        Gzip | Bzip | Lz4 | Lzma | Snappy | Zstd | Age | Lzw => {
             panic!("Not an archive! This should never happen, if it does, something is wrong with `CompressionFormat::is_archive()`. Please report this error!");
         }
     };
-    list::list_files(archive_path, files, list_options)?;
+    list::list_files(archive_path, files, list_options, &mut *out)?;
+    Ok(())
+}
+
+/// Input cap per nested archive peeked by `--peek-nested`; a nested bomb
+/// should not be able to fill memory through the peek.
+const PEEK_MAX_BYTES: u64 = 64 * 1024 * 1024;
+/// Entry cap per nested archive peeked by `--peek-nested`.
+const PEEK_MAX_ENTRIES: usize = 10_000;
+
+/// Lists the archive like the plain output, but entries recognized as
+/// archives by their extension get their own contents printed indented
+/// beneath them (`--peek-nested`, down to `--peek-depth` levels).
+pub fn peek_archive_contents(
+    archive_path: &Path,
+    formats: &[CompressionFormat],
+    depth: usize,
+    out: &mut dyn Write,
+) -> crate::Result<()> {
+    let _ = writeln!(out, "Archive: {}", crate::utils::EscapedPathDisplay::new(archive_path));
+
+    match formats {
+        [Zip] => {
+            let mut archive = zip::ZipArchive::new(fs::File::open(archive_path)?)?;
+            for idx in 0..archive.len() {
+                let mut entry = archive.by_index(idx)?;
+                let name = entry.name().to_owned();
+                let _ = writeln!(out, "{name}");
+                if !entry.is_dir() {
+                    peek_entry(&name, &mut entry, 1, depth, out)?;
+                }
+            }
+        }
+        [Tar, decoder_formats @ ..] => {
+            let reader = fs::File::open(archive_path)?;
+            let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, reader));
+            for format in decoder_formats.iter().rev() {
+                reader = chain_peek_decoder(format, reader)?;
+            }
+
+            let mut archive = tar::Archive::new(reader);
+            for entry in archive.entries()? {
+                let mut entry = entry?;
+                let name = entry.path()?.to_string_lossy().into_owned();
+                let _ = writeln!(out, "{name}");
+                if entry.header().entry_type().is_file() {
+                    peek_entry(&name, &mut entry, 1, depth, out)?;
+                }
+            }
+        }
+        _ => {
+            return Err(crate::error::FinalError::with_title("Cannot peek into this archive")
+                .detail("--peek-nested supports zip and (optionally compressed) tar archives")
+                .into())
+        }
+    }
+
     Ok(())
 }
+
+/// Peeks one entry: when its name parses to a listable archive chain, the
+/// contents are buffered (capped) and printed indented one level deeper.
+fn peek_entry(
+    name: &str,
+    reader: &mut dyn Read,
+    level: usize,
+    max_depth: usize,
+    out: &mut dyn Write,
+) -> crate::Result<()> {
+    if level > max_depth {
+        return Ok(());
+    }
+
+    let chain = crate::extension::flatten_compression_formats(&crate::extension::extensions_from_path(Path::new(
+        name,
+    )));
+    let listable = matches!(chain.as_slice(), [Zip] | [Tar, ..])
+        && chain
+            .iter()
+            .skip(1)
+            .all(|format| !format.is_archive() && *format != Age);
+    if !listable {
+        return Ok(());
+    }
+
+    // The nested bytes are buffered with a hard cap, a nested decompression
+    // bomb only costs the cap
+    let mut data = vec![];
+    let truncated = reader.take(PEEK_MAX_BYTES).read_to_end(&mut data)? as u64 == PEEK_MAX_BYTES;
+    if truncated {
+        let _ = writeln!(out, "{}… (too large to peek into)", "  ".repeat(level));
+        return Ok(());
+    }
+
+    let indent = "  ".repeat(level);
+    let result = (|| -> crate::Result<()> {
+        match chain.as_slice() {
+            [Zip] => {
+                let mut archive = zip::ZipArchive::new(io::Cursor::new(&data))?;
+                for idx in 0..archive.len().min(PEEK_MAX_ENTRIES) {
+                    let mut entry = archive.by_index(idx)?;
+                    let entry_name = entry.name().to_owned();
+                    let _ = writeln!(out, "{indent}{entry_name}");
+                    if !entry.is_dir() {
+                        peek_entry(&entry_name, &mut entry, level + 1, max_depth, out)?;
+                    }
+                }
+            }
+            [Tar, decoder_formats @ ..] => {
+                let mut reader: Box<dyn Read> = Box::new(io::Cursor::new(&data));
+                for format in decoder_formats.iter().rev() {
+                    reader = chain_peek_decoder(format, reader)?;
+                }
+
+                let mut archive = tar::Archive::new(reader);
+                for entry in archive.entries()?.take(PEEK_MAX_ENTRIES) {
+                    let mut entry = entry?;
+                    let entry_name = entry.path()?.to_string_lossy().into_owned();
+                    let _ = writeln!(out, "{indent}{entry_name}");
+                    if entry.header().entry_type().is_file() {
+                        peek_entry(&entry_name, &mut entry, level + 1, max_depth, out)?;
+                    }
+                }
+            }
+            _ => unreachable!("checked by listable above"),
+        }
+        Ok(())
+    })();
+
+    // A corrupt (or merely misnamed) nested entry should not fail the
+    // whole listing
+    if result.is_err() {
+        let _ = writeln!(out, "{indent}… (could not be listed)");
+    }
+
+    Ok(())
+}
+
+fn chain_peek_decoder<'r>(
+    format: &CompressionFormat,
+    reader: Box<dyn Read + 'r>,
+) -> crate::Result<Box<dyn Read + 'r>> {
+    Ok(match format {
+        Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+        Bzip => Box::new(bzip2::read::BzDecoder::new(reader)),
+        Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+        Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+        Snappy => Box::new(snap::read::FrameDecoder::new(reader)),
+        Zstd => Box::new(zstd::stream::Decoder::new(reader)?),
+        Lzw => Box::new(io::Cursor::new(crate::unlzw::decode(reader)?)),
+        Tar | Zip | Rar | SevenZip | Iso | Age => unreachable!("not chainable decoders"),
+    })
+}
@@ -1,22 +1,42 @@
 //! Receive command from the cli and call the respective function for that command.
 
+#[cfg(feature = "tui")]
+mod browse;
 mod compress;
+mod convert;
 mod decompress;
+mod diff;
+mod estimate;
 mod list;
+mod recompress;
+mod repair;
+#[cfg(feature = "remote")]
+mod remote;
+#[cfg(feature = "mount")]
+mod mount;
 
-use std::{ops::ControlFlow, path::PathBuf};
+use std::{
+    io::Write,
+    ops::ControlFlow,
+    path::{Path, PathBuf},
+};
 
 use rayon::prelude::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 use utils::colors;
 
 use crate::{
     check,
     cli::Subcommand,
-    commands::{compress::compress_files, decompress::decompress_file, list::list_archive_contents},
+    commands::{
+        compress::{compress_files, CompressOptions},
+        decompress::{decompress_file, DecompressOptions},
+        list::list_archive_contents,
+    },
     error::{Error, FinalError},
     extension::{self, parse_format},
     list::ListOptions,
-    utils::{self, colors::*, logger::info_accessible, to_utf, EscapedPathDisplay, FileVisibilityPolicy},
+    utils::{self, colors::*, logger::info_accessible, to_utf, ConflictPolicy, EscapedPathDisplay, FileVisibilityPolicy},
     CliArgs, QuestionPolicy,
 };
 
@@ -30,6 +50,408 @@ fn warn_user_about_loading_zip_in_memory() {
     eprintln!("{}[WARNING]{}: {ZIP_IN_MEMORY_LIMITATION_WARNING}", *ORANGE, *RESET);
 }
 
+/// Spawns `$PAGER` (defaulting to `less -FRX`, which exits immediately when
+/// the listing fits on one screen) with a piped stdin, for `ouch list`.
+fn spawn_pager() -> Option<std::process::Child> {
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".into());
+
+    let mut command = std::process::Command::new("sh");
+    command.arg("-c");
+    if pager.trim() == "less" {
+        command.arg("less -FRX");
+    } else {
+        command.arg(&pager);
+    }
+
+    command.stdin(std::process::Stdio::piped()).spawn().ok()
+}
+
+/// Runs the `--exec` command in the output directory after a successful
+/// extraction, with `{}` expanded to the output path.
+fn run_exec_command(command: &str, output_dir: &Path) -> crate::Result<()> {
+    #[cfg(unix)]
+    let (shell, flag) = ("sh", "-c");
+    #[cfg(not(unix))]
+    let (shell, flag) = ("cmd", "/C");
+
+    // `{}` expands to the absolute output path, since the command already
+    // runs inside the output directory
+    let absolute_output = fs_err::canonicalize(output_dir).unwrap_or_else(|_| output_dir.to_path_buf());
+    let expanded = command.replace("{}", &utils::to_utf(&absolute_output));
+    let status = std::process::Command::new(shell)
+        .arg(flag)
+        .arg(&expanded)
+        .current_dir(output_dir)
+        .status()
+        .map_err(|err| {
+            crate::Error::from(
+                FinalError::with_title("Could not run the --exec command")
+                    .detail(format!("Command: {expanded}"))
+                    .detail(format!("Error: {err}.")),
+            )
+        })?;
+
+    if !status.success() {
+        return Err(FinalError::with_title("The --exec command failed")
+            .detail(format!("Command exited with: {status}"))
+            .into());
+    }
+
+    Ok(())
+}
+
+/// Deletes the source files that a successful compression archived (the
+/// same walk, honoring the size and time filters so excluded files
+/// survive), and with `remove_empty_dirs` prunes directories the removal
+/// emptied, bottom-up.
+fn remove_archived_sources(
+    inputs: &[PathBuf],
+    file_visibility_policy: &FileVisibilityPolicy,
+    size_filter: utils::SizeFilter,
+    time_filter: utils::TimeFilter,
+    remove_empty_dirs: bool,
+    use_trash: bool,
+) -> crate::Result<()> {
+    let mut removed = 0;
+    let mut directories = vec![];
+
+    for input in inputs {
+        for entry in file_visibility_policy.build_walker(input)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Ok(metadata) = path.metadata() else { continue };
+
+            if metadata.is_dir() {
+                directories.push(path.to_path_buf());
+            } else if metadata.is_file()
+                && (!size_filter.is_active() || size_filter.allows(metadata.len()))
+                && (!time_filter.is_active() || metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime)))
+            {
+                utils::remove_or_trash(path, use_trash)?;
+                removed += 1;
+            }
+        }
+    }
+
+    info_accessible(format!("Removed {removed} source files."));
+
+    if remove_empty_dirs {
+        // Deepest first, so emptied parents become removable; remove_dir
+        // refuses non-empty directories, which is exactly what we want for
+        // anything still holding excluded files
+        directories.sort_by_key(|dir| std::cmp::Reverse(dir.components().count()));
+        let mut pruned = 0;
+        for dir in directories {
+            if std::fs::remove_dir(&dir).is_ok() {
+                pruned += 1;
+            }
+        }
+        info_accessible(format!("Pruned {pruned} empty directories."));
+    }
+
+    Ok(())
+}
+
+/// Reads the just-written archive back into a scratch directory and
+/// compares every extracted file against its source by hash, see
+/// `--verify`. The same walk and filters as the compression run, so the
+/// comparison set matches what was archived.
+#[allow(clippy::too_many_arguments)]
+fn verify_archive_roundtrip(
+    inputs: &[PathBuf],
+    output_path: &Path,
+    formats: Vec<crate::extension::Extension>,
+    file_visibility_policy: &FileVisibilityPolicy,
+    base_dir: Option<&Path>,
+    size_filter: utils::SizeFilter,
+    time_filter: utils::TimeFilter,
+    temp_parent: &Path,
+    raw: bool,
+) -> crate::Result<()> {
+    let scratch = tempfile::tempdir_in(temp_parent)?;
+    let extract_dir = scratch.path().to_path_buf();
+
+    let (pathbase, _) = extension::separate_known_extensions_from_name(output_path);
+    let file_name = pathbase.file_name().unwrap_or_default().to_owned();
+    let is_single_stream = formats
+        .first()
+        .and_then(|extension| extension.compression_formats.first())
+        .is_some_and(|format| !format.is_archive());
+    decompress_file(DecompressOptions {
+        input_file_path: output_path,
+        formats,
+        output_dir: &extract_dir,
+        output_file_path: extract_dir.join(&file_name),
+        question_policy: QuestionPolicy::AlwaysYes,
+        on_conflict: Some(ConflictPolicy::Overwrite),
+        quiet: true,
+        no_smart_unpack: true,
+        absolute_paths: false,
+        preserve_special: false,
+        temp_dir: temp_parent,
+        age_identity: None,
+        preserve_attributes: false,
+        on_duplicate: None,
+        bomb_guard: utils::bomb::BombGuardSettings {
+            max_ratio: None,
+            max_extracted_size: None,
+            max_entries: None,
+            disabled: false,
+        },
+        zstd_dictionary: None,
+        owner_map: None,
+        use_trash: false,
+        strict_tar: false,
+        allow_setuid: false,
+        raw,
+        use_stored_name: false,
+        non_utf8: None,
+        buffer_size: None,
+        retry: 0,
+    })
+    .map_err(|err| {
+        crate::Error::from(
+            FinalError::with_title("Verification failed, the archive does not read back")
+                .detail(format!("Error: {err}")),
+        )
+    })?;
+
+    // Single-stream outputs extract under the archive's own stem, not the
+    // input's name, so the one input compares against that file directly
+    if is_single_stream {
+        let extracted = extract_dir.join(&file_name);
+        let [input] = inputs else {
+            return Err(FinalError::with_title("Verification failed")
+                .detail("Single-stream archives hold exactly one input")
+                .into());
+        };
+        let matches = extracted.is_file()
+            && utils::checksum::hash_file(input, crate::cli::ChecksumAlgorithm::Blake3)?
+                == utils::checksum::hash_file(&extracted, crate::cli::ChecksumAlgorithm::Blake3)?;
+        if !matches {
+            return Err(FinalError::with_title("Verification failed")
+                .detail(format!(
+                    "'{}' does not match its copy in the archive",
+                    EscapedPathDisplay::new(input)
+                ))
+                .into());
+        }
+
+        info_accessible("Verified 1 file against the written archive.".into());
+        return Ok(());
+    }
+
+    let mut verified: u64 = 0;
+    for input in inputs {
+        // Entry paths mirror the archiving walk: relative to --base, or to
+        // the input's parent directory
+        let prefix = match base_dir {
+            Some(base_dir) => base_dir.to_path_buf(),
+            None => input.parent().unwrap_or(Path::new("")).to_path_buf(),
+        };
+
+        for entry in file_visibility_policy.build_walker(input)? {
+            let entry = entry?;
+            let path = entry.path();
+            let Ok(metadata) = path.metadata() else { continue };
+            if !metadata.is_file() {
+                continue;
+            }
+            if size_filter.is_active() && !size_filter.allows(metadata.len()) {
+                continue;
+            }
+            if time_filter.is_active() && !metadata.modified().is_ok_and(|mtime| time_filter.allows(mtime)) {
+                continue;
+            }
+
+            let relative = path.strip_prefix(&prefix).unwrap_or(path);
+            let extracted = extract_dir.join(relative);
+            let matches = extracted.is_file()
+                && utils::checksum::hash_file(path, crate::cli::ChecksumAlgorithm::Blake3)?
+                    == utils::checksum::hash_file(&extracted, crate::cli::ChecksumAlgorithm::Blake3)?;
+            if !matches {
+                return Err(FinalError::with_title("Verification failed")
+                    .detail(format!(
+                        "'{}' does not match its copy in the archive",
+                        EscapedPathDisplay::new(path)
+                    ))
+                    .into());
+            }
+            verified += 1;
+        }
+    }
+
+    info_accessible(format!("Verified {verified} files against the written archive."));
+
+    Ok(())
+}
+
+/// Reads the newline (or NUL, with `--null`) separated input list given to
+/// `--entries-from`, canonicalizing each listed path.
+fn read_entries_from(path: &Path, null_separated: bool, ignore_missing: bool) -> crate::Result<Vec<PathBuf>> {
+    let contents = fs_err::read(path)?;
+    let separator = if null_separated { b'\0' } else { b'\n' };
+
+    let mut entries = vec![];
+    for raw_entry in contents.split(|byte| *byte == separator) {
+        let raw_entry = if null_separated {
+            raw_entry
+        } else {
+            raw_entry.strip_suffix(b"\r").unwrap_or(raw_entry)
+        };
+        if raw_entry.is_empty() {
+            continue;
+        }
+
+        #[cfg(unix)]
+        let entry = {
+            use std::os::unix::ffi::OsStrExt;
+            PathBuf::from(std::ffi::OsStr::from_bytes(raw_entry))
+        };
+        #[cfg(not(unix))]
+        let entry = PathBuf::from(String::from_utf8_lossy(raw_entry).into_owned());
+
+        match fs_err::canonicalize(&entry) {
+            Ok(canonical) => entries.push(canonical),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound && ignore_missing => {
+                utils::logger::warning(format!("Skipping missing entry '{}'", entry.display()));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(entries)
+}
+
+/// Spawns the shell command given to `--pipe-through` with a piped stdin.
+fn spawn_pipe_through_child(command: &str) -> crate::Result<std::process::Child> {
+    #[cfg(unix)]
+    let (shell, flag) = ("sh", "-c");
+    #[cfg(not(unix))]
+    let (shell, flag) = ("cmd", "/C");
+
+    std::process::Command::new(shell)
+        .arg(flag)
+        .arg(command)
+        .stdin(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            FinalError::with_title("Could not spawn the --pipe-through command")
+                .detail(format!("Command: {command}"))
+                .detail(format!("Error: {err}."))
+                .into()
+        })
+}
+
+/// Prints every supported format with its capabilities, default level and
+/// valid range, requested with `--list-formats` and derived from the
+/// [`crate::extension::supported_formats`] table.
+fn list_formats() -> crate::Result<()> {
+    for info in crate::extension::supported_formats() {
+        let mut capabilities = vec![];
+        if info.can_compress {
+            capabilities.push("compress");
+        }
+        if info.can_decompress {
+            capabilities.push("decompress");
+        }
+        if info.is_archive {
+            capabilities.push("list");
+        }
+
+        let mut description = capabilities.join("+");
+        if let (Some(default), Some(range)) = (info.format.default_level(), info.format.level_range()) {
+            description.push_str(&format!(
+                ", default level {default} (valid from {} to {})",
+                range.start(),
+                range.end()
+            ));
+        }
+        if let Some(feature) = info.requires_feature {
+            description.push_str(&format!(", needs the '{feature}' cargo feature"));
+        }
+
+        println!("{}	{}	{description}", info.name, info.extensions.join("/"));
+    }
+
+    Ok(())
+}
+
+/// Prints the resolved (de)compression chain for a path, requested with
+/// `--explain`. Goes to stderr and does not affect the operation itself.
+fn explain_chain(
+    path: &Path,
+    formats: &[extension::CompressionFormat],
+    decompressing: bool,
+    level: Option<i16>,
+    threads: Option<usize>,
+) {
+    let mut parts: Vec<String> = formats.iter().map(ToString::to_string).collect();
+    if decompressing {
+        parts.reverse();
+    }
+    let chain = parts.join(" → ");
+
+    if decompressing {
+        info_accessible(format!("Resolved chain for '{}': {chain}", to_utf(path)));
+    } else {
+        let level = level.map_or_else(|| "default".into(), |level| level.to_string());
+        let threads = threads.unwrap_or_else(num_cpus::get_physical);
+        info_accessible(format!(
+            "Resolved chain for '{}': {chain} (level {level}, threads {threads})",
+            to_utf(path)
+        ));
+    }
+}
+
+/// Parse a `--newer-than`/`--older-than` value: RFC3339, epoch seconds, or
+/// a relative duration like `7d`, `24h`, `30m` or `90s` counted back from now.
+fn parse_time_spec(value: &str) -> crate::Result<std::time::SystemTime> {
+    let value = value.trim();
+
+    // Relative forms first, they are unambiguous
+    if let Some((amount, unit)) = value
+        .char_indices()
+        .last()
+        .map(|(idx, unit)| (&value[..idx], unit))
+    {
+        if let Ok(amount) = amount.parse::<u64>() {
+            let seconds = match unit {
+                's' => Some(amount),
+                'm' => Some(amount * 60),
+                'h' => Some(amount * 60 * 60),
+                'd' => Some(amount * 60 * 60 * 24),
+                _ => None,
+            };
+            if let Some(seconds) = seconds {
+                return Ok(std::time::SystemTime::now() - std::time::Duration::from_secs(seconds));
+            }
+        }
+    }
+
+    let parsed = parse_mtime(value).map_err(|_| {
+        FinalError::with_title(format!("Invalid time value: '{value}'"))
+            .detail("Times are given as RFC3339, unix epoch seconds, or relative like 7d/24h/30m")
+    })?;
+    Ok(std::time::UNIX_EPOCH + std::time::Duration::from_secs(parsed.unix_timestamp().max(0) as u64))
+}
+
+/// Parse a `--mtime` value, accepting unix epoch seconds or an RFC3339 timestamp.
+fn parse_mtime(value: &str) -> crate::Result<OffsetDateTime> {
+    let parsed = if let Ok(epoch) = value.parse::<i64>() {
+        OffsetDateTime::from_unix_timestamp(epoch).ok()
+    } else {
+        OffsetDateTime::parse(value, &Rfc3339).ok()
+    };
+
+    parsed.ok_or_else(|| {
+        FinalError::with_title(format!("Invalid --mtime value: '{value}'"))
+            .detail("Timestamps are given as unix epoch seconds or in the RFC3339 format")
+            .hint("Examples: --mtime 1716145000, --mtime 2024-05-19T20:16:40Z")
+            .into()
+    })
+}
+
 /// Warn the user that (de)compressing this .7z archive might freeze their system.
 fn warn_user_about_loading_sevenz_in_memory() {
     const SEVENZ_IN_MEMORY_LIMITATION_WARNING: &str = "\n  \
@@ -45,25 +467,138 @@ fn warn_user_about_loading_sevenz_in_memory() {
 ///
 /// There are a lot of custom errors to give enough error description and explanation.
 pub fn run(
-    args: CliArgs,
+    mut args: CliArgs,
     question_policy: QuestionPolicy,
     file_visibility_policy: FileVisibilityPolicy,
 ) -> crate::Result<()> {
-    match args.cmd {
+    if args.list_formats {
+        return list_formats();
+    }
+
+    let Some(cmd) = args.cmd.take() else {
+        // clap's arg_required_else_help handles the empty invocation, getting
+        // here requires a global flag without a subcommand
+        return Err(FinalError::with_title("No command was supplied")
+            .hint("Check `ouch --help` for usage.")
+            .into());
+    };
+
+    match cmd {
         Subcommand::Compress {
             files,
-            output: output_path,
             level,
+            strict_level,
             fast,
             slow,
+            auto_level,
+            force_zip64,
+            mtime,
+            threads,
+            base_dir,
+            min_size,
+            max_size,
+            each,
+            no_dir_entries,
+            lz4_content_size,
+            profile,
+            dedup,
+            explain,
+            age_recipient,
+            no_recursive: _,
+            pipe_through,
+            include: _,
+            exclude: _,
+            ignore_case: _,
+            exclude_vcs: _,
+            checksum,
+            scan_total,
+            no_clobber,
+            default_format,
+            xz_extreme,
+            zstd_ref,
+            entries_from,
+            null,
+            ignore_missing,
+            split_by_dir,
+            preserve_btime,
+            force,
+            also_format,
+            zstd_checksum,
+            io_threads,
+            preset: _,
+            listed_incremental,
+            error_on_empty,
+            relativize_symlinks,
+            manifest,
+            remove,
+            remove_empty_dirs,
+            name_by_hash,
+            preserve_input_order: _,
+            raw,
+            level_for,
+            normalize_permissions,
+            no_gzip_name,
+            fsync,
+            skip_zeros,
+            non_utf8,
+            embed_total_size,
+            par_block_size,
+            metadata_only,
+            verify,
+            split_size,
+            solid,
+            solid_block_size,
+            jobs,
+            exclude_caches: _,
+            exclude_caches_all: _,
+            newer_than,
+            older_than,
+            prune_empty,
+            no_compression,
         } => {
+            // The last positional argument is the output file
+            let mut files = files;
+            let output_path = files.pop().expect("clap requires at least one value");
+
+            if let Some(entries_path) = entries_from {
+                files.extend(read_entries_from(&entries_path, null, ignore_missing)?);
+            }
+
             // After cleaning, if there are no input files left, exit
             if files.is_empty() {
                 return Err(FinalError::with_title("No files to compress").into());
             }
 
+            // --metadata-only catalogs the inputs instead of archiving them
+            if let Some(metadata_format) = metadata_only {
+                return compress::write_metadata_index(
+                    &files,
+                    &output_path,
+                    metadata_format,
+                    &file_visibility_policy,
+                    question_policy,
+                );
+            }
+
+            let profile = match profile {
+                Some(name) => crate::profiles::load_profile(&name)?,
+                None => crate::profiles::Profile::default(),
+            };
+
+            // Explicit command line flags win over the profile's settings
+            let format_flag = if no_compression {
+                // --no-compression is a shorthand for a bare tar container
+                Some("tar".into())
+            } else {
+                args.format.or_else(|| profile.format.map(Into::into))
+            };
+            let level = level.or(profile.level);
+            let threads = threads.or(profile.threads);
+            let min_size = min_size.or(profile.min_size);
+            let max_size = max_size.or(profile.max_size);
+
             // Formats from path extension, like "file.tar.gz.xz" -> vec![Tar, Gzip, Lzma]
-            let (formats_from_flag, formats) = match args.format {
+            let (formats_from_flag, mut formats) = match format_flag {
                 Some(formats) => {
                     let parsed_formats = parse_format(&formats)?;
                     (Some(formats), parsed_formats)
@@ -71,19 +606,36 @@ pub fn run(
                 None => (None, extension::extensions_from_path(&output_path)),
             };
 
-            check::check_invalid_compression_with_non_archive_format(
-                &formats,
-                &output_path,
-                &files,
-                formats_from_flag.as_ref(),
-            )?;
-            check::check_archive_formats_position(&formats, &output_path)?;
+            // With --default-format, an output name without a recognized
+            // extension gets the configured format and its suffix appended
+            let mut output_path = output_path;
+            if formats.is_empty() {
+                if let Some(default_format) = default_format {
+                    formats = parse_format(&default_format)?;
+                    output_path = PathBuf::from(format!(
+                        "{}.{}",
+                        output_path.display(),
+                        default_format.to_string_lossy()
+                    ));
+                    info_accessible(format!("Applying the default format, writing '{}'.", to_utf(&output_path)));
+                }
+            }
 
-            let output_file = match utils::ask_to_create_file(&output_path, question_policy)? {
-                Some(writer) => writer,
-                None => return Ok(()),
+            let mtime = match mtime {
+                Some(value) => Some(parse_mtime(&value)?),
+                None => None,
             };
 
+            // With --each or --split-by-dir the trailing positional is just
+            // another input, not the output the check would compare against
+            if !each && !split_by_dir {
+                check::check_output_format_consistency(&output_path, &formats, formats_from_flag.as_ref(), force)?;
+            }
+
+            if let (true, Some(level)) = (strict_level, level) {
+                check::check_level_in_format_ranges(level, &formats)?;
+            }
+
             let level = if fast {
                 Some(1) // Lowest level of compression
             } else if slow {
@@ -92,46 +644,628 @@ pub fn run(
                 level
             };
 
-            let compress_result = compress_files(
-                files,
-                formats,
-                output_file,
-                &output_path,
-                args.quiet,
-                question_policy,
-                file_visibility_policy,
-                level,
-            );
-
-            if let Ok(true) = compress_result {
-                // this is only printed once, so it doesn't result in much text. On the other hand,
-                // having a final status message is important especially in an accessibility context
-                // as screen readers may not read a commands exit code, making it hard to reason
-                // about whether the command succeeded without such a message
-                info_accessible(format!("Successfully compressed '{}'.", to_utf(&output_path)));
+            if explain {
+                explain_chain(&output_path, &extension::flatten_compression_formats(&formats), false, level, threads);
+            }
+
+            let base_dir = base_dir.map(fs_err::canonicalize).transpose()?;
+
+            // Per-layer levels from --level-for, e.g. "zst=19"
+            let mut level_overrides = vec![];
+            for spec in &level_for {
+                let invalid = || {
+                    FinalError::with_title(format!("Invalid --level-for value: '{spec}'"))
+                        .hint("Expected FORMAT=LEVEL, e.g. --level-for zst=19")
+                };
+                let (format_text, level_text) = spec.split_once('=').ok_or_else(invalid)?;
+                let parsed = parse_format(std::ffi::OsStr::new(format_text))?;
+                let [format] = extension::flatten_compression_formats(&parsed)[..] else {
+                    return Err(invalid().into());
+                };
+                if format.level_range().is_none() {
+                    return Err(FinalError::with_title(format!(
+                        "The '{format}' format does not take a compression level"
+                    ))
+                    .into());
+                }
+                let layer_level: i16 = level_text.trim().parse().map_err(|_| invalid())?;
+                level_overrides.push((format, layer_level));
+            }
+
+            let size_filter = utils::SizeFilter {
+                min: min_size.as_deref().map(utils::parse_bytes).transpose()?,
+                max: max_size.as_deref().map(utils::parse_bytes).transpose()?,
+            };
+
+            let time_filter = utils::TimeFilter {
+                newer_than: newer_than.as_deref().map(parse_time_spec).transpose()?,
+                older_than: older_than.as_deref().map(parse_time_spec).transpose()?,
+            };
+
+            let zstd_dictionary = zstd_ref.map(fs_err::read).transpose()?;
+
+            // With --scan-total a quick pre-walk sums sizes and counts files,
+            // so the walk progress has an accurate denominator
+            let total_files = if scan_total {
+                let mut total_files: u64 = 0;
+                let mut total_size: u64 = 0;
+                for path in &files {
+                    for entry in file_visibility_policy.build_walker(path)? {
+                        let entry = entry?;
+                        total_files += 1;
+                        // Sizes must match what compression will read: the
+                        // link target when links are followed (the default),
+                        // the link itself with --relativize-symlinks
+                        let metadata = if relativize_symlinks {
+                            entry.path().symlink_metadata()
+                        } else {
+                            std::fs::metadata(entry.path())
+                        };
+                        if let Ok(metadata) = metadata {
+                            if metadata.is_file() {
+                                total_size += metadata.len();
+                            }
+                        }
+                    }
+                }
+                info_accessible(format!(
+                    "Compressing {total_files} files, {} in total.",
+                    utils::Bytes::new(total_size)
+                ));
+                Some(total_files)
             } else {
-                // If Ok(false) or Err() occurred, delete incomplete file at `output_path`
-                //
-                // if deleting fails, print an extra alert message pointing
-                // out that we left a possibly CORRUPTED file at `output_path`
-                if utils::remove_file_or_dir(&output_path).is_err() {
-                    eprintln!("{red}FATAL ERROR:\n", red = *colors::RED);
-                    eprintln!(
-                        "  Ouch failed to delete the file '{}'.",
-                        EscapedPathDisplay::new(&output_path)
+                None
+            };
+
+            // Compresses one set of input files into `output_path`, deleting
+            // the incomplete output file on failure or cancellation
+            let compress_single = |input_files: Vec<PathBuf>, output_path: &Path| -> crate::Result<bool> {
+                // --name-by-hash writes to a hidden temp name first, the
+                // real name is only known once the content hash is
+                let hashed_temp_path;
+                let output_path = if name_by_hash && output_path != Path::new("-") {
+                    let temp_name = format!(
+                        ".{}.ouch-tmp",
+                        output_path.file_name().unwrap_or_default().to_string_lossy()
                     );
-                    eprintln!("  Please delete it manually.");
-                    eprintln!("  This file is corrupted if compression didn't finished.");
+                    hashed_temp_path = output_path.with_file_name(temp_name);
+                    hashed_temp_path.as_path()
+                } else {
+                    output_path
+                };
+
+                // Verification re-reads the finished archive, which the
+                // streaming/pipe outputs and undecryptable chains rule out
+                if verify
+                    && (pipe_through.is_some()
+                        || output_path == Path::new("-")
+                        || split_size.is_some()
+                        || formats.iter().any(|extension| {
+                            extension.compression_formats.contains(&extension::CompressionFormat::Age)
+                        }))
+                {
+                    return Err(FinalError::with_title("Cannot use --verify here")
+                        .detail("The archive has to be readable back from disk, unencrypted")
+                        .into());
+                }
+
+                // --split-size replaces the single output file with
+                // numbered parts, which the flags below all assume exists
+                if split_size.is_some()
+                    && (pipe_through.is_some()
+                        || output_path == Path::new("-")
+                        || name_by_hash
+                        || checksum.is_some()
+                        || fsync)
+                {
+                    return Err(FinalError::with_title("Cannot use --split-size here")
+                        .detail("Split output writes numbered part files instead of the single output")
+                        .detail("path that --pipe-through, '-', --name-by-hash, --checksum and --fsync rely on")
+                        .into());
+                }
+
+                // --no-clobber refuses to run before any work is done, unlike
+                // the interactive overwrite prompt
+                if no_clobber && question_policy == QuestionPolicy::AlwaysYes {
+                    return Err(FinalError::with_title("--no-clobber cannot be combined with --yes").into());
+                }
+                if no_clobber && output_path.exists() {
+                    return Err(FinalError::with_title("Output file already exists")
+                        .detail(format!("Would overwrite '{}'", EscapedPathDisplay::new(output_path)))
+                        .hint("Remove the file first, or drop --no-clobber to be asked about overwriting.")
+                        .into());
+                }
+
+                check::check_invalid_compression_with_non_archive_format(
+                    &formats,
+                    output_path,
+                    &input_files,
+                    formats_from_flag.as_ref(),
+                )?;
+                check::check_archive_formats_position(&formats, output_path)?;
+                check::check_age_position(&formats)?;
+
+                // Creating the missing parent directories mirrors what
+                // decompression does for --dir
+                if let Some(parent) = output_path.parent() {
+                    if !parent.as_os_str().is_empty() {
+                        utils::create_dir_if_non_existent(parent)?;
+                    }
+                    // Fail fast on an unwritable destination, before the
+                    // input walk spends time on a doomed run
+                    if output_path != Path::new("-") {
+                        utils::check_directory_writable(parent)?;
+                    }
+                }
+
+                // Opening the output for writing truncates it, so an output
+                // that IS one of the inputs would destroy that input before
+                // a single byte was read from it
+                if output_path != Path::new("-") {
+                    if let Ok(canonical_output) = fs_err::canonicalize(output_path) {
+                        if let Some(colliding) = input_files
+                            .iter()
+                            .find(|input| fs_err::canonicalize(input).is_ok_and(|input| input == canonical_output))
+                        {
+                            return Err(FinalError::with_title("The output file is also an input")
+                                .detail(format!(
+                                    "Compressing '{}' into itself would destroy it",
+                                    EscapedPathDisplay::new(colliding)
+                                ))
+                                .hint("Pick a different output name.")
+                                .into());
+                        }
+                    }
+                }
 
-                    if compress_result.is_err() {
-                        eprintln!("  Compression failed for reasons below.");
+                // With --pipe-through the bytes go to a child process and no
+                // output file is created, the name only determines the format
+                let mut pipe_child = None;
+                let output_file: Box<dyn Send + Write> = match &pipe_through {
+                    Some(command) => {
+                        let mut child = spawn_pipe_through_child(command)?;
+                        let stdin = child.stdin.take().expect("stdin was requested piped");
+                        pipe_child = Some(child);
+                        Box::new(stdin)
                     }
+                    None if output_path == Path::new("-") => {
+                        // "-" streams the compressed bytes to stdout
+                        Box::new(std::io::stdout())
+                    }
+                    None => match utils::ask_to_create_file(output_path, question_policy, None, None, args.trash)? {
+                        Some(writer) => Box::new(writer),
+                        None => return Ok(false),
+                    },
+                };
+
+                // Prepare the --also-format outputs, their names derived
+                // from the primary output with the compression suffix swapped
+                let mut also_outputs = vec![];
+                let mut also_paths = vec![];
+                for also in &also_format {
+                    let parsed = parse_format(also)?;
+                    let flat = extension::flatten_compression_formats(&parsed);
+                    if flat.iter().any(extension::CompressionFormat::is_archive) {
+                        return Err(FinalError::with_title("Invalid --also-format value")
+                            .detail("Only compression formats can be teed, not containers")
+                            .hint("Example: --also-format zst")
+                            .into());
+                    }
+
+                    let (base_name, _) = extension::separate_known_extensions_from_name(output_path);
+                    let mut also_name = base_name.to_string_lossy().into_owned();
+                    if let Some(first) = formats.first().filter(|format| format.is_archive()) {
+                        also_name.push('.');
+                        also_name.push_str(&first.to_string());
+                    }
+                    also_name.push('.');
+                    also_name.push_str(&extension::canonical_extension(&parsed));
+                    let also_path = output_path.parent().unwrap_or(Path::new("")).join(also_name);
+
+                    let Some(file) = utils::ask_to_create_file(&also_path, question_policy, None, None, args.trash)? else {
+                        return Ok(false);
+                    };
+                    also_outputs.push(crate::commands::compress::AlsoOutput {
+                        file: Box::new(file),
+                        formats: flat,
+                    });
+                    also_paths.push(also_path);
                 }
+
+                // Both verification and --remove need the input list after
+                // compress_files consumed it
+                let input_files_for_removal = if remove || verify {
+                    input_files.clone()
+                } else {
+                    vec![]
+                };
+
+                let compress_result = compress_files(CompressOptions {
+                    files: input_files,
+                    extensions: formats.clone(),
+                    output_file,
+                    output_path,
+                    quiet: args.quiet,
+                    question_policy,
+                    file_visibility_policy: file_visibility_policy.clone(),
+                    level,
+                    force_zip64,
+                    mtime,
+                    threads,
+                    base_dir: base_dir.clone(),
+                    size_filter,
+                    time_filter,
+                    prune_empty,
+                    no_dir_entries,
+                    lz4_content_size,
+                    dedup,
+                    age_recipients: &age_recipient,
+                    total_files,
+                    xz_extreme,
+                    zstd_dictionary: zstd_dictionary.as_deref(),
+                    auto_level,
+                    preserve_btime,
+                    also_outputs,
+                    zstd_checksum,
+                    io_threads,
+                    listed_incremental: listed_incremental.clone(),
+                    error_on_empty,
+                    relativize_symlinks,
+                    manifest,
+                    output_is_seekable: pipe_through.is_none()
+                        && output_path != Path::new("-")
+                        && split_size.is_none(),
+                    raw,
+                    level_overrides: level_overrides.clone(),
+                    normalize_permissions,
+                    no_gzip_name,
+                    skip_zeros,
+                    non_utf8,
+                    embed_total_size,
+                    par_block_size: par_block_size
+                        .as_deref()
+                        .map(utils::parse_bytes)
+                        .transpose()?
+                        .map(|size| size as usize),
+                    split_size: split_size.as_deref().map(utils::parse_bytes).transpose()?,
+                    solid,
+                    solid_block_size: solid_block_size
+                        .as_deref()
+                        .map(utils::parse_bytes)
+                        .transpose()?,
+                    retry: args.retry,
+                });
+
+                if let Some(mut child) = pipe_child {
+                    let status = child.wait()?;
+                    if compress_result.is_ok() && !status.success() {
+                        return Err(FinalError::with_title("The --pipe-through command failed")
+                            .detail(format!("Command exited with: {status}"))
+                            .into());
+                    }
+
+                    return compress_result;
+                }
+
+                if let Ok(true) = compress_result {
+                    // data has to be durable before any rename publishes it
+                    if fsync && output_path != Path::new("-") {
+                        utils::sync_file_and_parent(output_path)?;
+                    }
+
+                    let mut output_path = output_path.to_path_buf();
+                    if name_by_hash && output_path != Path::new("-") {
+                        let digest =
+                            utils::checksum::hash_file(&output_path, crate::cli::ChecksumAlgorithm::Sha256)?;
+                        let final_path = output_path
+                            .with_file_name(format!("{digest}.{}", extension::canonical_extension(&formats)));
+                        fs_err::rename(&output_path, &final_path)?;
+                        output_path = final_path;
+                        if fsync {
+                            // persist the renamed directory entry as well
+                            utils::sync_file_and_parent(&output_path)?;
+                        }
+                    }
+                    let output_path = output_path.as_path();
+
+                    utils::report::record_format_chain(extension::canonical_extension(&formats));
+                    if let Ok(metadata) = fs_err::metadata(output_path) {
+                        utils::report::record_output(output_path, metadata.len());
+                    }
+
+                    // this is only printed once, so it doesn't result in much text. On the other hand,
+                    // having a final status message is important especially in an accessibility context
+                    // as screen readers may not read a commands exit code, making it hard to reason
+                    // about whether the command succeeded without such a message
+                    info_accessible(format!("Successfully compressed '{}'.", to_utf(output_path)));
+                    for also_path in &also_paths {
+                        info_accessible(format!("Successfully compressed '{}'.", to_utf(also_path)));
+                    }
+
+                    if verify {
+                        // Debug-build fault hook for the --verify tests:
+                        // flips the last byte of the archive to emulate
+                        // silent corruption
+                        #[cfg(debug_assertions)]
+                        if std::env::var_os("OUCH_CORRUPT_OUTPUT").is_some() {
+                            let mut bytes = fs_err::read(output_path)?;
+                            let middle = bytes.len() / 2;
+                            if let Some(byte) = bytes.get_mut(middle) {
+                                *byte ^= 0xFF;
+                            }
+                            fs_err::write(output_path, bytes)?;
+                        }
+
+                        verify_archive_roundtrip(
+                            &input_files_for_removal,
+                            output_path,
+                            formats.clone(),
+                            &file_visibility_policy,
+                            base_dir.as_deref(),
+                            size_filter,
+                            time_filter,
+                            &utils::resolve_temp_dir(args.temp_dir.as_deref())?,
+                            raw,
+                        )?;
+                    }
+
+                    if let Some(algorithm) = checksum {
+                        utils::checksum::write_checksum_sidecar(output_path, algorithm)?;
+                    }
+
+                    // --remove only runs after a fully successful compression
+                    if remove {
+                        remove_archived_sources(
+                            &input_files_for_removal,
+                            &file_visibility_policy,
+                            size_filter,
+                            time_filter,
+                            remove_empty_dirs,
+                            args.trash,
+                        )?;
+                    }
+                } else if output_path != Path::new("-") {
+                    for also_path in &also_paths {
+                        let _ = utils::remove_file_or_dir(also_path);
+                    }
+
+                    // If Ok(false) or Err() occurred, delete incomplete file at `output_path`
+                    //
+                    // if deleting fails, print an extra alert message pointing
+                    // out that we left a possibly CORRUPTED file at `output_path`
+                    if utils::remove_file_or_dir(output_path).is_err() {
+                        eprintln!("{red}FATAL ERROR:\n", red = *colors::RED);
+                        eprintln!(
+                            "  Ouch failed to delete the file '{}'.",
+                            EscapedPathDisplay::new(output_path)
+                        );
+                        eprintln!("  Please delete it manually.");
+                        eprintln!("  This file is corrupted if compression didn't finished.");
+
+                        if compress_result.is_err() {
+                            eprintln!("  Compression failed for reasons below.");
+                        }
+                    }
+                }
+
+                compress_result
+            };
+
+            if split_by_dir {
+                if formats_from_flag.is_none() {
+                    return Err(FinalError::with_title("The --split-by-dir flag requires --format")
+                        .hint("The per-subdirectory archives are named after the subdirectories.")
+                        .hint("Example: ouch compress --split-by-dir --format tar.zst parent/ out/")
+                        .into());
+                }
+                let suffix = extension::canonical_extension(&formats);
+
+                let [parent] = files.as_slice() else {
+                    return Err(FinalError::with_title("--split-by-dir takes exactly one input directory").into());
+                };
+                if !parent.is_dir() {
+                    return Err(FinalError::with_title("--split-by-dir needs a directory input")
+                        .detail(format!("{} is not a directory", EscapedPathDisplay::new(parent)))
+                        .into());
+                }
+
+                utils::create_dir_if_non_existent(&output_path)?;
+
+                // Immediate subdirectories each get their own archive,
+                // loose files are bundled into '_root'
+                let mut subdirectories = vec![];
+                let mut loose_files = vec![];
+                for entry in fs_err::read_dir(parent)? {
+                    let entry = entry?;
+                    if entry.file_type()?.is_dir() {
+                        subdirectories.push(entry.path());
+                    } else {
+                        loose_files.push(entry.path());
+                    }
+                }
+                subdirectories.sort();
+                loose_files.sort();
+
+                let mut produced = 0;
+                let mut jobs: Vec<(String, Vec<PathBuf>)> = subdirectories
+                    .into_iter()
+                    .map(|dir| {
+                        let name = dir.file_name().expect("read_dir yields real names").to_string_lossy().into_owned();
+                        (name, vec![dir])
+                    })
+                    .collect();
+                if !loose_files.is_empty() {
+                    jobs.push(("_root".into(), loose_files));
+                }
+
+                for (name, inputs) in jobs {
+                    let archive_path = output_path.join(format!("{name}.{suffix}"));
+                    if compress_single(inputs, &archive_path)? {
+                        let size = fs_err::metadata(&archive_path).map(|metadata| metadata.len()).unwrap_or(0);
+                        info_accessible(format!(
+                            "Wrote {} ({}).",
+                            to_utf(&archive_path),
+                            utils::Bytes::new(size)
+                        ));
+                        produced += 1;
+                    }
+                }
+
+                info_accessible(format!("Produced {produced} archives in '{}'.", to_utf(&output_path)));
+
+                return Ok(());
             }
 
-            compress_result.map(|_| ())
+            if each {
+                // With --each the trailing positional is just another input,
+                // each output name is derived from its input plus the
+                // --format suffix
+                if formats_from_flag.is_none() {
+                    return Err(FinalError::with_title("The --each flag requires --format")
+                        .hint("There is no single output name to infer the format from.")
+                        .hint("Example: ouch compress --each --format gz a.txt b.txt")
+                        .into());
+                }
+                let suffix = extension::canonical_extension(&formats);
+
+                let mut inputs = files;
+                // The trailing positional skipped canonicalization since it
+                // is normally the output; as an input it needs it
+                inputs.push(fs_err::canonicalize(&output_path)?);
+
+                let total = inputs.len();
+
+                // The archive builders chdir into each input's parent and
+                // restore the process-global cwd afterwards; two jobs doing
+                // that concurrently race no matter where the inputs live.
+                // Single-stream formats open their one input by absolute
+                // path and never touch the cwd, so only they parallelize.
+                let chain_uses_chdir = formats
+                    .first()
+                    .and_then(|extension| extension.compression_formats.first())
+                    .is_some_and(|format| format.is_archive());
+                let jobs = if jobs > 1 && !also_format.is_empty() {
+                    return Err(FinalError::with_title("--jobs cannot be combined with --also-format").into());
+                } else if jobs > 1 && chain_uses_chdir {
+                    utils::logger::warning(
+                        "--jobs only parallelizes single-stream formats (gz, zst, ...), running sequentially".into(),
+                    );
+                    1
+                } else {
+                    jobs.max(1)
+                };
+
+                let successes = if jobs > 1 {
+                    use rayon::prelude::*;
+
+                    let pool = rayon::ThreadPoolBuilder::new()
+                        .num_threads(jobs)
+                        .build()
+                        .expect("building a rayon pool cannot fail with these settings");
+                    let results: Vec<(PathBuf, crate::Result<bool>)> = pool.install(|| {
+                        inputs
+                            .par_iter()
+                            .map(|input| {
+                                let output_path = PathBuf::from(format!("{}.{suffix}", input.display()));
+                                (input.clone(), compress_single(vec![input.clone()], &output_path))
+                            })
+                            .collect()
+                    });
+
+                    // Aggregate failures so one bad input doesn't hide the rest
+                    let mut successes = 0;
+                    let mut failures = vec![];
+                    for (input, result) in results {
+                        match result {
+                            Ok(true) => successes += 1,
+                            Ok(false) => {}
+                            Err(err) => failures.push(format!("{}: {err}", input.display())),
+                        }
+                    }
+                    if !failures.is_empty() {
+                        let mut error =
+                            FinalError::with_title(format!("{} of {total} compressions failed", failures.len()));
+                        for failure in failures {
+                            error = error.detail(failure);
+                        }
+                        return Err(error.into());
+                    }
+                    successes
+                } else {
+                    let mut successes = 0;
+                    for input in inputs {
+                        let output_path = PathBuf::from(format!("{}.{suffix}", input.display()));
+                        if compress_single(vec![input], &output_path)? {
+                            successes += 1;
+                        }
+                    }
+                    successes
+                };
+
+                info_accessible(format!("Successfully compressed {successes} of {total} files."));
+
+                Ok(())
+            } else {
+                compress_single(files, &output_path).map(|_| ())
+            }
         }
-        Subcommand::Decompress { files, output_dir } => {
+        Subcommand::Decompress {
+            files,
+            output_dir,
+            no_smart_unpack,
+            absolute_paths,
+            on_conflict,
+            preserve_special,
+            explain,
+            trust_magic,
+            verify_signature,
+            keyring,
+            age_identity,
+            preserve_attributes,
+            on_duplicate,
+            unnest,
+            max_depth,
+            owner_map,
+            owner_map_default,
+            zstd_ref,
+            max_ratio,
+            max_extracted_size,
+            max_entries,
+            no_bomb_check,
+            exec,
+            preview_conflicts,
+            strict_tar,
+            allow_setuid,
+            verify_manifest,
+            raw,
+            use_stored_name,
+            non_utf8,
+            buffer_size,
+            dry_run,
+        } => {
+            // Remote inputs are downloaded (resumably) into the temp
+            // directory first, then treated like local archives
+            #[cfg(feature = "remote")]
+            let files = {
+                let temp_dir = utils::resolve_temp_dir(args.temp_dir.as_deref())?;
+                let mut files = files;
+                for file in &mut files {
+                    if remote::is_url(file) {
+                        *file = remote::download_to_cache(&file.to_string_lossy(), &temp_dir)?;
+                    }
+                }
+                files
+            };
+            #[cfg(not(feature = "remote"))]
+            if files.iter().any(|file| {
+                let text = file.to_string_lossy();
+                text.starts_with("http://") || text.starts_with("https://")
+            }) {
+                return Err(FinalError::with_title("Cannot download remote archives")
+                    .detail("This build of ouch does not include the 'remote' feature")
+                    .into());
+            }
+
             let mut output_paths = vec![];
             let mut formats = vec![];
 
@@ -148,7 +1282,9 @@ pub fn run(
                 for path in files.iter() {
                     let (pathbase, mut file_formats) = extension::separate_known_extensions_from_name(path);
 
-                    if let ControlFlow::Break(_) = check::check_mime_type(path, &mut file_formats, question_policy)? {
+                    if let ControlFlow::Break(_) =
+                        check::check_mime_type(path, &mut file_formats, question_policy, trust_magic)?
+                    {
                         return Ok(());
                     }
 
@@ -158,6 +1294,46 @@ pub fn run(
             }
 
             check::check_missing_formats_when_decompressing(&files, &formats)?;
+            for file_formats in &formats {
+                check::check_format_chain_is_decodable(file_formats)?;
+            }
+
+            if let Some(signature_path) = verify_signature {
+                let keyring_path = keyring.expect("clap ensures --verify-signature requires --keyring");
+                let [archive] = files.as_slice() else {
+                    return Err(FinalError::with_title("--verify-signature takes exactly one archive").into());
+                };
+                check::check_detached_signature(archive, &signature_path, &keyring_path)?;
+            }
+
+            if absolute_paths {
+                utils::logger::warning(
+                    "Extracting with --absolute-paths: entries stored with absolute paths will be \
+                     written outside of the output directory"
+                        .into(),
+                );
+            }
+
+            if explain {
+                for (path, formats) in files.iter().zip(formats.iter()) {
+                    explain_chain(path, &extension::flatten_compression_formats(formats), true, None, None);
+                }
+            }
+
+            let bomb_guard_settings = utils::bomb::BombGuardSettings {
+                max_ratio,
+                max_extracted_size: max_extracted_size.as_deref().map(utils::parse_bytes).transpose()?,
+                max_entries,
+                disabled: no_bomb_check,
+            };
+
+            let zstd_dictionary = zstd_ref.map(fs_err::read).transpose()?;
+
+            let owner_map = owner_map
+                .map(|text| utils::OwnerMap::parse(&text, owner_map_default))
+                .transpose()?;
+
+            let temp_dir = utils::resolve_temp_dir(args.temp_dir.as_deref())?;
 
             // The directory that will contain the output files
             // We default to the current directory if the user didn't specify an output directory with --dir
@@ -167,6 +1343,102 @@ pub fn run(
             } else {
                 PathBuf::from(".")
             };
+            // Fail fast before any archive bytes are read, extraction into
+            // an unwritable target would otherwise die mid-way (a dry run
+            // reports the problem instead of erroring)
+            let output_writable = utils::check_directory_writable(&output_dir).is_ok();
+            if !dry_run && !output_writable {
+                utils::check_directory_writable(&output_dir)?;
+            }
+
+            // --preview-conflicts only reports what an extraction would
+            // collide with, nothing is written
+            if preview_conflicts || dry_run {
+                let mut conflicts = 0;
+                let mut declared_size: u64 = 0;
+                for ((input_path, formats), file_name) in files.iter().zip(&formats).zip(&output_paths) {
+                    let flattened = extension::flatten_compression_formats(formats);
+
+                    let targets: Vec<PathBuf> = if flattened.first().is_some_and(|format| format.is_archive()) {
+                        let entries = diff::collect_entries(input_path, &flattened, false)?;
+                        declared_size += entries.values().map(|entry| entry.size).sum::<u64>();
+                        if no_smart_unpack {
+                            entries.into_keys().collect()
+                        } else {
+                            // Smart unpack moves the root elements, so those
+                            // are where collisions happen
+                            let mut roots: Vec<PathBuf> = entries
+                                .into_keys()
+                                .filter_map(|path| path.iter().next().map(PathBuf::from))
+                                .collect();
+                            roots.sort();
+                            roots.dedup();
+                            roots
+                        }
+                    } else {
+                        // Single-stream sizes are unknown up front; count
+                        // the compressed size as a lower bound
+                        declared_size += fs_err::metadata(input_path).map(|metadata| metadata.len()).unwrap_or(0);
+                        vec![PathBuf::from(file_name)]
+                    };
+
+                    for target in targets {
+                        let resolved = output_dir.join(&target);
+                        if resolved.exists() {
+                            conflicts += 1;
+                            let action = match on_conflict {
+                                None => "would prompt",
+                                Some(ConflictPolicy::Overwrite) => "would be overwritten",
+                                Some(ConflictPolicy::Skip) => "would be skipped",
+                                Some(ConflictPolicy::Rename) => "would extract under a renamed path",
+                                Some(ConflictPolicy::Newer) => "would be overwritten if the archive is newer",
+                            };
+                            println!("{}: {action}", utils::to_utf(&resolved));
+                        }
+                    }
+                }
+
+                if !dry_run {
+                    info_accessible(format!("{conflicts} existing paths would conflict."));
+                    return Ok(());
+                }
+
+                // The dry run turns every pre-flight signal into a plain
+                // would-succeed / would-fail verdict
+                let mut problems = vec![];
+                if !output_writable {
+                    problems.push(format!(
+                        "the output directory '{}' is not writable",
+                        utils::to_utf(&output_dir)
+                    ));
+                }
+                if conflicts > 0 {
+                    problems.push(format!("{conflicts} existing paths conflict (see above)"));
+                }
+                if let Some(available) = utils::available_disk_space(&output_dir) {
+                    if declared_size > available {
+                        problems.push(format!(
+                            "insufficient disk space: the archives declare {} but only {} is free",
+                            utils::Bytes::new(declared_size),
+                            utils::Bytes::new(available)
+                        ));
+                    }
+                }
+
+                if problems.is_empty() {
+                    info_accessible(format!(
+                        "Dry run: extraction of {} would succeed.",
+                        utils::Bytes::new(declared_size)
+                    ));
+                    return Ok(());
+                }
+
+                let mut error = FinalError::with_title("Dry run: extraction would fail");
+                for problem in problems {
+                    error = error.detail(format!("because {problem}"));
+                }
+                return Err(error.into());
+            }
 
             files
                 .par_iter()
@@ -174,17 +1446,227 @@ pub fn run(
                 .zip(output_paths)
                 .try_for_each(|((input_path, formats), file_name)| {
                     let output_file_path = output_dir.join(file_name); // Path used by single file format archives
-                    decompress_file(
-                        input_path,
+                    decompress_file(DecompressOptions {
+                        input_file_path: input_path,
                         formats,
-                        &output_dir,
+                        output_dir: &output_dir,
                         output_file_path,
                         question_policy,
-                        args.quiet,
-                    )
-                })
+                        on_conflict,
+                        quiet: args.quiet,
+                        no_smart_unpack,
+                        absolute_paths,
+                        preserve_special,
+                        temp_dir: &temp_dir,
+                        age_identity: age_identity.as_deref(),
+                        preserve_attributes,
+                        on_duplicate,
+                        bomb_guard: bomb_guard_settings,
+                        zstd_dictionary: zstd_dictionary.as_deref(),
+                        owner_map: owner_map.as_ref(),
+                        use_trash: args.trash,
+                        strict_tar,
+                        allow_setuid,
+                        raw,
+                        use_stored_name,
+                        non_utf8,
+                        buffer_size: buffer_size
+                            .as_deref()
+                            .map(utils::parse_bytes)
+                            .transpose()?
+                            .map(|size| size as usize),
+                        retry: args.retry,
+                    })
+                })?;
+
+            if unnest {
+                decompress::unnest_extracted_archives(
+                    &output_dir,
+                    max_depth,
+                    question_policy,
+                    on_conflict,
+                    args.quiet,
+                    &temp_dir,
+                    bomb_guard_settings,
+                )?;
+            }
+
+            if verify_manifest {
+                decompress::verify_extracted_manifest(&output_dir)?;
+            }
+
+            // --exec runs only after everything above succeeded
+            if let Some(command) = exec {
+                run_exec_command(&command, &output_dir)?;
+            }
+
+            Ok(())
+        }
+        #[cfg(feature = "mount")]
+        Subcommand::Mount { archive, mount_point } => {
+            let formats = match args.format {
+                Some(format) => parse_format(&format)?,
+                None => {
+                    let mut formats = extension::extensions_from_path(&archive);
+                    if let ControlFlow::Break(_) = check::check_mime_type(&archive, &mut formats, question_policy, false)? {
+                        return Ok(());
+                    }
+                    formats
+                }
+            };
+
+            check::check_missing_formats_when_decompressing(std::slice::from_ref(&archive), std::slice::from_ref(&formats))?;
+
+            let formats = extension::flatten_compression_formats(&formats);
+            mount::mount_archive(&archive, &mount_point, formats)
+        }
+        Subcommand::Estimate {
+            files,
+            level,
+            json,
+            sample,
+        } => {
+            let Some(format) = args.format else {
+                return Err(FinalError::with_title("Cannot estimate without a format")
+                    .hint("Pass the target format explicitly, e.g. --format tar.zst")
+                    .into());
+            };
+            let formats = parse_format(&format)?;
+            let formats = extension::flatten_compression_formats(&formats);
+
+            estimate::estimate_compressed_size(files, formats, level, json, sample, file_visibility_policy)
+        }
+        #[cfg(feature = "tui")]
+        Subcommand::Browse { archive } => {
+            let mut formats = extension::extensions_from_path(&archive);
+            if let ControlFlow::Break(_) = check::check_mime_type(&archive, &mut formats, question_policy, false)? {
+                return Ok(());
+            }
+            check::check_missing_formats_when_decompressing(std::slice::from_ref(&archive), std::slice::from_ref(&formats))?;
+
+            browse::browse(&archive, extension::flatten_compression_formats(&formats))
+        }
+        Subcommand::Recompress {
+            input,
+            output,
+            passthrough,
+        } => {
+            match recompress::pick_mode(&input, &output)? {
+                recompress::RecompressMode::ZipToZip => {
+                    recompress::recompress_zip(&input, &output, passthrough, question_policy)
+                }
+                recompress::RecompressMode::Stream { decoders, encoders } => {
+                    if passthrough {
+                        return Err(FinalError::with_title("--passthrough only applies to zip rewriting").into());
+                    }
+                    recompress::recompress_stream(&input, &output, &decoders, &encoders, question_policy)
+                }
+            }
+        }
+        Subcommand::Repair {
+            input,
+            output,
+            include_partial,
+        } => {
+            let input_formats = extension::extensions_from_path(&input);
+            check::check_missing_formats_when_decompressing(
+                std::slice::from_ref(&input),
+                std::slice::from_ref(&input_formats),
+            )?;
+            let output_formats = extension::extensions_from_path(&output);
+
+            repair::repair(&input, &output, input_formats, output_formats, include_partial, question_policy)
+        }
+        Subcommand::Convert { input, output } => {
+            let mut input_formats = extension::extensions_from_path(&input);
+            if let ControlFlow::Break(_) = check::check_mime_type(&input, &mut input_formats, question_policy, false)? {
+                return Ok(());
+            }
+            check::check_missing_formats_when_decompressing(
+                std::slice::from_ref(&input),
+                std::slice::from_ref(&input_formats),
+            )?;
+            let output_formats = extension::extensions_from_path(&output);
+
+            convert::convert(&input, &output, input_formats, output_formats, question_policy)
+        }
+        Subcommand::Diff { archives, content } => {
+            let mut formats = vec![];
+            for path in archives.iter() {
+                let file_formats = match args.format.as_ref() {
+                    Some(format) => parse_format(format)?,
+                    None => extension::extensions_from_path(path),
+                };
+                formats.push(file_formats);
+            }
+
+            check::check_missing_formats_when_decompressing(&archives, &formats)?;
+
+            let [first, second] = archives.as_slice() else {
+                unreachable!("clap enforces exactly two archives");
+            };
+            let mut formats = formats.into_iter().map(|formats| extension::flatten_compression_formats(&formats));
+            let (first_formats, second_formats) =
+                (formats.next().expect("two archives"), formats.next().expect("two archives"));
+
+            diff::diff_archives(first, second, first_formats, second_formats, content)
+        }
+        Subcommand::Man { output } => {
+            use clap::CommandFactory;
+
+            let command = CliArgs::command();
+
+            match output {
+                Some(dir) => {
+                    utils::create_dir_if_non_existent(&dir)?;
+
+                    let main_page = dir.join("ouch.1");
+                    clap_mangen::Man::new(command.clone())
+                        .render(&mut fs_err::File::create(&main_page)?)
+                        .map_err(crate::Error::from)?;
+
+                    for subcommand in command.get_subcommands() {
+                        let name = format!("ouch-{}", subcommand.get_name());
+                        clap_mangen::Man::new(subcommand.clone().name(name.clone()))
+                            .render(&mut fs_err::File::create(dir.join(format!("{name}.1")))?)
+                            .map_err(crate::Error::from)?;
+                    }
+
+                    info_accessible(format!("Wrote man pages into {}.", utils::nice_directory_display(&dir)));
+                }
+                None => {
+                    clap_mangen::Man::new(command)
+                        .render(&mut std::io::stdout())
+                        .map_err(crate::Error::from)?;
+                }
+            }
+
+            Ok(())
         }
-        Subcommand::List { archives: files, tree } => {
+        Subcommand::Completions { shell } => {
+            use clap::CommandFactory;
+
+            clap_complete::generate(shell, &mut CliArgs::command(), "ouch", &mut std::io::stdout());
+            Ok(())
+        }
+        Subcommand::List {
+            archives: files,
+            tree,
+            only,
+            null,
+            long,
+            no_pager,
+            json,
+            ndjson,
+            bytes,
+            full_paths,
+            total_only,
+            top,
+            utc,
+            iso,
+            peek_nested,
+            peek_depth,
+        } => {
             let mut formats = vec![];
 
             if let Some(format) = args.format {
@@ -196,7 +1678,7 @@ pub fn run(
                 for path in files.iter() {
                     let mut file_formats = extension::extensions_from_path(path);
 
-                    if let ControlFlow::Break(_) = check::check_mime_type(path, &mut file_formats, question_policy)? {
+                    if let ControlFlow::Break(_) = check::check_mime_type(path, &mut file_formats, question_policy, false)? {
                         return Ok(());
                     }
 
@@ -206,15 +1688,55 @@ pub fn run(
 
             // Ensure we were not told to list the content of a non-archive compressed file
             check::check_for_non_archive_formats(&files, &formats)?;
+            for file_formats in &formats {
+                check::check_format_chain_is_decodable(file_formats)?;
+            }
+
+            let temp_dir = utils::resolve_temp_dir(args.temp_dir.as_deref())?;
 
-            let list_options = ListOptions { tree };
+            let list_options = ListOptions {
+                tree,
+                only,
+                null,
+                long,
+                json,
+                ndjson,
+                bytes,
+                full_paths,
+                total_only,
+                top,
+                utc,
+                iso,
+            };
+
+            // Long listings page through $PAGER on a terminal; --null, json
+            // output, --no-pager and redirected output always print directly
+            let use_pager = !no_pager && !null && !json && !ndjson && atty::is(atty::Stream::Stdout);
+            let mut pager_child = None;
+            let mut out: Box<dyn Write> = match if use_pager { spawn_pager() } else { None } {
+                Some(mut child) => {
+                    let stdin = child.stdin.take().expect("stdin was requested piped");
+                    pager_child = Some(child);
+                    Box::new(stdin)
+                }
+                None => Box::new(std::io::stdout()),
+            };
 
             for (i, (archive_path, formats)) in files.iter().zip(formats).enumerate() {
                 if i > 0 {
-                    println!();
+                    let _ = writeln!(out);
                 }
                 let formats = extension::flatten_compression_formats(&formats);
-                list_archive_contents(archive_path, formats, list_options, question_policy)?;
+                if peek_nested {
+                    list::peek_archive_contents(archive_path, &formats, peek_depth.unwrap_or(1), &mut out)?;
+                } else {
+                    list_archive_contents(archive_path, formats, list_options, question_policy, &temp_dir, &mut out)?;
+                }
+            }
+
+            drop(out);
+            if let Some(mut child) = pager_child {
+                let _ = child.wait();
             }
 
             Ok(())
@@ -0,0 +1,402 @@
+//! Read-only FUSE mounting of archives, allows browsing large archives
+//! without extracting them to disk.
+//!
+//! The directory structure is built from the archive listing at mount time,
+//! file contents are decompressed lazily on first read and cached. For tar
+//! archives this means re-reading the (possibly compressed) stream up to the
+//! requested entry, zip entries are decompressed individually.
+
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    io::{BufReader, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::{Duration, SystemTime},
+};
+
+use fs_err as fs;
+use fuser::{
+    Config, Errno, FileAttr, FileHandle, FileType, Filesystem, Generation, INodeNo, LockOwner, MountOption,
+    OpenFlags, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry, Request,
+};
+
+use crate::{
+    error::FinalError,
+    extension::CompressionFormat::{self, *},
+    utils::logger::info_accessible,
+    utils::EscapedPathDisplay,
+    BUFFER_CAPACITY,
+};
+
+/// How long the kernel may cache attributes and entries we reply with.
+/// The archive cannot change while mounted, so a long TTL is fine.
+const TTL: Duration = Duration::from_secs(60);
+
+static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn mark_sigint(_signal: libc::c_int) {
+    SIGINT_RECEIVED.store(true, Ordering::Relaxed);
+}
+
+/// Mount the archive at `archive_path` onto `mount_point` until the
+/// filesystem is unmounted or the user hits Ctrl-C.
+pub fn mount_archive(archive_path: &Path, mount_point: &Path, formats: Vec<CompressionFormat>) -> crate::Result<()> {
+    if !mount_point.is_dir() {
+        return Err(FinalError::with_title("Cannot mount archive")
+            .detail(format!(
+                "The mount point '{}' is not a directory",
+                EscapedPathDisplay::new(mount_point)
+            ))
+            .into());
+    }
+
+    let filesystem = ArchiveFilesystem::new(archive_path, formats)?;
+
+    let mut config = Config::default();
+    config.mount_options = vec![MountOption::RO, MountOption::FSName("ouch".into())];
+
+    // Mount in the background so we can keep watching for Ctrl-C and
+    // unmount cleanly instead of leaving a dangling mount behind
+    unsafe {
+        libc::signal(libc::SIGINT, mark_sigint as *const () as libc::sighandler_t);
+    }
+    let session = fuser::spawn_mount(filesystem, mount_point, &config)?;
+
+    info_accessible(format!(
+        "Mounted '{}' read-only at '{}', press Ctrl-C (or umount it) to exit.",
+        EscapedPathDisplay::new(archive_path),
+        EscapedPathDisplay::new(mount_point)
+    ));
+
+    while !SIGINT_RECEIVED.load(Ordering::Relaxed) && !session.guard.is_finished() {
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    let result = if session.guard.is_finished() {
+        // Unmounted externally, e.g. by `umount`
+        session.join()
+    } else {
+        session.umount_and_join()
+    };
+
+    result.map_err(|err| {
+        FinalError::with_title("Mount session ended with an error")
+            .detail(err.to_string())
+            .into()
+    })
+}
+
+/// Where file contents are loaded from when a mounted file is read.
+enum Backend {
+    Zip(Mutex<zip::ZipArchive<fs::File>>),
+    /// The archive is re-read (and re-decompressed) up to the requested
+    /// entry, results are cached by the filesystem afterwards
+    Tar {
+        archive_path: PathBuf,
+        formats: Vec<CompressionFormat>,
+    },
+    /// Streaming-only chains without an archive format present a single
+    /// file, decompressed once at mount time
+    Single(Vec<u8>),
+}
+
+impl Backend {
+    fn load(&self, entry_index: usize) -> crate::Result<Vec<u8>> {
+        match self {
+            Backend::Zip(archive) => {
+                let mut archive = archive.lock().expect("no other user of this lock can panic");
+                let mut entry = archive.by_index(entry_index)?;
+                // The declared size is untrusted, let the read grow the
+                // buffer from the actual bytes instead
+                let mut contents = vec![];
+                entry.read_to_end(&mut contents)?;
+                Ok(contents)
+            }
+            Backend::Tar { archive_path, formats } => {
+                let reader = chain_reader_decoder(archive_path, formats)?;
+                let mut archive = tar::Archive::new(reader);
+                let entry = archive
+                    .entries()?
+                    .nth(entry_index)
+                    .expect("entry was present when the archive was indexed")?;
+                let mut contents = vec![];
+                entry.take(u64::MAX).read_to_end(&mut contents)?;
+                Ok(contents)
+            }
+            Backend::Single(contents) => Ok(contents.clone()),
+        }
+    }
+}
+
+/// Opens `archive_path` and chains a decoder for every compression format,
+/// mirroring the decoder chaining done when decompressing.
+fn chain_reader_decoder(archive_path: &Path, formats: &[CompressionFormat]) -> crate::Result<Box<dyn Read>> {
+    let reader = fs::File::open(archive_path)?;
+    let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, reader));
+
+    for format in formats.iter().rev() {
+        reader = match format {
+            Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Bzip => Box::new(bzip2::read::BzDecoder::new(reader)),
+            Lz4 => Box::new(lz4_flex::frame::FrameDecoder::new(reader)),
+            Lzma => Box::new(xz2::read::XzDecoder::new_multi_decoder(reader)),
+            Snappy => Box::new(snap::read::FrameDecoder::new(reader)),
+            Zstd => Box::new(zstd::stream::Decoder::new(reader)?),
+            Age => {
+                return Err(FinalError::with_title("Cannot mount age-encrypted archives")
+                    .hint("Decompress the archive instead: ouch decompress <ARCHIVE>")
+                    .into())
+            }
+            Lzw => Box::new(std::io::Cursor::new(crate::unlzw::decode(reader)?)),
+            Tar | Zip | Rar | SevenZip | Iso => unreachable!(),
+        };
+    }
+
+    Ok(reader)
+}
+
+/// A single file or directory in the mounted archive, inode = index + 1.
+struct Node {
+    is_dir: bool,
+    size: u64,
+    /// Index of this entry within the archive, unused for directories
+    entry_index: usize,
+    parent: u64,
+    children: Vec<(OsString, u64)>,
+}
+
+struct ArchiveFilesystem {
+    backend: Backend,
+    nodes: Vec<Node>,
+    /// Contents of already-read files, keyed by inode
+    cache: Mutex<HashMap<u64, Arc<Vec<u8>>>>,
+    uid: u32,
+    gid: u32,
+    mounted_at: SystemTime,
+}
+
+impl ArchiveFilesystem {
+    fn new(archive_path: &Path, formats: Vec<CompressionFormat>) -> crate::Result<Self> {
+        let mut filesystem = Self {
+            backend: Backend::Single(vec![]),
+            nodes: vec![Node {
+                is_dir: true,
+                size: 0,
+                entry_index: 0,
+                parent: u64::from(INodeNo::ROOT),
+                children: vec![],
+            }],
+            cache: Mutex::new(HashMap::new()),
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            mounted_at: SystemTime::now(),
+        };
+
+        filesystem.backend = match formats.as_slice() {
+            [Zip] => {
+                let mut archive = zip::ZipArchive::new(fs::File::open(archive_path)?)?;
+                for idx in 0..archive.len() {
+                    let entry = archive.by_index(idx)?;
+                    let Some(path) = entry.enclosed_name().map(Path::to_owned) else {
+                        continue;
+                    };
+                    filesystem.insert_path(&path, entry.is_dir(), entry.size(), idx);
+                }
+                Backend::Zip(Mutex::new(archive))
+            }
+            [Tar, single_file_formats @ ..] => {
+                let reader = chain_reader_decoder(archive_path, single_file_formats)?;
+                let mut archive = tar::Archive::new(reader);
+                for (idx, entry) in archive.entries()?.enumerate() {
+                    let entry = entry?;
+                    let path = entry.path()?.into_owned();
+                    let is_dir = entry.header().entry_type().is_dir();
+                    filesystem.insert_path(&path, is_dir, entry.size(), idx);
+                }
+                Backend::Tar {
+                    archive_path: archive_path.to_owned(),
+                    formats: single_file_formats.to_vec(),
+                }
+            }
+            [Zip | Rar | SevenZip | Iso, ..] => {
+                return Err(FinalError::with_title("Cannot mount archive")
+                    .detail(format!(
+                        "Mounting is supported for tar (optionally compressed), zip and \
+                         single-file compression formats, not for '{formats:?}'"
+                    ))
+                    .hint("Decompress the archive instead: ouch decompress <ARCHIVE>")
+                    .into());
+            }
+            single_file_formats => {
+                let mut reader = chain_reader_decoder(archive_path, single_file_formats)?;
+                let mut contents = vec![];
+                reader.read_to_end(&mut contents)?;
+
+                // A plain compressed stream holds a single file, named
+                // after the archive with the compression extensions dropped
+                let (remaining_path, _) = crate::extension::separate_known_extensions_from_name(archive_path);
+                let name = remaining_path.file_name().unwrap_or_else(|| OsStr::new("contents"));
+                filesystem.insert_path(Path::new(name), false, contents.len() as u64, 0);
+                Backend::Single(contents)
+            }
+        };
+
+        Ok(filesystem)
+    }
+
+    /// Inserts an archive entry, creating intermediate directories as needed.
+    fn insert_path(&mut self, path: &Path, is_dir: bool, size: u64, entry_index: usize) {
+        let mut current = u64::from(INodeNo::ROOT);
+
+        let components: Vec<_> = path.iter().collect();
+        for (depth, part) in components.iter().enumerate() {
+            let is_last = depth == components.len() - 1;
+
+            let current_idx = current as usize - 1;
+            if let Some(&(_, child)) = self.nodes[current_idx].children.iter().find(|(name, _)| name == *part) {
+                current = child;
+                continue;
+            }
+
+            let new_inode = self.nodes.len() as u64 + 1;
+            self.nodes.push(Node {
+                is_dir: !is_last || is_dir,
+                size: if is_last { size } else { 0 },
+                entry_index,
+                parent: current,
+                children: vec![],
+            });
+            self.nodes[current_idx].children.push((part.to_os_string(), new_inode));
+            current = new_inode;
+        }
+    }
+
+    fn node(&self, ino: INodeNo) -> Option<&Node> {
+        self.nodes.get(u64::from(ino) as usize - 1)
+    }
+
+    fn attr(&self, ino: INodeNo, node: &Node) -> FileAttr {
+        FileAttr {
+            ino,
+            size: node.size,
+            blocks: node.size.div_ceil(512),
+            atime: self.mounted_at,
+            mtime: self.mounted_at,
+            ctime: self.mounted_at,
+            crtime: self.mounted_at,
+            kind: if node.is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            },
+            perm: if node.is_dir { 0o555 } else { 0o444 },
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    fn contents(&self, ino: u64, entry_index: usize) -> crate::Result<Arc<Vec<u8>>> {
+        let mut cache = self.cache.lock().expect("no other user of this lock can panic");
+        if let Some(contents) = cache.get(&ino) {
+            return Ok(Arc::clone(contents));
+        }
+
+        let contents = Arc::new(self.backend.load(entry_index)?);
+        cache.insert(ino, Arc::clone(&contents));
+        Ok(contents)
+    }
+}
+
+impl Filesystem for ArchiveFilesystem {
+    fn lookup(&self, _req: &Request, parent: INodeNo, name: &OsStr, reply: ReplyEntry) {
+        let Some(parent_node) = self.node(parent) else {
+            return reply.error(Errno::ENOENT);
+        };
+
+        match parent_node.children.iter().find(|(child_name, _)| child_name == name) {
+            Some(&(_, child)) => {
+                let ino = INodeNo(child);
+                let node = self.node(ino).expect("children only hold valid inodes");
+                reply.entry(&TTL, &self.attr(ino, node), Generation(0));
+            }
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn getattr(&self, _req: &Request, ino: INodeNo, _fh: Option<FileHandle>, reply: ReplyAttr) {
+        match self.node(ino) {
+            Some(node) => reply.attr(&TTL, &self.attr(ino, node)),
+            None => reply.error(Errno::ENOENT),
+        }
+    }
+
+    fn readdir(&self, _req: &Request, ino: INodeNo, _fh: FileHandle, offset: u64, mut reply: ReplyDirectory) {
+        let Some(node) = self.node(ino) else {
+            return reply.error(Errno::ENOENT);
+        };
+        if !node.is_dir {
+            return reply.error(Errno::ENOTDIR);
+        }
+
+        let dot_entries = [
+            (u64::from(ino), FileType::Directory, OsString::from(".")),
+            (node.parent, FileType::Directory, OsString::from("..")),
+        ];
+        let children = node.children.iter().map(|(name, child)| {
+            let kind = if self.node(INodeNo(*child)).expect("children only hold valid inodes").is_dir {
+                FileType::Directory
+            } else {
+                FileType::RegularFile
+            };
+            (*child, kind, name.clone())
+        });
+
+        for (i, (entry_ino, kind, name)) in dot_entries
+            .into_iter()
+            .chain(children)
+            .enumerate()
+            .skip(offset as usize)
+        {
+            if reply.add(INodeNo(entry_ino), i as u64 + 1, kind, &name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn read(
+        &self,
+        _req: &Request,
+        ino: INodeNo,
+        _fh: FileHandle,
+        offset: u64,
+        size: u32,
+        _flags: OpenFlags,
+        _lock_owner: Option<LockOwner>,
+        reply: ReplyData,
+    ) {
+        let Some(node) = self.node(ino) else {
+            return reply.error(Errno::ENOENT);
+        };
+        if node.is_dir {
+            return reply.error(Errno::EISDIR);
+        }
+
+        match self.contents(u64::from(ino), node.entry_index) {
+            Ok(contents) => {
+                let start = offset.min(contents.len() as u64) as usize;
+                let end = (offset + u64::from(size)).min(contents.len() as u64) as usize;
+                reply.data(&contents[start..end]);
+            }
+            Err(_) => reply.error(Errno::EIO),
+        }
+    }
+}
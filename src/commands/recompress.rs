@@ -0,0 +1,176 @@
+//! Rewrite an archive into a new container, see `ouch recompress`.
+//!
+//! Supports zip to zip (where `--passthrough` copies the raw
+//! already-compressed entry bytes without recompressing them), and
+//! container-preserving stream rewrites like tar.gz to tar.zst, where the
+//! decoder feeds the encoder directly without touching the tar structure.
+
+use std::{
+    io::{self, BufReader, Read, Seek, Write},
+    path::Path,
+};
+
+use fs_err as fs;
+
+use crate::{
+    commands::convert::{chain_default_decoder, chain_default_encoder},
+    error::FinalError,
+    extension::CompressionFormat::{self, *},
+    utils::{self, logger::info_accessible, to_utf},
+    QuestionPolicy, BUFFER_CAPACITY,
+};
+
+/// Copies all entries of `input_path` into a fresh zip at `output_path`.
+///
+/// With `passthrough` the raw compressed entry data is copied unchanged
+/// (keeping each entry's method, timestamps and attributes), avoiding the
+/// decompress+recompress cost; otherwise entries are decoded and deflated
+/// again, which also normalizes entries whose methods a consumer may not
+/// support.
+pub fn recompress_zip(
+    input_path: &Path,
+    output_path: &Path,
+    passthrough: bool,
+    question_policy: QuestionPolicy,
+) -> crate::Result<()> {
+    let mut archive = zip::ZipArchive::new(fs::File::open(input_path)?)?;
+
+    let Some(output_file) = utils::ask_to_create_file(output_path, question_policy, None, None, false)? else {
+        return Ok(());
+    };
+    let mut writer = zip::ZipWriter::new(output_file);
+
+    for idx in 0..archive.len() {
+        if passthrough {
+            let entry = archive.by_index_raw(idx)?;
+            writer.raw_copy_file(entry)?;
+        } else {
+            copy_recompressed(&mut archive, idx, &mut writer)?;
+        }
+    }
+
+    writer.finish()?;
+
+    info_accessible(format!(
+        "Successfully rewrote '{}' into '{}'{}.",
+        to_utf(input_path),
+        to_utf(output_path),
+        if passthrough { " without recompressing" } else { "" },
+    ));
+
+    Ok(())
+}
+
+fn copy_recompressed<W: Write + Seek>(
+    archive: &mut zip::ZipArchive<fs::File>,
+    idx: usize,
+    writer: &mut zip::ZipWriter<W>,
+) -> crate::Result<()> {
+    let mut entry = archive.by_index(idx)?;
+
+    let mut options = zip::write::FileOptions::default().last_modified_time(entry.last_modified());
+    if let Some(mode) = entry.unix_mode() {
+        options = options.unix_permissions(mode);
+    }
+
+    if entry.is_dir() {
+        writer.add_directory(entry.name().to_owned(), options)?;
+    } else {
+        writer.start_file(entry.name().to_owned(), options)?;
+        io::copy(&mut entry, writer)?;
+    }
+
+    Ok(())
+}
+
+/// Picks how the recompression runs: zip archives rewrite entry by entry,
+/// everything sharing its container (or a bare single-stream chain) streams
+/// decoder-into-encoder.
+pub enum RecompressMode {
+    ZipToZip,
+    /// Decoders on the read side, encoders on the write side, the contained
+    /// byte stream (e.g. the tar layout) passes through untouched
+    Stream {
+        decoders: Vec<CompressionFormat>,
+        encoders: Vec<CompressionFormat>,
+    },
+}
+
+pub fn pick_mode(input_path: &Path, output_path: &Path) -> crate::Result<RecompressMode> {
+    let is_zip = |path: &Path| {
+        path.extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+    };
+    if is_zip(input_path) && is_zip(output_path) {
+        return Ok(RecompressMode::ZipToZip);
+    }
+
+    let input_chain = crate::extension::flatten_compression_formats(&crate::extension::extensions_from_path(input_path));
+    let output_chain =
+        crate::extension::flatten_compression_formats(&crate::extension::extensions_from_path(output_path));
+
+    // Chains whose container matches (tar.gz -> tar.zst) or that have no
+    // container at all (file.gz -> file.zst) recompress as a stream rewrite
+    let (decoders, encoders) = match (input_chain.as_slice(), output_chain.as_slice()) {
+        ([Tar, decoders @ ..], [Tar, encoders @ ..]) => (decoders, encoders),
+        (decoders, encoders)
+            if !decoders.is_empty()
+                && !encoders.is_empty()
+                && decoders.iter().chain(encoders).all(|format| !format.is_archive()) =>
+        {
+            (decoders, encoders)
+        }
+        _ => {
+            return Err(FinalError::with_title("Cannot recompress this combination")
+                .detail("Recompression keeps the container: zip to zip, tar chains to tar")
+                .detail("chains, or single-stream formats to single-stream formats")
+                .hint("Use `ouch convert` to change the container itself.")
+                .into())
+        }
+    };
+
+    if decoders.iter().chain(encoders).any(|format| *format == Age) {
+        return Err(FinalError::with_title("Cannot recompress this combination")
+            .detail("Age encryption layers cannot be rewritten in passing")
+            .into());
+    }
+
+    Ok(RecompressMode::Stream {
+        decoders: decoders.to_vec(),
+        encoders: encoders.to_vec(),
+    })
+}
+
+/// Streams the decoded inner bytes straight into the output encoders, with
+/// the pull-based copy keeping memory bounded to the codec buffers.
+pub fn recompress_stream(
+    input_path: &Path,
+    output_path: &Path,
+    decoders: &[CompressionFormat],
+    encoders: &[CompressionFormat],
+    question_policy: QuestionPolicy,
+) -> crate::Result<()> {
+    let Some(output_file) = utils::ask_to_create_file(output_path, question_policy, None, None, false)? else {
+        return Ok(());
+    };
+
+    let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, fs::File::open(input_path)?));
+    for format in decoders.iter().rev() {
+        reader = chain_default_decoder(format, reader)?;
+    }
+    let mut writer: Box<dyn Write> = Box::new(io::BufWriter::with_capacity(BUFFER_CAPACITY, output_file));
+    for format in encoders.iter().rev() {
+        writer = chain_default_encoder(format, writer)?;
+    }
+
+    io::copy(&mut reader, &mut writer)?;
+    writer.flush()?;
+
+    info_accessible(format!(
+        "Successfully rewrote '{}' into '{}'.",
+        to_utf(input_path),
+        to_utf(output_path),
+    ));
+
+    Ok(())
+}
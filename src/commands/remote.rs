@@ -0,0 +1,99 @@
+//! Downloading remote archives before decompression, see the `remote`
+//! cargo feature.
+
+use std::{
+    hash::{Hash, Hasher},
+    io,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+
+use crate::{
+    error::FinalError,
+    utils::{
+        logger::{info_accessible, warning},
+        Bytes,
+    },
+};
+
+/// Whether an input names a remote archive to download.
+pub fn is_url(path: &Path) -> bool {
+    let text = path.to_string_lossy();
+    text.starts_with("http://") || text.starts_with("https://")
+}
+
+/// Downloads `url` into a cache file under `temp_dir`, resuming an
+/// interrupted partial download with an HTTP Range request. Servers that
+/// ignore the range restart the download from scratch (with a warning).
+///
+/// The cache name keeps the remote file name, so format inference from the
+/// extension keeps working on the downloaded copy.
+pub fn download_to_cache(url: &str, temp_dir: &Path) -> crate::Result<PathBuf> {
+    let file_name = url
+        .rsplit('/')
+        .next()
+        .filter(|name| !name.is_empty())
+        .unwrap_or("download");
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    url.hash(&mut hasher);
+    let cache_path = temp_dir.join(format!("ouch-download-{:016x}-{file_name}", hasher.finish()));
+
+    let resume_from = fs::metadata(&cache_path).map(|metadata| metadata.len()).unwrap_or(0);
+
+    let http_error = |err: ureq::Error| {
+        crate::Error::from(
+            FinalError::with_title(format!("Could not download '{url}'")).detail(err.to_string()),
+        )
+    };
+
+    let mut request = ureq::get(url);
+    if resume_from > 0 {
+        info_accessible(format!(
+            "Resuming download at {} from a previous attempt.",
+            Bytes::new(resume_from)
+        ));
+        request = request.header("Range", &format!("bytes={resume_from}-"));
+    }
+    let mut response = request.call().map_err(http_error)?;
+
+    let mut cache_file;
+    match response.status().as_u16() {
+        // Partial content: the server honors the range, append to the cache
+        206 => {
+            cache_file = fs::OpenOptions::new().append(true).open(&cache_path)?;
+        }
+        200 => {
+            if resume_from > 0 {
+                warning("The server does not support resuming, restarting the download".into());
+            }
+            cache_file = fs::File::create(&cache_path)?;
+        }
+        // Requested range not satisfiable: the cached copy is already complete
+        416 => {
+            info_accessible("The cached download is already complete.".into());
+            return Ok(cache_path);
+        }
+        status => {
+            return Err(FinalError::with_title(format!("Could not download '{url}'"))
+                .detail(format!("The server responded with status {status}"))
+                .into());
+        }
+    }
+
+    let mut reader = response.body_mut().as_reader();
+    let downloaded = io::copy(&mut reader, &mut cache_file).inspect_err(|_| {
+        // A partial cache stays around so the next run can resume
+        info_accessible(format!(
+            "Download interrupted, rerun to resume from '{}'.",
+            cache_path.display()
+        ));
+    })?;
+
+    info_accessible(format!(
+        "Downloaded {} from '{url}'.",
+        Bytes::new(downloaded + if response.status().as_u16() == 206 { resume_from } else { 0 })
+    ));
+
+    Ok(cache_path)
+}
@@ -0,0 +1,140 @@
+//! Salvage the intact entries of a damaged tar, see `ouch repair`.
+//!
+//! Entries are copied into a fresh archive until the first read error (a
+//! truncated compression stream, a corrupt header); everything recovered up
+//! to that point ends up in a valid tar.
+
+use std::{
+    io::{self, BufReader, Read, Write},
+    path::Path,
+};
+
+use fs_err as fs;
+
+use crate::{
+    commands::convert::{chain_default_decoder, chain_default_encoder},
+    error::FinalError,
+    extension::{CompressionFormat::*, Extension},
+    utils::{self, logger::info_accessible, to_utf},
+    QuestionPolicy, BUFFER_CAPACITY,
+};
+
+pub fn repair(
+    input_path: &Path,
+    output_path: &Path,
+    input_formats: Vec<Extension>,
+    output_formats: Vec<Extension>,
+    include_partial: bool,
+    question_policy: QuestionPolicy,
+) -> crate::Result<()> {
+    let input_chain = crate::extension::flatten_compression_formats(&input_formats);
+    let output_chain = crate::extension::flatten_compression_formats(&output_formats);
+
+    let unsupported = |side: &str| {
+        crate::Error::from(
+            FinalError::with_title("Cannot repair this archive")
+                .detail(format!("The {side} must be a (optionally compressed) tar archive"))
+                .hint("Repair salvages complete entries from damaged tars; other formats")
+                .hint("keep their own recovery records (zip central directory, rar rr)."),
+        )
+    };
+    let [Tar, decoder_formats @ ..] = input_chain.as_slice() else {
+        return Err(unsupported("input"));
+    };
+    let [Tar, encoder_formats @ ..] = output_chain.as_slice() else {
+        return Err(unsupported("output"));
+    };
+
+    let Some(output_file) = utils::ask_to_create_file(output_path, question_policy, None, None, false)? else {
+        return Ok(());
+    };
+
+    let mut reader: Box<dyn Read> = Box::new(BufReader::with_capacity(BUFFER_CAPACITY, fs::File::open(input_path)?));
+    for format in decoder_formats.iter().rev() {
+        reader = chain_default_decoder(format, reader)?;
+    }
+    let mut writer: Box<dyn Write> = Box::new(io::BufWriter::with_capacity(BUFFER_CAPACITY, output_file));
+    for format in encoder_formats.iter().rev() {
+        writer = chain_default_encoder(format, writer)?;
+    }
+
+    let mut archive = tar::Archive::new(reader);
+    let mut builder = tar::Builder::new(writer);
+
+    let mut recovered: u64 = 0;
+    let mut partial_recovered = false;
+    let mut stopped_at = None;
+
+    'salvage: {
+        let entries = match archive.entries() {
+            Ok(entries) => entries,
+            Err(err) => {
+                stopped_at = Some(format!("the archive start ({err})"));
+                break 'salvage;
+            }
+        };
+
+        for entry in entries {
+            let mut entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    stopped_at = Some(format!("entry {} ({err})", recovered + 1));
+                    break 'salvage;
+                }
+            };
+            let mut header = entry.header().clone();
+            let path = match entry.path() {
+                Ok(path) => path.into_owned(),
+                Err(err) => {
+                    stopped_at = Some(format!("entry {} ({err})", recovered + 1));
+                    break 'salvage;
+                }
+            };
+
+            // Buffering the whole entry makes truncation detectable before
+            // anything of it is committed to the output
+            let declared_size = entry.size();
+            let mut data = Vec::with_capacity(declared_size.min(BUFFER_CAPACITY as u64) as usize);
+            let read_result = entry.read_to_end(&mut data);
+            let complete = read_result.is_ok() && data.len() as u64 == declared_size;
+
+            if complete {
+                builder.append_data(&mut header, &path, &data[..])?;
+                recovered += 1;
+                continue;
+            }
+
+            stopped_at = Some(match read_result {
+                Ok(_) => format!("'{}', truncated mid-entry", path.display()),
+                Err(err) => format!("'{}' ({err})", path.display()),
+            });
+
+            // --include-partial keeps whatever prefix of the broken entry
+            // was readable, under its original name
+            if include_partial && !data.is_empty() && header.entry_type().is_file() {
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, &path, &data[..])?;
+                partial_recovered = true;
+            }
+            break 'salvage;
+        }
+    }
+
+    builder.into_inner()?.flush()?;
+
+    let mut message = format!(
+        "Recovered {recovered} complete entries from '{}' into '{}'.",
+        to_utf(input_path),
+        to_utf(output_path)
+    );
+    if partial_recovered {
+        message.push_str(" The readable prefix of the damaged entry was included.");
+    }
+    info_accessible(message);
+    match stopped_at {
+        Some(location) => info_accessible(format!("Reading stopped at {location}.")),
+        None => info_accessible("The whole archive was readable, no data was lost.".into()),
+    }
+
+    Ok(())
+}
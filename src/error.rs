@@ -7,13 +7,49 @@ use std::{
     fmt::{self, Display},
 };
 
-use crate::{accessible::is_running_in_accessible_mode, utils::colors::*};
+use once_cell::sync::OnceCell;
+
+use crate::{
+    accessible::is_running_in_accessible_mode,
+    extension::{PRETTY_SUPPORTED_ALIASES, PRETTY_SUPPORTED_EXTENSIONS},
+    utils::colors::*,
+};
+
+/// Global flag for debug mode, set by `--debug` or `OUCH_LOG=debug`.
+static DEBUG: OnceCell<bool> = OnceCell::new();
+
+/// Global flag for strict mode, set by `--strict`.
+static STRICT: OnceCell<bool> = OnceCell::new();
+
+/// Check if `Ouch` should treat warnings as fatal.
+pub fn is_running_in_strict_mode() -> bool {
+    STRICT.get().copied().unwrap_or(false)
+}
+
+/// Set the value of the global [`STRICT`] flag.
+pub fn set_strict(value: bool) {
+    if STRICT.get().is_none() {
+        STRICT.set(value).unwrap();
+    }
+}
+
+/// Check if `Ouch` should print detailed error chains.
+pub fn is_running_in_debug_mode() -> bool {
+    DEBUG.get().copied().unwrap_or(false)
+}
+
+/// Set the value of the global [`DEBUG`] flag.
+pub fn set_debug(value: bool) {
+    if DEBUG.get().is_none() {
+        DEBUG.set(value).unwrap();
+    }
+}
 
 /// All errors that can be generated by `ouch`
 #[derive(Debug)]
 pub enum Error {
     /// Not every IoError, some of them get filtered by `From<io::Error>` into other variants
-    IoError { reason: String },
+    IoError { source: std::io::Error },
     /// From lzzzz::lz4f::Error
     Lz4Error { reason: String },
     /// Detected from io::Error if .kind() is io::ErrorKind::NotFound
@@ -39,6 +75,18 @@ pub enum Error {
     /// Recognised but unsupported format
     // currently only RAR when built without the `unrar` feature
     UnsupportedFormat { reason: String },
+    /// Format not recognized from the file extension
+    UnknownFormat { extension: String },
+    /// From iso9660::ISOError
+    IsoError { reason: String },
+    /// Detected from io::Error when the raw os error is ENOSPC
+    OutOfSpace { error_title: String },
+    /// A format chain that cannot be encoded or decoded, e.g. an archive
+    /// format nested inside a compression layer
+    InvalidFormatChain { reason: String },
+    /// The output (or extraction target) directory failed the pre-flight
+    /// writability probe
+    OutputNotWritable { path: String },
 }
 
 /// Alias to std's Result with ouch's Error
@@ -132,7 +180,7 @@ impl fmt::Display for Error {
                     .detail("This is unadvisable since ouch does compressions in-memory.")
                     .hint("Use a more appropriate tool for this, such as rsync.")
             }
-            Error::IoError { reason } => FinalError::with_title(reason.to_string()),
+            Error::IoError { source } => FinalError::with_title(source.to_string()),
             Error::Lz4Error { reason } => FinalError::with_title(reason.to_string()),
             Error::AlreadyExists { error_title } => {
                 FinalError::with_title(error_title.to_string()).detail("File already exists")
@@ -148,14 +196,70 @@ impl fmt::Display for Error {
             Error::UnsupportedFormat { reason } => {
                 FinalError::with_title("Recognised but unsupported format").detail(reason.clone())
             }
+            Error::IsoError { reason } => FinalError::with_title("Invalid iso image").detail(reason.clone()),
+            Error::InvalidFormatChain { reason } => FinalError::with_title("Invalid format chain")
+                .detail(reason.clone())
+                .hint("Archive formats (tar, zip, 7z, rar, iso) can only be the first layer of a chain."),
+            Error::OutOfSpace { error_title } => FinalError::with_title("The target disk ran out of space")
+                .detail(error_title.clone())
+                .detail("Partial output was cleaned up where possible")
+                .hint("Free up space, or choose another output location (see --dir and --temp-dir)."),
+            Error::OutputNotWritable { path } => {
+                FinalError::with_title(format!("The output directory '{path}' is not writable"))
+                    .detail("Nothing was read or compressed, the check runs before any work starts")
+                    .hint("Fix the directory permissions, or pick another output location.")
+            }
+            Error::UnknownFormat { extension } => {
+                FinalError::with_title(format!("Unsupported or unrecognized format: .{extension}"))
+                    .detail(format!("Supported extensions are: {PRETTY_SUPPORTED_EXTENSIONS}"))
+                    .detail(format!("Supported aliases are: {PRETTY_SUPPORTED_ALIASES}"))
+                    .hint("Pass the format explicitly with the '--format' flag:")
+                    .hint("  ouch <COMMAND> <FILE> --format tar.gz")
+            }
         };
 
         write!(f, "{err}")
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::IoError { source } => Some(source),
+            Error::SevenzipError(source) => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Whether an io error is ENOSPC, looking through wrappers (like the ones
+/// fs_err adds) via the source chain.
+fn is_out_of_space(err: &std::io::Error) -> bool {
+    if err.raw_os_error() == Some(libc::ENOSPC) {
+        return true;
+    }
+
+    let mut source = std::error::Error::source(err);
+    while let Some(cause) = source {
+        if let Some(io_err) = cause.downcast_ref::<std::io::Error>() {
+            if io_err.raw_os_error() == Some(libc::ENOSPC) {
+                return true;
+            }
+        }
+        source = cause.source();
+    }
+
+    false
+}
+
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Self {
+        if is_out_of_space(&err) {
+            return Self::OutOfSpace {
+                error_title: err.to_string(),
+            };
+        }
+
         match err.kind() {
             std::io::ErrorKind::NotFound => Self::NotFound {
                 error_title: err.to_string(),
@@ -166,9 +270,7 @@ impl From<std::io::Error> for Error {
             std::io::ErrorKind::AlreadyExists => Self::AlreadyExists {
                 error_title: err.to_string(),
             },
-            _other => Self::IoError {
-                reason: err.to_string(),
-            },
+            _other => Self::IoError { source: err },
         }
     }
 }
@@ -196,6 +298,14 @@ impl From<unrar::error::UnrarError> for Error {
     }
 }
 
+impl From<iso9660::ISOError> for Error {
+    fn from(err: iso9660::ISOError) -> Self {
+        Self::IsoError {
+            reason: format!("{err:?}"),
+        }
+    }
+}
+
 impl From<sevenz_rust::Error> for Error {
     fn from(err: sevenz_rust::Error) -> Self {
         Self::SevenzipError(err)
@@ -215,3 +325,18 @@ impl From<FinalError> for Error {
         Self::Custom { reason: err }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enospc_maps_to_out_of_space() {
+        let err = std::io::Error::from_raw_os_error(libc::ENOSPC);
+        assert!(matches!(Error::from(err), Error::OutOfSpace { .. }));
+
+        let err = std::io::Error::from_raw_os_error(libc::EACCES);
+        assert!(!matches!(Error::from(err), Error::OutOfSpace { .. }));
+    }
+}
@@ -21,14 +21,17 @@ pub const SUPPORTED_EXTENSIONS: &[&str] = &[
     #[cfg(feature = "unrar")]
     "rar",
     "7z",
+    "iso",
+    "age",
+    "Z",
 ];
 
 pub const SUPPORTED_ALIASES: &[&str] = &["tgz", "tbz", "tlz4", "txz", "tzlma", "tsz", "tzst"];
 
 #[cfg(not(feature = "unrar"))]
-pub const PRETTY_SUPPORTED_EXTENSIONS: &str = "tar, zip, bz, bz2, gz, lz4, xz, lzma, sz, zst, 7z";
+pub const PRETTY_SUPPORTED_EXTENSIONS: &str = "tar, zip, bz, bz2, gz, lz4, xz, lzma, sz, zst, 7z, iso, age, Z";
 #[cfg(feature = "unrar")]
-pub const PRETTY_SUPPORTED_EXTENSIONS: &str = "tar, zip, bz, bz2, gz, lz4, xz, lzma, sz, zst, rar, 7z";
+pub const PRETTY_SUPPORTED_EXTENSIONS: &str = "tar, zip, bz, bz2, gz, lz4, xz, lzma, sz, zst, rar, 7z, iso, age, Z";
 
 pub const PRETTY_SUPPORTED_ALIASES: &str = "tgz, tbz, tlz4, txz, tzlma, tsz, tzst";
 
@@ -97,20 +100,81 @@ pub enum CompressionFormat {
     Rar,
     /// .7z
     SevenZip,
+    /// .iso (listing and extraction only)
+    Iso,
+    /// .age, an encryption layer rather than a compression format
+    Age,
+    /// .Z, the classic Unix compress(1) format (decompression only)
+    Lzw,
+}
+
+impl fmt::Display for CompressionFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Gzip => "gz",
+            Bzip => "bz",
+            Lz4 => "lz4",
+            Lzma => "xz",
+            Snappy => "sz",
+            Zstd => "zst",
+            Tar => "tar",
+            Zip => "zip",
+            Rar => "rar",
+            SevenZip => "7z",
+            Iso => "iso",
+            Age => "age",
+            Lzw => "Z",
+        };
+
+        write!(f, "{text}")
+    }
 }
 
 impl CompressionFormat {
+    /// Whether this format bundles multiple files, as opposed to
+    /// compressing a single stream.
+    pub fn is_archive(&self) -> bool {
+        self.is_archive_format()
+    }
+
+    /// The valid `--level` range of this format, `None` for formats without
+    /// an adjustable compression level.
+    pub fn level_range(&self) -> Option<std::ops::RangeInclusive<i32>> {
+        match self {
+            Gzip | Lzma | Snappy => Some(0..=9),
+            Bzip => Some(1..=9),
+            Zstd => Some(zstd::zstd_safe::min_c_level()..=zstd::zstd_safe::max_c_level()),
+            Lz4 | Tar | Zip | Rar | SevenZip | Iso | Age | Lzw => None,
+        }
+    }
+
+    /// The level used when `--level` is not given, `None` for formats
+    /// without an adjustable compression level. Centralized so the defaults
+    /// behave predictably and can be tuned in one place.
+    pub fn default_level(&self) -> Option<i16> {
+        match self {
+            Gzip | Bzip | Lzma => Some(6),
+            Zstd => Some(zstd::DEFAULT_COMPRESSION_LEVEL as i16),
+            // snappy doesn't meaningfully use levels, this is what the gzp
+            // worker pool always received
+            Snappy => Some(0),
+            Lz4 | Tar | Zip | Rar | SevenZip | Iso | Age | Lzw => None,
+        }
+    }
+
     /// Currently supported archive formats are .tar (and aliases to it) and .zip
     fn is_archive_format(&self) -> bool {
         // Keep this match like that without a wildcard `_` so we don't forget to update it
         match self {
-            Tar | Zip | Rar | SevenZip => true,
+            Tar | Zip | Rar | SevenZip | Iso => true,
             Gzip => false,
             Bzip => false,
             Lz4 => false,
             Lzma => false,
             Snappy => false,
             Zstd => false,
+            Age => false,
+            Lzw => false,
         }
     }
 }
@@ -134,6 +198,9 @@ fn to_extension(ext: &[u8]) -> Option<Extension> {
             b"zst" => &[Zstd],
             b"rar" => &[Rar],
             b"7z" => &[SevenZip],
+            b"iso" => &[Iso],
+            b"age" => &[Age],
+            b"Z" | b"z" => &[Lzw],
             _ => return None,
         },
         ext.to_str_lossy(),
@@ -214,6 +281,161 @@ pub fn flatten_compression_formats(extensions: &[Extension]) -> Vec<CompressionF
         .collect()
 }
 
+/// Metadata and capabilities of one supported format, the single source of
+/// truth behind `--list-formats` and available to library consumers.
+#[derive(Debug, Clone, Copy)]
+pub struct FormatInfo {
+    pub format: CompressionFormat,
+    /// Human readable name
+    pub name: &'static str,
+    /// Extensions recognized for this format
+    pub extensions: &'static [&'static str],
+    pub can_compress: bool,
+    pub can_decompress: bool,
+    /// Archive formats bundle multiple files and can be listed
+    pub is_archive: bool,
+    /// Cargo feature required for support, `None` when always available
+    pub requires_feature: Option<&'static str>,
+}
+
+/// Every format ouch knows about, with its capabilities.
+pub fn supported_formats() -> &'static [FormatInfo] {
+    const FORMATS: &[FormatInfo] = &[
+        FormatInfo {
+            format: Tar,
+            name: "tar",
+            extensions: &["tar", "tgz", "tbz", "tbz2", "tlz4", "txz", "tlzma", "tsz", "tzst"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: true,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Zip,
+            name: "zip",
+            extensions: &["zip"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: true,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: SevenZip,
+            name: "7z",
+            extensions: &["7z"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: true,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Rar,
+            name: "rar",
+            extensions: &["rar"],
+            can_compress: false,
+            can_decompress: cfg!(feature = "unrar"),
+            is_archive: true,
+            requires_feature: Some("unrar"),
+        },
+        FormatInfo {
+            format: Iso,
+            name: "iso9660",
+            extensions: &["iso"],
+            can_compress: false,
+            can_decompress: true,
+            is_archive: true,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Gzip,
+            name: "gzip",
+            extensions: &["gz"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Bzip,
+            name: "bzip2",
+            extensions: &["bz", "bz2"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Lz4,
+            name: "lz4",
+            extensions: &["lz4"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Lzma,
+            name: "xz/lzma",
+            extensions: &["xz", "lzma"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Snappy,
+            name: "snappy",
+            extensions: &["sz"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Zstd,
+            name: "zstd",
+            extensions: &["zst"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Lzw,
+            name: "compress (.Z)",
+            extensions: &["Z"],
+            can_compress: false,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+        FormatInfo {
+            format: Age,
+            name: "age encryption",
+            extensions: &["age"],
+            can_compress: true,
+            can_decompress: true,
+            is_archive: false,
+            requires_feature: None,
+        },
+    ];
+
+    FORMATS
+}
+
+/// The canonical file extension for a parsed format chain, used when
+/// deriving output names for the multi-output features (`--each`,
+/// `--split-by-dir`, `--also-format`): aliases like `tgz` expand to their
+/// full `tar.gz` form so derived names stay predictable.
+pub fn canonical_extension(extensions: &[Extension]) -> String {
+    let parts: Vec<String> = flatten_compression_formats(extensions)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    parts.join(".")
+}
+
 /// Builds a suggested output file in scenarios where the user tried to compress
 /// a folder into a non-archive compression format, for error message purposes
 ///
@@ -262,6 +484,20 @@ mod tests {
         assert_eq!(formats, vec![Tar, Gzip]);
     }
 
+    #[test]
+    fn canonical_extensions() {
+        let canonical = |format: &str| canonical_extension(&parse_format(std::ffi::OsStr::new(format)).unwrap());
+
+        assert_eq!(canonical("zst"), "zst");
+        assert_eq!(canonical("gz"), "gz");
+        assert_eq!(canonical("tar.zst"), "tar.zst");
+        assert_eq!(canonical("tgz"), "tar.gz");
+        assert_eq!(canonical("txz"), "tar.xz");
+        assert_eq!(canonical("tzst"), "tar.zst");
+        assert_eq!(canonical("zip"), "zip");
+        assert_eq!(canonical("tar.gz.zst"), "tar.gz.zst");
+    }
+
     #[test]
     fn builds_suggestion_correctly() {
         assert_eq!(build_archive_file_suggestion(Path::new("linux.png"), ".tar"), None);
@@ -1,18 +1,40 @@
 //! Some implementation helpers related to the 'list' command.
 
 use std::{
-    io::{stdout, Write},
+    io::Write,
     path::{Path, PathBuf},
 };
 
 use self::tree::Tree;
-use crate::{accessible::is_running_in_accessible_mode, utils::EscapedPathDisplay};
+use crate::{accessible::is_running_in_accessible_mode, cli::EntryKind, utils::EscapedPathDisplay};
 
 /// Options controlling how archive contents should be listed
 #[derive(Debug, Clone, Copy)]
 pub struct ListOptions {
     /// Whether to show a tree view
     pub tree: bool,
+    /// Restrict the shown entries to files or directories, see `--only`
+    pub only: Option<EntryKind>,
+    /// Print entries NUL separated without decorations, see `--null`
+    pub null: bool,
+    /// Show per-entry method, sizes and ratio, see `--long`
+    pub long: bool,
+    /// Print a buffered JSON array, see `--json`
+    pub json: bool,
+    /// Stream newline-delimited JSON entries, see `--ndjson`
+    pub ndjson: bool,
+    /// Show raw byte counts in the long listing, see `--bytes`
+    pub bytes: bool,
+    /// Never elide long paths in the long listing, see `--full-paths`
+    pub full_paths: bool,
+    /// Print only the summed uncompressed size, see `--total-only`
+    pub total_only: bool,
+    /// Show only the N largest entries, see `--top`
+    pub top: Option<usize>,
+    /// Show entry timestamps in UTC instead of local time, see `--utc`
+    pub utc: bool,
+    /// Show entry timestamps as full RFC3339, see `--iso`
+    pub iso: bool,
 }
 
 /// Represents a single file in an archive, used in `list::list_files()`
@@ -23,6 +45,72 @@ pub struct FileInArchive {
 
     /// Whether this file is a directory
     pub is_dir: bool,
+
+    /// Per-entry compression details, where the backend provides them
+    /// (shown by `--long`)
+    pub details: Option<EntryDetails>,
+
+    /// Where a symlink entry points to, shown as `link -> target`
+    pub link_target: Option<PathBuf>,
+
+    /// The entry's modification time, where the backend stores one
+    pub mtime: Option<time::OffsetDateTime>,
+}
+
+/// Compression details of one archive entry, see `ouch list --long`.
+#[derive(Debug, Clone)]
+pub struct EntryDetails {
+    /// Compression method storing this entry, e.g. "deflate" or "store"
+    pub method: String,
+    pub compressed_size: u64,
+    pub uncompressed_size: u64,
+}
+
+/// One entry in the JSON/ndjson listing output.
+#[derive(serde::Serialize)]
+struct JsonEntry {
+    path: String,
+    is_dir: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    link_target: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    compressed_size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    mtime: Option<String>,
+}
+
+impl From<FileInArchive> for JsonEntry {
+    fn from(file: FileInArchive) -> Self {
+        let (method, compressed_size, size) = match file.details {
+            Some(details) => (
+                Some(details.method),
+                Some(details.compressed_size),
+                Some(details.uncompressed_size),
+            ),
+            None => (None, None, None),
+        };
+
+        Self {
+            path: file.path.to_string_lossy().into_owned(),
+            is_dir: file.is_dir,
+            link_target: file.link_target.map(|target| target.to_string_lossy().into_owned()),
+            method,
+            compressed_size,
+            size,
+            // JSON output always carries RFC3339 UTC, independent of the
+            // --utc/--iso display flags, so consumers can rely on it
+            mtime: file.mtime.and_then(|mtime| {
+                mtime
+                    .to_offset(time::UtcOffset::UTC)
+                    .format(&time::format_description::well_known::Rfc3339)
+                    .ok()
+            }),
+        }
+    }
 }
 
 /// Actually print the files
@@ -31,17 +119,200 @@ pub fn list_files(
     archive: &Path,
     files: impl IntoIterator<Item = crate::Result<FileInArchive>>,
     list_options: ListOptions,
+    out: &mut dyn Write,
 ) -> crate::Result<()> {
-    let out = &mut stdout().lock();
+
+    // Restrict to files or directories when `--only` was given
+    let files = files.into_iter().filter(|file| match (list_options.only, file) {
+        (Some(EntryKind::Files), Ok(file)) => !file.is_dir,
+        (Some(EntryKind::Dirs), Ok(file)) => file.is_dir,
+        _ => true,
+    });
+
+    // --top keeps only the N largest file entries, sorted descending by
+    // uncompressed size (the stored size doubles as it for plain tar, whose
+    // backend reports the two as equal), feeding whichever output style
+    // was picked
+    let files: Box<dyn Iterator<Item = crate::Result<FileInArchive>>> = match list_options.top {
+        Some(top) => {
+            let mut entries = files.collect::<crate::Result<Vec<FileInArchive>>>()?;
+            entries.retain(|file| !file.is_dir);
+            entries.sort_by_key(|file| {
+                std::cmp::Reverse(file.details.as_ref().map_or(0, |details| details.uncompressed_size))
+            });
+            entries.truncate(top);
+            Box::new(entries.into_iter().map(Ok))
+        }
+        None => Box::new(files),
+    };
+
+    // --total-only sums the declared entry sizes without printing a listing
+    if list_options.total_only {
+        let mut total: u64 = 0;
+        for file in files {
+            let file = file?;
+            if let (false, Some(details)) = (file.is_dir, file.details) {
+                total += details.uncompressed_size;
+            }
+        }
+
+        if list_options.json {
+            let _ = writeln!(out, "{{\"total_size\":{total}}}");
+        } else {
+            let _ = writeln!(out, "{}", crate::utils::Bytes::new(total));
+        }
+        return Ok(());
+    }
+
+    // ndjson streams one entry object per line without buffering, json
+    // collects everything into one array first (simpler to consume, but
+    // holds all entries in memory)
+    if list_options.ndjson {
+        for file in files {
+            let entry = JsonEntry::from(file?);
+            let _ = writeln!(out, "{}", serde_json::to_string(&entry).expect("entries serialize cleanly"));
+        }
+        return Ok(());
+    }
+    if list_options.json {
+        let entries: Vec<JsonEntry> = files
+            .into_iter()
+            .map(|file| file.map(JsonEntry::from))
+            .collect::<crate::Result<_>>()?;
+        let _ = writeln!(out, "{}", serde_json::to_string(&entries).expect("entries serialize cleanly"));
+        return Ok(());
+    }
+
+    // NUL-separated output is bare entry paths, without the archive header
+    // or any coloring, ready for xargs -0
+    if list_options.null {
+        for file in files {
+            let FileInArchive { path, .. } = file?;
+            let _ = write!(out, "{}\0", EscapedPathDisplay::new(&path));
+        }
+        return Ok(());
+    }
+
     let _ = writeln!(out, "Archive: {}", EscapedPathDisplay::new(archive));
 
+    // The long listing shows each entry's compression details in aligned
+    // columns, similar to `unzip -v`
+    if list_options.long {
+        // The size/method columns take this many cells, the rest of the
+        // terminal belongs to the (possibly elided) name
+        const COLUMNS_WIDTH: usize = 46;
+
+        // RFC3339 timestamps (--iso) are wider than the default
+        // `2024-01-02 03:04` rendering
+        let time_width = if list_options.iso { 25 } else { 16 };
+
+        let name_budget = crate::utils::terminal_width()
+            .filter(|_| !list_options.full_paths)
+            .map(|width| width.saturating_sub(COLUMNS_WIDTH + time_width + 1).max(16));
+        let render_name = |path: &PathBuf| {
+            let name = EscapedPathDisplay::new(path).to_string();
+            match name_budget {
+                Some(budget) => crate::utils::elide_middle(&name, budget),
+                None => name,
+            }
+        };
+        let render_size = |size: u64| {
+            if list_options.bytes {
+                size.to_string()
+            } else {
+                crate::utils::Bytes::new(size).to_string()
+            }
+        };
+
+        // Timestamps show in local time unless --utc was given; entries
+        // without a stored mtime show `-`
+        let offset = if list_options.utc {
+            time::UtcOffset::UTC
+        } else {
+            crate::utils::local_offset()
+        };
+        let short_format = time::format_description::parse("[year]-[month]-[day] [hour]:[minute]")
+            .expect("the format description is valid");
+        let render_mtime = |mtime: Option<time::OffsetDateTime>| {
+            mtime
+                .and_then(|mtime| {
+                    let mtime = mtime.to_offset(offset);
+                    if list_options.iso {
+                        mtime.format(&time::format_description::well_known::Rfc3339).ok()
+                    } else {
+                        mtime.format(&short_format).ok()
+                    }
+                })
+                .unwrap_or_else(|| "-".into())
+        };
+
+        let _ = writeln!(
+            out,
+            "{:<10} {:>12} {:>12} {:>7} {:>time_width$}  name",
+            "method", "compressed", "size", "ratio", "modified"
+        );
+        for file in files {
+            let FileInArchive {
+                path,
+                is_dir,
+                details,
+                mtime,
+                ..
+            } = file?;
+            match details {
+                Some(details) if !is_dir => {
+                    let ratio = if details.uncompressed_size > 0 {
+                        details.compressed_size as f64 / details.uncompressed_size as f64 * 100.0
+                    } else {
+                        100.0
+                    };
+                    let _ = writeln!(
+                        out,
+                        "{:<10} {:>12} {:>12} {:>6.1}% {:>time_width$}  {}",
+                        details.method,
+                        render_size(details.compressed_size),
+                        render_size(details.uncompressed_size),
+                        ratio,
+                        render_mtime(mtime),
+                        render_name(&path),
+                    );
+                }
+                _ => {
+                    let _ = writeln!(
+                        out,
+                        "{:<10} {:>12} {:>12} {:>7} {:>time_width$}  {}",
+                        "-",
+                        "-",
+                        "-",
+                        "-",
+                        render_mtime(mtime),
+                        render_name(&path),
+                    );
+                }
+            }
+        }
+        return Ok(());
+    }
+
     if list_options.tree {
         let tree = files.into_iter().collect::<crate::Result<Tree>>()?;
         tree.print(out);
     } else {
         for file in files {
-            let FileInArchive { path, is_dir } = file?;
-            print_entry(out, EscapedPathDisplay::new(&path), is_dir);
+            let FileInArchive {
+                path,
+                is_dir,
+                link_target,
+                ..
+            } = file?;
+            match link_target {
+                Some(target) => print_entry(
+                    out,
+                    format!("{} -> {}", EscapedPathDisplay::new(&path), EscapedPathDisplay::new(&target)),
+                    is_dir,
+                ),
+                None => print_entry(out, EscapedPathDisplay::new(&path), is_dir),
+            }
         }
     }
     Ok(())
@@ -49,7 +320,7 @@ pub fn list_files(
 
 /// Print an entry and highlight directories, either by coloring them
 /// if that's supported or by adding a trailing /
-fn print_entry(out: &mut impl Write, name: impl std::fmt::Display, is_dir: bool) {
+fn print_entry(out: &mut dyn Write, name: impl std::fmt::Display, is_dir: bool) {
     use crate::utils::colors::*;
 
     if is_dir {
@@ -129,13 +400,13 @@ mod tree {
         }
 
         /// Print the file tree using Unicode line characters
-        pub fn print(&self, out: &mut impl Write) {
+        pub fn print(&self, out: &mut dyn Write) {
             for (i, (name, subtree)) in self.children.iter().enumerate() {
                 subtree.print_(out, name, "", i == self.children.len() - 1);
             }
         }
         /// Print the tree by traversing it recursively
-        fn print_(&self, out: &mut impl Write, name: &OsStr, prefix: &str, last: bool) {
+        fn print_(&self, out: &mut dyn Write, name: &OsStr, prefix: &str, last: bool) {
             // If there are no further elements in the parent directory, add
             // "└── " to the prefix, otherwise add "├── "
             let final_part = match last {
@@ -143,7 +414,7 @@ mod tree {
                 false => draw::FINAL_BRANCH,
             };
 
-            print!("{prefix}{final_part}");
+            let _ = write!(out, "{prefix}{final_part}");
             let is_dir = match self.file {
                 Some(FileInArchive { is_dir, .. }) => is_dir,
                 None => true,
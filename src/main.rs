@@ -6,6 +6,8 @@ pub mod commands;
 pub mod error;
 pub mod extension;
 pub mod list;
+pub mod profiles;
+pub mod unlzw;
 pub mod utils;
 
 use std::{env, path::PathBuf};
@@ -27,12 +29,33 @@ static CURRENT_DIRECTORY: Lazy<PathBuf> = Lazy::new(|| env::current_dir().unwrap
 pub const EXIT_FAILURE: i32 = libc::EXIT_FAILURE;
 
 fn main() {
+    // Must happen before any thread exists, the lookup refuses afterwards
+    utils::capture_local_offset();
     let handler = spawn_logger_thread();
-    let result = run();
+    let mut result = run();
     handler.shutdown_and_wait();
 
+    // --strict turns any emitted warning into a failure after the operation
+    if result.is_ok() && error::is_running_in_strict_mode() && utils::logger::warnings_were_emitted() {
+        result = Err(error::FinalError::with_title("Warnings were emitted while running in --strict mode").into());
+    }
+
+    utils::report::write_if_enabled(result.as_ref().err());
+
     if let Err(err) = result {
         eprintln!("{err}");
+
+        // Requested with --debug or OUCH_LOG=debug: show the underlying
+        // error chain and the debug representation of the error
+        if error::is_running_in_debug_mode() {
+            let mut source = std::error::Error::source(&err);
+            while let Some(cause) = source {
+                eprintln!("  caused by: {cause}");
+                source = cause.source();
+            }
+            eprintln!("  debug: {err:?}");
+        }
+
         std::process::exit(EXIT_FAILURE);
     }
 }
@@ -0,0 +1,89 @@
+//! Named compression profiles, selected with `--profile`.
+//!
+//! Profiles bundle commonly repeated compression settings. They are loaded
+//! from `~/.config/ouch/profiles.toml` (honoring `XDG_CONFIG_HOME`), where
+//! each table is one profile:
+//!
+//! ```toml
+//! [backup]
+//! format = "tar.zst"
+//! level = 19
+//! threads = 8
+//! min_size = "1kB"
+//! ```
+//!
+//! The `fast` and `max` profiles are built in and work without a config
+//! file. Explicit command line flags always override profile values.
+
+use std::{collections::BTreeMap, env, path::PathBuf};
+
+use fs_err as fs;
+use serde::Deserialize;
+
+use crate::error::FinalError;
+
+/// Settings bundled under one profile name.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    pub format: Option<String>,
+    pub level: Option<i16>,
+    pub threads: Option<usize>,
+    pub min_size: Option<String>,
+    pub max_size: Option<String>,
+}
+
+/// Loads the profile called `name`, from the built-in ones or the user's
+/// `profiles.toml`.
+pub fn load_profile(name: &str) -> crate::Result<Profile> {
+    // Built-in profiles, available without a config file
+    match name {
+        "fast" => {
+            return Ok(Profile {
+                level: Some(1),
+                ..Profile::default()
+            })
+        }
+        "max" => {
+            return Ok(Profile {
+                level: Some(i16::MAX),
+                ..Profile::default()
+            })
+        }
+        _ => {}
+    }
+
+    let path = profiles_config_path().ok_or_else(|| {
+        FinalError::with_title(format!("Unknown profile '{name}'"))
+            .detail("Could not locate the configuration directory to look for profiles.toml")
+            .hint("The built-in profiles are: fast, max")
+    })?;
+
+    let contents = fs::read_to_string(&path).map_err(|_| {
+        FinalError::with_title(format!("Unknown profile '{name}'"))
+            .detail(format!("No profile file found at {}", path.display()))
+            .hint("The built-in profiles are: fast, max")
+    })?;
+
+    let mut profiles: BTreeMap<String, Profile> = toml::from_str(&contents).map_err(|err| {
+        FinalError::with_title(format!("Failed to parse {}", path.display())).detail(err.to_string())
+    })?;
+
+    profiles.remove(name).ok_or_else(|| {
+        let mut available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        available.extend(["fast", "max"]);
+
+        FinalError::with_title(format!("Unknown profile '{name}'"))
+            .detail(format!("Available profiles: {}", available.join(", ")))
+            .into()
+    })
+}
+
+/// `$XDG_CONFIG_HOME/ouch/profiles.toml`, falling back to `~/.config`.
+fn profiles_config_path() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+
+    Some(base.join("ouch").join("profiles.toml"))
+}
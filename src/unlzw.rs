@@ -0,0 +1,162 @@
+//! Decoder for the classic Unix compress(1) `.Z` format (LZW).
+//!
+//! Decompression only: producing `.Z` files is long obsolete, but reading
+//! them rounds out compatibility with legacy archives. The stream is decoded
+//! fully in memory, which is fine for the sizes these files come in.
+//!
+//! The format is LZW with codes growing from 9 bits up to the width stored
+//! in the header, written LSB-first, with the quirk that the code stream is
+//! padded to a code-width-sized group boundary whenever the width changes or
+//! the table is cleared (mirroring ncompress/gzip's unlzw).
+
+use std::io::{self, Read};
+
+pub const MAGIC: [u8; 2] = [0x1F, 0x9D];
+const MAX_BITS_MASK: u8 = 0x1F;
+const BLOCK_MODE: u8 = 0x80;
+const INIT_BITS: usize = 9;
+/// In block mode, code 256 clears the table.
+const CLEAR: u32 = 256;
+/// First free table slot in block mode.
+const FIRST: u32 = 257;
+
+/// Reads a whole compress(1) stream and returns the decompressed bytes.
+pub fn decode(mut reader: impl Read) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader.read_to_end(&mut data)?;
+    decode_buffer(&data)
+}
+
+fn corrupt() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "corrupt .Z (compress) stream")
+}
+
+fn decode_buffer(data: &[u8]) -> io::Result<Vec<u8>> {
+    if data.len() < 3 || data[..2] != MAGIC {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "not a .Z (compress) stream, bad magic bytes",
+        ));
+    }
+
+    let max_bits = (data[2] & MAX_BITS_MASK) as usize;
+    let block_mode = data[2] & BLOCK_MODE != 0;
+    if !(INIT_BITS..=16).contains(&max_bits) {
+        return Err(corrupt());
+    }
+    let max_max_code: u32 = 1 << max_bits;
+
+    let mut prefixes = vec![0u32; 1 << max_bits];
+    let mut suffixes = vec![0u8; 1 << max_bits];
+    for (code, suffix) in suffixes.iter_mut().take(256).enumerate() {
+        *suffix = code as u8;
+    }
+
+    let total_bits = data.len() * 8;
+    let mut n_bits = INIT_BITS;
+    let mut max_code: u32 = (1 << n_bits) - 1;
+    let mut free_ent: u32 = if block_mode { FIRST } else { 256 };
+    // Bit cursor and the base of the current code group, see module docs
+    let mut bit_pos: usize = 3 * 8;
+    let mut group_base: usize = bit_pos;
+
+    let mut old_code: Option<u32> = None;
+    let mut final_char: u8 = 0;
+    let mut output = vec![];
+    let mut stack = vec![];
+
+    // Skips the rest of the current code group, called when the code width
+    // is about to change
+    let skip_group_padding = |bit_pos: &mut usize, group_base: &mut usize, n_bits: usize| {
+        let group_bits = n_bits * 8;
+        let offset = (*bit_pos - *group_base).div_ceil(group_bits) * group_bits;
+        *bit_pos = *group_base + offset;
+        *group_base = *bit_pos;
+    };
+
+    loop {
+        if free_ent > max_code {
+            skip_group_padding(&mut bit_pos, &mut group_base, n_bits);
+            if n_bits < max_bits {
+                n_bits += 1;
+            }
+            max_code = if n_bits == max_bits {
+                max_max_code
+            } else {
+                (1 << n_bits) - 1
+            };
+        }
+
+        if bit_pos + n_bits > total_bits {
+            break;
+        }
+        let code = read_code(data, bit_pos, n_bits);
+        bit_pos += n_bits;
+
+        let Some(previous_code) = old_code else {
+            // The first code is always a literal
+            if code >= 256 {
+                return Err(corrupt());
+            }
+            final_char = code as u8;
+            output.push(final_char);
+            old_code = Some(code);
+            continue;
+        };
+
+        if block_mode && code == CLEAR {
+            // Table cleared: slot 256 gets one garbage entry afterwards
+            // (it is shadowed by the CLEAR code), keeping the table in sync
+            // with the encoder which restarts at FIRST
+            free_ent = FIRST - 1;
+            skip_group_padding(&mut bit_pos, &mut group_base, n_bits);
+            n_bits = INIT_BITS;
+            max_code = (1 << n_bits) - 1;
+            continue;
+        }
+
+        let incoming_code = code;
+        let mut code = code;
+        stack.clear();
+
+        if code >= free_ent {
+            // The KwKwK case: the code about to be defined
+            if code > free_ent {
+                return Err(corrupt());
+            }
+            stack.push(final_char);
+            code = previous_code;
+        }
+        while code >= 256 {
+            stack.push(suffixes[code as usize]);
+            code = prefixes[code as usize];
+        }
+        final_char = code as u8;
+        stack.push(final_char);
+        output.extend(stack.iter().rev());
+
+        if free_ent < max_max_code {
+            prefixes[free_ent as usize] = previous_code;
+            suffixes[free_ent as usize] = final_char;
+            free_ent += 1;
+        }
+        old_code = Some(incoming_code);
+    }
+
+    Ok(output)
+}
+
+/// Reads `n_bits` (at most 16) LSB-first at the given bit position.
+fn read_code(data: &[u8], bit_pos: usize, n_bits: usize) -> u32 {
+    let byte_pos = bit_pos / 8;
+    let shift = bit_pos % 8;
+
+    let mut word: u32 = 0;
+    for i in 0..3 {
+        if let Some(byte) = data.get(byte_pos + i) {
+            word |= u32::from(*byte) << (8 * i);
+        }
+    }
+
+    (word >> shift) & ((1 << n_bits) - 1)
+}
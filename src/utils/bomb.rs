@@ -0,0 +1,138 @@
+//! Guard against decompression bombs during extraction.
+
+use std::{
+    io::{self, Read, Write},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{error::FinalError, utils::Bytes, BUFFER_CAPACITY};
+
+/// Extraction stops once the output exceeds this many times the archive
+/// size, unless overridden (legitimate streams top out around a
+/// thousandfold for all supported formats).
+const DEFAULT_MAX_RATIO: u64 = 10_000;
+/// Small archives always get at least this budget, so the ratio guard
+/// doesn't trip on tiny inputs.
+const MINIMUM_LIMIT: u64 = 64 * 1024 * 1024;
+/// Refuse archives declaring more entries than this up front, unless
+/// overridden; bombs can also use millions of tiny entries.
+const DEFAULT_MAX_ENTRIES: u64 = 1_000_000;
+
+/// The `--max-ratio`/`--max-extracted-size`/`--no-bomb-check` settings.
+#[derive(Debug, Clone, Copy)]
+pub struct BombGuardSettings {
+    pub max_ratio: Option<u64>,
+    pub max_extracted_size: Option<u64>,
+    pub max_entries: Option<u64>,
+    pub disabled: bool,
+}
+
+/// Tracks cumulative bytes written by one extraction and errors out when
+/// the configured limit is crossed.
+pub struct BombGuard {
+    limit: Option<u64>,
+    entry_limit: Option<u64>,
+    written: AtomicU64,
+}
+
+impl BombGuard {
+    pub fn new(archive_size: u64, settings: BombGuardSettings) -> Self {
+        let limit = if settings.disabled {
+            None
+        } else {
+            let ratio = settings.max_ratio.unwrap_or(DEFAULT_MAX_RATIO);
+            let ratio_limit = archive_size.saturating_mul(ratio).max(MINIMUM_LIMIT);
+            Some(match settings.max_extracted_size {
+                Some(absolute) => absolute.min(ratio_limit),
+                None => ratio_limit,
+            })
+        };
+
+        let entry_limit = if settings.disabled {
+            None
+        } else {
+            Some(settings.max_entries.unwrap_or(DEFAULT_MAX_ENTRIES))
+        };
+
+        Self {
+            limit,
+            entry_limit,
+            written: AtomicU64::new(0),
+        }
+    }
+
+    /// Up-front refusal based on what the archive's central directory
+    /// declares, before anything is extracted.
+    pub fn check_declared(&self, entries: u64, declared_size: u64) -> crate::Result<()> {
+        if let Some(entry_limit) = self.entry_limit {
+            if entries > entry_limit {
+                return Err(FinalError::with_title("Refusing to extract, possible decompression bomb")
+                    .detail(format!(
+                        "The archive declares {entries} entries, more than the limit of {entry_limit}"
+                    ))
+                    .hint("Raise the limit with --max-entries, or disable this protection")
+                    .hint("entirely with --no-bomb-check.")
+                    .into());
+            }
+        }
+
+        if let Some(limit) = self.limit {
+            if declared_size > limit {
+                return Err(FinalError::with_title("Refusing to extract, possible decompression bomb")
+                    .detail(format!(
+                        "The archive declares {} of output, more than the extraction limit of {}",
+                        Bytes::new(declared_size),
+                        Bytes::new(limit)
+                    ))
+                    .hint("Raise the limit with --max-ratio or --max-extracted-size,")
+                    .hint("or disable this protection entirely with --no-bomb-check.")
+                    .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Accounts for `bytes` about to be written, erroring when the total
+    /// crosses the limit.
+    pub fn add(&self, bytes: u64) -> crate::Result<()> {
+        let Some(limit) = self.limit else {
+            return Ok(());
+        };
+
+        let written = self.written.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        if written > limit {
+            return Err(FinalError::with_title("Extraction aborted, possible decompression bomb")
+                .detail(format!(
+                    "The output exceeded the extraction limit of {}",
+                    Bytes::new(limit)
+                ))
+                .hint("Raise the limit with --max-ratio or --max-extracted-size,")
+                .hint("or disable this protection entirely with --no-bomb-check.")
+                .into());
+        }
+
+        Ok(())
+    }
+
+    /// Copies a whole stream while accounting every chunk, for the
+    /// single-file decompression path.
+    pub fn copy_guarded(&self, reader: &mut impl Read, writer: &mut impl Write) -> crate::Result<u64> {
+        let mut buffer = [0; BUFFER_CAPACITY];
+        let mut total = 0;
+
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            self.add(read as u64)?;
+            writer.write_all(&buffer[..read])?;
+            total += read as u64;
+        }
+
+        Ok(total)
+    }
+}
@@ -0,0 +1,55 @@
+//! Checksum computation for archive outputs, see `--checksum`.
+
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use fs_err as fs;
+
+use crate::{cli::ChecksumAlgorithm, utils::logger::info_accessible, BUFFER_CAPACITY};
+
+/// Above this size blake3 switches to its multithreaded mmap mode, hashing
+/// with all cores instead of a streaming read.
+const BLAKE3_PARALLEL_THRESHOLD: u64 = 1024 * 1024;
+
+/// Hashes `path` and writes a sidecar file next to it in the usual
+/// `<digest>  <filename>` format understood by the *sum tools.
+pub fn write_checksum_sidecar(path: &Path, algorithm: ChecksumAlgorithm) -> crate::Result<PathBuf> {
+    let digest = hash_file(path, algorithm)?;
+
+    let file_name = path
+        .file_name()
+        .expect("output paths always have a file name")
+        .to_string_lossy();
+    let sidecar_path = PathBuf::from(format!("{}.{algorithm}", path.display()));
+    fs::write(&sidecar_path, format!("{digest}  {file_name}\n"))?;
+
+    info_accessible(format!("Wrote {algorithm} checksum to '{}'.", sidecar_path.display()));
+
+    Ok(sidecar_path)
+}
+
+/// Computes the hex digest of a file with the given algorithm.
+pub fn hash_file(path: &Path, algorithm: ChecksumAlgorithm) -> crate::Result<String> {
+    // blake3 keeps its multithreaded mmap fast path for large archives,
+    // everything else streams through the shared HashingReader
+    if algorithm == ChecksumAlgorithm::Blake3 {
+        let mut hasher = blake3::Hasher::new();
+        if fs::metadata(path)?.len() >= BLAKE3_PARALLEL_THRESHOLD {
+            hasher.update_mmap_rayon(path)?;
+        } else {
+            hasher.update_mmap(path)?;
+        }
+        return Ok(hasher.finalize().to_hex().to_string());
+    }
+
+    let mut reader = crate::utils::io::HashingReader::new(fs::File::open(path)?, algorithm);
+    let mut buffer = [0; BUFFER_CAPACITY];
+    loop {
+        if reader.read(&mut buffer)? == 0 {
+            break;
+        }
+    }
+    Ok(reader.digest())
+}
@@ -1,6 +1,7 @@
 use std::path::Path;
 
 /// Determines which files should be read or ignored during directory walking
+#[derive(Debug, Clone, Default)]
 pub struct FileVisibilityPolicy {
     /// Enables reading .ignore files.
     ///
@@ -19,22 +20,40 @@ pub struct FileVisibilityPolicy {
 
     /// Enables reading `.git/info/exclude` files.
     pub read_git_exclude: bool,
-}
 
-impl Default for FileVisibilityPolicy {
-    fn default() -> Self {
-        Self {
-            read_ignore: false,
-            read_hidden: true,
-            read_git_ignore: false,
-            read_git_exclude: false,
-        }
-    }
+    /// Maximum depth to recurse into directories, `None` for no limit.
+    ///
+    /// Set to `Some(1)` by `--no-recursive` to only take the direct
+    /// children of directory inputs.
+    pub max_depth: Option<usize>,
+
+    /// Globs from `--include`: when non-empty, only matching files are walked.
+    pub include: Vec<String>,
+
+    /// Globs from `--exclude`: matching files are skipped.
+    pub exclude: Vec<String>,
+
+    /// Match the include/exclude globs case-insensitively, see `--ignore-case`.
+    pub ignore_case: bool,
+
+    /// Walk each directory in sorted (deterministic) listing order,
+    /// see `--preserve-input-order`.
+    pub sort_entries: bool,
+
+    /// Skip the contents of directories tagged with a valid CACHEDIR.TAG,
+    /// keeping the tag file itself, see `--exclude-caches`.
+    pub exclude_caches: bool,
+
+    /// Skip tagged cache directories entirely, see `--exclude-caches-all`.
+    pub exclude_caches_all: bool,
 }
 
 impl FileVisibilityPolicy {
     pub fn new() -> Self {
-        Self::default()
+        Self {
+            read_hidden: true,
+            ..Self::default()
+        }
     }
 
     #[must_use]
@@ -67,13 +86,94 @@ impl FileVisibilityPolicy {
         Self { read_hidden, ..self }
     }
 
+    #[must_use]
+    /// Limits how deep the walk recurses into directories.
+    pub fn max_depth(self, max_depth: Option<usize>) -> Self {
+        Self { max_depth, ..self }
+    }
+
+    #[must_use]
+    /// Configures skipping of CACHEDIR.TAG-tagged cache directories.
+    pub fn exclude_caches(self, exclude_caches: bool, exclude_caches_all: bool) -> Self {
+        Self {
+            exclude_caches,
+            exclude_caches_all,
+            ..self
+        }
+    }
+
+    #[must_use]
+    /// Walks each directory in sorted listing order.
+    pub fn sort_entries(self, sort_entries: bool) -> Self {
+        Self { sort_entries, ..self }
+    }
+
+    #[must_use]
+    /// Sets the include/exclude globs and whether they match case-insensitively.
+    pub fn glob_filters(self, include: Vec<String>, exclude: Vec<String>, ignore_case: bool) -> Self {
+        Self {
+            include,
+            exclude,
+            ignore_case,
+            ..self
+        }
+    }
+
     /// Walks through a directory using [`ignore::Walk`]
-    pub fn build_walker(&self, path: impl AsRef<Path>) -> ignore::Walk {
-        ignore::WalkBuilder::new(path)
+    ///
+    /// See the cache directory tagging standard: <https://bford.info/cachedir/>
+    pub fn build_walker(&self, path: impl AsRef<Path>) -> crate::Result<ignore::Walk> {
+        let mut builder = ignore::WalkBuilder::new(&path);
+        builder
             .git_exclude(self.read_git_exclude)
             .git_ignore(self.read_git_ignore)
             .ignore(self.read_ignore)
             .hidden(self.read_hidden)
-            .build()
+            .max_depth(self.max_depth);
+
+        if self.sort_entries {
+            builder.sort_by_file_name(std::cmp::Ord::cmp);
+        }
+
+        if self.exclude_caches || self.exclude_caches_all {
+            let prune_whole_directory = self.exclude_caches_all;
+            builder.filter_entry(move |entry| {
+                let path = entry.path();
+                if entry.file_type().is_some_and(|file_type| file_type.is_dir()) {
+                    // Tagged directories are pruned entirely in the
+                    // --exclude-caches-all flavor
+                    !(prune_whole_directory && is_cachedir_tagged(path))
+                } else {
+                    // In the default flavor the tag file itself is kept as a
+                    // marker, everything next to it is skipped
+                    path.file_name().is_some_and(|name| name == "CACHEDIR.TAG")
+                        || !path.parent().is_some_and(is_cachedir_tagged)
+                }
+            });
+        }
+
+        if !self.include.is_empty() || !self.exclude.is_empty() {
+            let mut overrides = ignore::overrides::OverrideBuilder::new(&path);
+            overrides.case_insensitive(self.ignore_case)?;
+            for glob in &self.include {
+                overrides.add(glob)?;
+            }
+            for glob in &self.exclude {
+                overrides.add(&format!("!{glob}"))?;
+            }
+            builder.overrides(overrides.build()?);
+        }
+
+        Ok(builder.build())
     }
 }
+
+
+/// Whether a directory carries a valid `CACHEDIR.TAG` signature.
+fn is_cachedir_tagged(dir: &Path) -> bool {
+    const SIGNATURE: &[u8] = b"Signature: 8a477f597d28d172789f06886806bc55";
+
+    std::fs::read(dir.join("CACHEDIR.TAG"))
+        .map(|contents| contents.starts_with(SIGNATURE))
+        .unwrap_or(false)
+}
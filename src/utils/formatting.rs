@@ -1,6 +1,23 @@
 use std::{borrow::Cow, cmp, fmt::Display, path::Path};
 
-use crate::CURRENT_DIRECTORY;
+use once_cell::sync::OnceCell;
+
+use crate::{error::FinalError, CURRENT_DIRECTORY};
+
+static LOCAL_OFFSET: OnceCell<time::UtcOffset> = OnceCell::new();
+
+/// Captures the local UTC offset; must run before any thread spawns, since
+/// the lookup reads the environment and refuses once the process is
+/// multi-threaded.
+pub fn capture_local_offset() {
+    let _ = LOCAL_OFFSET.set(time::UtcOffset::current_local_offset().unwrap_or(time::UtcOffset::UTC));
+}
+
+/// The local UTC offset captured at startup, falling back to UTC when it
+/// could not be determined.
+pub fn local_offset() -> time::UtcOffset {
+    LOCAL_OFFSET.get().copied().unwrap_or(time::UtcOffset::UTC)
+}
 
 /// Converts invalid UTF-8 bytes to the Unicode replacement codepoint (�) in its Display implementation.
 pub struct EscapedPathDisplay<'a> {
@@ -45,7 +62,7 @@ impl Display for EscapedPathDisplay<'_> {
 /// This is different from [`Path::display`].
 ///
 /// See <https://gist.github.com/marcospb19/ebce5572be26397cf08bbd0fd3b65ac1> for a comparison.
-pub fn to_utf(os_str: &Path) -> Cow<str> {
+pub fn to_utf(os_str: &Path) -> Cow<'_, str> {
     let format = || {
         let text = format!("{os_str:?}");
         Cow::Owned(text.trim_matches('"').to_string())
@@ -79,7 +96,7 @@ pub fn pretty_format_list_of_paths(os_strs: &[impl AsRef<Path>]) -> String {
 }
 
 /// Display the directory name, but use "current directory" when necessary.
-pub fn nice_directory_display(path: &Path) -> Cow<str> {
+pub fn nice_directory_display(path: &Path) -> Cow<'_, str> {
     if path == Path::new(".") {
         Cow::Borrowed("current directory")
     } else {
@@ -120,10 +137,141 @@ impl std::fmt::Display for Bytes {
     }
 }
 
+/// Parses a size in bytes, accepting the suffixes kB, MB, GB and TB (powers
+/// of 1000) as well as KiB, MiB, GiB and TiB (powers of 1024), e.g. "4096",
+/// "1.5MB" or "2GiB".
+pub fn parse_bytes(text: &str) -> crate::Result<u64> {
+    let invalid_input = || {
+        FinalError::with_title(format!("Invalid size value: '{text}'"))
+            .detail("Sizes are given in bytes, with an optional unit suffix")
+            .hint("Examples: 4096, 500kB, 1.5MB, 2GiB")
+    };
+
+    let text = text.trim();
+    let unit_start = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(unit_start);
+
+    let number: f64 = number.parse().map_err(|_| invalid_input())?;
+    let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1000,
+        "m" | "mb" => 1000_u64.pow(2),
+        "g" | "gb" => 1000_u64.pow(3),
+        "t" | "tb" => 1000_u64.pow(4),
+        "ki" | "kib" => 1024,
+        "mi" | "mib" => 1024_u64.pow(2),
+        "gi" | "gib" => 1024_u64.pow(3),
+        "ti" | "tib" => 1024_u64.pow(4),
+        _ => return Err(invalid_input().into()),
+    };
+
+    Ok((number * multiplier as f64) as u64)
+}
+
+/// Inclusive size range accepted for compression inputs, set by the
+/// `--min-size` and `--max-size` flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SizeFilter {
+    pub min: Option<u64>,
+    pub max: Option<u64>,
+}
+
+impl SizeFilter {
+    /// Whether any size bound was requested.
+    pub fn is_active(&self) -> bool {
+        self.min.is_some() || self.max.is_some()
+    }
+
+    /// Whether a file of `size` bytes passes the filter.
+    pub fn allows(&self, size: u64) -> bool {
+        self.min.is_none_or(|min| size >= min) && self.max.is_none_or(|max| size <= max)
+    }
+}
+
+/// Modification-time window accepted for compression inputs, set by the
+/// `--newer-than` and `--older-than` flags.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TimeFilter {
+    pub newer_than: Option<std::time::SystemTime>,
+    pub older_than: Option<std::time::SystemTime>,
+}
+
+impl TimeFilter {
+    /// Whether any time bound was requested.
+    pub fn is_active(&self) -> bool {
+        self.newer_than.is_some() || self.older_than.is_some()
+    }
+
+    /// Whether a file modified at `mtime` passes the filter.
+    pub fn allows(&self, mtime: std::time::SystemTime) -> bool {
+        self.newer_than.is_none_or(|bound| mtime >= bound) && self.older_than.is_none_or(|bound| mtime <= bound)
+    }
+}
+
+/// Width of the terminal in columns, when stdout is one.
+pub fn terminal_width() -> Option<usize> {
+    #[cfg(unix)]
+    {
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        if unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+            return Some(size.ws_col as usize);
+        }
+    }
+
+    std::env::var("COLUMNS").ok()?.parse().ok()
+}
+
+/// Elides the middle of an overlong path-ish string to fit `max` columns,
+/// keeping the start and the (usually more interesting) file name end:
+/// `foo/very/deep/path.txt` becomes `foo/.../path.txt`.
+pub fn elide_middle(text: &str, max: usize) -> String {
+    const ELLIPSIS: &str = "/.../";
+
+    if text.chars().count() <= max || max < ELLIPSIS.len() + 2 {
+        return text.to_string();
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let keep = max - ELLIPSIS.len();
+    // The file name end is usually the interesting part, keep more of it
+    let head = keep / 3;
+    let tail = keep - head;
+
+    let mut elided: String = chars[..head].iter().collect();
+    elided.push_str(ELLIPSIS);
+    elided.extend(&chars[chars.len() - tail..]);
+    elided
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_elide_middle() {
+        assert_eq!(elide_middle("short.txt", 20), "short.txt");
+        let elided = elide_middle("foo/very/deep/nested/path/file.txt", 20);
+        assert!(elided.chars().count() <= 20);
+        assert!(elided.starts_with("foo/"));
+        assert!(elided.ends_with("file.txt"));
+        assert!(elided.contains("/.../"));
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("0").unwrap(), 0);
+        assert_eq!(parse_bytes("4096").unwrap(), 4096);
+        assert_eq!(parse_bytes("500kB").unwrap(), 500_000);
+        assert_eq!(parse_bytes("1.5MB").unwrap(), 1_500_000);
+        assert_eq!(parse_bytes("2GiB").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert_eq!(parse_bytes(" 10 KiB ").unwrap(), 10240);
+        assert!(parse_bytes("banana").is_err());
+        assert!(parse_bytes("10XB").is_err());
+        assert!(parse_bytes("").is_err());
+    }
+
     #[test]
     fn test_pretty_bytes_formatting() {
         fn format_bytes(bytes: u64) -> String {
@@ -4,30 +4,177 @@ use std::{
     env,
     io::Read,
     path::{Path, PathBuf},
+    time::SystemTime,
 };
 
 use fs_err as fs;
 
-use super::user_wants_to_overwrite;
+use super::{ask_overwrite_action, OverwriteAction};
 use crate::{
+    error::FinalError,
     extension::Extension,
-    utils::{logger::info_accessible, EscapedPathDisplay},
+    utils::{
+        logger::{info_accessible, warning},
+        ConflictPolicy, DuplicatePolicy, EscapedPathDisplay,
+    },
     QuestionPolicy,
 };
 
-/// Remove `path` asking the user to overwrite if necessary.
+/// How a conflicting output path got resolved by `resolve_path_conflict`.
+pub enum ConflictResolution {
+    /// Write to this path (possibly renamed), any old file was removed
+    Write(PathBuf),
+    /// Keep the existing file and skip this output
+    Skip,
+    /// The user refused the overwrite prompt, abort the operation
+    Abort,
+}
+
+/// Resolve a conflict at the output `path` following the `--on-conflict`
+/// policy, falling back to the interactive overwrite prompt.
 ///
-/// * `Ok(true)` means the path is clear,
-/// * `Ok(false)` means the user doesn't want to overwrite
-/// * `Err(_)` is an error
-pub fn clear_path(path: &Path, question_policy: QuestionPolicy) -> crate::Result<bool> {
-    if path.exists() && !user_wants_to_overwrite(path, question_policy)? {
-        return Ok(false);
+/// `source_mtime` is the modification time of the incoming file, used by the
+/// `newer` policy (which keeps the existing file when in doubt).
+pub fn resolve_path_conflict(
+    path: &Path,
+    source_mtime: Option<SystemTime>,
+    conflict_policy: Option<ConflictPolicy>,
+    question_policy: QuestionPolicy,
+    use_trash: bool,
+) -> crate::Result<ConflictResolution> {
+    reject_symlink_output(path)?;
+
+    if !path.exists() {
+        return Ok(ConflictResolution::Write(path.to_path_buf()));
+    }
+
+    match conflict_policy {
+        None => match ask_overwrite_action(path, question_policy)? {
+            OverwriteAction::Overwrite => {
+                remove_or_trash(path, use_trash)?;
+                Ok(ConflictResolution::Write(path.to_path_buf()))
+            }
+            OverwriteAction::Cancel => Ok(ConflictResolution::Abort),
+            OverwriteAction::Rename(new_name) => Ok(ConflictResolution::Write(path.with_file_name(new_name))),
+        },
+        Some(ConflictPolicy::Overwrite) => {
+            remove_or_trash(path, use_trash)?;
+            Ok(ConflictResolution::Write(path.to_path_buf()))
+        }
+        Some(ConflictPolicy::Skip) => Ok(ConflictResolution::Skip),
+        Some(ConflictPolicy::Rename) => Ok(ConflictResolution::Write(rename_for_available_filename(path))),
+        Some(ConflictPolicy::Newer) => {
+            let existing_mtime = fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+            match (source_mtime, existing_mtime) {
+                (Some(source), Some(existing)) if source > existing => {
+                    remove_or_trash(path, use_trash)?;
+                    Ok(ConflictResolution::Write(path.to_path_buf()))
+                }
+                _ => Ok(ConflictResolution::Skip),
+            }
+        }
+    }
+}
+
+/// First available path of the form `file (1).txt` next to `path`.
+fn rename_for_available_filename(path: &Path) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension();
+
+    for n in 1.. {
+        let mut file_name = format!("{stem} ({n})");
+        if let Some(extension) = extension {
+            file_name.push('.');
+            file_name.push_str(&extension.to_string_lossy());
+        }
+
+        let candidate = path.with_file_name(file_name);
+        if !candidate.exists() && !is_symlink(&candidate) {
+            return candidate;
+        }
+    }
+
+    unreachable!("some rename suffix is always available")
+}
+
+/// Check that an output path is not an existing symlink, erroring to avoid
+/// following it and overwriting an unexpected target (e.g. planted in a
+/// shared directory).
+pub fn reject_symlink_output(path: &Path) -> crate::Result<()> {
+    if is_symlink(path) {
+        return Err(FinalError::with_title(format!(
+            "The output path {} is a symlink",
+            EscapedPathDisplay::new(path)
+        ))
+        .detail("Refusing to write through an existing symlink")
+        .hint("Remove the symlink first, or pick another output path")
+        .into());
+    }
+
+    Ok(())
+}
+
+/// Pre-flight probe that a target directory is writable, by creating (and
+/// dropping) an anonymous temporary file in it. Fails fast with a clear
+/// error before an expensive walk or extraction only dies on its first
+/// write at the very end.
+pub fn check_directory_writable(directory: &Path) -> crate::Result<()> {
+    let probed = if directory.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        directory
+    };
+
+    if tempfile::tempfile_in(probed).is_err() {
+        return Err(crate::Error::OutputNotWritable {
+            path: probed.to_string_lossy().into_owned(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Free disk space available to the process on the filesystem holding
+/// `path`, `None` where the platform (or a debug-build test override via
+/// `OUCH_FAKE_FREE_SPACE`) cannot say.
+pub fn available_disk_space(path: &Path) -> Option<u64> {
+    #[cfg(debug_assertions)]
+    if let Some(faked) = std::env::var("OUCH_FAKE_FREE_SPACE").ok().and_then(|value| value.parse().ok()) {
+        return Some(faked);
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+        let mut stats: libc::statvfs = unsafe { std::mem::zeroed() };
+        // SAFETY: the path is a valid NUL-terminated string and stats is a
+        // properly sized out-parameter
+        if unsafe { libc::statvfs(path.as_ptr(), &mut stats) } != 0 {
+            return None;
+        }
+        Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        None
     }
+}
+
+/// Flushes a finished output file and its directory entry to disk, for
+/// `--fsync`: syncing only the file leaves the new name itself unpersisted
+/// until the filesystem gets around to writing the parent directory.
+pub fn sync_file_and_parent(path: &Path) -> crate::Result<()> {
+    fs::File::open(path)?.sync_all()?;
 
-    remove_file_or_dir(path)?;
+    #[cfg(unix)]
+    if let Some(parent) = path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        fs::File::open(parent)?.sync_all()?;
+    }
 
-    Ok(true)
+    Ok(())
 }
 
 pub fn remove_file_or_dir(path: &Path) -> crate::Result<()> {
@@ -39,6 +186,32 @@ pub fn remove_file_or_dir(path: &Path) -> crate::Result<()> {
     Ok(())
 }
 
+/// Clears an output path that is about to be overwritten: with `--trash` the
+/// old file goes to the system trash so mistakes stay recoverable, falling
+/// back to permanent deletion (with a warning) where no trash exists.
+pub fn remove_or_trash(path: &Path, use_trash: bool) -> crate::Result<()> {
+    if !use_trash || (!path.is_dir() && !path.is_file()) {
+        return remove_file_or_dir(path);
+    }
+
+    match trash::delete(path) {
+        Ok(()) => {
+            info_accessible(format!(
+                "Moved {} to the trash.",
+                EscapedPathDisplay::new(path)
+            ));
+            Ok(())
+        }
+        Err(err) => {
+            warning(format!(
+                "Could not move {} to the trash ({err}), deleting it permanently",
+                EscapedPathDisplay::new(path)
+            ));
+            remove_file_or_dir(path)
+        }
+    }
+}
+
 /// Creates a directory at the path, if there is nothing there.
 pub fn create_dir_if_non_existent(path: &Path) -> crate::Result<()> {
     if !path.exists() {
@@ -61,9 +234,73 @@ pub fn cd_into_same_dir_as(filename: &Path) -> crate::Result<PathBuf> {
     Ok(previous_location)
 }
 
+/// Changes the process' directory for archiving `filename`: into `base_dir`
+/// when one was given with `--base` (entries get stored relative to it),
+/// otherwise into the directory containing `filename`.
+///
+/// Returns the previous location and the clean relative path entries are
+/// stored under.
+pub fn cd_for_archiving(filename: &Path, base_dir: Option<&Path>) -> crate::Result<(PathBuf, PathBuf)> {
+    let Some(base_dir) = base_dir else {
+        let previous_location = cd_into_same_dir_as(filename)?;
+        // Unwrap safety:
+        //   paths should be canonicalized by now, and the root directory rejected.
+        return Ok((previous_location, filename.file_name().unwrap().into()));
+    };
+
+    let relative_path = filename.strip_prefix(base_dir).ok().filter(|path| !path.as_os_str().is_empty());
+    let Some(relative_path) = relative_path else {
+        return Err(FinalError::with_title("Cannot compress with --base")
+            .detail(format!(
+                "The input {} is not inside of the base directory {}",
+                EscapedPathDisplay::new(filename),
+                EscapedPathDisplay::new(base_dir),
+            ))
+            .into());
+    };
+
+    let previous_location = env::current_dir()?;
+    env::set_current_dir(base_dir)?;
+
+    Ok((previous_location, relative_path.to_path_buf()))
+}
+
 /// Try to detect the file extension by looking for known magic strings
 /// Source: <https://en.wikipedia.org/wiki/List_of_file_signatures>
 pub fn try_infer_extension(path: &Path) -> Option<Extension> {
+    let buf = {
+        let mut buf = [0; 270];
+
+        // Error cause will be ignored, so use std::fs instead of fs_err
+        let result = std::fs::File::open(path).map(|mut file| file.read(&mut buf));
+
+        // In case of file open or read failure, could not infer a extension
+        if result.is_err() {
+            return None;
+        }
+        buf
+    };
+
+    detect_extension_from_magic(&buf)
+}
+
+/// Detects the compression format of a stream by its magic bytes, reading
+/// (and consuming) up to the first 270 bytes.
+pub fn detect_format_from_magic(reader: &mut impl Read) -> Option<crate::extension::CompressionFormat> {
+    let mut buf = [0; 270];
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(_) => return None,
+        }
+    }
+
+    detect_extension_from_magic(&buf).map(|extension| extension.compression_formats[0])
+}
+
+fn detect_extension_from_magic(buf: &[u8]) -> Option<Extension> {
     fn is_zip(buf: &[u8]) -> bool {
         buf.len() >= 3
             && buf[..=1] == [0x50, 0x4B]
@@ -98,46 +335,304 @@ pub fn try_infer_extension(path: &Path) -> Option<Extension> {
     fn is_sevenz(buf: &[u8]) -> bool {
         buf.starts_with(&[0x37, 0x7A, 0xBC, 0xAF, 0x27, 0x1C])
     }
-
-    let buf = {
-        let mut buf = [0; 270];
-
-        // Error cause will be ignored, so use std::fs instead of fs_err
-        let result = std::fs::File::open(path).map(|mut file| file.read(&mut buf));
-
-        // In case of file open or read failure, could not infer a extension
-        if result.is_err() {
-            return None;
-        }
-        buf
-    };
+    fn is_unlzw(buf: &[u8]) -> bool {
+        buf.starts_with(&crate::unlzw::MAGIC)
+    }
 
     use crate::extension::CompressionFormat::*;
-    if is_zip(&buf) {
+    if is_zip(buf) {
         Some(Extension::new(&[Zip], "zip"))
-    } else if is_tar(&buf) {
+    } else if is_tar(buf) {
         Some(Extension::new(&[Tar], "tar"))
-    } else if is_gz(&buf) {
+    } else if is_gz(buf) {
         Some(Extension::new(&[Gzip], "gz"))
-    } else if is_bz2(&buf) {
+    } else if is_bz2(buf) {
         Some(Extension::new(&[Bzip], "bz2"))
-    } else if is_xz(&buf) {
+    } else if is_xz(buf) {
         Some(Extension::new(&[Lzma], "xz"))
-    } else if is_lz4(&buf) {
+    } else if is_lz4(buf) {
         Some(Extension::new(&[Lz4], "lz4"))
-    } else if is_sz(&buf) {
+    } else if is_sz(buf) {
         Some(Extension::new(&[Snappy], "sz"))
-    } else if is_zst(&buf) {
+    } else if is_zst(buf) {
         Some(Extension::new(&[Zstd], "zst"))
-    } else if is_rar(&buf) {
+    } else if is_rar(buf) {
         Some(Extension::new(&[Rar], "rar"))
-    } else if is_sevenz(&buf) {
+    } else if is_sevenz(buf) {
         Some(Extension::new(&[SevenZip], "7z"))
+    } else if is_unlzw(buf) {
+        Some(Extension::new(&[Lzw], "Z"))
     } else {
         None
     }
 }
 
+/// Remaps stored uids/gids to local ones during extraction, built from
+/// `--owner-map` (and `--owner-map-default` for unmapped ids).
+#[derive(Debug, Clone)]
+pub struct OwnerMap {
+    map: std::collections::HashMap<u64, u64>,
+    fallback: Option<u64>,
+}
+
+impl OwnerMap {
+    /// Parses mappings of the form `1000:1001,0:0`.
+    pub fn parse(text: &str, fallback: Option<u64>) -> crate::Result<Self> {
+        let invalid_input = || {
+            FinalError::with_title(format!("Invalid --owner-map value: '{text}'"))
+                .detail("Mappings are comma-separated old:new id pairs")
+                .hint("Example: --owner-map 1000:1001,0:0")
+        };
+
+        let mut map = std::collections::HashMap::new();
+        for pair in text.split(',') {
+            let (old, new) = pair.split_once(':').ok_or_else(invalid_input)?;
+            let old: u64 = old.trim().parse().map_err(|_| invalid_input())?;
+            let new: u64 = new.trim().parse().map_err(|_| invalid_input())?;
+            map.insert(old, new);
+        }
+
+        Ok(Self { map, fallback })
+    }
+
+    /// The local id to use for a stored id.
+    pub fn resolve(&self, id: u64) -> u64 {
+        self.map.get(&id).copied().or(self.fallback).unwrap_or(id)
+    }
+}
+
+/// Entry paths already written during one extraction, tracking exact
+/// duplicates everywhere and case-folding collisions on targets where
+/// `File.txt` and `file.txt` are the same file.
+pub struct WrittenPaths {
+    exact: std::collections::HashSet<PathBuf>,
+    /// Maps lowercased paths to the original name, present when the target
+    /// filesystem is case-insensitive
+    folded: Option<std::collections::HashMap<String, PathBuf>>,
+}
+
+impl WrittenPaths {
+    /// Probes whether `output_dir` is case-insensitive (macOS, Windows and
+    /// specially mounted directories elsewhere).
+    pub fn for_output_dir(output_dir: &Path) -> Self {
+        let probe = output_dir.join(".ouch-case-probe");
+        let case_insensitive = std::fs::File::create(&probe)
+            .map(|_| output_dir.join(".OUCH-CASE-PROBE").exists())
+            .unwrap_or(cfg!(any(windows, target_os = "macos")));
+        let _ = std::fs::remove_file(&probe);
+
+        Self {
+            exact: std::collections::HashSet::new(),
+            folded: case_insensitive.then(std::collections::HashMap::new),
+        }
+    }
+
+    /// The outcome of inserting `path`: fine, an exact repeat, or a
+    /// case-insensitive collision with an earlier entry.
+    fn insert(&mut self, path: &Path) -> Insertion {
+        if !self.exact.insert(path.to_path_buf()) {
+            return Insertion::Duplicate;
+        }
+
+        if let Some(folded) = self.folded.as_mut() {
+            let key = path.to_string_lossy().to_lowercase();
+            if let Some(original) = folded.get(&key) {
+                return Insertion::CaseCollision(original.clone());
+            }
+            folded.insert(key, path.to_path_buf());
+        }
+
+        Insertion::New
+    }
+}
+
+enum Insertion {
+    New,
+    Duplicate,
+    CaseCollision(PathBuf),
+}
+
+/// Tracks entry paths already written during one extraction and applies the
+/// `--on-duplicate` policy when a path repeats, exactly or (on
+/// case-insensitive targets) up to case folding.
+///
+/// Returns `Break` when the duplicate entry should be skipped; the default
+/// lets the duplicate overwrite with a warning.
+pub fn handle_duplicate_entry(
+    path: &Path,
+    written_paths: &mut WrittenPaths,
+    on_duplicate: Option<DuplicatePolicy>,
+) -> crate::Result<std::ops::ControlFlow<()>> {
+    use std::ops::ControlFlow;
+
+    let description = match written_paths.insert(path) {
+        Insertion::New => return Ok(ControlFlow::Continue(())),
+        Insertion::Duplicate => format!("The entry '{}' appears more than once", EscapedPathDisplay::new(path)),
+        Insertion::CaseCollision(original) => format!(
+            "The entry '{}' collides with '{}' on this case-insensitive filesystem",
+            EscapedPathDisplay::new(path),
+            EscapedPathDisplay::new(&original)
+        ),
+    };
+
+    match on_duplicate {
+        None => {
+            warning(format!("{description}, overwriting the earlier one"));
+            Ok(ControlFlow::Continue(()))
+        }
+        Some(DuplicatePolicy::Overwrite) => Ok(ControlFlow::Continue(())),
+        Some(DuplicatePolicy::Skip) => Ok(ControlFlow::Break(())),
+        Some(DuplicatePolicy::Error) => Err(FinalError::with_title("Colliding entries in archive")
+            .detail(description)
+            .hint("Use --on-duplicate overwrite or --on-duplicate skip to extract anyway.")
+            .into()),
+    }
+}
+
+/// Resolve the directory used for intermediate temporary files: the
+/// `--temp-dir` value when given (validated writable up front), otherwise
+/// the system default, which honors TMPDIR on unix.
+pub fn resolve_temp_dir(temp_dir: Option<&Path>) -> crate::Result<PathBuf> {
+    let Some(temp_dir) = temp_dir else {
+        return Ok(env::temp_dir());
+    };
+
+    tempfile::tempfile_in(temp_dir).map_err(|err| {
+        FinalError::with_title("The --temp-dir directory is not usable")
+            .detail(format!(
+                "Could not create a temporary file in {}",
+                EscapedPathDisplay::new(temp_dir)
+            ))
+            .detail(format!("Error: {err}."))
+    })?;
+
+    Ok(temp_dir.to_path_buf())
+}
+
+/// Directories that (transitively) contain at least one file passing the
+/// size and time filters, computed by a pre-walk for `--prune-empty` so
+/// directory entries emptied by the filters can be skipped.
+pub fn dirs_with_passing_files(
+    root: &Path,
+    file_visibility_policy: &crate::utils::FileVisibilityPolicy,
+    size_filter: crate::utils::SizeFilter,
+    time_filter: crate::utils::TimeFilter,
+) -> crate::Result<std::collections::HashSet<PathBuf>> {
+    let mut kept = std::collections::HashSet::new();
+
+    for entry in file_visibility_policy.build_walker(root)? {
+        let entry = entry?;
+        let path = entry.path();
+        let Ok(metadata) = std::fs::metadata(path) else { continue };
+        if !metadata.is_file() {
+            continue;
+        }
+        if size_filter.is_active() && !size_filter.allows(metadata.len()) {
+            continue;
+        }
+        if time_filter.is_active() {
+            let Ok(mtime) = metadata.modified() else { continue };
+            if !time_filter.allows(mtime) {
+                continue;
+            }
+        }
+
+        let mut ancestor = path.parent();
+        while let Some(dir) = ancestor {
+            if dir.as_os_str().is_empty() || !kept.insert(dir.to_path_buf()) {
+                break;
+            }
+            ancestor = dir.parent();
+        }
+    }
+
+    Ok(kept)
+}
+
+/// The canonical mode stored by `--normalize-permissions`: 0755 for
+/// directories and executables (any exec bit on unix, the is_executable
+/// extension heuristics elsewhere), 0644 for everything else.
+pub fn normalized_mode(path: &Path, metadata: &std::fs::Metadata) -> u32 {
+    if metadata.is_dir() {
+        return 0o755;
+    }
+
+    #[cfg(unix)]
+    let executable = {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.permissions().mode() & 0o111 != 0
+    };
+    #[cfg(not(unix))]
+    let executable = is_executable::is_executable(path);
+    #[cfg(unix)]
+    let _ = path;
+
+    if executable {
+        0o755
+    } else {
+        0o644
+    }
+}
+
+/// Rewrites an absolute symlink target that falls inside the archived root
+/// into a relative one, so the link survives extraction elsewhere; targets
+/// outside the tree are left alone (`None`). See `--relativize-symlinks`.
+///
+/// `link_path` is the entry path as stored in the archive (relative to the
+/// walk), `walk_root` the first component of those paths, and `root_abs`
+/// the absolute directory that component corresponds to.
+pub fn relativize_symlink_target(
+    target: &Path,
+    link_path: &Path,
+    walk_root: &Path,
+    root_abs: &Path,
+) -> Option<PathBuf> {
+    let suffix = target.strip_prefix(root_abs).ok()?;
+
+    let link_parent = link_path.parent().unwrap_or(Path::new(""));
+    let depth = link_parent
+        .strip_prefix(walk_root)
+        .unwrap_or(link_parent)
+        .components()
+        .count();
+
+    let mut relative = PathBuf::new();
+    for _ in 0..depth {
+        relative.push("..");
+    }
+    relative.push(suffix);
+
+    Some(relative)
+}
+
+/// On Windows, turns the path absolute and applies the `\\?\` extended
+/// length prefix so entries deeper than MAX_PATH can be created during
+/// extraction. Returns the path unchanged on other platforms.
+#[cfg(windows)]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    use std::ffi::OsString;
+
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        crate::CURRENT_DIRECTORY.join(path)
+    };
+
+    if absolute.as_os_str().as_encoded_bytes().starts_with(br"\\?\") {
+        return absolute;
+    }
+
+    let mut prefixed = OsString::from(r"\\?\");
+    prefixed.push(absolute.as_os_str());
+    PathBuf::from(prefixed)
+}
+
+/// Extraction paths need no prefixing outside of Windows.
+#[cfg(not(windows))]
+pub fn extended_length_path(path: &Path) -> PathBuf {
+    path.to_path_buf()
+}
+
 /// Returns true if a path is a symlink.
 /// This is the same as the nightly <https://doc.rust-lang.org/std/path/struct.Path.html#method.is_symlink>
 /// Useful to detect broken symlinks when compressing. (So we can safely ignore them)
@@ -146,3 +641,32 @@ pub fn is_symlink(path: &Path) -> bool {
         .map(|m| m.file_type().is_symlink())
         .unwrap_or(false)
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn written_paths_detect_case_collisions() {
+        let mut written = WrittenPaths {
+            exact: std::collections::HashSet::new(),
+            folded: Some(std::collections::HashMap::new()),
+        };
+
+        assert!(matches!(written.insert(Path::new("File.txt")), Insertion::New));
+        assert!(matches!(written.insert(Path::new("File.txt")), Insertion::Duplicate));
+        assert!(matches!(
+            written.insert(Path::new("file.TXT")),
+            Insertion::CaseCollision(original) if original == Path::new("File.txt")
+        ));
+
+        // Case-sensitive targets keep both
+        let mut written = WrittenPaths {
+            exact: std::collections::HashSet::new(),
+            folded: None,
+        };
+        assert!(matches!(written.insert(Path::new("File.txt")), Insertion::New));
+        assert!(matches!(written.insert(Path::new("file.txt")), Insertion::New));
+    }
+}
@@ -1,4 +1,10 @@
-use std::io::{self, stderr, stdout, StderrLock, StdoutLock, Write};
+use std::{
+    hash::Hasher,
+    io::{self, stderr, stdout, Read, StderrLock, StdoutLock, Write},
+    sync::mpsc,
+};
+
+use crate::BUFFER_CAPACITY;
 
 type StdioOutputLocks = (StdoutLock<'static>, StderrLock<'static>);
 
@@ -10,3 +16,593 @@ pub fn lock_and_flush_output_stdio() -> io::Result<StdioOutputLocks> {
 
     Ok((stdout, stderr))
 }
+
+/// Hashes a reader's whole content, used for content comparisons (not a
+/// cryptographic hash).
+pub fn hash_reader(reader: &mut impl Read) -> io::Result<u64> {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    let mut buffer = [0; BUFFER_CAPACITY];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.write(&buffer[..read]);
+    }
+
+    Ok(hasher.finish())
+}
+
+/// Hashes a reader's whole content with blake3, for places that declare
+/// byte-identity from the digest alone (like the `--dedup` hard links): a
+/// 64-bit unkeyed hash could be collided, silently swapping file contents.
+pub fn hash_reader_blake3(reader: &mut impl Read) -> io::Result<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = [0; BUFFER_CAPACITY];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(*hasher.finalize().as_bytes())
+}
+
+
+/// Entropy above this many bits per byte marks data as effectively
+/// incompressible, see `--auto-level`.
+pub const INCOMPRESSIBLE_ENTROPY: f64 = 7.5;
+/// Entropy below this marks data as highly compressible.
+pub const COMPRESSIBLE_ENTROPY: f64 = 5.0;
+
+/// Estimates a buffer's Shannon entropy in bits per byte: close to 8 means
+/// effectively incompressible data, low values compress well.
+pub fn shannon_entropy(buf: &[u8]) -> f64 {
+    if buf.is_empty() {
+        return 0.0;
+    }
+
+    let mut histogram = [0u64; 256];
+    for byte in buf {
+        histogram[*byte as usize] += 1;
+    }
+
+    let len = buf.len() as f64;
+    histogram
+        .iter()
+        .filter(|count| **count > 0)
+        .map(|count| {
+            let p = *count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+
+/// Reads ahead on a background thread, handing chunks over a bounded
+/// channel so input latency (e.g. network mounts) overlaps with
+/// compression. See `--io-threads`.
+pub struct PrefetchReader {
+    receiver: mpsc::Receiver<io::Result<Vec<u8>>>,
+    current: Vec<u8>,
+    position: usize,
+    done: bool,
+}
+
+impl PrefetchReader {
+    /// Spawns the reader thread, keeping up to `depth` chunks in flight.
+    pub fn new(mut reader: impl Read + Send + 'static, depth: usize) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(depth.max(1));
+
+        std::thread::spawn(move || {
+            loop {
+                let mut chunk = vec![0; BUFFER_CAPACITY];
+                match reader.read(&mut chunk) {
+                    Ok(0) => break,
+                    Ok(read) => {
+                        chunk.truncate(read);
+                        if sender.send(Ok(chunk)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        let _ = sender.send(Err(err));
+                        break;
+                    }
+                }
+            }
+        });
+
+        Self {
+            receiver,
+            current: vec![],
+            position: 0,
+            done: false,
+        }
+    }
+}
+
+impl Read for PrefetchReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.position >= self.current.len() {
+            if self.done {
+                return Ok(0);
+            }
+            match self.receiver.recv() {
+                Ok(Ok(chunk)) => {
+                    self.current = chunk;
+                    self.position = 0;
+                }
+                // An error from the reader thread surfaces here
+                Ok(Err(err)) => return Err(err),
+                Err(_) => {
+                    self.done = true;
+                    return Ok(0);
+                }
+            }
+        }
+
+        let available = &self.current[self.position..];
+        let amount = available.len().min(buf.len());
+        buf[..amount].copy_from_slice(&available[..amount]);
+        self.position += amount;
+        Ok(amount)
+    }
+}
+
+
+/// Transient error kinds worth retrying on flaky (network) filesystems.
+fn is_transient(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::Interrupted | io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Runs one I/O operation with up to `attempts` extra tries on transient
+/// errors, sleeping with exponential backoff in between. Non-retryable
+/// errors propagate immediately.
+fn with_retries<T>(attempts: u32, mut operation: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut tried = 0;
+    loop {
+        match operation() {
+            Err(err) if is_transient(err.kind()) && tried < attempts => {
+                tried += 1;
+                std::thread::sleep(std::time::Duration::from_millis(10u64 << tried.min(10)));
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reader retrying transient failures with backoff, see `--retry`.
+pub struct RetryingReader<R> {
+    inner: R,
+    attempts: u32,
+}
+
+impl<R: Read> RetryingReader<R> {
+    pub fn new(inner: R, attempts: u32) -> Self {
+        Self { inner, attempts }
+    }
+}
+
+impl<R: Read> Read for RetryingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        with_retries(self.attempts, || inner.read(buf))
+    }
+}
+
+/// Writer retrying transient failures with backoff, see `--retry`.
+pub struct RetryingWriter<W> {
+    inner: W,
+    attempts: u32,
+}
+
+impl<W: Write> RetryingWriter<W> {
+    pub fn new(inner: W, attempts: u32) -> Self {
+        Self { inner, attempts }
+    }
+}
+
+impl<W: Write> Write for RetryingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        with_retries(self.attempts, || inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        let inner = &mut self.inner;
+        with_retries(self.attempts, || inner.flush())
+    }
+}
+
+/// Cooperative Ctrl-C handling for long buffered phases (e.g. writing out
+/// an in-memory 7z archive): the first interrupt raises a flag the phase
+/// checks, so it can stop cleanly and let the normal cleanup run.
+pub mod interrupt {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn mark_interrupted(_signal: libc::c_int) {
+        INTERRUPTED.store(true, Ordering::Relaxed);
+    }
+
+    /// Installs the flag-raising SIGINT handler (unix only, elsewhere the
+    /// default termination stays in place).
+    pub fn arm() {
+        #[cfg(unix)]
+        unsafe {
+            libc::signal(libc::SIGINT, mark_interrupted as *const () as libc::sighandler_t);
+        }
+    }
+
+    pub fn interrupted() -> bool {
+        INTERRUPTED.load(Ordering::Relaxed)
+    }
+}
+
+
+/// Streaming digest state shared by every hashing feature (sidecars,
+/// manifests, content-addressed naming, verification).
+pub enum DigestState {
+    Blake3(Box<blake3::Hasher>),
+    Sha256(sha2::Sha256),
+    Sha512(sha2::Sha512),
+    Crc32(crc32fast::Hasher),
+}
+
+impl DigestState {
+    pub fn new(algorithm: crate::cli::ChecksumAlgorithm) -> Self {
+        use crate::cli::ChecksumAlgorithm::*;
+        use sha2::Digest;
+
+        match algorithm {
+            Blake3 => Self::Blake3(Box::new(blake3::Hasher::new())),
+            Sha256 => Self::Sha256(sha2::Sha256::new()),
+            Sha512 => Self::Sha512(sha2::Sha512::new()),
+            Crc32 => Self::Crc32(crc32fast::Hasher::new()),
+        }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        use sha2::Digest;
+
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            Self::Sha256(hasher) => hasher.update(bytes),
+            Self::Sha512(hasher) => hasher.update(bytes),
+            Self::Crc32(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// The final digest, lowercase hex.
+    pub fn finalize(self) -> String {
+        use std::fmt::Write as _;
+
+        use sha2::Digest;
+
+        let bytes: Vec<u8> = match self {
+            Self::Blake3(hasher) => hasher.finalize().as_bytes().to_vec(),
+            Self::Sha256(hasher) => hasher.finalize().to_vec(),
+            Self::Sha512(hasher) => hasher.finalize().to_vec(),
+            Self::Crc32(hasher) => return format!("{:08x}", hasher.finalize()),
+        };
+
+        let mut hex = String::with_capacity(bytes.len() * 2);
+        for byte in bytes {
+            let _ = write!(hex, "{byte:02x}");
+        }
+        hex
+    }
+}
+
+/// Reader computing a digest of everything passing through, unchanged.
+pub struct HashingReader<R> {
+    inner: R,
+    state: DigestState,
+}
+
+impl<R: Read> HashingReader<R> {
+    pub fn new(inner: R, algorithm: crate::cli::ChecksumAlgorithm) -> Self {
+        Self {
+            inner,
+            state: DigestState::new(algorithm),
+        }
+    }
+
+    pub fn digest(self) -> String {
+        self.state.finalize()
+    }
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.state.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+/// Writer computing a digest of everything passing through, unchanged.
+pub struct HashingWriter<W> {
+    inner: W,
+    state: DigestState,
+}
+
+impl<W: Write> HashingWriter<W> {
+    pub fn new(inner: W, algorithm: crate::cli::ChecksumAlgorithm) -> Self {
+        Self {
+            inner,
+            state: DigestState::new(algorithm),
+        }
+    }
+
+    pub fn digest(self) -> (W, String) {
+        (self.inner, self.state.finalize())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.state.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer rolling over to a new numbered part file every `part_size`
+/// bytes, see `--split-size`: `archive.tar.gz` becomes `archive.tar.gz.001`,
+/// `.002`, … and `cat parts > whole` restores the original stream. Works
+/// without seeking, so any streamable format chain can be split.
+pub struct SplittingWriter {
+    base_path: std::path::PathBuf,
+    part_size: u64,
+    written_in_part: u64,
+    part_index: u32,
+    current: Option<io::BufWriter<fs_err::File>>,
+}
+
+impl SplittingWriter {
+    pub fn new(base_path: std::path::PathBuf, part_size: u64) -> Self {
+        Self {
+            base_path,
+            part_size: part_size.max(1),
+            written_in_part: 0,
+            part_index: 0,
+            current: None,
+        }
+    }
+
+    /// The path of the numbered part file, e.g. `archive.tar.gz.001`.
+    fn part_path(&self) -> std::path::PathBuf {
+        let mut path = self.base_path.clone().into_os_string();
+        path.push(format!(".{:03}", self.part_index));
+        path.into()
+    }
+
+    fn current_part(&mut self) -> io::Result<&mut io::BufWriter<fs_err::File>> {
+        if self.current.is_none() {
+            self.part_index += 1;
+            self.written_in_part = 0;
+            let file = fs_err::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.part_path())?;
+            self.current = Some(io::BufWriter::with_capacity(BUFFER_CAPACITY, file));
+        }
+        Ok(self.current.as_mut().expect("opened above"))
+    }
+
+    /// Flushes and closes the part being written, rolling over to the next
+    /// number on the following write.
+    fn finish_part(&mut self) -> io::Result<()> {
+        if let Some(mut part) = self.current.take() {
+            part.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for SplittingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        // Opening the part resets the room accounting, so it has to happen
+        // before the cap below; write_all loops the remainder into the
+        // next part
+        self.current_part()?;
+        let room = self.part_size - self.written_in_part;
+        let capped = (buf.len() as u64).min(room) as usize;
+
+        let written = self.current.as_mut().expect("opened above").write(&buf[..capped])?;
+        self.written_in_part += written as u64;
+        if self.written_in_part >= self.part_size {
+            self.finish_part()?;
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.current.as_mut() {
+            Some(part) => part.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for SplittingWriter {
+    fn drop(&mut self) {
+        let _ = self.finish_part();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::ChecksumAlgorithm;
+
+    #[test]
+    fn retrying_reader_survives_transient_errors() {
+        struct FlakyReader {
+            failures_left: u32,
+            data: &'static [u8],
+        }
+        impl Read for FlakyReader {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.failures_left > 0 {
+                    self.failures_left -= 1;
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "transient"));
+                }
+                self.data.read(buf)
+            }
+        }
+
+        let mut reader = RetryingReader::new(
+            FlakyReader {
+                failures_left: 2,
+                data: b"survived",
+            },
+            3,
+        );
+        let mut out = vec![];
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"survived");
+
+        // Too few attempts: the transient error propagates
+        let mut reader = RetryingReader::new(
+            FlakyReader {
+                failures_left: 2,
+                data: b"",
+            },
+            1,
+        );
+        assert!(reader.read_to_end(&mut vec![]).is_err());
+    }
+
+    #[test]
+    fn splitting_writer_rolls_over_exact_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("stream.bin");
+
+        let mut writer = SplittingWriter::new(base.clone(), 4);
+        writer.write_all(b"0123456789").unwrap();
+        drop(writer);
+
+        let read_part = |index: u32| std::fs::read(format!("{}.{index:03}", base.display()));
+        assert_eq!(read_part(1).unwrap(), b"0123");
+        assert_eq!(read_part(2).unwrap(), b"4567");
+        assert_eq!(read_part(3).unwrap(), b"89");
+        assert!(read_part(4).is_err());
+        // The base path itself is never written, only the parts
+        assert!(!base.exists());
+    }
+
+    #[test]
+    fn hashing_wrappers_digest_known_vectors() {
+        // Bytes pass through unchanged while hashing
+        let mut reader = HashingReader::new(&b"abc"[..], ChecksumAlgorithm::Sha256);
+        let mut passed = vec![];
+        reader.read_to_end(&mut passed).unwrap();
+        assert_eq!(passed, b"abc");
+        assert_eq!(
+            reader.digest(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+
+        let mut writer = HashingWriter::new(vec![], ChecksumAlgorithm::Crc32);
+        writer.write_all(b"123456789").unwrap();
+        let (inner, digest) = writer.digest();
+        assert_eq!(inner, b"123456789");
+        assert_eq!(digest, "cbf43926");
+
+        let mut writer = HashingWriter::new(vec![], ChecksumAlgorithm::Blake3);
+        writer.write_all(b"hello").unwrap();
+        let (_, digest) = writer.digest();
+        assert_eq!(digest, blake3::hash(b"hello").to_hex().to_string());
+    }
+}
+
+/// Process-wide cap on simultaneously-open data files, see
+/// `--max-open-files`. Parallel paths (--jobs, multi-archive decompression)
+/// can otherwise exhaust the fd limit on large trees.
+pub mod fd_quota {
+    use std::sync::{Condvar, Mutex};
+
+    use once_cell::sync::OnceCell;
+
+    struct Quota {
+        available: Mutex<usize>,
+        freed: Condvar,
+    }
+
+    static QUOTA: OnceCell<Quota> = OnceCell::new();
+
+    /// Progress must stay possible even under a silly limit: every holder
+    /// releases its permit before acquiring the next one.
+    const MINIMUM_LIMIT: usize = 2;
+
+    /// Arms the quota, with the default leaving half of the soft
+    /// RLIMIT_NOFILE (a conservative constant elsewhere) for everything
+    /// that is not a data file: stdio, the output, dynamic libraries.
+    pub fn configure(limit: Option<usize>) {
+        let limit = limit.unwrap_or_else(default_limit).max(MINIMUM_LIMIT);
+        let _ = QUOTA.set(Quota {
+            available: Mutex::new(limit),
+            freed: Condvar::new(),
+        });
+    }
+
+    fn default_limit() -> usize {
+        #[cfg(unix)]
+        {
+            let mut rlimit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+            // SAFETY: rlimit is a properly sized out-parameter
+            if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut rlimit) } == 0 {
+                return (rlimit.rlim_cur as usize / 2).max(MINIMUM_LIMIT);
+            }
+        }
+        256
+    }
+
+    /// One open-file slot; dropping it frees the slot for the next opener.
+    pub struct Permit;
+
+    impl Drop for Permit {
+        fn drop(&mut self) {
+            if let Some(quota) = QUOTA.get() {
+                *quota.available.lock().expect("no user of this lock panics") += 1;
+                quota.freed.notify_one();
+            }
+        }
+    }
+
+    /// Blocks until an open-file slot is free. Without a configured quota
+    /// (unit tests, library use) the permit is a no-op.
+    pub fn acquire() -> Permit {
+        if let Some(quota) = QUOTA.get() {
+            let mut available = quota.available.lock().expect("no user of this lock panics");
+            while *available == 0 {
+                available = quota.freed.wait(available).expect("no user of this lock panics");
+            }
+            *available -= 1;
+        }
+        Permit
+    }
+}
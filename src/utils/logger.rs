@@ -1,4 +1,7 @@
-use std::sync::{mpsc, OnceLock};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    mpsc, OnceLock,
+};
 
 pub use logger_thread::spawn_logger_thread;
 
@@ -38,6 +41,8 @@ fn info_with_accessibility(contents: String, accessible: bool) {
 }
 
 pub fn warning(contents: String) {
+    WARNING_EMITTED.store(true, Ordering::Relaxed);
+
     logger_thread::send_log_message(PrintMessage {
         contents,
         // Warnings are important and unlikely to flood, so they should be displayed
@@ -46,6 +51,14 @@ pub fn warning(contents: String) {
     });
 }
 
+/// Whether any warning was emitted during this run, checked by `--strict`.
+static WARNING_EMITTED: AtomicBool = AtomicBool::new(false);
+
+/// True if `warning` was called at least once, see `--strict`.
+pub fn warnings_were_emitted() -> bool {
+    WARNING_EMITTED.load(Ordering::Relaxed)
+}
+
 #[derive(Debug)]
 enum Message {
     FlushAndShutdown,
@@ -156,7 +169,10 @@ mod logger_thread {
             shutdown_barrier: shutdown_barrier.clone(),
         };
 
-        rayon::spawn(move || run_logger(log_receiver, shutdown_barrier));
+        // The logger lives for the whole program and must not occupy a rayon
+        // worker: on machines with few cores that starves (or deadlocks)
+        // everything else using the global pool, like the parallel hashers
+        std::thread::spawn(move || run_logger(log_receiver, shutdown_barrier));
 
         handle
     }
@@ -3,28 +3,44 @@
 //! In here we have the logic for custom formatting, some file and directory utils, and user
 //! stdin interaction helpers.
 
+pub mod bomb;
+pub mod checksum;
 pub mod colors;
 mod file_visibility;
 mod formatting;
 mod fs;
 pub mod io;
 pub mod logger;
+mod progress;
 mod question;
+pub mod report;
 
 pub use file_visibility::FileVisibilityPolicy;
+pub use progress::DiscoveryCounter;
 pub use formatting::{
-    nice_directory_display, pretty_format_list_of_paths, strip_cur_dir, to_utf, Bytes, EscapedPathDisplay,
+    capture_local_offset, elide_middle, local_offset, nice_directory_display, parse_bytes,
+    pretty_format_list_of_paths, strip_cur_dir, terminal_width, to_utf, Bytes, EscapedPathDisplay, SizeFilter,
+    TimeFilter,
 };
 pub use fs::{
-    cd_into_same_dir_as, clear_path, create_dir_if_non_existent, is_symlink, remove_file_or_dir, try_infer_extension,
+    available_disk_space, cd_for_archiving, cd_into_same_dir_as, check_directory_writable, create_dir_if_non_existent,
+    detect_format_from_magic, dirs_with_passing_files,
+    extended_length_path, handle_duplicate_entry, is_symlink, reject_symlink_output, remove_file_or_dir,
+    normalized_mode, relativize_symlink_target, remove_or_trash, resolve_path_conflict, resolve_temp_dir, sync_file_and_parent, try_infer_extension, ConflictResolution, OwnerMap, WrittenPaths,
 };
 pub use question::{
-    ask_to_create_file, user_wants_to_continue, user_wants_to_overwrite, QuestionAction, QuestionPolicy,
+    ask_overwrite_action, ask_passphrase, ask_to_create_file, user_wants_to_continue, ConflictPolicy,
+    DuplicatePolicy, OverwriteAction, QuestionAction, QuestionPolicy,
 };
-pub use utf8::{get_invalid_utf8_paths, is_invalid_utf8};
+pub use utf8::{get_invalid_utf8_paths, is_invalid_utf8, resolve_non_utf8_name};
 
 mod utf8 {
-    use std::{ffi::OsStr, path::PathBuf};
+    use std::{
+        ffi::OsStr,
+        path::{Path, PathBuf},
+    };
+
+    use crate::{cli::NonUtf8Policy, error::FinalError, utils::logger::warning};
 
     /// Check, without allocating, if os_str can be converted into &str
     pub fn is_invalid_utf8(os_str: impl AsRef<OsStr>) -> bool {
@@ -35,4 +51,36 @@ mod utf8 {
     pub fn get_invalid_utf8_paths(paths: &[PathBuf]) -> Vec<&PathBuf> {
         paths.iter().filter(|path| is_invalid_utf8(path)).collect()
     }
+
+    /// Resolves an entry name for a format that requires valid UTF-8 names
+    /// (zip, 7z) under the `--non-utf8` policy: `Ok(Some(name))` stores the
+    /// entry, `Ok(None)` omits it. Without a policy the historical abort
+    /// stays in place.
+    pub fn resolve_non_utf8_name(
+        path: &Path,
+        policy: Option<NonUtf8Policy>,
+        format: &str,
+    ) -> crate::Result<Option<String>> {
+        if let Some(name) = path.to_str() {
+            return Ok(Some(name.to_owned()));
+        }
+
+        match policy {
+            None | Some(NonUtf8Policy::Error) => {
+                Err(FinalError::with_title(format!("{format} requires that all entry names are valid UTF-8"))
+                    .detail(format!("File at '{path:?}' has a non-UTF-8 name"))
+                    .hint("Pass --non-utf8 skip or --non-utf8 sanitize to continue without it.")
+                    .into())
+            }
+            Some(NonUtf8Policy::Skip) => {
+                warning(format!("Skipping '{path:?}', its name is not valid UTF-8"));
+                Ok(None)
+            }
+            Some(NonUtf8Policy::Sanitize) => {
+                let sanitized = path.to_string_lossy().into_owned();
+                warning(format!("Sanitized the non-UTF-8 name {path:?} to '{sanitized}'"));
+                Ok(Some(sanitized))
+            }
+        }
+    }
 }
@@ -0,0 +1,146 @@
+//! Progress feedback for the directory-walk phase of compression.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::utils::{logger::info, Bytes};
+
+/// How often the discovery counter reports, in number of entries.
+const DISCOVERY_LOG_INTERVAL: usize = 1000;
+
+/// How far back the throughput average looks; a windowed rate keeps the
+/// displayed speed from jittering with every burst.
+const RATE_WINDOW: Duration = Duration::from_secs(5);
+
+/// Counts entries yielded by the directory walk during compression and
+/// periodically reports the total, so enumerating a huge directory tree
+/// doesn't look like a hang. With a known total (`--scan-total`) the report
+/// grows a percentage, a smoothed throughput and an ETA.
+pub struct DiscoveryCounter {
+    count: usize,
+    /// Known entry total from a `--scan-total` pre-walk, turning the counter
+    /// into a percentage-capable progress report
+    total: Option<u64>,
+    quiet: bool,
+    started_at: Instant,
+    bytes: u64,
+    /// (timestamp, cumulative bytes) samples bounding the rate window;
+    /// only touched once per report, so the per-entry cost stays a counter
+    /// increment
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl DiscoveryCounter {
+    pub fn new(quiet: bool) -> Self {
+        Self {
+            count: 0,
+            total: None,
+            quiet,
+            started_at: Instant::now(),
+            bytes: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_total(self, total: Option<u64>) -> Self {
+        Self { total, ..self }
+    }
+
+    /// Account the size of an entry about to be processed, feeding the
+    /// throughput average.
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.bytes += bytes;
+    }
+
+    /// Count one more discovered entry, reporting every `DISCOVERY_LOG_INTERVAL` of them.
+    pub fn tick(&mut self) {
+        self.count += 1;
+        if self.quiet || !self.count.is_multiple_of(DISCOVERY_LOG_INTERVAL) {
+            return;
+        }
+
+        let now = Instant::now();
+        self.samples.push_back((now, self.bytes));
+        while let Some((sampled_at, _)) = self.samples.front() {
+            if now.duration_since(*sampled_at) <= RATE_WINDOW || self.samples.len() <= 2 {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        let mut line = match self.total {
+            Some(total) => {
+                let percent = self.count as f64 / total.max(1) as f64 * 100.0;
+                format!("Discovered {} of {total} files ({percent:.0}%)", self.count)
+            }
+            None => format!("Discovered {} files", self.count),
+        };
+
+        if let Some(rate) = self.windowed_rate(now) {
+            line.push_str(&format!(" | {}/s", Bytes::new(rate)));
+        }
+        if let Some(eta) = self.eta(now) {
+            line.push_str(&format!(" | ETA {eta}"));
+        }
+
+        info(line);
+    }
+
+    /// Bytes per second over the sampling window, `None` until the window
+    /// has two samples some time apart.
+    fn windowed_rate(&self, now: Instant) -> Option<u64> {
+        let (window_start, bytes_at_start) = self.samples.front()?;
+        let elapsed = now.duration_since(*window_start).as_secs_f64();
+        if elapsed < 0.1 || self.bytes <= *bytes_at_start {
+            return None;
+        }
+        Some(((self.bytes - bytes_at_start) as f64 / elapsed) as u64)
+    }
+
+    /// Remaining time estimated from the fraction of entries done, as
+    /// `MM:SS` (or `HH:MM:SS` past an hour); needs the `--scan-total` total.
+    fn eta(&self, now: Instant) -> Option<String> {
+        let total = self.total?;
+        if self.count == 0 || self.count as u64 >= total {
+            return None;
+        }
+
+        let elapsed = now.duration_since(self.started_at).as_secs_f64();
+        let remaining = (elapsed * (total - self.count as u64) as f64 / self.count as f64) as u64;
+        Some(if remaining >= 3600 {
+            format!("{:02}:{:02}:{:02}", remaining / 3600, remaining % 3600 / 60, remaining % 60)
+        } else {
+            format!("{:02}:{:02}", remaining / 60, remaining % 60)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rate_and_eta_come_from_the_window_and_total() {
+        let mut counter = DiscoveryCounter::new(true).with_total(Some(2000));
+        counter.started_at = Instant::now() - Duration::from_secs(10);
+        counter.count = 1000;
+        counter.bytes = 50 * 1024 * 1024;
+        counter.samples.push_back((Instant::now() - Duration::from_secs(5), 0));
+
+        let now = Instant::now();
+        let rate = counter.windowed_rate(now).unwrap();
+        // 50 MiB over ~5s, allow slack for the test's own elapsed time
+        assert!((9..=11).contains(&(rate / (1024 * 1024))), "{rate}");
+
+        // Half done after 10s means roughly 10s to go
+        let eta = counter.eta(now).unwrap();
+        assert_eq!(eta, "00:10");
+
+        // Without a total there is no ETA
+        counter.total = None;
+        assert!(counter.eta(now).is_none());
+    }
+}
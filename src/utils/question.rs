@@ -29,6 +29,8 @@ pub enum QuestionPolicy {
     AlwaysNo,
 }
 
+pub use crate::cli::{ConflictPolicy, DuplicatePolicy};
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Determines which action is being questioned
 pub enum QuestionAction {
@@ -38,31 +40,104 @@ pub enum QuestionAction {
     Decompression,
 }
 
-/// Check if QuestionPolicy flags were set, otherwise, ask user if they want to overwrite.
-pub fn user_wants_to_overwrite(path: &Path, question_policy: QuestionPolicy) -> crate::Result<bool> {
+/// The user's choice when an output file already exists, see
+/// [`ask_overwrite_action`].
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum OverwriteAction {
+    /// Replace the existing file
+    Overwrite,
+    /// Abort the operation
+    Cancel,
+    /// Write under this new file name instead
+    Rename(String),
+}
+
+/// Check if QuestionPolicy flags were set, otherwise, ask the user whether to
+/// overwrite, cancel, or type a new name for the conflicting file.
+pub fn ask_overwrite_action(path: &Path, question_policy: QuestionPolicy) -> crate::Result<OverwriteAction> {
     match question_policy {
-        QuestionPolicy::AlwaysYes => Ok(true),
-        QuestionPolicy::AlwaysNo => Ok(false),
+        QuestionPolicy::AlwaysYes => Ok(OverwriteAction::Overwrite),
+        QuestionPolicy::AlwaysNo => Ok(OverwriteAction::Cancel),
         QuestionPolicy::Ask => {
             let path = to_utf(strip_cur_dir(path));
-            let path = Some(&*path);
-            let placeholder = Some("FILE");
-            Confirmation::new("Do you want to overwrite 'FILE'?", placeholder).ask(path)
+
+            let _locks = lock_and_flush_output_stdio()?;
+            let mut stdin_lock = stdin().lock();
+
+            // Ask the same question to end while no valid answers are given
+            loop {
+                if is_running_in_accessible_mode() {
+                    eprintln!(
+                        "Do you want to overwrite '{path}'? {}yes{}/{}no{}/{}rename{}: ",
+                        *colors::GREEN,
+                        *colors::RESET,
+                        *colors::RED,
+                        *colors::RESET,
+                        *colors::YELLOW,
+                        *colors::RESET
+                    );
+                } else {
+                    eprintln!(
+                        "Do you want to overwrite '{path}'? [{}Y{}/{}n{}/{}r{}] ",
+                        *colors::GREEN,
+                        *colors::RESET,
+                        *colors::RED,
+                        *colors::RESET,
+                        *colors::YELLOW,
+                        *colors::RESET
+                    );
+                }
+
+                let mut answer = String::new();
+                let bytes_read = stdin_lock.read_line(&mut answer)?;
+                if bytes_read == 0 {
+                    let error = FinalError::with_title("Unexpected EOF when asking question.")
+                        .detail("Expected 'y', 'n' or 'r' as answer, but found EOF instead.")
+                        .hint("If using Ouch in scripting, consider using `--yes` and `--no`.");
+
+                    return Err(error.into());
+                }
+
+                answer.make_ascii_lowercase();
+                match answer.trim() {
+                    "" | "y" | "yes" => return Ok(OverwriteAction::Overwrite),
+                    "n" | "no" => return Ok(OverwriteAction::Cancel),
+                    "r" | "rename" => {
+                        eprintln!("Type the new file name: ");
+                        let mut new_name = String::new();
+                        if stdin_lock.read_line(&mut new_name)? == 0 {
+                            return Ok(OverwriteAction::Cancel);
+                        }
+                        let new_name = new_name.trim();
+                        if new_name.is_empty() {
+                            continue;
+                        }
+                        return Ok(OverwriteAction::Rename(new_name.to_owned()));
+                    }
+                    _ => continue, // Try again
+                }
+            }
         }
     }
 }
 
 /// Create the file if it doesn't exist and if it does then ask to overwrite it.
 /// If the user doesn't want to overwrite then we return [`Ok(None)`]
-pub fn ask_to_create_file(path: &Path, question_policy: QuestionPolicy) -> Result<Option<fs::File>> {
+pub fn ask_to_create_file(
+    path: &Path,
+    question_policy: QuestionPolicy,
+    conflict_policy: Option<ConflictPolicy>,
+    source_mtime: Option<std::time::SystemTime>,
+    use_trash: bool,
+) -> Result<Option<fs::File>> {
+    utils::reject_symlink_output(path)?;
+
     match fs::OpenOptions::new().write(true).create_new(true).open(path) {
         Ok(w) => Ok(Some(w)),
         Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
-            if user_wants_to_overwrite(path, question_policy)? {
-                utils::remove_file_or_dir(path)?;
-                Ok(Some(fs::File::create(path)?))
-            } else {
-                Ok(None)
+            match utils::resolve_path_conflict(path, source_mtime, conflict_policy, question_policy, use_trash)? {
+                utils::ConflictResolution::Write(path) => Ok(Some(fs::File::create(path)?)),
+                utils::ConflictResolution::Skip | utils::ConflictResolution::Abort => Ok(None),
             }
         }
         Err(e) => Err(Error::from(e)),
@@ -168,3 +243,23 @@ impl<'a> Confirmation<'a> {
         }
     }
 }
+
+/// Asks the user to type a passphrase, reading one line from stdin.
+pub fn ask_passphrase(prompt: &str) -> crate::Result<String> {
+    let _locks = lock_and_flush_output_stdio()?;
+    let mut stdin_lock = stdin().lock();
+
+    eprintln!("{prompt}");
+
+    let mut passphrase = String::new();
+    let bytes_read = stdin_lock.read_line(&mut passphrase)?;
+
+    if bytes_read == 0 {
+        let error = FinalError::with_title("Unexpected EOF when asking for a passphrase.")
+            .hint("If using Ouch in scripting, consider supplying --age-recipient or --age-identity.");
+
+        return Err(error.into());
+    }
+
+    Ok(passphrase.trim_end_matches(['\r', '\n']).to_owned())
+}
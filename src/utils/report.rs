@@ -0,0 +1,89 @@
+//! Machine-readable end-of-run report, written by `--result-json`.
+//!
+//! The schema is stable: `{"status": "ok"|"error", "error": string?,
+//! "format_chain": string?, "output": string?, "output_size": number?,
+//! "files_processed": number, "duration_ms": number}`. The report is
+//! written even under `--quiet` and even when the operation failed (the
+//! `error` field carries the message), so CI dashboards always get an
+//! outcome.
+
+use std::{
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::Instant,
+};
+
+use once_cell::sync::{Lazy, OnceCell};
+use serde::Serialize;
+
+static DESTINATION: OnceCell<PathBuf> = OnceCell::new();
+static STARTED_AT: Lazy<Instant> = Lazy::new(Instant::now);
+static DATA: Mutex<ReportData> = Mutex::new(ReportData {
+    format_chain: None,
+    output: None,
+    output_size: None,
+    files_processed: 0,
+});
+
+#[derive(Serialize)]
+struct ReportData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format_chain: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_size: Option<u64>,
+    files_processed: u64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(flatten)]
+    data: &'a ReportData,
+    duration_ms: u128,
+}
+
+/// Arms report writing, called during argument validation.
+pub fn enable(path: PathBuf) {
+    Lazy::force(&STARTED_AT);
+    let _ = DESTINATION.set(path);
+}
+
+pub fn record_format_chain(chain: String) {
+    DATA.lock().expect("no user of this lock panics").format_chain = Some(chain);
+}
+
+pub fn record_output(path: &Path, size: u64) {
+    let mut data = DATA.lock().expect("no user of this lock panics");
+    data.output = Some(path.to_string_lossy().into_owned());
+    data.output_size = Some(size);
+}
+
+pub fn add_files_processed(count: u64) {
+    DATA.lock().expect("no user of this lock panics").files_processed += count;
+}
+
+/// Writes the report if `--result-json` was given; `-` goes to stderr.
+pub fn write_if_enabled(error: Option<&crate::Error>) {
+    let Some(destination) = DESTINATION.get() else {
+        return;
+    };
+
+    let data = DATA.lock().expect("no user of this lock panics");
+    let report = Report {
+        status: if error.is_some() { "error" } else { "ok" },
+        error: error.map(ToString::to_string),
+        data: &data,
+        duration_ms: STARTED_AT.elapsed().as_millis(),
+    };
+    let json = serde_json::to_string(&report).expect("the report serializes cleanly");
+
+    if destination == Path::new("-") {
+        eprintln!("{json}");
+    } else if let Err(err) = std::fs::write(destination, format!("{json}\n")) {
+        eprintln!("Failed to write the --result-json report: {err}");
+    }
+}
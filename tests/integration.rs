@@ -77,7 +77,7 @@ fn create_random_files(dir: impl Into<PathBuf>, depth: u8, rng: &mut SmallRng) {
 
     // create more random files in 0 to 2 new directories
     for _ in 0..rng.gen_range(0..=2u32) {
-        create_random_files(&tempfile::tempdir_in(dir).unwrap().into_path(), depth - 1, rng);
+        create_random_files(tempfile::tempdir_in(dir).unwrap().into_path(), depth - 1, rng);
     }
 }
 
@@ -151,6 +151,1826 @@ fn multiple_files(
     assert_same_directory(before, after, !matches!(ext, DirectoryExtension::Zip));
 }
 
+/// --format tar (and the --no-compression shorthand) produce a plain,
+/// spec-compliant tar that lists and round-trips
+#[test]
+fn plain_tar_via_explicit_format() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    fs::write(tree.join("a.txt"), "plain").unwrap();
+
+    ouch!("-A", "c", "--format", "tar", tree, dir.join("explicit.out"));
+    let names: Vec<String> = tar::Archive::new(fs::File::open(dir.join("explicit.out")).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.iter().any(|name| name.ends_with("a.txt")));
+
+    ouch!("-A", "c", "--no-compression", tree, dir.join("bare.out"));
+    let out = &dir.join("out");
+    ouch!("-A", "d", "--format", "tar", dir.join("bare.out"), "-d", out);
+    assert_eq!(fs::read_to_string(out.join("tree/a.txt")).unwrap(), "plain");
+}
+
+/// --newer-than/--older-than filter by mtime and --prune-empty drops the
+/// directories the filters emptied
+#[test]
+fn time_filters_and_prune_empty() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir_all(tree.join("old")).unwrap();
+    fs::create_dir_all(tree.join("new")).unwrap();
+    fs::write(tree.join("old/o.txt"), "old").unwrap();
+    fs::write(tree.join("new/n.txt"), "new").unwrap();
+    // Push one file far into the past
+    let past = filetime_creation::FileTime::from_unix_time(1_000_000_000, 0);
+    filetime_creation::set_file_mtime(tree.join("old/o.txt"), past).unwrap();
+
+    ouch!("-A", "c", "--newer-than", "7d", "--prune-empty", tree, dir.join("recent.tar"));
+    let names: Vec<String> = tar::Archive::new(fs::File::open(dir.join("recent.tar")).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.iter().any(|name| name.ends_with("n.txt")));
+    assert!(!names.iter().any(|name| name.ends_with("o.txt")));
+    assert!(!names.iter().any(|name| name.ends_with("old")), "emptied dir pruned");
+
+    ouch!("-A", "c", "--older-than", "2020-01-01T00:00:00Z", tree, dir.join("old.tar"));
+    let names: Vec<String> = tar::Archive::new(fs::File::open(dir.join("old.tar")).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.iter().any(|name| name.ends_with("o.txt")));
+    assert!(!names.iter().any(|name| name.ends_with("n.txt")));
+}
+
+/// --exclude-caches skips CACHEDIR.TAG-tagged contents, keeping the tag
+#[test]
+fn exclude_caches_skips_tagged_directories() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir_all(tree.join("cache")).unwrap();
+    fs::create_dir_all(tree.join("src")).unwrap();
+    fs::write(
+        tree.join("cache/CACHEDIR.TAG"),
+        "Signature: 8a477f597d28d172789f06886806bc55\n",
+    )
+    .unwrap();
+    fs::write(tree.join("cache/junk.bin"), "junk").unwrap();
+    fs::write(tree.join("src/main.rs"), "fn main() {}").unwrap();
+
+    ouch!("-A", "c", "--exclude-caches", tree, dir.join("a.tar"));
+    let names: Vec<String> = tar::Archive::new(fs::File::open(dir.join("a.tar")).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert!(names.iter().any(|name| name.ends_with("CACHEDIR.TAG")));
+    assert!(names.iter().any(|name| name.ends_with("main.rs")));
+    assert!(!names.iter().any(|name| name.ends_with("junk.bin")));
+}
+
+/// Zips with a leading self-extractor stub extract fine: the central
+/// directory is located by scanning from the end
+#[test]
+fn sfx_zip_with_leading_stub_extracts() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("f.txt"), "sfx-payload").unwrap();
+    ouch!("-A", "c", dir.join("f.txt"), dir.join("clean.zip"));
+
+    let mut sfx = b"FAKE-EXE-STUB-".repeat(64);
+    sfx.extend(fs::read(dir.join("clean.zip")).unwrap());
+    fs::write(dir.join("sfx.zip"), sfx).unwrap();
+
+    let out = &dir.join("out");
+    ouch!("-A", "d", dir.join("sfx.zip"), "-d", out);
+    assert_eq!(fs::read_to_string(out.join("f.txt")).unwrap(), "sfx-payload");
+}
+
+/// --level-for gives each layer of a chain its own level
+#[test]
+fn per_layer_levels_apply_to_their_layer() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("t.txt"), "compressible text ".repeat(20_000)).unwrap();
+
+    ouch!("-A", "c", "--level-for", "gz=1", dir.join("t.txt"), dir.join("fast.gz.zst"));
+    ouch!("-A", "c", "--level-for", "gz=9", dir.join("t.txt"), dir.join("best.gz.zst"));
+    let fast = fs::metadata(dir.join("fast.gz.zst")).unwrap().len();
+    let best = fs::metadata(dir.join("best.gz.zst")).unwrap().len();
+    assert!(best < fast, "level 9 for the gz layer should shrink the chain ({best} vs {fast})");
+
+    // And the chain still round trips
+    let out = &dir.join("out");
+    ouch!("-A", "d", dir.join("best.gz.zst"), "-d", out);
+    assert_eq!(
+        fs::read_to_string(out.join("best")).unwrap(),
+        "compressible text ".repeat(20_000)
+    );
+}
+
+/// A .tar.gz that is really just a .gz falls back to writing the decoded
+/// single file with a warning, instead of failing on the tar layer
+#[test]
+fn mislabeled_tar_gz_falls_back_to_single_file() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("payload.txt"), "not actually tar").unwrap();
+    ouch!("-A", "c", dir.join("payload.txt"), dir.join("real.gz"));
+    fs::rename(dir.join("real.gz"), dir.join("fake.tar.gz")).unwrap();
+
+    let out = &dir.join("out");
+    let output = ouch!("-A", "d", dir.join("fake.tar.gz"), "-d", out);
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("does not contain a tar archive"));
+    assert_eq!(fs::read_to_string(out.join("fake")).unwrap(), "not actually tar");
+}
+
+/// --preserve-input-order stores entries in argument order, recursing
+/// each input deterministically
+#[test]
+fn preserve_input_order_matches_argument_order() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("zebra.txt"), "z").unwrap();
+    fs::write(dir.join("apple.txt"), "a").unwrap();
+    let sub = &dir.join("sub");
+    fs::create_dir(sub).unwrap();
+    fs::write(sub.join("b.txt"), "b").unwrap();
+    fs::write(sub.join("a.txt"), "a").unwrap();
+
+    let archive = &dir.join("ordered.tar");
+    ouch!(
+        "-A",
+        "c",
+        "--preserve-input-order",
+        dir.join("zebra.txt"),
+        dir.join("apple.txt"),
+        sub,
+        archive
+    );
+
+    let names: Vec<String> = tar::Archive::new(fs::File::open(archive).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+        .collect();
+    assert_eq!(names, ["zebra.txt", "apple.txt", "sub", "sub/a.txt", "sub/b.txt"]);
+}
+
+/// The --scan-total pre-scan sizes symlinks like compression reads them:
+/// target sizes when following (the default), link sizes otherwise
+#[cfg(unix)]
+#[test]
+fn scan_total_follows_symlinks_like_compression() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    let big = &dir.join("big.bin");
+    fs::write(big, vec![0u8; 100_000]).unwrap();
+    std::os::unix::fs::symlink(big, tree.join("link.bin")).unwrap();
+
+    let output = ouch!("-A", "c", "--scan-total", tree, dir.join("a.tar"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("100.00 kiB") || stderr.contains("97.66 kiB"),
+        "the scanned total should reflect the 100kB target, got: {stderr}"
+    );
+}
+
+/// Backslash separators in zip entry names (Windows-made archives)
+/// extract as nested directories on unix
+#[test]
+fn backslash_zip_entries_extract_nested() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let archive = &dir.join("win.zip");
+
+    let file = fs::File::create(archive).unwrap();
+    let mut writer = zip::ZipWriter::new(file);
+    writer
+        .start_file("dir\\file.txt", zip::write::FileOptions::default())
+        .unwrap();
+    std::io::Write::write_all(&mut writer, b"nested").unwrap();
+    writer.finish().unwrap();
+
+    let out = &dir.join("out");
+    ouch!("-A", "d", archive, "-d", out);
+    assert_eq!(fs::read_to_string(out.join("dir/file.txt")).unwrap(), "nested");
+}
+
+/// --remove deletes archived sources and --remove-empty-dirs prunes what
+/// that emptied, while excluded files and their directories survive
+#[test]
+fn remove_sources_and_prune_empty_dirs() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir_all(tree.join("gone")).unwrap();
+    fs::create_dir_all(tree.join("kept")).unwrap();
+    fs::write(tree.join("gone/a.txt"), "a").unwrap();
+    fs::write(tree.join("kept/b.log"), "b").unwrap();
+
+    ouch!(
+        "-A",
+        "c",
+        "--remove",
+        "--remove-empty-dirs",
+        "--exclude",
+        "*.log",
+        tree,
+        dir.join("out.tar")
+    );
+
+    assert!(!tree.join("gone/a.txt").exists());
+    assert!(!tree.join("gone").exists(), "emptied directory should be pruned");
+    assert_eq!(fs::read_to_string(tree.join("kept/b.log")).unwrap(), "b");
+    assert!(tree.join("kept").exists());
+}
+
+/// `--remove` must only delete what the walk actually archived: files the
+/// time filter excluded were never stored and have to survive
+#[test]
+fn remove_spares_time_filtered_files() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    fs::write(tree.join("old.txt"), "old").unwrap();
+    fs::write(tree.join("new.txt"), "new").unwrap();
+    let ancient = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000_000);
+    std::fs::File::open(tree.join("old.txt"))
+        .unwrap()
+        .set_times(std::fs::FileTimes::new().set_modified(ancient))
+        .unwrap();
+
+    ouch!("-A", "c", "--newer-than", "7d", "--remove", tree, dir.join("out.tar"));
+
+    assert!(!tree.join("new.txt").exists());
+    assert_eq!(fs::read_to_string(tree.join("old.txt")).unwrap(), "old");
+}
+
+/// --manifest writes an in-archive digest list that --verify-manifest
+/// checks, catching tampered files
+#[test]
+fn manifest_round_trip_detects_tampering() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    fs::write(tree.join("a.txt"), "alpha").unwrap();
+    fs::write(tree.join("b.txt"), "beta").unwrap();
+    let archive = &dir.join("m.tar.gz");
+    ouch!("-A", "c", "--manifest", tree, archive);
+
+    let out = &dir.join("out");
+    ouch!("-A", "d", "--verify-manifest", archive, "-d", out);
+
+    // Tamper with an extracted file and re-verify against a fresh extraction
+    fs::write(out.join("m/tree/a.txt"), "tampered").unwrap();
+    crate::utils::cargo_bin()
+        .args(["decompress", "--yes", "--verify-manifest", "--on-conflict", "skip", "-d"])
+        .arg(out)
+        .arg(archive)
+        .assert()
+        .failure();
+}
+
+/// Special mode bits round trip through tar: sticky freely, setgid only
+/// with --allow-setuid
+#[cfg(unix)]
+#[test]
+fn setgid_round_trip_requires_allow_setuid() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    let shared = &tree.join("shared");
+    fs::create_dir(shared).unwrap();
+    fs::set_permissions(shared, std::fs::Permissions::from_mode(0o2755)).unwrap();
+    let archive = &dir.join("tree.tar");
+    ouch!("-A", "c", tree, archive);
+
+    // Without the flag the setgid bit is stripped with a warning
+    let out = &dir.join("plain");
+    let output = ouch!("-A", "d", archive, "-d", out);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--allow-setuid"));
+    let mode = fs::metadata(out.join("tree/shared")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o755);
+
+    // With it the full mode is restored
+    let out = &dir.join("allowed");
+    ouch!("-A", "d", "--allow-setuid", archive, "-d", out);
+    let mode = fs::metadata(out.join("tree/shared")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o7777, 0o2755);
+}
+
+/// Zip extraction refuses archives whose central directory already
+/// declares more entries than --max-entries allows
+#[test]
+fn zip_entry_count_limit_aborts_up_front() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input_dir = &dir.join("d");
+    fs::create_dir(input_dir).unwrap();
+    for i in 0..5 {
+        fs::write(input_dir.join(format!("f{i}")), "x").unwrap();
+    }
+    ouch!("-A", "c", input_dir, dir.join("z.zip"));
+
+    crate::utils::cargo_bin()
+        .args(["decompress", "--yes", "--max-entries", "2", "-d"])
+        .arg(dir.join("out"))
+        .arg(dir.join("z.zip"))
+        .assert()
+        .failure();
+
+    // Raising the limit (or the default) extracts normally
+    ouch!("-A", "d", "--max-entries", "100", dir.join("z.zip"), "-d", dir.join("out2"));
+    assert!(dir.join("out2/d/f0").exists());
+}
+
+/// Trailing bytes after the tar end marker are tolerated by default and
+/// rejected by --strict-tar
+#[test]
+fn strict_tar_rejects_trailing_garbage() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("f.txt"), "content").unwrap();
+    ouch!("-A", "c", dir.join("f.txt"), dir.join("good.tar"));
+    let mut dirty = fs::read(dir.join("good.tar")).unwrap();
+    dirty.extend_from_slice(b"GARBAGE-AFTER-EOF");
+    fs::write(dir.join("dirty.tar"), dirty).unwrap();
+
+    // Lenient default extracts fine
+    let out = &dir.join("lenient");
+    ouch!("-A", "d", dir.join("dirty.tar"), "-d", out);
+    assert_eq!(fs::read_to_string(out.join("f.txt")).unwrap(), "content");
+
+    // Strict mode refuses the same archive, but accepts a clean one
+    crate::utils::cargo_bin()
+        .args(["decompress", "--yes", "--strict-tar", "-d"])
+        .arg(dir.join("strict"))
+        .arg(dir.join("dirty.tar"))
+        .assert()
+        .failure();
+    ouch!("-A", "d", "--strict-tar", dir.join("good.tar"), "-d", dir.join("strict-ok"));
+}
+
+/// `--relativize-symlinks` stores intra-tree absolute symlinks as relative
+/// link entries that survive extraction elsewhere
+#[cfg(unix)]
+#[test]
+fn relativize_symlinks_round_trip() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir_all(tree.join("sub")).unwrap();
+    fs::write(tree.join("real.txt"), "real content").unwrap();
+    std::os::unix::fs::symlink(tree.join("real.txt"), tree.join("sub/link.txt")).unwrap();
+    let archive = &dir.join("tree.tar");
+    let out = &dir.join("out");
+
+    ouch!("-A", "c", "--relativize-symlinks", tree, archive);
+    ouch!("-A", "d", archive, "-d", out);
+
+    let restored = out.join("tree/sub/link.txt");
+    let target = fs::read_link(&restored).unwrap();
+    assert_eq!(target, PathBuf::from("../real.txt"));
+    assert_eq!(fs::read_to_string(&restored).unwrap(), "real content");
+}
+
+/// Impossible format chains (archives nested inside compression layers)
+/// error gracefully instead of panicking
+#[test]
+fn impossible_format_chains_error_cleanly() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("input"), "data").unwrap();
+    ouch!("-A", "c", dir.join("input"), dir.join("file.gz"));
+
+    for chain in ["gz.zip", "gz.7z", "zst.tar", "gz.rar"] {
+        let output = crate::utils::cargo_bin()
+            .args(["decompress", "--yes", "--format", chain, "-d"])
+            .arg(dir.join("out"))
+            .arg(dir.join("file.gz"))
+            .output()
+            .unwrap();
+        assert!(!output.status.success(), "chain {chain} should fail");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("Invalid format chain"),
+            "chain {chain} should report an invalid chain, got: {stderr}"
+        );
+    }
+}
+
+/// --quiet never blocks on an interactive prompt: overwrite questions
+/// resolve negatively instead
+#[test]
+fn quiet_mode_never_prompts() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("input.txt"), "new").unwrap();
+    fs::write(dir.join("out.gz"), "existing").unwrap();
+
+    // No --yes/--no and no stdin: a prompt would error on EOF (or hang
+    // with an open terminal), quiet mode must succeed by skipping
+    crate::utils::cargo_bin()
+        .args(["--quiet", "compress"])
+        .arg(dir.join("input.txt"))
+        .arg(dir.join("out.gz"))
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(dir.join("out.gz")).unwrap(), "existing");
+}
+
+/// Compressing a directory into a single-stream format is rejected up
+/// front with a suggestion to insert tar, instead of silently writing
+/// only the first file
+#[test]
+fn directory_into_single_stream_format_errors() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let somedir = &dir.join("somedir");
+    fs::create_dir(somedir).unwrap();
+    fs::write(somedir.join("a.txt"), "a").unwrap();
+    fs::write(somedir.join("b.txt"), "b").unwrap();
+
+    let output = crate::utils::cargo_bin()
+        .args(["compress", "--yes"])
+        .arg(somedir)
+        .arg(dir.join("out.gz"))
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("does not accept multiple files"));
+    assert!(stderr.contains("tar."));
+    assert!(!dir.join("out.gz").exists());
+}
+
+/// fd-passing paths from process substitution stream through the
+/// non-seekable code paths
+#[cfg(unix)]
+#[test]
+fn decompress_from_fd_path() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("file.txt"), "fd-stream").unwrap();
+    ouch!("-A", "c", dir.join("file.txt"), dir.join("a.tar"));
+
+    let ouch_bin = assert_cmd::cargo::cargo_bin("ouch");
+    let status = std::process::Command::new("bash")
+        .arg("-c")
+        .arg(format!(
+            "{} -A --yes --quiet decompress --format tar -d {} <(cat {})",
+            ouch_bin.display(),
+            dir.join("out").display(),
+            dir.join("a.tar").display(),
+        ))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(fs::read_to_string(dir.join("out/file.txt")).unwrap(), "fd-stream");
+}
+
+/// Answering 'r' at the overwrite prompt extracts under the typed new name
+#[test]
+fn interactive_rename_choice_on_conflict() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("file.txt");
+    fs::write(input, "from archive").unwrap();
+    let archive = &dir.join("archive.tar");
+    ouch!("-A", "c", input, archive);
+    let out = &dir.join("out");
+    fs::create_dir(out).unwrap();
+    fs::write(out.join("file.txt"), "on disk").unwrap();
+
+    crate::utils::cargo_bin()
+        .args(["-A", "decompress", "-d"])
+        .arg(out)
+        .arg(archive)
+        .write_stdin("r\nrenamed.txt\n")
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "on disk");
+    assert_eq!(fs::read_to_string(out.join("renamed.txt")).unwrap(), "from archive");
+}
+
+/// `ouch compress . out.tar` must not include the growing output file in
+/// its own archive
+#[test]
+fn output_file_is_excluded_from_its_own_archive() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("file.txt"), "content").unwrap();
+
+    let output = ouch!("-A", "c", dir, dir.join("out.tar"));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("skipping"));
+
+    let names: Vec<_> = tar::Archive::new(fs::File::open(dir.join("out.tar")).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().into_owned())
+        .collect();
+    assert!(names.iter().any(|name| name.ends_with("file.txt")));
+    assert!(!names.iter().any(|name| name.ends_with("out.tar")));
+
+    // The same footgun through zip
+    let output = ouch!("-A", "c", dir, dir.join("out.zip"));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("skipping"));
+    let archive = zip::ZipArchive::new(fs::File::open(dir.join("out.zip")).unwrap()).unwrap();
+    assert!(!archive.file_names().any(|name| name.ends_with("out.zip")));
+}
+
+/// Stored modes reflect the on-disk permissions, not the process umask,
+/// and round trip through tar and zip
+#[cfg(unix)]
+#[test]
+fn permissions_round_trip_independent_of_umask() {
+    use std::os::unix::fs::PermissionsExt;
+
+    for extension in ["tar", "zip"] {
+        let dir = tempdir().unwrap();
+        let dir = dir.path();
+        let before = &dir.join("before");
+        fs::create_dir(before).unwrap();
+        let secret = &before.join("secret.txt");
+        fs::write(secret, "private").unwrap();
+        fs::set_permissions(secret, std::fs::Permissions::from_mode(0o600)).unwrap();
+        let archive = &dir.join(format!("archive.{extension}"));
+        let after = &dir.join("after");
+
+        ouch!("-A", "c", secret, archive);
+        ouch!("-A", "d", archive, "-d", after);
+
+        let mode = fs::metadata(after.join("secret.txt")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600, "testing {extension}");
+    }
+}
+
+/// Multi-level nonexistent output paths are created with their parents,
+/// for both the compression output and the extraction directory
+#[test]
+fn output_paths_create_missing_parents() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("file.txt"), "content").unwrap();
+
+    let archive = &dir.join("deep/nested/dir/archive.tar.gz");
+    ouch!("-A", "c", dir.join("file.txt"), archive);
+    assert!(archive.exists());
+
+    let out = &dir.join("very/deep/out");
+    ouch!("-A", "d", archive, "-d", out);
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "content");
+}
+
+/// `--on-duplicate` controls what happens when an archive holds several
+/// entries with the same path
+#[test]
+fn on_duplicate_policies() {
+    let build_archive = |dir: &std::path::Path| {
+        let archive = dir.join("dup.tar");
+        let mut builder = tar::Builder::new(fs::File::create(&archive).unwrap());
+        for content in [&b"first"[..], &b"later"[..]] {
+            let mut header = tar::Header::new_gnu();
+            header.set_path("file.txt").unwrap();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append(&header, content).unwrap();
+        }
+        builder.finish().unwrap();
+        archive
+    };
+
+    // Default: the later entry wins, with a warning
+    let dir = tempdir().unwrap();
+    let archive = build_archive(dir.path());
+    let out = &dir.path().join("default");
+    let output = ouch!("-A", "d", &archive, "-d", out);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("appears more than once"));
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "later");
+
+    // skip keeps the first occurrence
+    let out = &dir.path().join("skip");
+    ouch!("-A", "d", &archive, "-d", out, "--on-duplicate", "skip");
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "first");
+
+    // error aborts the extraction
+    let out = &dir.path().join("error");
+    fs::create_dir(out).unwrap();
+    crate::utils::cargo_bin()
+        .args(["decompress", "--yes", "--on-duplicate", "error", "-d"])
+        .arg(out)
+        .arg(&archive)
+        .assert()
+        .failure();
+}
+
+/// Non-regular files are skipped inside archives and rejected as
+/// single-file inputs, instead of hanging on open
+#[cfg(unix)]
+#[test]
+fn non_regular_file_inputs_are_handled() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input_dir = &dir.join("input");
+    fs::create_dir(input_dir).unwrap();
+    fs::write(input_dir.join("file.txt"), "content").unwrap();
+
+    let fifo = input_dir.join("pipe");
+    let fifo_cstr = std::ffi::CString::new(fifo.as_os_str().as_encoded_bytes()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(fifo_cstr.as_ptr(), 0o644) }, 0);
+
+    // Archive mode: the FIFO is skipped with a warning
+    let archive = &dir.join("archive.tar");
+    let output = ouch!("-A", "c", input_dir, archive);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("not a regular file"));
+    let names: Vec<_> = tar::Archive::new(fs::File::open(archive).unwrap())
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().into_owned())
+        .collect();
+    assert!(!names.iter().any(|name| name.ends_with("pipe")));
+
+    // Single-file mode: a clear error instead of blocking on the FIFO
+    crate::utils::cargo_bin()
+        .args(["compress", "--yes"])
+        .arg(&fifo)
+        .arg(dir.join("pipe.gz"))
+        .assert()
+        .failure();
+}
+
+/// An '.age' encrypted archive round trips with a recipient key and its
+/// identity file
+#[test]
+fn age_round_trip_with_recipient_and_identity() {
+    use age::secrecy::ExposeSecret;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    let before_dir = &before.join("dir");
+    fs::create_dir_all(before_dir).unwrap();
+    fs::write(before_dir.join("file.txt"), "top secret").unwrap();
+    let archive = &dir.join("archive.tar.gz.age");
+    let after = &dir.join("after");
+
+    let identity = age::x25519::Identity::generate();
+    let identity_path = &dir.join("identity.txt");
+    fs::write(identity_path, identity.to_string().expose_secret()).unwrap();
+
+    ouch!("-A", "c", "--age-recipient", identity.to_public().to_string(), before_dir, archive);
+    ouch!("-A", "d", "--age-identity", identity_path, archive, "-d", after);
+
+    assert_same_directory(before, after, false);
+}
+
+/// Listing surfaces pax global header metadata instead of showing it as a file
+#[test]
+fn list_shows_pax_global_header_metadata() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let archive = &dir.join("pax.tar");
+
+    // A pax global header is an entry of type 'g' holding length-prefixed
+    // key=value records
+    let mut builder = tar::Builder::new(fs::File::create(archive).unwrap());
+    let record = b"25 comment=built by ouch\n";
+    let mut header = tar::Header::new_ustar();
+    header.set_entry_type(tar::EntryType::XGlobalHeader);
+    header.set_path("pax_global_header").unwrap();
+    header.set_size(record.len() as u64);
+    header.set_cksum();
+    builder.append(&header, &record[..]).unwrap();
+    let mut file_header = tar::Header::new_gnu();
+    file_header.set_path("file.txt").unwrap();
+    file_header.set_size(5);
+    file_header.set_cksum();
+    builder.append(&file_header, &b"hello"[..]).unwrap();
+    builder.finish().unwrap();
+
+    let output = ouch!("-A", "l", archive);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stderr.contains("comment=built by ouch"));
+    assert!(stdout.contains("file.txt"));
+    assert!(!stdout.contains("pax_global_header"));
+}
+
+/// `list --peek-nested` also lists the contents of archives stored inside
+/// the archive, indented beneath their entry
+#[test]
+fn peek_nested_lists_inner_archives() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let inner = &dir.join("inner");
+    fs::create_dir(inner).unwrap();
+    fs::write(inner.join("secret.txt"), "inside").unwrap();
+    let nested_zip = &dir.join("nested.zip");
+    ouch!("-A", "c", inner, nested_zip);
+
+    let outer = &dir.join("outer");
+    fs::create_dir(outer).unwrap();
+    fs::copy(nested_zip, outer.join("nested.zip")).unwrap();
+    fs::write(outer.join("readme.txt"), "outside").unwrap();
+    let archive = &dir.join("archive.tar");
+    ouch!("-A", "c", outer, archive);
+
+    let output = ouch!("-A", "l", archive, "--peek-nested", "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("outer/nested.zip"), "{stdout}");
+    assert!(stdout.contains("  inner/secret.txt"), "{stdout}");
+    assert!(stdout.contains("outer/readme.txt"), "{stdout}");
+}
+
+/// `list --long` shows entry mtimes; `--utc` pins the timezone and `--iso`
+/// switches to full RFC3339
+#[test]
+fn list_long_shows_entry_timestamps() {
+    const FIXED_MTIME: u64 = 1_000_000_000; // 2001-09-09T01:46:40Z
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("file.txt");
+    fs::write(input, "content").unwrap();
+    let archive = &dir.join("archive.tar");
+    ouch!("-A", "c", "--mtime", FIXED_MTIME.to_string(), input, archive);
+
+    let output = ouch!("-A", "l", archive, "--long", "--utc", "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2001-09-09 01:46"), "{stdout}");
+
+    let output = ouch!("-A", "l", archive, "--long", "--utc", "--iso", "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("2001-09-09T01:46:40Z"), "{stdout}");
+
+    let output = ouch!("-A", "l", archive, "--json", "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("\"mtime\":\"2001-09-09T01:46:40Z\""), "{stdout}");
+}
+
+/// A symlink entry whose central directory declares an absurd size must not
+/// drive an allocation while listing; the target read is capped
+#[cfg(unix)]
+#[test]
+fn listing_caps_symlink_target_reads() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let tree = &dir.join("tree");
+    fs::create_dir(tree).unwrap();
+    fs::write(tree.join("target.txt"), "real").unwrap();
+    std::os::unix::fs::symlink("target.txt", tree.join("link")).unwrap();
+    let archive = &dir.join("archive.zip");
+    ouch!("-A", "c", tree, archive);
+
+    // Patch the symlink entry's declared uncompressed size to ~4 GiB
+    let mut bytes = fs::read(archive).unwrap();
+    let mut index = 0;
+    while let Some(found) = bytes[index..].windows(4).position(|window| window == b"PK\x01\x02") {
+        let record = index + found;
+        let name_len = u16::from_le_bytes([bytes[record + 28], bytes[record + 29]]) as usize;
+        if bytes[record + 46..record + 46 + name_len].ends_with(b"link") {
+            bytes[record + 24..record + 28].copy_from_slice(&0xFFFF_FFFEu32.to_le_bytes());
+        }
+        index = record + 4;
+    }
+    let evil = &dir.join("evil.zip");
+    fs::write(evil, bytes).unwrap();
+
+    let output = ouch!("-A", "l", evil, "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("link"), "{stdout}");
+}
+
+/// `list --top N` shows only the N largest entries, sorted descending
+#[test]
+fn list_top_shows_largest_entries() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("big.bin"), vec![b'a'; 50_000]).unwrap();
+    fs::write(before.join("mid.bin"), vec![b'b'; 20_000]).unwrap();
+    fs::write(before.join("small.bin"), vec![b'c'; 100]).unwrap();
+    let archive = &dir.join("archive.tar");
+    ouch!("-A", "c", before, archive);
+
+    let output = ouch!("-A", "l", archive, "--top", "2", "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("big.bin"));
+    assert!(stdout.contains("mid.bin"));
+    assert!(!stdout.contains("small.bin"));
+    let big_position = stdout.find("big.bin").unwrap();
+    let mid_position = stdout.find("mid.bin").unwrap();
+    assert!(big_position < mid_position);
+}
+
+/// Old-style (V7) tars carry no ustar magic; the tar-or-not probe has to
+/// accept them via the header checksum
+#[test]
+fn old_style_v7_tar_extracts() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    // A V7 header is name + mode/uid/gid/size/mtime + checksum, no magic
+    let contents = b"old-style";
+    let mut header = [0u8; 512];
+    header[..5].copy_from_slice(b"f.txt");
+    header[100..107].copy_from_slice(b"0000644");
+    header[108..115].copy_from_slice(b"0000000");
+    header[116..123].copy_from_slice(b"0000000");
+    header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+    header[136..147].copy_from_slice(b"00000000000");
+    header[148..156].copy_from_slice(b"        ");
+    let checksum: u32 = header.iter().map(|byte| u32::from(*byte)).sum();
+    header[148..155].copy_from_slice(format!("{checksum:06o}\0").as_bytes());
+
+    let mut tar_bytes = header.to_vec();
+    tar_bytes.extend_from_slice(contents);
+    tar_bytes.resize(1024, 0);
+    tar_bytes.resize(2048, 0);
+    let archive = &dir.join("v7.tar");
+    fs::write(archive, tar_bytes).unwrap();
+
+    let out = &dir.join("out");
+    fs::create_dir(out).unwrap();
+    ouch!("-A", "d", archive, "-d", out);
+    assert_eq!(fs::read(out.join("f.txt")).unwrap(), contents);
+}
+
+/// With `--each` the trailing positional is an input, so the output/format
+/// consistency check must not reject inputs whose extension differs
+#[test]
+fn each_accepts_inputs_with_other_extensions() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("a.txt.gz"), "not really gzip, just an input name").unwrap();
+    fs::write(dir.join("b.txt.gz"), "second input").unwrap();
+
+    ouch!(
+        "-A",
+        "c",
+        "--each",
+        "--format",
+        "zst",
+        dir.join("a.txt.gz"),
+        dir.join("b.txt.gz")
+    );
+    assert!(dir.join("a.txt.gz.zst").exists());
+    assert!(dir.join("b.txt.gz.zst").exists());
+}
+
+/// `--jobs` only parallelizes single-stream formats: the archive builders
+/// chdir and would race on the process-global working directory
+#[test]
+fn jobs_parallelizes_single_stream_formats_only() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::create_dir(dir.join("a")).unwrap();
+    fs::write(dir.join("a/x.txt"), "one").unwrap();
+    fs::write(dir.join("y.txt"), "two").unwrap();
+
+    // Single-stream inputs may live in different directories
+    ouch!(
+        "-A",
+        "c",
+        "--each",
+        "--format",
+        "zst",
+        "--jobs",
+        "4",
+        dir.join("a/x.txt"),
+        dir.join("y.txt")
+    );
+    assert!(dir.join("a/x.txt.zst").exists());
+    assert!(dir.join("y.txt.zst").exists());
+
+    // Archive formats fall back to sequential instead of racing the cwd
+    let output = ouch!("-A", "c", "--each", "--format", "tar.gz", "--jobs", "4", dir.join("a"), dir.join("y.txt"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("running sequentially"), "{stderr}");
+    assert!(dir.join("a.tar.gz").exists());
+    assert!(dir.join("y.txt.tar.gz").exists());
+}
+
+/// `--also-format` derives sibling outputs from one walk; each branch must
+/// encode the raw input, not the primary branch's compressed bytes
+#[test]
+fn also_format_encodes_the_raw_input() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    // Single-stream primary: the .zst sibling has to hold the file itself
+    let input = &dir.join("file.txt");
+    fs::write(input, "plain text payload").unwrap();
+    ouch!("-A", "c", input, dir.join("out.gz"), "--also-format", "zst");
+    let single = &dir.join("single");
+    fs::create_dir(single).unwrap();
+    ouch!("-A", "d", dir.join("out.zst"), "-d", single);
+    assert_eq!(fs::read(single.join("out")).unwrap(), b"plain text payload");
+
+    // Tar primary: the sibling is a full tar.zst of the same walk
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("a.txt"), "tarred").unwrap();
+    ouch!("-A", "c", before, dir.join("arch.tar.gz"), "--also-format", "zst");
+    let from_tar = &dir.join("from_tar");
+    fs::create_dir(from_tar).unwrap();
+    ouch!("-A", "d", dir.join("arch.tar.zst"), "-d", from_tar);
+    assert_eq!(fs::read(from_tar.join("before/a.txt")).unwrap(), b"tarred");
+}
+
+/// `--max-open-files` caps the open-file slots; archiving many more files
+/// than the limit still succeeds, each open waiting for a free slot
+#[test]
+fn max_open_files_quota_is_respected() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    for index in 0..200 {
+        fs::write(before.join(format!("file{index:03}.txt")), "contents").unwrap();
+    }
+    let archive = &dir.join("archive.tar.gz");
+
+    ouch!("-A", "c", before, archive, "--max-open-files", "2");
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after);
+    assert_eq!(fs::read_dir(after.join("before")).unwrap().count(), 200);
+}
+
+/// `recompress` streams a tar.gz straight into a tar.zst without touching
+/// the tar structure, keeping the contents identical
+#[test]
+fn recompress_streams_between_tar_chains() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    let data: Vec<u8> = (0..1_000_000u32).map(|n| (n.wrapping_mul(31) >> 3) as u8).collect();
+    fs::write(before.join("big.bin"), &data).unwrap();
+    fs::write(before.join("text.txt"), "hello".repeat(10_000)).unwrap();
+    let gz = &dir.join("archive.tar.gz");
+    ouch!("-A", "c", before, gz);
+
+    let zst = &dir.join("archive.tar.zst");
+    ouch!("-A", "recompress", gz, zst);
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", zst, "-d", after);
+    assert_same_directory(before, after.join("before"), false);
+}
+
+/// `--dry-run` reports conflicts and insufficient disk space instead of
+/// extracting anything
+#[test]
+fn dry_run_reports_preflight_problems() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("x.txt"), "contents").unwrap();
+    let archive = &dir.join("archive.tar.gz");
+    ouch!("-A", "c", before, archive);
+
+    let clean = &dir.join("clean");
+    let output = ouch!("-A", "d", archive, "--dry-run", "-d", clean);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("would succeed"), "{stderr}");
+    assert!(!clean.join("before").exists());
+
+    // An existing root conflicts
+    fs::create_dir(clean.join("before")).unwrap();
+    let output = crate::utils::cargo_bin()
+        .args(["--yes", "d", &archive.to_string_lossy(), "--dry-run", "-d", &clean.to_string_lossy()])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    assert!(String::from_utf8(output.stderr).unwrap().contains("would fail"));
+
+    // The debug-build space override simulates a full disk
+    let output = crate::utils::cargo_bin()
+        .env("OUCH_FAKE_FREE_SPACE", "1")
+        .args([
+            "--yes",
+            "d",
+            &archive.to_string_lossy(),
+            "--dry-run",
+            "-d",
+            &dir.join("fresh").to_string_lossy(),
+        ])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    assert!(String::from_utf8(output.stderr).unwrap().contains("insufficient disk space"));
+}
+
+/// `--verify` reads the finished archive back and compares the extracted
+/// files against their sources; an injected corruption must fail it
+#[test]
+fn verify_round_trips_and_catches_corruption() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("a.txt"), "alpha").unwrap();
+    fs::write(before.join("b.bin"), (0..=255u8).cycle().take(50_000).collect::<Vec<u8>>()).unwrap();
+
+    let good = &dir.join("good.tar.gz");
+    ouch!("-A", "c", before, good, "--verify");
+
+    // The debug-build hook flips a byte of the archive before verification
+    crate::utils::cargo_bin()
+        .env("OUCH_CORRUPT_OUTPUT", "1")
+        .args([
+            "--yes",
+            "compress",
+            "--verify",
+            &before.to_string_lossy(),
+            &dir.join("bad.tar.gz").to_string_lossy(),
+        ])
+        .assert()
+        .failure();
+}
+
+/// `--buffer-size` grows the decode-side input buffering without changing
+/// the extracted bytes
+#[test]
+fn buffer_size_does_not_affect_extraction() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    let data: Vec<u8> = (0..300_000u32).map(|n| (n % 251) as u8).collect();
+    fs::write(before.join("data.bin"), &data).unwrap();
+    let archive = &dir.join("archive.tar.gz");
+    ouch!("-A", "c", before, archive);
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after, "--buffer-size", "4MiB");
+    assert_eq!(fs::read(after.join("before/data.bin")).unwrap(), data);
+}
+
+/// An output path that is literally one of the inputs is refused before the
+/// input gets truncated; the usual `file.txt` → `file.txt.gz` case still works
+#[test]
+fn output_colliding_with_input_is_refused() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("file.txt");
+    fs::write(input, "precious").unwrap();
+
+    crate::utils::cargo_bin()
+        .args([
+            "--yes",
+            "compress",
+            "--format",
+            "gz",
+            &input.to_string_lossy(),
+            &input.to_string_lossy(),
+        ])
+        .assert()
+        .failure();
+    // The input must survive untouched
+    assert_eq!(fs::read(input).unwrap(), b"precious");
+
+    ouch!("-A", "c", input, dir.join("file.txt.gz"));
+    assert_eq!(fs::read(input).unwrap(), b"precious");
+}
+
+/// `--metadata-only` writes an index sidecar of the walked files instead of
+/// an archive
+#[test]
+fn metadata_only_writes_an_index() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir_all(before.join("sub")).unwrap();
+    fs::write(before.join("a.txt"), "alpha").unwrap();
+    fs::write(before.join("sub/b.txt"), "beta").unwrap();
+    let index = &dir.join("index.json");
+
+    ouch!("-A", "c", before, index, "--metadata-only", "json");
+
+    let entries: serde_json::Value = serde_json::from_str(&fs::read_to_string(index).unwrap()).unwrap();
+    let entries = entries.as_array().unwrap();
+    assert_eq!(entries.len(), 2);
+    for entry in entries {
+        let path = entry["path"].as_str().unwrap();
+        let expected = if path.ends_with("a.txt") { "alpha" } else { "beta" };
+        assert_eq!(entry["size"].as_u64().unwrap(), expected.len() as u64);
+        let digest = {
+            use sha2::Digest;
+            let mut hasher = sha2::Sha256::new();
+            hasher.update(expected.as_bytes());
+            hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>()
+        };
+        assert_eq!(entry["sha256"].as_str().unwrap(), digest);
+        assert!(entry["mtime"].as_u64().unwrap() > 0);
+    }
+}
+
+/// Spanned zips (`.z01` + final `.zip`) are reassembled from their parts
+/// during listing and extraction
+#[test]
+fn spanned_zip_extracts_across_parts() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+
+    // Build a zip and split its bytes into a .z01 part and the final .zip
+    // (offsets written by the zip crate are absolute, i.e. disk 0 based)
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(vec![]));
+    let options = zip::write::FileOptions::default();
+    writer.start_file("first.txt", options).unwrap();
+    std::io::Write::write_all(&mut writer, b"across the split").unwrap();
+    writer.start_file("second.txt", options).unwrap();
+    std::io::Write::write_all(&mut writer, &vec![b'x'; 5000]).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    let split_point = bytes.len() / 2;
+    fs::write(dir.join("parts.z01"), &bytes[..split_point]).unwrap();
+    let final_zip = &dir.join("parts.zip");
+    fs::write(final_zip, &bytes[split_point..]).unwrap();
+
+    let output = ouch!("-A", "l", final_zip, "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("first.txt"));
+    assert!(stdout.contains("second.txt"));
+
+    let out = &dir.join("out");
+    fs::create_dir(out).unwrap();
+    ouch!("-A", "d", final_zip, "-d", out);
+    assert_eq!(fs::read(out.join("parts/first.txt")).unwrap(), b"across the split");
+    assert_eq!(fs::read(out.join("parts/second.txt")).unwrap(), vec![b'x'; 5000]);
+}
+
+/// `--par-block-size` tunes the parallel gzip pipeline without affecting
+/// correctness
+#[test]
+fn par_block_size_round_trips() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    let data: Vec<u8> = (0..500_000u32).map(|n| (n % 97) as u8).collect();
+    fs::write(before.join("data.bin"), &data).unwrap();
+    let archive = &dir.join("archive.tar.gz");
+
+    ouch!("-A", "c", before, archive, "--par-block-size", "64kB", "-j", "2");
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after);
+    assert_eq!(fs::read(after.join("before/data.bin")).unwrap(), data);
+}
+
+/// `repair` copies the complete entries of a truncated tar into a fresh,
+/// valid archive
+#[test]
+fn repair_salvages_complete_entries() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("a.txt"), "first").unwrap();
+    fs::write(before.join("b.txt"), "second").unwrap();
+    fs::write(before.join("big.bin"), vec![b'Z'; 100_000]).unwrap();
+    let archive = &dir.join("whole.tar");
+    ouch!("-A", "c", before, archive);
+
+    // Cut the archive mid-way through the large entry
+    let bytes = fs::read(archive).unwrap();
+    let truncated = &dir.join("truncated.tar");
+    fs::write(truncated, &bytes[..bytes.len() / 2]).unwrap();
+
+    let repaired = &dir.join("repaired.tar");
+    ouch!("-A", "repair", truncated, repaired);
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", repaired, "-d", after);
+    assert_eq!(fs::read(after.join("before/a.txt")).unwrap(), b"first");
+    assert_eq!(fs::read(after.join("before/b.txt")).unwrap(), b"second");
+    assert!(!after.join("before/big.bin").exists());
+}
+
+/// Stored (uncompressed) zip entries written by --auto-level must carry a
+/// correct CRC32, otherwise integrity checkers reject the archive
+#[test]
+fn stored_zip_entries_have_correct_crcs() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("random.bin");
+    // Incompressible bytes make --auto-level pick the stored method
+    let data: Vec<u8> = (0..200_000u32)
+        .map(|n| (n.wrapping_mul(2_654_435_761) >> 13) as u8)
+        .collect();
+    fs::write(input, &data).unwrap();
+    let archive = &dir.join("archive.zip");
+    ouch!("-A", "c", input, archive, "--auto-level");
+
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive).unwrap()).unwrap();
+    let mut entry = zip.by_name("random.bin").unwrap();
+    assert_eq!(entry.compression(), zip::CompressionMethod::Stored);
+    assert_eq!(entry.crc32(), crc32fast::hash(&data));
+    // Reading to the end makes the zip crate verify the CRC itself
+    let mut contents = vec![];
+    std::io::Read::read_to_end(&mut entry, &mut contents).unwrap();
+    assert_eq!(contents, data);
+}
+
+/// `--embed-total-size` stores the grand total in a pax global header that
+/// listing surfaces without scanning the whole archive
+#[test]
+fn embed_total_size_round_trips() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("a.txt"), "12345").unwrap();
+    fs::write(before.join("b.txt"), "1234567890").unwrap();
+    let archive = &dir.join("archive.tar");
+
+    ouch!("-A", "c", before, archive, "--embed-total-size");
+
+    let output = ouch!("-A", "l", archive, "--no-pager");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("OUCH.total_size=15"), "{stderr}");
+
+    // The metadata record must not leak into the extraction
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after);
+    assert_same_directory(before, after.join("before"), false);
+    assert!(!after.join("pax_global_header").exists());
+}
+
+/// Zip entries store mtime and unix mode, and extraction restores both
+#[cfg(unix)]
+#[test]
+fn zip_round_trip_preserves_mtime_and_permissions() {
+    use std::os::unix::fs::PermissionsExt;
+
+    const FIXED_MTIME: u64 = 1_000_000_000;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("script.sh");
+    fs::write(input, "#!/bin/sh\n").unwrap();
+    fs::set_permissions(input, std::fs::Permissions::from_mode(0o754)).unwrap();
+    let archive = &dir.join("archive.zip");
+    let out = &dir.join("out");
+
+    ouch!("-A", "c", "--mtime", FIXED_MTIME.to_string(), input, archive);
+    ouch!("-A", "d", archive, "-d", out);
+
+    let metadata = fs::metadata(out.join("script.sh")).unwrap();
+    assert_eq!(metadata.permissions().mode() & 0o777, 0o754);
+    let expected = std::time::UNIX_EPOCH + std::time::Duration::from_secs(FIXED_MTIME);
+    assert_eq!(metadata.modified().unwrap(), expected);
+}
+
+/// FIFOs in tar archives are recreated with --preserve-special and skipped
+/// otherwise
+#[cfg(unix)]
+#[test]
+fn preserve_special_recreates_fifos() {
+    use std::os::unix::fs::FileTypeExt;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let archive = &dir.join("special.tar");
+
+    let mut builder = tar::Builder::new(fs::File::create(archive).unwrap());
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Fifo);
+    header.set_path("pipe").unwrap();
+    header.set_mode(0o644);
+    header.set_size(0);
+    header.set_cksum();
+    builder.append(&header, std::io::empty()).unwrap();
+    builder.finish().unwrap();
+
+    let skipped = &dir.join("skipped");
+    fs::create_dir(skipped).unwrap();
+    ouch!("-A", "d", archive, "-d", skipped);
+    assert!(!skipped.join("pipe").exists());
+
+    let restored = &dir.join("restored");
+    fs::create_dir(restored).unwrap();
+    ouch!("-A", "d", archive, "-d", restored, "--preserve-special");
+    let file_type = fs::symlink_metadata(restored.join("pipe")).unwrap().file_type();
+    assert!(file_type.is_fifo());
+}
+
+/// `--each` compresses every input into its own archive
+#[test]
+fn each_compresses_inputs_individually() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    fs::write(dir.join("a.txt"), "aaa").unwrap();
+    fs::write(dir.join("b.txt"), "bbb").unwrap();
+
+    ouch!("-A", "c", "--each", "--format", "gz", dir.join("a.txt"), dir.join("b.txt"));
+
+    for name in ["a", "b"] {
+        let out = &dir.join(format!("out-{name}"));
+        ouch!("-A", "d", dir.join(format!("{name}.txt.gz")), "-d", out);
+        assert_eq!(
+            fs::read_to_string(out.join(format!("{name}.txt"))).unwrap(),
+            name.chars().next().unwrap().to_string().repeat(3)
+        );
+    }
+}
+
+/// Concatenated gzip/zstd/xz streams must be fully consumed, not just the
+/// first member
+#[test]
+fn concatenated_streams_fully_decode() {
+    for extension in ["gz", "zst", "xz"] {
+        let dir = tempdir().unwrap();
+        let dir = dir.path();
+        fs::write(dir.join("first"), "first part\n").unwrap();
+        fs::write(dir.join("second"), "second part\n").unwrap();
+        ouch!("-A", "c", dir.join("first"), dir.join("first.x").with_extension(extension));
+        ouch!("-A", "c", dir.join("second"), dir.join("second.x").with_extension(extension));
+
+        // Concatenate both compressed streams into a single file
+        let mut concatenated = fs::read(dir.join("first.x").with_extension(extension)).unwrap();
+        concatenated.extend(fs::read(dir.join("second.x").with_extension(extension)).unwrap());
+        let concatenated_path = &dir.join(format!("concatenated.{extension}"));
+        fs::write(concatenated_path, concatenated).unwrap();
+
+        let out = &dir.join("out");
+        ouch!("-A", "d", concatenated_path, "-d", out);
+        assert_eq!(
+            fs::read_to_string(out.join("concatenated")).unwrap(),
+            "first part\nsecond part\n",
+            "testing extension {extension}"
+        );
+    }
+}
+
+/// 7z entry paths follow the same -C/--base semantics as tar and zip
+#[test]
+fn sevenz_entry_paths_with_absolute_inputs_and_base() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let nested = &dir.join("project/src");
+    fs::create_dir_all(nested).unwrap();
+    fs::write(nested.join("main.rs"), "fn main() {}").unwrap();
+
+    // Absolute input without --base: stored relative to the input's parent
+    let archive = &dir.join("plain.7z");
+    ouch!("-A", "c", nested.join("main.rs"), archive);
+    let out = &dir.join("out-plain");
+    ouch!("-A", "d", archive, "-d", out);
+    assert!(out.join("main.rs").exists());
+
+    // With --base the stored path is relative to the base directory
+    let archive = &dir.join("based.7z");
+    ouch!("-A", "c", "-C", dir.join("project"), nested.join("main.rs"), archive);
+    let out = &dir.join("out-based");
+    ouch!("-A", "d", archive, "-d", out);
+    assert!(out.join("src/main.rs").exists());
+}
+
+/// `--base` stores entry paths relative to the given directory
+#[test]
+fn base_dir_makes_entry_paths_relative() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let nested = &dir.join("project/src");
+    fs::create_dir_all(nested).unwrap();
+    fs::write(nested.join("main.rs"), "fn main() {}").unwrap();
+    let archive = &dir.join("archive.tar");
+
+    ouch!("-A", "c", "-C", dir.join("project"), nested.join("main.rs"), archive);
+
+    let mut entries = tar::Archive::new(fs::File::open(archive).unwrap());
+    let names: Vec<_> = entries
+        .entries()
+        .unwrap()
+        .map(|entry| entry.unwrap().path().unwrap().into_owned())
+        .collect();
+    assert_eq!(names, [PathBuf::from("src/main.rs")]);
+}
+
+/// `--on-conflict` resolves clashes with existing files without prompting
+#[test]
+fn on_conflict_policies() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("file.txt");
+    let archive = &dir.join("archive.tar");
+    let out = &dir.join("out");
+    fs::write(input, "from archive").unwrap();
+    // Pin the entry mtime far in the past so the `newer` policy sees the
+    // on-disk file as more recent
+    ouch!("-A", "c", "--mtime", "1000000000", input, archive);
+    fs::create_dir(out).unwrap();
+    fs::write(out.join("file.txt"), "on disk").unwrap();
+
+    ouch!("-A", "d", archive, "-d", out, "--on-conflict", "skip");
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "on disk");
+
+    ouch!("-A", "d", archive, "-d", out, "--on-conflict", "newer");
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "on disk");
+
+    ouch!("-A", "d", archive, "-d", out, "--on-conflict", "rename");
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "on disk");
+    assert_eq!(fs::read_to_string(out.join("file (1).txt")).unwrap(), "from archive");
+
+    ouch!("-A", "d", archive, "-d", out, "--on-conflict", "overwrite");
+    assert_eq!(fs::read_to_string(out.join("file.txt")).unwrap(), "from archive");
+}
+
+/// Writing the output through an existing symlink is refused, preventing
+/// symlink-based overwrite attacks in shared directories
+#[cfg(unix)]
+#[test]
+fn refuses_to_write_output_through_symlink() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("input");
+    let target = &dir.join("target");
+    let link = &dir.join("out.tar.gz");
+    fs::write(input, "content").unwrap();
+    fs::write(target, "do not touch").unwrap();
+    std::os::unix::fs::symlink(target, link).unwrap();
+
+    crate::utils::cargo_bin()
+        .args(["compress", "--yes"])
+        .arg(input)
+        .arg(link)
+        .assert()
+        .failure();
+
+    assert_eq!(fs::read_to_string(target).unwrap(), "do not touch");
+}
+
+/// `--mtime` pins the modification time of every archive entry
+#[test]
+fn mtime_overrides_entry_timestamps() {
+    const FIXED_MTIME: u64 = 1_000_000_000;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("file"), "content").unwrap();
+    let archive = &dir.join("before.tar");
+    let after = &dir.join("after");
+
+    ouch!("-A", "c", "--mtime", FIXED_MTIME.to_string(), before, archive);
+    ouch!("-A", "d", archive, "-d", after);
+
+    let modified = fs::metadata(after.join("before/file")).unwrap().modified().unwrap();
+    let expected = std::time::UNIX_EPOCH + std::time::Duration::from_secs(FIXED_MTIME);
+    assert_eq!(modified, expected);
+}
+
+/// An archive exceeding the entry-count threshold must produce a zip64
+/// central directory that can be read back
+#[test]
+fn zip64_entry_count_round_trip() {
+    const NUM_ENTRIES: usize = u16::MAX as usize + 1;
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    for i in 0..NUM_ENTRIES {
+        fs::write(before.join(format!("file{i:05}")), []).unwrap();
+    }
+    let archive = &dir.join("archive.zip");
+    ouch!("-A", "-q", "c", before, archive);
+
+    let archive = zip::ZipArchive::new(fs::File::open(archive).unwrap()).unwrap();
+    // all the files plus the root directory entry
+    assert_eq!(archive.len(), NUM_ENTRIES + 1);
+}
+
+/// Entries stored with an absolute path must land under the output directory
+/// by default instead of being written to their absolute location
+#[test]
+fn absolute_entry_paths_are_stripped_on_extraction() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let archive = &dir.join("evil.tar");
+    let after = &dir.join("after");
+
+    // Craft a tar containing a `/tmp/evil` entry by writing the header name
+    // directly, since `tar::Header::set_path` refuses absolute paths
+    let mut builder = tar::Builder::new(fs::File::create(archive).unwrap());
+    let mut header = tar::Header::new_gnu();
+    let name = b"/tmp/evil";
+    header.as_old_mut().name[..name.len()].copy_from_slice(name);
+    header.set_size(4);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append(&header, &b"evil"[..]).unwrap();
+    builder.finish().unwrap();
+
+    ouch!("-A", "d", archive, "-d", after);
+
+    assert_eq!(fs::read(after.join("tmp/evil")).unwrap(), b"evil");
+}
+
+/// Extracting an archive with multiple root entries and `--no-smart-unpack`
+/// should not wrap them in an extra directory named after the archive
+#[test]
+fn no_smart_unpack_with_multiple_files() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("file1.txt"), "content").unwrap();
+    fs::write(before.join("file2.txt"), "content").unwrap();
+    let archive = &dir.join("archive.tar.gz");
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "c", before.join("file1.txt"), before.join("file2.txt"), archive);
+    ouch!("-A", "d", archive, "-d", after, "--no-smart-unpack");
+    assert_same_directory(before, after, false);
+}
+
+/// `--non-utf8` picks what happens to names that are not valid UTF-8 when
+/// the format requires it: error (the default), skip, or sanitize
+#[cfg(unix)]
+#[test]
+fn non_utf8_policies() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("input");
+    fs::create_dir(input).unwrap();
+    fs::write(input.join(OsStr::from_bytes(b"bad\xff.txt")), "x").unwrap();
+    fs::write(input.join("good.txt"), "y").unwrap();
+
+    // Without a policy zip compression aborts, like it always has
+    crate::utils::cargo_bin()
+        .args([
+            "--yes",
+            "compress",
+            &input.to_string_lossy(),
+            &dir.join("error.zip").to_string_lossy(),
+        ])
+        .assert()
+        .failure();
+
+    let skipped = &dir.join("skip.zip");
+    ouch!("-A", "c", input, skipped, "--non-utf8", "skip");
+    let output = ouch!("-A", "l", skipped, "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("good.txt"));
+    assert!(!stdout.contains("bad"));
+
+    let sanitized = &dir.join("sanitize.zip");
+    ouch!("-A", "c", input, sanitized, "--non-utf8", "sanitize");
+    let output = ouch!("-A", "l", sanitized, "--no-pager");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("bad\u{FFFD}.txt"), "{stdout}");
+}
+
+/// Raw tar names that differ only in invalid bytes collapse to the same
+/// sanitized name; the `--on-duplicate` policy has to see that collision
+#[cfg(unix)]
+#[test]
+fn sanitized_name_collisions_respect_on_duplicate() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let archive = &dir.join("dupe.tar");
+
+    let mut builder = tar::Builder::new(fs::File::create(archive).unwrap());
+    for (raw_name, data) in [(&b"bad\xff.txt"[..], &b"FIRST"[..]), (&b"bad\xfe.txt"[..], &b"SECOND"[..])] {
+        use std::os::unix::ffi::OsStrExt;
+        let mut header = tar::Header::new_gnu();
+        header.set_path(std::ffi::OsStr::from_bytes(raw_name)).unwrap();
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+        builder.append(&header, data).unwrap();
+    }
+    builder.finish().unwrap();
+
+    let out = &dir.join("out");
+    fs::create_dir(out).unwrap();
+    crate::utils::cargo_bin()
+        .args([
+            "--yes",
+            "d",
+            &archive.to_string_lossy(),
+            "-d",
+            &out.to_string_lossy(),
+            "--non-utf8",
+            "sanitize",
+            "--on-duplicate",
+            "error",
+        ])
+        .assert()
+        .failure();
+}
+
+/// An unwritable output directory fails the pre-flight probe before any
+/// work starts (root bypasses permission bits, so this only runs unprivileged)
+#[cfg(unix)]
+#[test]
+fn unwritable_output_directory_fails_fast() {
+    use std::os::unix::fs::PermissionsExt;
+
+    // SAFETY: geteuid is always safe to call
+    if unsafe { libc::geteuid() } == 0 {
+        return;
+    }
+
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let input = &dir.join("file.txt");
+    fs::write(input, "content").unwrap();
+    let read_only = &dir.join("read_only");
+    fs::create_dir(read_only).unwrap();
+    fs::set_permissions(read_only, std::fs::Permissions::from_mode(0o555)).unwrap();
+
+    let output = crate::utils::cargo_bin()
+        .args([
+            "--yes",
+            "compress",
+            &input.to_string_lossy(),
+            &read_only.join("out.tar.gz").to_string_lossy(),
+        ])
+        .assert()
+        .failure()
+        .get_output()
+        .clone();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("not writable"), "{stderr}");
+
+    fs::set_permissions(read_only, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// `--solid` groups 7z entries into shared compression blocks, which beats
+/// per-entry streams on many similar files
+#[test]
+fn solid_sevenz_is_smaller_on_similar_files() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    // Similar-but-not-identical files: per-entry compression pays the full
+    // price for each, a solid block amortizes it
+    let base: Vec<u8> = (0..20_000u32).map(|n| (n % 251) as u8 ^ (n / 7) as u8).collect();
+    for index in 0..10 {
+        let mut data = base.clone();
+        data[index * 100] ^= 0xFF;
+        fs::write(before.join(format!("file{index}.dat")), data).unwrap();
+    }
+
+    let plain = &dir.join("plain.7z");
+    let solid = &dir.join("solid.7z");
+    ouch!("-A", "c", before, plain);
+    ouch!("-A", "c", before, solid, "--solid");
+    assert!(fs::metadata(solid).unwrap().len() < fs::metadata(plain).unwrap().len());
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", solid, "-d", after);
+    assert_same_directory(before, after.join("before"), false);
+}
+
+/// `--skip-zeros` stores all-zero files as tiny placeholders that grow back
+/// to their original size on extraction
+#[test]
+fn skip_zeros_stores_placeholders() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("zeros.bin"), vec![0u8; 4 * 1024 * 1024]).unwrap();
+    fs::write(before.join("data.txt"), "not zeros").unwrap();
+    let archive = &dir.join("archive.tar");
+
+    ouch!("-A", "c", before, archive, "--skip-zeros");
+    assert!(fs::metadata(archive).unwrap().len() < 64 * 1024);
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after);
+    let restored = fs::read(after.join("before/zeros.bin")).unwrap();
+    assert_eq!(restored.len(), 4 * 1024 * 1024);
+    assert!(restored.iter().all(|byte| *byte == 0));
+    assert_eq!(fs::read(after.join("before/data.txt")).unwrap(), b"not zeros");
+}
+
+/// `--fsync` flushes the archive to disk before success is reported; the
+/// output must be complete and readable afterwards
+#[test]
+fn fsync_leaves_a_complete_archive() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir(before).unwrap();
+    fs::write(before.join("file.txt"), "durable").unwrap();
+    let archive = &dir.join("archive.tar.gz");
+
+    ouch!("-A", "c", before, archive, "--fsync");
+
+    assert!(archive.exists());
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", archive, "-d", after);
+    assert_same_directory(before, after.join("before"), false);
+}
+
+/// `convert` changes the container while keeping paths and contents, so a
+/// zip → tar.gz → zip round trip ends with the original entries
+#[test]
+fn convert_round_trip_keeps_contents() {
+    let dir = tempdir().unwrap();
+    let dir = dir.path();
+    let before = &dir.join("before");
+    fs::create_dir_all(before.join("sub")).unwrap();
+    fs::write(before.join("a.txt"), "first").unwrap();
+    fs::write(before.join("sub/b.txt"), "second").unwrap();
+    let original = &dir.join("original.zip");
+    let tarball = &dir.join("converted.tar.gz");
+    let round_tripped = &dir.join("round_tripped.zip");
+
+    ouch!("-A", "c", before, original);
+    ouch!("-A", "convert", original, tarball);
+    ouch!("-A", "convert", tarball, round_tripped);
+
+    let after = &dir.join("after");
+    fs::create_dir(after).unwrap();
+    ouch!("-A", "d", round_tripped, "-d", after);
+    assert_same_directory(after.join("before"), before, false);
+}
+
+#[test]
+fn unpack_dot_z() -> Result<(), Box<dyn std::error::Error>> {
+    let mut datadir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?);
+    datadir.push("tests/data");
+
+    // Plain compressed file
+    let dir = tempdir()?;
+    ouch!("-A", "d", datadir.join("testfile.txt.Z"), "-d", dir.path());
+    let content = fs::read_to_string(dir.path().join("testfile.txt"))?;
+    assert!(content.starts_with("The quick brown fox"));
+
+    // Chained with tar
+    let dir = tempdir()?;
+    ouch!("-A", "d", datadir.join("testfile.tar.Z"), "-d", dir.path());
+    let content = fs::read_to_string(dir.path().join("testfile.txt"))?;
+    assert!(content.starts_with("The quick brown fox"));
+
+    Ok(())
+}
+
+#[test]
+fn unpack_iso() -> Result<(), Box<dyn std::error::Error>> {
+    let dir = tempdir()?;
+    let dirpath = dir.path();
+
+    let mut datadir = PathBuf::from(std::env::var("CARGO_MANIFEST_DIR")?);
+    datadir.push("tests/data");
+    ouch!("-A", "d", datadir.join("test.iso"), "-d", dirpath);
+
+    let content = fs::read_to_string(dirpath.join("HELLO.TXT"))?;
+    assert_eq!(content, "hello from iso\n");
+
+    Ok(())
+}
+
 #[cfg(feature = "unrar")]
 #[test]
 fn unpack_rar() -> Result<(), Box<dyn std::error::Error>> {
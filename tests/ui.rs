@@ -1,14 +1,14 @@
-/// Snapshot tests for Ouch's output.
-///
-/// See CONTRIBUTING.md for a brief guide on how to use [`insta`] for these tests.
-/// [`insta`]: https://docs.rs/insta
+//! Snapshot tests for Ouch's output.
+//!
+//! See CONTRIBUTING.md for a brief guide on how to use [`insta`] for these tests.
+//! [`insta`]: https://docs.rs/insta
 
 #[macro_use]
 mod utils;
 
 use std::{ffi::OsStr, io, path::Path, process::Output};
 
-use insta::assert_display_snapshot as ui;
+use insta::assert_snapshot as ui;
 use regex::Regex;
 
 use crate::utils::create_files_in;
@@ -114,6 +114,9 @@ fn ui_test_usage_help_flag() {
     insta::with_settings!({filters => vec![
         // binary name is `ouch.exe` on Windows and `ouch` on everywhere else
         (r"(Usage:.*\b)ouch(\.exe)?\b", "${1}<OUCH_BIN>"),
+        // feature-gated subcommands would make the snapshot depend on the
+        // enabled cargo features
+        (r"(?m)^  (mount|browse)\s.*\n", ""),
     ]}, {
         ui!(output_to_string(ouch!("--help")));
         ui!(output_to_string(ouch!("-h")));